@@ -11,28 +11,43 @@
 #![allow(dead_code)]
 
 use crate::agent::tool_executor::{self, DispatchResult};
+use crate::cascade::{Cascade, EscalationSignal};
+use crate::classifier::{self, Classification, ClassificationSource};
 use crate::cli::Context;
 use crate::compact;
+use crate::config::Target;
+use crate::cost::{
+    self, format_cost, format_tokens, BudgetKind, BudgetStatus, LedgerEntry, OperationCost,
+};
+use crate::events::{self, Event, RunContext};
 use crate::llm::{self, LlmClient};
+use crate::metrics;
+use crate::model_routing::{RouteCategory, RoutingContext};
 use crate::plan::{self, PlanPhase};
+use crate::privacy::{self, PrivacyAuditLog, PrivacyLevel, PrivacyScanner};
+use crate::provider_health::{self, ProviderHealthRegistry};
+use crate::spans;
 use crate::tool_display;
 use anyhow::Result;
 use serde_json::{json, Value};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Instant;
 
 /// Default maximum iterations per turn
 pub const DEFAULT_MAX_ITERATIONS: usize = 12;
 
 /// Doom loop detection threshold - break after this many identical tool calls
-const DOOM_LOOP_THRESHOLD: usize = 3;
+pub(crate) const DOOM_LOOP_THRESHOLD: usize = 3;
 
 /// Tools that are safe to run in parallel (read-only, no side effects)
 const PURE_TOOLS: &[&str] = &["Read", "Glob", "Search", "Grep"];
 
 /// Hash a tool call for doom loop detection
-fn hash_tool_call(name: &str, args: &Value) -> u64 {
+pub(crate) fn hash_tool_call(name: &str, args: &Value) -> u64 {
     let mut hasher = DefaultHasher::new();
     name.hash(&mut hasher);
     // Use string representation of args for consistent hashing
@@ -47,19 +62,19 @@ fn is_pure_tool(name: &str) -> bool {
 
 /// Doom loop detector using a ring buffer of recent tool calls
 #[derive(Debug, Default)]
-struct DoomLoopDetector {
+pub(crate) struct DoomLoopDetector {
     recent_calls: Vec<u64>,
 }
 
 impl DoomLoopDetector {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             recent_calls: Vec::with_capacity(DOOM_LOOP_THRESHOLD),
         }
     }
 
     /// Record a tool call and return true if doom loop detected
-    fn record(&mut self, hash: u64) -> bool {
+    pub(crate) fn record(&mut self, hash: u64) -> bool {
         self.recent_calls.push(hash);
 
         // Only check for doom loop if we have enough calls
@@ -74,7 +89,7 @@ impl DoomLoopDetector {
     }
 
     /// Reset after a different call breaks the pattern
-    fn reset(&mut self) {
+    pub(crate) fn reset(&mut self) {
         self.recent_calls.clear();
     }
 }
@@ -149,6 +164,13 @@ pub trait AgentHooks {
     /// This is called at the start of each iteration.
     fn build_system_prompt(&self, ctx: &Context, in_planning_mode: bool) -> String;
 
+    /// Sampling overrides for this agent.
+    ///
+    /// Merged on top of the target's `[targets."model@backend"]` settings.
+    fn sampling(&self) -> llm::SamplingParams {
+        llm::SamplingParams::default()
+    }
+
     /// Filter or transform tool schemas.
     ///
     /// Called after loading base schemas to allow filtering or modification.
@@ -257,6 +279,686 @@ fn apply_skill_tool_filter(ctx: &Context, mut schemas: Vec<Value>) -> Vec<Value>
     schemas
 }
 
+/// Compact history when the upcoming request nears the target's context window.
+///
+/// The budget is measured in estimated tokens for the whole request (system
+/// prompt, tool schemas and history) against `ModelCapabilities::context_window`.
+pub(crate) fn auto_compact(
+    ctx: &Context,
+    target: &Target,
+    system_prompt: &str,
+    tool_schemas: &[Value],
+    messages: &mut Vec<Value>,
+) {
+    let context_config = ctx.config.borrow().context.clone();
+    let context_window = ctx
+        .model_router
+        .borrow()
+        .get_capabilities(&target.model)
+        .context_window;
+    let counter = ctx.token_counter.borrow().clone();
+    let used = counter.count_text(&target.model, system_prompt)
+        + counter.count_messages(&target.model, messages)
+        + counter.count_tools(&target.model, tool_schemas);
+
+    if !compact::needs_compaction(used, &context_config, context_window) {
+        return;
+    }
+
+    trace(
+        ctx,
+        "COMPACT",
+        &format!(
+            "Auto-compacting context (~{} of {} tokens)",
+            used,
+            compact::context_limit(&context_config, context_window)
+        ),
+    );
+    let mut backends = ctx.backends.borrow_mut();
+    if let Ok(client) = backends.get_client(&target.backend) {
+        match compact::compact_messages(messages, &context_config, client, &target.model, &counter)
+        {
+            Ok((compacted, result)) => {
+                eprintln!("[auto-compact] {}", compact::format_result(&result));
+                *messages = compacted;
+            }
+            Err(e) => {
+                eprintln!("[auto-compact] Failed: {}", e);
+            }
+        }
+    }
+}
+
+/// How many times a single turn may recover from a context-length rejection
+pub(crate) const MAX_OVERFLOW_RECOVERIES: usize = 2;
+
+/// Recover after a backend rejected a request for exceeding its context window.
+///
+/// The first attempt compacts history harder than auto-compaction and retries
+/// on the same target. If compaction was already tried or freed nothing, the
+/// request is rerouted to a target with a larger context window. Every step is
+/// recorded in the transcript. Returns the target to retry with, or `None`
+/// when neither helps.
+///
+/// `target` must be the target privacy admitted for the rejected request, so
+/// the summarizer only sees history that target already received. A rerouted
+/// target is returned unvetted; callers run `enforce_privacy` before using it.
+pub(crate) fn recover_context_overflow(
+    ctx: &Context,
+    target: &Target,
+    system_prompt: &str,
+    tool_schemas: &[Value],
+    messages: &mut Vec<Value>,
+    error: &anyhow::Error,
+    attempt: usize,
+) -> Option<Target> {
+    let counter = ctx.token_counter.borrow().clone();
+    let estimate = |messages: &[Value]| {
+        counter.count_text(&target.model, system_prompt)
+            + counter.count_messages(&target.model, messages)
+            + counter.count_tools(&target.model, tool_schemas)
+    };
+
+    let used = estimate(messages);
+    eprintln!(
+        "[context] {} rejected the request as too long (~{} tokens estimated)",
+        target,
+        format_tokens(used as u64)
+    );
+    let _ = ctx
+        .transcript
+        .borrow_mut()
+        .context_overflow(&target.model, used, &error.to_string());
+
+    if attempt == 1 {
+        // Keep half as many turns as a normal compaction would
+        let mut context_config = ctx.config.borrow().context.clone();
+        context_config.keep_last_turns = (context_config.keep_last_turns / 2).max(1);
+
+        let compacted = {
+            let mut backends = ctx.backends.borrow_mut();
+            backends.get_client(&target.backend).and_then(|client| {
+                compact::compact_messages(
+                    messages,
+                    &context_config,
+                    client,
+                    &target.model,
+                    &counter,
+                )
+            })
+        };
+        match compacted {
+            Ok((compacted, result)) if result.compacted_count < result.original_count => {
+                eprintln!("[emergency-compact] {}", compact::format_result(&result));
+                let _ = ctx.transcript.borrow_mut().emergency_compact(
+                    result.original_tokens,
+                    result.compacted_tokens,
+                    true,
+                );
+                *messages = compacted;
+                return Some(target.clone());
+            }
+            Ok((_, result)) => {
+                let _ = ctx.transcript.borrow_mut().emergency_compact(
+                    result.original_tokens,
+                    result.compacted_tokens,
+                    false,
+                );
+            }
+            Err(e) => {
+                eprintln!("[emergency-compact] Failed: {}", e);
+                let _ = ctx
+                    .transcript
+                    .borrow_mut()
+                    .emergency_compact(used, used, false);
+            }
+        }
+    }
+
+    // The backend saw more than its window, so ask for strictly more than that
+    let router = ctx.model_router.borrow();
+    let window = router.get_capabilities(&target.model).context_window;
+    let needed = estimate(messages).max(window + 1);
+    let unavailable_backends = {
+        let backends = ctx.backends.borrow();
+        router
+            .route_targets()
+            .into_iter()
+            .map(|t| t.backend)
+            .filter(|b| !backends.is_available(b))
+            .collect()
+    };
+    let routing_ctx = RoutingContext {
+        unavailable_backends,
+        ..Default::default()
+    };
+    let rerouted = router.resolve_larger_window(target, needed, &routing_ctx);
+
+    let to = rerouted.as_ref().map(|t| t.to_string());
+    let _ = ctx
+        .transcript
+        .borrow_mut()
+        .context_reroute(&target.to_string(), to.as_deref(), needed);
+    match &to {
+        Some(to) => eprintln!("[context] Rerouting to {} for a larger context window", to),
+        None => eprintln!("[context] No routed target has a larger context window"),
+    }
+    rerouted
+}
+
+/// How many times a single turn may fail over to another target
+pub(crate) const MAX_FAILOVERS: usize = 3;
+
+/// Shared health registry for this process, sized from the context's config
+pub(crate) fn provider_health(ctx: &Context) -> Arc<ProviderHealthRegistry> {
+    let config = ctx.config.borrow();
+    provider_health::global(&config.health, &config.circuit_breaker)
+}
+
+/// Span name for LLM calls; `record_cost` adds usage to the latest one
+const LLM_SPAN: &str = "llm.call";
+
+/// Record how an LLM call went against its backend's health and circuit
+/// breaker, and in the request metrics and trace.
+///
+/// Context overflows, cancellations and other request errors say nothing
+/// about the backend, so they only count as failed requests in the metrics.
+pub(crate) fn record_call_outcome<T>(
+    ctx: &Context,
+    target: &Target,
+    started: Instant,
+    result: &Result<T>,
+) {
+    let health = provider_health(ctx);
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let attributes = vec![
+        ("gen_ai.system", target.backend.as_str().into()),
+        ("gen_ai.request.model", target.model.as_str().into()),
+    ];
+    match result {
+        Ok(_) => {
+            health.record_success(&target.backend, duration_ms);
+            metrics::record_success(&target.backend, &target.model, duration_ms);
+            spans::record(LLM_SPAN, started, attributes, None);
+        }
+        Err(e) => {
+            if llm::is_backend_failure(e) {
+                health.record_failure(&target.backend);
+            }
+            metrics::record_failure(&target.backend, &target.model, duration_ms);
+            spans::record(LLM_SPAN, started, attributes, Some(&e.to_string()));
+        }
+    }
+}
+
+/// Pick the next target from the configured fallback chain after `failed`.
+///
+/// Targets after `failed` in the chain are tried in order (the whole chain if
+/// `failed` is not part of it), skipping backends that are not configured,
+/// have no API key, or are refused by their circuit breaker or health. The
+/// decision is recorded in the transcript.
+pub(crate) fn failover_target(
+    ctx: &Context,
+    failed: &Target,
+    category: Option<RouteCategory>,
+    reason: &str,
+) -> Option<Target> {
+    let chain = ctx
+        .config
+        .borrow()
+        .fallback_chains
+        .get_chain(category.as_ref().map(|c| c.as_str()));
+    let start = chain
+        .iter()
+        .position(|t| t == failed)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    let health = provider_health(ctx);
+    let next = {
+        let backends = ctx.backends.borrow();
+        chain[start..]
+            .iter()
+            .filter(|t| t.backend != failed.backend)
+            .find(|t| backends.is_available(&t.backend) && health.admit(&t.backend))
+            .cloned()
+    };
+
+    let to = next.as_ref().map(|t| t.to_string());
+    let _ =
+        ctx.transcript
+            .borrow_mut()
+            .backend_failover(&failed.to_string(), to.as_deref(), reason);
+    match &to {
+        Some(to) => eprintln!("[failover] {} {}; failing over to {}", failed, reason, to),
+        None => eprintln!(
+            "[failover] {} {}; no healthy fallback target",
+            failed, reason
+        ),
+    }
+    next
+}
+
+/// Make sure `target` may be called now, failing over down the chain if its
+/// backend's circuit is open or it is cooling down after repeated failures.
+pub(crate) fn admit_target(
+    ctx: &Context,
+    target: &Target,
+    category: Option<RouteCategory>,
+) -> Result<Target> {
+    if provider_health(ctx).admit(&target.backend) {
+        return Ok(target.clone());
+    }
+    failover_target(ctx, target, category, "is unavailable (circuit open)").ok_or_else(|| {
+        anyhow::anyhow!(
+            "Backend '{}' is unavailable (circuit open) and no fallback target is healthy",
+            target.backend
+        )
+    })
+}
+
+/// Scan an outgoing request and pick a target that honours its privacy level.
+///
+/// The level starts at `[privacy] default_level` and escalates to strict when
+/// the conversation (including tool results) matches a sensitive pattern.
+/// Strict requests go to a ZDR backend or are refused; sensitive requests
+/// prefer one and fall back to `target`, which is recorded in the audit file.
+pub(crate) fn enforce_privacy(
+    ctx: &Context,
+    target: &Target,
+    messages: &[Value],
+) -> Result<Target> {
+    let config = ctx.config.borrow().privacy.clone();
+    let audit = config.audit_zdr_violations;
+    let scan = PrivacyScanner::new(config).scan_messages(messages);
+    let level = scan.level;
+    if !level.prefers_zdr() {
+        return Ok(target.clone());
+    }
+
+    let zdr_map: HashMap<String, bool> = ctx
+        .config
+        .borrow()
+        .backends
+        .iter()
+        .map(|(name, b)| (name.clone(), b.zdr))
+        .collect();
+    if zdr_map.get(&target.backend).copied().unwrap_or(false) {
+        return Ok(target.clone());
+    }
+
+    // Candidates: the fallback chain first, then everything routing knows about
+    let mut candidates = ctx.config.borrow().fallback_chains.get_chain(None);
+    candidates.extend(ctx.model_router.borrow().route_targets());
+    let health = provider_health(ctx);
+    let zdr_target = {
+        let backends = ctx.backends.borrow();
+        let usable: Vec<String> = candidates
+            .iter()
+            .map(|t| t.backend.clone())
+            .filter(|b| backends.is_available(b) && health.admit(b))
+            .collect();
+        let zdr_backends = privacy::filter_zdr_backends(&usable, &zdr_map, true);
+        candidates
+            .into_iter()
+            .find(|t| zdr_backends.contains(&t.backend))
+    };
+
+    let record = |target: &str, action: &str| {
+        let _ = ctx.transcript.borrow_mut().privacy_decision(
+            level.as_str(),
+            target,
+            action,
+            &scan.matched_patterns,
+        );
+    };
+    if let Some(zdr_target) = zdr_target {
+        eprintln!(
+            "[privacy] {} request routed from {} to ZDR target {}",
+            level.as_str(),
+            target,
+            zdr_target
+        );
+        record(&zdr_target.to_string(), "rerouted");
+        return Ok(zdr_target);
+    }
+
+    if level == PrivacyLevel::Strict {
+        record(&target.to_string(), "refused");
+        let reason = if scan.escalated {
+            format!(
+                " (escalated by sensitive patterns: {})",
+                scan.matched_patterns.join(", ")
+            )
+        } else {
+            String::new()
+        };
+        return Err(anyhow::anyhow!(
+            "Privacy level strict{} requires a ZDR backend, but none is available; refusing to send to {}",
+            reason,
+            target
+        ));
+    }
+
+    eprintln!(
+        "[privacy] No ZDR backend available; sending sensitive request to {}",
+        target
+    );
+    record(&target.to_string(), "sent_non_zdr");
+    if audit {
+        if let Some(path) = privacy::audit_log_path() {
+            PrivacyAuditLog::persistent(path).record_violation(
+                level,
+                &target.backend,
+                false,
+                scan.matched_patterns.clone(),
+            );
+        }
+    }
+    Ok(target.clone())
+}
+
+/// Move a cascading turn to its next target after a failure signal.
+///
+/// Returns the new target, or None when the chain is exhausted.
+pub(crate) fn escalate_cascade(
+    ctx: &Context,
+    cascade: &mut Cascade,
+    signal: &EscalationSignal,
+) -> Option<Target> {
+    let from = cascade.current().to_string();
+    let next = cascade.escalate()?;
+    eprintln!("[cascade] {} on {}; escalating to {}", signal, from, next);
+    let _ = ctx.transcript.borrow_mut().cascade_escalation(
+        cascade.category().as_str(),
+        &from,
+        &next.to_string(),
+        signal.as_str(),
+        &signal.to_string(),
+    );
+    Some(next)
+}
+
+/// Label a user prompt with a route category when the classifier is enabled.
+///
+/// Cached results are reused per prompt hash. The classifier call runs on
+/// `[model_routing.classifier].target`, else the `fast` route, else
+/// `fallback`; any failure falls back to keyword matching. So does a prompt
+/// the privacy level keeps off the classifier's backend, since the classifier
+/// sees the raw prompt before the turn's own privacy check.
+pub(crate) fn classify_prompt(
+    ctx: &Context,
+    prompt: &str,
+    fallback: &Target,
+) -> Option<Classification> {
+    let settings = ctx.config.borrow().model_routing.classifier.clone();
+    if !settings.enabled {
+        return None;
+    }
+
+    let classification = match classifier::cached(prompt) {
+        Some(hit) => hit,
+        None => {
+            let target = settings
+                .target
+                .as_deref()
+                .and_then(Target::parse)
+                .unwrap_or_else(|| {
+                    ctx.model_router
+                        .borrow()
+                        .resolve(RouteCategory::Fast, fallback)
+                });
+            match call_classifier(ctx, &target, prompt) {
+                Ok((category, complexity)) => {
+                    classifier::remember(prompt, category, complexity);
+                    Classification {
+                        category,
+                        complexity: Some(complexity),
+                        source: ClassificationSource::Llm,
+                    }
+                }
+                Err(e) => {
+                    verbose(ctx, &format!("Prompt classifier failed: {}", e));
+                    Classification::from_keywords(prompt)
+                }
+            }
+        }
+    };
+
+    let complexity = classification.complexity.map(|c| c.as_str());
+    let _ = ctx.transcript.borrow_mut().prompt_classified(
+        classification.category.as_str(),
+        complexity,
+        classification.source.as_str(),
+    );
+    trace(
+        ctx,
+        "CLASSIFY",
+        &format!(
+            "{} ({}, via {})",
+            classification.category.as_str(),
+            complexity.unwrap_or("unknown complexity"),
+            classification.source.as_str()
+        ),
+    );
+    Some(classification)
+}
+
+/// Whether the privacy level lets `prompt` go to `target` as is
+fn privacy_permits(ctx: &Context, target: &Target, prompt: &str) -> bool {
+    let config = ctx.config.borrow();
+    let scan = PrivacyScanner::new(config.privacy.clone())
+        .scan_messages(&[json!({ "role": "user", "content": prompt })]);
+    !scan.level.prefers_zdr() || config.backends.get(&target.backend).is_some_and(|b| b.zdr)
+}
+
+fn call_classifier(
+    ctx: &Context,
+    target: &Target,
+    prompt: &str,
+) -> Result<(RouteCategory, classifier::Complexity)> {
+    let target = admit_target(ctx, target, None)?;
+    if !privacy_permits(ctx, &target, prompt) {
+        anyhow::bail!(
+            "privacy level needs a ZDR backend and {} is not one",
+            target.backend
+        );
+    }
+    let request = classifier::request(&target, prompt);
+    let response = {
+        let mut backends = ctx.backends.borrow_mut();
+        let client = backends.get_client(&target.backend)?;
+        let started = Instant::now();
+        let response = client.chat_with_cancel(&request, &ctx.cancel);
+        record_call_outcome(ctx, &target, started, &response);
+        response?
+    };
+    if let Some(usage) = &response.usage {
+        record_cost(
+            ctx,
+            &target,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            Some("classifier"),
+        );
+    }
+    let reply = response
+        .choices
+        .first()
+        .and_then(|c| c.message.content.as_deref())
+        .unwrap_or_default();
+    classifier::parse_reply(reply)
+        .ok_or_else(|| anyhow::anyhow!("unrecognized classifier reply: {}", reply.trim()))
+}
+
+/// Open the root span of a turn, seeded from the session's run context
+pub(crate) fn start_turn_span(ctx: &Context, agent: Option<&str>) -> spans::SpanGuard {
+    let run = RunContext {
+        session_id: ctx.session_id.clone(),
+        agent_id: agent.map(String::from),
+        turn_number: Some(*ctx.turn_counter.borrow()),
+    };
+    spans::start_turn(&ctx.config.borrow().telemetry, &run)
+}
+
+/// Record a finished turn's totals on its span
+pub(crate) fn end_turn_span(
+    span: &mut spans::SpanGuard,
+    target: &Target,
+    stats: &crate::agent::CommandStats,
+) {
+    span.set("gen_ai.request.model", target.model.as_str());
+    span.set("gen_ai.system", target.backend.as_str());
+    span.set("gen_ai.usage.input_tokens", stats.input_tokens);
+    span.set("gen_ai.usage.output_tokens", stats.output_tokens);
+    span.set("turn.tool_uses", stats.tool_uses);
+}
+
+/// Project key used for per-project budgets
+fn project_key(ctx: &Context) -> String {
+    ctx.root.display().to_string()
+}
+
+/// Price an LLM call, add it to the session costs and the persistent ledger,
+/// and announce budgets as they cross 50%, 80% and 100%.
+pub(crate) fn record_cost(
+    ctx: &Context,
+    target: &Target,
+    input_tokens: u64,
+    output_tokens: u64,
+    agent: Option<&str>,
+) -> OperationCost {
+    let turn_number = *ctx.turn_counter.borrow();
+    let op = ctx.session_costs.borrow_mut().record_operation(
+        turn_number,
+        &target.model,
+        input_tokens,
+        output_tokens,
+    );
+
+    let config = ctx.config.borrow().cost_tracking.clone();
+    let project = project_key(ctx);
+    let crossed = {
+        let mut ledger = cost::ledger().lock().unwrap_or_else(|e| e.into_inner());
+        ledger.record(LedgerEntry {
+            ts: chrono::Utc::now(),
+            session_id: ctx.session_id.clone(),
+            project: project.clone(),
+            model: target.model.clone(),
+            backend: target.backend.clone(),
+            agent: agent.map(|a| a.to_string()),
+            input_tokens,
+            output_tokens,
+            cost_usd: op.cost_usd,
+        });
+        ledger.crossed_thresholds(&config, &ctx.session_id, &project)
+    };
+    metrics::record_tokens(&target.backend, &target.model, input_tokens, output_tokens);
+    metrics::record_cost(&target.backend, &target.model, op.cost_usd);
+    spans::annotate_last(
+        LLM_SPAN,
+        vec![
+            ("gen_ai.usage.input_tokens", input_tokens.into()),
+            ("gen_ai.usage.output_tokens", output_tokens.into()),
+            ("cost.usd", op.cost_usd.into()),
+        ],
+    );
+    for (status, fraction) in crossed {
+        eprintln!(
+            "[cost] {} budget {:.0}% used ({} of {})",
+            status.kind.label(),
+            fraction * 100.0,
+            format_cost(status.spent_usd),
+            format_cost(status.limit_usd)
+        );
+        events::emit(Event::cost_threshold_warning(
+            &ctx.session_id,
+            status.spent_usd,
+            status.limit_usd * fraction,
+        ));
+        if status.exceeded() {
+            events::emit(Event::cost_budget_exceeded(
+                &ctx.session_id,
+                status.limit_usd,
+                status.spent_usd,
+            ));
+        }
+    }
+    op
+}
+
+/// First exceeded budget that hasn't been approved for overrun
+pub(crate) fn exceeded_budget(ctx: &Context) -> Option<BudgetStatus> {
+    let config = ctx.config.borrow().cost_tracking.clone();
+    cost::ledger()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .exceeded(&config, &ctx.session_id, &project_key(ctx))
+}
+
+/// Fraction of the tightest session or daily budget still unspent, for routing
+pub(crate) fn budget_remaining(ctx: &Context) -> Option<f64> {
+    let config = ctx.config.borrow().cost_tracking.clone();
+    cost::ledger()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .budgets(&config, &ctx.session_id, &project_key(ctx))
+        .iter()
+        .filter(|b| matches!(b.kind, BudgetKind::Session | BudgetKind::Daily))
+        .map(|b| 1.0 - b.fraction().min(1.0))
+        .reduce(f64::min)
+}
+
+/// Allow the rest of a budget period to run over its limit
+pub(crate) fn approve_budget_overrun(period: &str) {
+    cost::ledger()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .approve_overrun(period);
+}
+
+/// Stop before an LLM call once a budget is spent.
+///
+/// Interactive sessions pause and ask whether to keep going; print mode
+/// (and a "no") stops the turn with an error.
+pub(crate) fn enforce_budget(ctx: &Context) -> Result<()> {
+    let Some(status) = exceeded_budget(ctx) else {
+        return Ok(());
+    };
+    let summary = format!(
+        "{} budget exceeded: {} spent of {}",
+        status.kind.label(),
+        format_cost(status.spent_usd),
+        format_cost(status.limit_usd)
+    );
+
+    if ctx.args.prompt.is_none() {
+        println!("{}", summary);
+        print!(
+            "Continue past the budget for this {}? [y/N]: ",
+            period_noun(&status)
+        );
+        io::stdout().flush().ok();
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).is_ok() {
+            let input = input.trim().to_lowercase();
+            if input == "y" || input == "yes" {
+                approve_budget_overrun(&status.period);
+                return Ok(());
+            }
+        }
+    }
+    Err(anyhow::anyhow!("{}; stopping", summary))
+}
+
+fn period_noun(status: &BudgetStatus) -> &'static str {
+    match status.kind {
+        BudgetKind::Session => "session",
+        BudgetKind::Daily => "day",
+        BudgetKind::ProjectMonthly => "month",
+    }
+}
+
 /// Process plan mode output
 fn process_plan_output(ctx: &Context, content: &str) {
     let goal = ctx
@@ -309,6 +1011,7 @@ pub fn run_loop<H: AgentHooks>(
     let mut turn_result = TurnResult::default();
     let mut collected_response = String::new();
     let _ = ctx.transcript.borrow_mut().user_message(user_input);
+    let mut turn_span = start_turn_span(ctx, None);
 
     messages.push(json!({
         "role": "user",
@@ -316,21 +1019,89 @@ pub fn run_loop<H: AgentHooks>(
     }));
 
     // Resolve target
-    let target = {
-        let current = ctx.current_target.borrow();
-        if let Some(t) = current.as_ref() {
-            t.clone()
-        } else {
-            ctx.config
-                .borrow()
-                .get_default_target()
-                .ok_or_else(|| anyhow::anyhow!("No target configured. Use --target or /target"))?
-        }
+    let pinned = ctx.current_target.borrow().clone();
+    let mut target = match &pinned {
+        Some(t) => t.clone(),
+        None => ctx
+            .config
+            .borrow()
+            .get_default_target()
+            .ok_or_else(|| anyhow::anyhow!("No target configured. Use --target or /target"))?,
     };
+
+    // Classify the prompt; unless the user pinned a target, route on it
+    let classification = classify_prompt(ctx, user_input, &target);
+    {
+        let router = ctx.model_router.borrow();
+        let unavailable_backends = {
+            let backends = ctx.backends.borrow();
+            router
+                .route_targets()
+                .into_iter()
+                .map(|t| t.backend)
+                .filter(|b| !backends.is_available(b))
+                .collect()
+        };
+        let routing_ctx = RoutingContext {
+            unavailable_backends,
+            budget_remaining: budget_remaining(ctx),
+            ..Default::default()
+        };
+        let routed = match (&classification, &pinned) {
+            (Some(classification), None) => {
+                Some(router.decide(classification.category, &routing_ctx, &target))
+            }
+            // Pinned and unclassified targets still answer to the budget
+            _ => {
+                let category = classification
+                    .as_ref()
+                    .map(|c| c.category)
+                    .unwrap_or_else(|| RouteCategory::from_prompt(user_input));
+                router.cap_for_budget(category, &routing_ctx, &target)
+            }
+        };
+        if let Some(decision) = routed {
+            let _ = ctx.transcript.borrow_mut().routing_decision(
+                decision.category.as_str(),
+                &decision.target.to_string(),
+                &decision.reason,
+            );
+            trace(ctx, "ROUTE", &decision.reason);
+            target = decision.target;
+        }
+    }
     let bash_config = ctx.config.borrow().bash.clone();
+    let mut sampling = ctx
+        .config
+        .borrow()
+        .sampling_for(&target)
+        .merged(&hooks.sampling());
 
     trace(ctx, "TARGET", &target.to_string());
 
+    // Cascade routing starts the turn on the cheapest target of its chain,
+    // unless the user pinned one
+    let mut cascade = if pinned.is_some() {
+        None
+    } else {
+        Cascade::new(
+            &ctx.config.borrow().model_routing.cascade,
+            classification
+                .as_ref()
+                .map(|c| c.category)
+                .unwrap_or(RouteCategory::Default),
+        )
+    };
+    if let Some(cascade) = &cascade {
+        target = cascade.current().clone();
+        sampling = ctx
+            .config
+            .borrow()
+            .sampling_for(&target)
+            .merged(&hooks.sampling());
+        trace(ctx, "CASCADE", &format!("starting on {}", target));
+    }
+
     // Check plan mode
     let plan_phase = ctx.plan_mode.borrow().phase;
     let in_planning_mode = plan_phase == PlanPhase::Planning;
@@ -357,6 +1128,8 @@ pub fn run_loop<H: AgentHooks>(
 
     // Initialize doom loop detector
     let mut doom_detector = DoomLoopDetector::new();
+    let mut overflow_recoveries = 0;
+    let mut failovers = 0;
 
     for iteration in 1..=max_iterations {
         trace(ctx, "ITER", &format!("Starting iteration {}", iteration));
@@ -367,27 +1140,6 @@ pub fn run_loop<H: AgentHooks>(
                 .borrow_mut()
                 .iteration_info(iteration as u32, 0, "awaiting_response");
 
-        // Auto-compaction: check if context is approaching limit
-        {
-            let context_config = &ctx.config.borrow().context;
-            if compact::needs_compaction(messages, context_config) {
-                trace(ctx, "COMPACT", "Auto-compacting context");
-                let mut backends = ctx.backends.borrow_mut();
-                if let Ok(client) = backends.get_client(&target.backend) {
-                    match compact::compact_messages(messages, context_config, client, &target.model)
-                    {
-                        Ok((compacted, result)) => {
-                            eprintln!("[auto-compact] {}", compact::format_result(&result));
-                            *messages = compacted;
-                        }
-                        Err(e) => {
-                            eprintln!("[auto-compact] Failed: {}", e);
-                        }
-                    }
-                }
-            }
-        }
-
         // Build system prompt via hooks
         let system_prompt = hooks.build_system_prompt(ctx, in_planning_mode);
 
@@ -399,7 +1151,25 @@ pub fn run_loop<H: AgentHooks>(
             );
         }
 
+        // Skip backends whose circuit is open, then apply the privacy level
+        enforce_budget(ctx)?;
+        let admitted = admit_target(ctx, &target, None)?;
+        let admitted = enforce_privacy(ctx, &admitted, messages)?;
+        if admitted != target {
+            target = admitted;
+            sampling = ctx
+                .config
+                .borrow()
+                .sampling_for(&target)
+                .merged(&hooks.sampling());
+        }
+
+        // Auto-compaction: check if context is approaching limit. Runs on the
+        // admitted target so history never reaches a backend privacy ruled out.
+        auto_compact(ctx, &target, &system_prompt, &tool_schemas, messages);
+
         // Make LLM request
+        let estimated_prompt_tokens;
         let response = {
             let mut backends = ctx.backends.borrow_mut();
             let client = backends.get_client(&target.backend)?;
@@ -418,27 +1188,90 @@ pub fn run_loop<H: AgentHooks>(
                 }));
             }
 
+            estimated_prompt_tokens = ctx.token_counter.borrow().count_request(
+                &target.model,
+                &req_messages,
+                &tool_schemas,
+            );
+
             let request = llm::ChatRequest {
                 model: target.model.clone(),
                 messages: req_messages,
                 tools: Some(tool_schemas.clone()),
                 tool_choice: Some("auto".to_string()),
+                sampling: sampling.clone(),
             };
 
-            client.chat(&request)?
+            let started = Instant::now();
+            let response = client.chat_with_cancel(&request, &ctx.cancel);
+            record_call_outcome(ctx, &target, started, &response);
+            response
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e)
+                if llm::is_context_overflow(&e)
+                    && overflow_recoveries < MAX_OVERFLOW_RECOVERIES =>
+            {
+                overflow_recoveries += 1;
+                match recover_context_overflow(
+                    ctx,
+                    &target,
+                    &system_prompt,
+                    &tool_schemas,
+                    messages,
+                    &e,
+                    overflow_recoveries,
+                ) {
+                    Some(next) => {
+                        if next != target {
+                            target = next;
+                            sampling = ctx
+                                .config
+                                .borrow()
+                                .sampling_for(&target)
+                                .merged(&hooks.sampling());
+                        }
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) if llm::is_backend_failure(&e) && failovers < MAX_FAILOVERS => {
+                failovers += 1;
+                match failover_target(ctx, &target, None, &format!("failed: {}", e)) {
+                    Some(next) => {
+                        target = next;
+                        sampling = ctx
+                            .config
+                            .borrow()
+                            .sampling_for(&target)
+                            .merged(&hooks.sampling());
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
         };
 
         // Track token usage
         if let Some(usage) = &response.usage {
+            ctx.token_counter.borrow_mut().observe(
+                &target.model,
+                estimated_prompt_tokens,
+                usage.prompt_tokens,
+            );
+
             turn_result.stats.input_tokens += usage.prompt_tokens;
             turn_result.stats.output_tokens += usage.completion_tokens;
 
-            let turn_number = *ctx.turn_counter.borrow();
-            let op = ctx.session_costs.borrow_mut().record_operation(
-                turn_number,
-                &target.model,
+            let op = record_cost(
+                ctx,
+                &target,
                 usage.prompt_tokens,
                 usage.completion_tokens,
+                None,
             );
 
             let _ = ctx.transcript.borrow_mut().token_usage(
@@ -456,6 +1289,7 @@ pub fn run_loop<H: AgentHooks>(
 
         let choice = &response.choices[0];
         let msg = &choice.message;
+        let mut escalation: Option<EscalationSignal> = None;
 
         // Warn if truncated
         if choice.finish_reason.as_deref() == Some("length") {
@@ -471,6 +1305,7 @@ pub fn run_loop<H: AgentHooks>(
                 }
                 collected_response.push_str(content);
                 let _ = ctx.transcript.borrow_mut().assistant_message(content);
+                escalation = cascade.as_ref().and_then(|c| c.observe_response(content));
 
                 if in_planning_mode {
                     process_plan_output(ctx, content);
@@ -493,6 +1328,18 @@ pub fn run_loop<H: AgentHooks>(
                     "role": "assistant",
                     "content": msg.content
                 }));
+                // A model that gave up gets another try on a stronger target
+                let next =
+                    escalation.and_then(|signal| escalate_cascade(ctx, cascade.as_mut()?, &signal));
+                if let Some(next) = next {
+                    target = next;
+                    sampling = ctx
+                        .config
+                        .borrow()
+                        .sampling_for(&target)
+                        .merged(&hooks.sampling());
+                    continue;
+                }
                 break;
             }
         };
@@ -534,7 +1381,7 @@ pub fn run_loop<H: AgentHooks>(
         // TODO: Convert to actual async parallel execution when agent loop goes async
         let mut tool_results: Vec<(String, String, Value)> = Vec::new();
 
-        for (tc, args_result) in pure_calls.into_iter().chain(effectful_calls.into_iter()) {
+        for (tc, args_result) in pure_calls.into_iter().chain(effectful_calls) {
             let name = &tc.function.name;
 
             // Handle JSON parse errors - return error to LLM so it can learn
@@ -557,6 +1404,22 @@ pub fn run_loop<H: AgentHooks>(
             // Doom loop detection
             let call_hash = hash_tool_call(name, &args);
             if doom_detector.record(call_hash) {
+                if cascade.as_ref().is_some_and(|c| c.can_escalate()) {
+                    let error_result = json!({
+                        "error": {
+                            "code": "doom_loop_detected",
+                            "message": format!(
+                                "Tool '{}' called {} times with identical arguments. \
+                                 Try a different approach.",
+                                name, DOOM_LOOP_THRESHOLD
+                            )
+                        }
+                    });
+                    tool_results.push((tc.id.clone(), name.clone(), error_result));
+                    escalation.get_or_insert(EscalationSignal::DoomLoop { tool: name.clone() });
+                    doom_detector.reset();
+                    continue;
+                }
                 eprintln!(
                     "⚠️  Doom loop detected: {} called {} times with same arguments. Breaking.",
                     name, DOOM_LOOP_THRESHOLD
@@ -633,6 +1496,13 @@ pub fn run_loop<H: AgentHooks>(
             verbose(ctx, &format!("Tool result: {} ok={}", name, ok));
             eprintln!("{}", tool_display::format_tool_result(name, &result));
 
+            if let Some(signal) = cascade
+                .as_mut()
+                .and_then(|c| c.observe_tool_result(name, &args, &result))
+            {
+                escalation.get_or_insert(signal);
+            }
+
             tool_results.push((tc.id.clone(), name.clone(), result));
 
             // Break if pending question
@@ -662,6 +1532,21 @@ pub fn run_loop<H: AgentHooks>(
         {
             break;
         }
+
+        // Carry the conversation over to a stronger target on failure signals
+        if let Some(signal) = escalation {
+            if let Some(next) = cascade
+                .as_mut()
+                .and_then(|c| escalate_cascade(ctx, c, &signal))
+            {
+                target = next;
+                sampling = ctx
+                    .config
+                    .borrow()
+                    .sampling_for(&target)
+                    .merged(&hooks.sampling());
+            }
+        }
     }
 
     // Run Stop hooks (skip if pending question)
@@ -690,6 +1575,7 @@ pub fn run_loop<H: AgentHooks>(
         Some(collected_response)
     };
 
+    end_turn_span(&mut turn_span, &target, &turn_result.stats);
     Ok(turn_result)
 }
 
@@ -717,4 +1603,31 @@ mod tests {
         assert!(config.include_task_tool);
         assert!(config.streaming);
     }
+
+    #[test]
+    fn test_privacy_permits_classifier_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = crate::config::Config::with_builtin_backends();
+        let ctx = crate::context_factory::build_context(
+            &cfg,
+            dir.path().to_path_buf(),
+            "classifier-test".to_string(),
+            None,
+        )
+        .unwrap();
+        let non_zdr = Target::parse("gpt-4o-mini@chatgpt").unwrap();
+        let zdr = Target::parse("claude-3-5-haiku-latest@claude").unwrap();
+
+        assert!(privacy_permits(&ctx, &non_zdr, "rename this function"));
+        assert!(!privacy_permits(
+            &ctx,
+            &non_zdr,
+            "my password is hunter2, fix the login"
+        ));
+        assert!(privacy_permits(
+            &ctx,
+            &zdr,
+            "my password is hunter2, fix the login"
+        ));
+    }
 }
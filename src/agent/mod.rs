@@ -21,4 +21,6 @@ pub use core::run_loop;
 
 // Re-export types from agent_impl (the original agent.rs, renamed)
 // These are kept for backward compatibility with cli.rs, subagent.rs, etc.
-pub use crate::agent_impl::{run_turn, run_turn_sync, CommandStats, PendingQuestion, TurnResult};
+pub use crate::agent_impl::{
+    build_system_prompt, run_turn, run_turn_sync, CommandStats, PendingQuestion, TurnResult,
+};
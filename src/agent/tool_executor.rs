@@ -53,7 +53,7 @@ pub fn dispatch_tool(
             Ok(tools::plan_mode::execute_enter(&ctx.plan_mode, goal))
         }
         "ExitPlanMode" => Ok(tools::plan_mode::execute_exit(&ctx.plan_mode)),
-        _ => tools::execute(name, args, &ctx.root, bash_config),
+        _ => tools::execute(name, args, &ctx.root, bash_config, &ctx.session_id),
     };
 
     match result {
@@ -4,9 +4,13 @@
 #![allow(clippy::await_holding_refcell_ref)]
 
 use crate::{
+    agent::core::{
+        admit_target, auto_compact, end_turn_span, enforce_budget, enforce_privacy,
+        failover_target, record_call_outcome, record_cost, recover_context_overflow,
+        start_turn_span, MAX_FAILOVERS, MAX_OVERFLOW_RECOVERIES,
+    },
     cli::Context,
-    compact,
-    llm::{self, LlmClient, StreamEvent},
+    llm::{self, StreamEvent},
     plan::{self, PlanPhase},
     policy::Decision,
     tool_display, tools,
@@ -16,6 +20,7 @@ use serde_json::{json, Value};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
+use std::time::Instant;
 
 const MAX_ITERATIONS: usize = 12;
 
@@ -126,6 +131,41 @@ fn verbose(ctx: &Context, message: &str) {
     }
 }
 
+/// Build the system prompt for a turn: base prompt, optimize mode, skill index
+/// and active skill instructions.
+pub fn build_system_prompt(ctx: &Context, in_planning_mode: bool) -> String {
+    let mut system_prompt = if in_planning_mode {
+        plan::PLAN_MODE_SYSTEM_PROMPT.to_string()
+    } else {
+        SYSTEM_PROMPT.to_string()
+    };
+
+    // Add optimization mode instructions if -O flag is set
+    if ctx.args.optimize {
+        system_prompt.push_str(
+            "\n\nAI-to-AI mode. Maximum information density. Structure over prose. No narration.",
+        );
+    }
+
+    // Add skill pack index
+    let skill_index = ctx.skill_index.borrow();
+    let skill_prompt = skill_index.format_for_prompt(50);
+    drop(skill_index);
+    if !skill_prompt.is_empty() {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&skill_prompt);
+    }
+
+    // Add active skill instructions
+    let active_skills = ctx.active_skills.borrow();
+    if !active_skills.is_empty() {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&active_skills.format_for_conversation());
+    }
+
+    system_prompt
+}
+
 /// Sync wrapper for worker.rs compatibility (deprecated - will be removed)
 pub fn run_turn_sync(
     ctx: &Context,
@@ -135,6 +175,7 @@ pub fn run_turn_sync(
     let mut turn_result = TurnResult::default();
     let mut collected_response = String::new();
     let _ = ctx.transcript.borrow_mut().user_message(user_input);
+    let mut turn_span = start_turn_span(ctx, None);
 
     messages.push(json!({
         "role": "user",
@@ -142,7 +183,7 @@ pub fn run_turn_sync(
     }));
 
     // Resolve target: override > config default
-    let target = {
+    let mut target = {
         let current = ctx.current_target.borrow();
         if let Some(t) = current.as_ref() {
             t.clone()
@@ -154,6 +195,7 @@ pub fn run_turn_sync(
         }
     };
     let bash_config = ctx.config.borrow().bash.clone();
+    let mut sampling = ctx.config.borrow().sampling_for(&target);
 
     trace(ctx, "TARGET", &target.to_string());
 
@@ -239,93 +281,114 @@ pub fn run_turn_sync(
 
     // Initialize doom loop detector
     let mut doom_detector = DoomLoopDetector::new();
+    let mut overflow_recoveries = 0;
+    let mut failovers = 0;
 
     for iteration in 1..=max_iterations {
         trace(ctx, "ITER", &format!("Starting iteration {}", iteration));
 
-        // Auto-compaction: check if context is approaching limit
-        {
-            let context_config = &ctx.config.borrow().context;
-            if compact::needs_compaction(messages, context_config) {
-                trace(ctx, "COMPACT", "Auto-compacting context");
-                let mut backends = ctx.backends.borrow_mut();
-                if let Ok(client) = backends.get_client(&target.backend) {
-                    match compact::compact_messages(messages, context_config, client, &target.model)
-                    {
-                        Ok((compacted, result)) => {
-                            eprintln!("[auto-compact] {}", compact::format_result(&result));
-                            *messages = compacted;
-                        }
-                        Err(e) => {
-                            eprintln!("[auto-compact] Failed: {}", e);
-                        }
-                    }
-                }
-            }
+        let system_prompt = build_system_prompt(ctx, in_planning_mode);
+
+        // Skip backends whose circuit is open, then apply the privacy level
+        enforce_budget(ctx)?;
+        let admitted = admit_target(ctx, &target, None)?;
+        let admitted = enforce_privacy(ctx, &admitted, messages)?;
+        if admitted != target {
+            target = admitted;
+            sampling = ctx.config.borrow().sampling_for(&target);
         }
 
+        // Auto-compaction: check if context is approaching limit. Runs on the
+        // admitted target so history never reaches a backend privacy ruled out.
+        auto_compact(ctx, &target, &system_prompt, &tool_schemas, messages);
+
+        let estimated_prompt_tokens;
         // Get client for target's backend (lazy-loaded)
         let response = {
             let mut backends = ctx.backends.borrow_mut();
             let client = backends.get_client(&target.backend)?;
 
-            // Build system prompt with skill pack info
-            let mut system_prompt = if in_planning_mode {
-                plan::PLAN_MODE_SYSTEM_PROMPT.to_string()
-            } else {
-                SYSTEM_PROMPT.to_string()
-            };
-
-            // Add optimization mode instructions if -O flag is set
-            if ctx.args.optimize {
-                system_prompt.push_str("\n\nAI-to-AI mode. Maximum information density. Structure over prose. No narration.");
-            }
-
-            // Add skill pack index
-            let skill_index = ctx.skill_index.borrow();
-            let skill_prompt = skill_index.format_for_prompt(50);
-            drop(skill_index);
-            if !skill_prompt.is_empty() {
-                system_prompt.push_str("\n\n");
-                system_prompt.push_str(&skill_prompt);
-            }
-
-            // Add active skill instructions
-            let active_skills = ctx.active_skills.borrow();
-            if !active_skills.is_empty() {
-                system_prompt.push_str("\n\n");
-                system_prompt.push_str(&active_skills.format_for_conversation());
-            }
-            drop(active_skills);
-
             let mut req_messages = vec![json!({
                 "role": "system",
                 "content": system_prompt
             })];
             req_messages.extend(messages.clone());
+            estimated_prompt_tokens = ctx.token_counter.borrow().count_request(
+                &target.model,
+                &req_messages,
+                &tool_schemas,
+            );
 
             let request = llm::ChatRequest {
                 model: target.model.clone(),
                 messages: req_messages,
                 tools: Some(tool_schemas.clone()),
                 tool_choice: Some("auto".to_string()),
+                sampling: sampling.clone(),
             };
 
-            client.chat(&request)?
+            let started = Instant::now();
+            let response = client.chat_with_cancel(&request, &ctx.cancel);
+            record_call_outcome(ctx, &target, started, &response);
+            response
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e)
+                if llm::is_context_overflow(&e)
+                    && overflow_recoveries < MAX_OVERFLOW_RECOVERIES =>
+            {
+                overflow_recoveries += 1;
+                match recover_context_overflow(
+                    ctx,
+                    &target,
+                    &system_prompt,
+                    &tool_schemas,
+                    messages,
+                    &e,
+                    overflow_recoveries,
+                ) {
+                    Some(next) => {
+                        if next != target {
+                            target = next;
+                            sampling = ctx.config.borrow().sampling_for(&target);
+                        }
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) if llm::is_backend_failure(&e) && failovers < MAX_FAILOVERS => {
+                failovers += 1;
+                match failover_target(ctx, &target, None, &format!("failed: {}", e)) {
+                    Some(next) => {
+                        target = next;
+                        sampling = ctx.config.borrow().sampling_for(&target);
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
         };
 
         // Track token usage from this LLM call
         if let Some(usage) = &response.usage {
+            ctx.token_counter.borrow_mut().observe(
+                &target.model,
+                estimated_prompt_tokens,
+                usage.prompt_tokens,
+            );
             turn_result.stats.input_tokens += usage.prompt_tokens;
             turn_result.stats.output_tokens += usage.completion_tokens;
 
             // Record cost for this operation
-            let turn_number = *ctx.turn_counter.borrow();
-            let op = ctx.session_costs.borrow_mut().record_operation(
-                turn_number,
-                &target.model,
+            let op = record_cost(
+                ctx,
+                &target,
                 usage.prompt_tokens,
                 usage.completion_tokens,
+                None,
             );
 
             // Log token usage to transcript
@@ -616,7 +679,7 @@ pub fn run_turn_sync(
                     tools::plan_mode::execute_exit(&ctx.plan_mode)
                 } else {
                     // Execute built-in tool
-                    tools::execute(name, args.clone(), &ctx.root, &bash_config)?
+                    tools::execute(name, args.clone(), &ctx.root, &bash_config, &ctx.session_id)?
                 }
             } else {
                 let reason = match decision {
@@ -705,6 +768,7 @@ pub fn run_turn_sync(
         turn_result.response_text = Some(collected_response);
     }
 
+    end_turn_span(&mut turn_span, &target, &turn_result.stats);
     Ok(turn_result)
 }
 
@@ -718,6 +782,7 @@ pub async fn run_turn(
     let mut turn_result = TurnResult::default();
     let mut collected_response = String::new();
     let _ = ctx.transcript.borrow_mut().user_message(user_input);
+    let mut turn_span = start_turn_span(ctx, None);
 
     messages.push(json!({
         "role": "user",
@@ -725,7 +790,7 @@ pub async fn run_turn(
     }));
 
     // Resolve target
-    let target = {
+    let mut target = {
         let current = ctx.current_target.borrow();
         if let Some(t) = current.as_ref() {
             t.clone()
@@ -737,6 +802,7 @@ pub async fn run_turn(
         }
     };
     let bash_config = ctx.config.borrow().bash.clone();
+    let mut sampling = ctx.config.borrow().sampling_for(&target);
 
     trace(ctx, "TARGET", &target.to_string());
 
@@ -814,84 +880,63 @@ pub async fn run_turn(
 
     // Initialize doom loop detector
     let mut doom_detector = DoomLoopDetector::new();
+    let mut overflow_recoveries = 0;
+    let mut failovers = 0;
 
     for iteration in 1..=max_iterations {
         trace(ctx, "ITER", &format!("Starting iteration {}", iteration));
 
-        // Auto-compaction: check if context is approaching limit
-        {
-            let context_config = &ctx.config.borrow().context;
-            if compact::needs_compaction(messages, context_config) {
-                trace(ctx, "COMPACT", "Auto-compacting context");
-                let mut backends = ctx.backends.borrow_mut();
-                if let Ok(client) = backends.get_client(&target.backend) {
-                    match compact::compact_messages(messages, context_config, client, &target.model)
-                    {
-                        Ok((compacted, result)) => {
-                            eprintln!("[auto-compact] {}", compact::format_result(&result));
-                            *messages = compacted;
-                        }
-                        Err(e) => {
-                            eprintln!("[auto-compact] Failed: {}", e);
-                        }
-                    }
-                }
-            }
+        let system_prompt = build_system_prompt(ctx, in_planning_mode);
+
+        // Skip backends whose circuit is open, then apply the privacy level
+        enforce_budget(ctx)?;
+        let admitted = admit_target(ctx, &target, None)?;
+        let admitted = enforce_privacy(ctx, &admitted, messages)?;
+        if admitted != target {
+            target = admitted;
+            sampling = ctx.config.borrow().sampling_for(&target);
         }
 
+        // Auto-compaction: check if context is approaching limit. Runs on the
+        // admitted target so history never reaches a backend privacy ruled out.
+        auto_compact(ctx, &target, &system_prompt, &tool_schemas, messages);
+
+        let estimated_prompt_tokens;
         // Get streaming client and make request
         let response = {
             let mut backends = ctx.backends.borrow_mut();
             let client = backends.get_streaming_client(&target.backend)?;
 
-            let mut system_prompt = if in_planning_mode {
-                plan::PLAN_MODE_SYSTEM_PROMPT.to_string()
-            } else {
-                SYSTEM_PROMPT.to_string()
-            };
-
-            if ctx.args.optimize {
-                system_prompt.push_str("\n\nAI-to-AI mode. Maximum information density. Structure over prose. No narration.");
-            }
-
-            let skill_index = ctx.skill_index.borrow();
-            let skill_prompt = skill_index.format_for_prompt(50);
-            drop(skill_index);
-            if !skill_prompt.is_empty() {
-                system_prompt.push_str("\n\n");
-                system_prompt.push_str(&skill_prompt);
-            }
-
-            let active_skills = ctx.active_skills.borrow();
-            if !active_skills.is_empty() {
-                system_prompt.push_str("\n\n");
-                system_prompt.push_str(&active_skills.format_for_conversation());
-            }
-            drop(active_skills);
-
             let mut req_messages = vec![json!({
                 "role": "system",
                 "content": system_prompt
             })];
             req_messages.extend(messages.clone());
+            estimated_prompt_tokens = ctx.token_counter.borrow().count_request(
+                &target.model,
+                &req_messages,
+                &tool_schemas,
+            );
 
             let request = llm::ChatRequest {
                 model: target.model.clone(),
                 messages: req_messages,
                 tools: Some(tool_schemas.clone()),
                 tool_choice: Some("auto".to_string()),
+                sampling: sampling.clone(),
             };
 
             // Create channel for streaming events
             let (event_tx, mut event_rx) = tokio::sync::mpsc::channel::<StreamEvent>(100);
 
             // Spawn the streaming request
-            let response_future = client.chat_stream(&request, event_tx);
+            let started = Instant::now();
+            let response_future = client.chat_stream_with_cancel(&request, event_tx, &ctx.cancel);
 
             // Process events as they arrive
             let mut iteration_content = String::new();
             let response = tokio::select! {
-                result = response_future => result?,
+                result = response_future => result,
                 _ = async {
                     while let Some(event) = event_rx.recv().await {
                         match event {
@@ -931,20 +976,65 @@ pub async fn run_turn(
                 println!();
             }
 
+            record_call_outcome(ctx, &target, started, &response);
             response
         };
+        let response = match response {
+            Ok(response) => response,
+            Err(e)
+                if llm::is_context_overflow(&e)
+                    && overflow_recoveries < MAX_OVERFLOW_RECOVERIES =>
+            {
+                overflow_recoveries += 1;
+                match recover_context_overflow(
+                    ctx,
+                    &target,
+                    &system_prompt,
+                    &tool_schemas,
+                    messages,
+                    &e,
+                    overflow_recoveries,
+                ) {
+                    Some(next) => {
+                        if next != target {
+                            target = next;
+                            sampling = ctx.config.borrow().sampling_for(&target);
+                        }
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) if llm::is_backend_failure(&e) && failovers < MAX_FAILOVERS => {
+                failovers += 1;
+                match failover_target(ctx, &target, None, &format!("failed: {}", e)) {
+                    Some(next) => {
+                        target = next;
+                        sampling = ctx.config.borrow().sampling_for(&target);
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
 
         // Track token usage
         if let Some(usage) = &response.usage {
+            ctx.token_counter.borrow_mut().observe(
+                &target.model,
+                estimated_prompt_tokens,
+                usage.prompt_tokens,
+            );
             turn_result.stats.input_tokens += usage.prompt_tokens;
             turn_result.stats.output_tokens += usage.completion_tokens;
 
-            let turn_number = *ctx.turn_counter.borrow();
-            let op = ctx.session_costs.borrow_mut().record_operation(
-                turn_number,
-                &target.model,
+            let op = record_cost(
+                ctx,
+                &target,
                 usage.prompt_tokens,
                 usage.completion_tokens,
+                None,
             );
 
             let _ = ctx.transcript.borrow_mut().token_usage(
@@ -1214,7 +1304,7 @@ pub async fn run_turn(
                 } else if name == "ExitPlanMode" {
                     tools::plan_mode::execute_exit(&ctx.plan_mode)
                 } else {
-                    tools::execute(name, args.clone(), &ctx.root, &bash_config)?
+                    tools::execute(name, args.clone(), &ctx.root, &bash_config, &ctx.session_id)?
                 }
             } else {
                 let reason = match decision {
@@ -1297,6 +1387,7 @@ pub async fn run_turn(
         turn_result.response_text = Some(collected_response);
     }
 
+    end_turn_span(&mut turn_span, &target, &turn_result.stats);
     Ok(turn_result)
 }
 
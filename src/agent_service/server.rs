@@ -1,16 +1,22 @@
 //! Unix socket server for the agent daemon.
 //! Listens for NDJSON requests and streams NDJSON events back.
+//!
+//! Each connection is read while its requests run, so a `Cancel` can arrive
+//! on the same connection as the turn it stops, or on another one. Turns of
+//! one session run one at a time, so each starts from the history the
+//! previous one saved.
 
 use crate::agent_service::turn_state::TurnStateStore;
 use crate::agent_service::worker::{self, WorkerConfig};
-use crate::protocol::internal::{AgentEvent, AgentMethod, AgentRequest};
+use crate::metrics;
+use crate::protocol::internal::{AgentEvent, AgentMethod, AgentRequest, UsageStats};
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
-use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::Path;
-use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::thread;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 /// Configuration for the agent server
 pub struct AgentServerConfig {
@@ -35,11 +41,26 @@ impl Default for AgentServerConfig {
     }
 }
 
+/// A running turn that a `Cancel` request can stop
+struct InFlight {
+    session_id: String,
+    cancel: CancellationToken,
+}
+
+/// Running turns, each under its own key so turns sharing a session don't
+/// replace each other
+type InFlightMap = Arc<Mutex<HashMap<String, InFlight>>>;
+
+/// Per-session locks that queue a session's turns behind each other
+type SessionLocks = Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>;
+
 /// The agent daemon server
 pub struct AgentServer {
     config: AgentServerConfig,
     /// Track in-flight requests for cancellation
-    in_flight: Arc<Mutex<HashMap<String, mpsc::Sender<()>>>>,
+    in_flight: InFlightMap,
+    /// Serialize turns per session
+    session_locks: SessionLocks,
     /// Turn state store for yield/resume
     turn_store: Arc<TurnStateStore>,
 }
@@ -54,12 +75,22 @@ impl AgentServer {
         Self {
             config,
             in_flight: Arc::new(Mutex::new(HashMap::new())),
+            session_locks: Arc::new(Mutex::new(HashMap::new())),
             turn_store,
         }
     }
 
-    /// Run the server (blocking)
+    /// Run the server (blocking) on a multi-threaded tokio runtime
     pub fn run(&self) -> std::io::Result<()> {
+        tokio::runtime::Builder::new_multi_thread()
+            .thread_name("brainpro-agent")
+            .enable_all()
+            .build()?
+            .block_on(self.serve())
+    }
+
+    /// Accept connections until the listener fails
+    async fn serve(&self) -> std::io::Result<()> {
         // Remove existing socket if present
         let socket_path = Path::new(&self.config.socket_path);
         if socket_path.exists() {
@@ -77,16 +108,24 @@ impl AgentServer {
             self.config.socket_path, self.config.gateway_mode, self.config.persona
         );
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
                     let in_flight = Arc::clone(&self.in_flight);
+                    let session_locks = Arc::clone(&self.session_locks);
                     let turn_store = Arc::clone(&self.turn_store);
                     let gateway_mode = self.config.gateway_mode;
                     let persona = self.config.persona.clone();
-                    thread::spawn(move || {
-                        if let Err(e) =
-                            handle_connection(stream, in_flight, turn_store, gateway_mode, &persona)
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(
+                            stream,
+                            in_flight,
+                            session_locks,
+                            turn_store,
+                            gateway_mode,
+                            persona,
+                        )
+                        .await
                         {
                             eprintln!("[agent] Connection error: {}", e);
                         }
@@ -97,33 +136,76 @@ impl AgentServer {
                 }
             }
         }
-
-        Ok(())
     }
 }
 
-/// Handle a single connection from the gateway
-fn handle_connection(
-    stream: UnixStream,
-    in_flight: Arc<Mutex<HashMap<String, mpsc::Sender<()>>>>,
-    turn_store: Arc<TurnStateStore>,
-    gateway_mode: bool,
-    persona: &str,
-) -> std::io::Result<()> {
-    let mut reader = BufReader::new(stream.try_clone()?);
-    let mut writer = stream;
+/// Cancel every running turn of a session. Returns how many were cancelled.
+fn cancel_session(in_flight: &InFlightMap, session_id: &str) -> usize {
+    let flights = in_flight.lock().unwrap();
+    let mut cancelled = 0;
+    for flight in flights.values().filter(|f| f.session_id == session_id) {
+        flight.cancel.cancel();
+        cancelled += 1;
+    }
+    cancelled
+}
 
-    let mut line = String::new();
+/// The lock a session's turns take before they run
+fn session_lock(locks: &SessionLocks, session_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+    Arc::clone(
+        locks
+            .lock()
+            .unwrap()
+            .entry(session_id.to_string())
+            .or_default(),
+    )
+}
 
-    loop {
-        line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
+/// Forget a session's lock once no turn holds or waits for it
+fn release_session_lock(locks: &SessionLocks, session_id: &str) {
+    let mut locks = locks.lock().unwrap();
+    if locks
+        .get(session_id)
+        .is_some_and(|lock| Arc::strong_count(lock) == 1)
+    {
+        locks.remove(session_id);
+    }
+}
 
-        if bytes_read == 0 {
-            // Connection closed
+/// Write events to the connection in the order they arrive
+async fn write_events(
+    mut writer: tokio::net::unix::OwnedWriteHalf,
+    mut events: mpsc::UnboundedReceiver<AgentEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        let json = event.to_ndjson();
+        if let Err(e) = writer.write_all(json.as_bytes()).await {
+            eprintln!("[agent] Write error: {}", e);
+            break;
+        }
+        if let Err(e) = writer.flush().await {
+            eprintln!("[agent] Flush error: {}", e);
             break;
         }
+    }
+}
 
+/// Handle a single connection from the gateway. Requests run concurrently
+/// and their events share the connection, each tagged with its request id.
+async fn handle_connection(
+    stream: UnixStream,
+    in_flight: InFlightMap,
+    session_locks: SessionLocks,
+    turn_store: Arc<TurnStateStore>,
+    gateway_mode: bool,
+    persona: String,
+) -> std::io::Result<()> {
+    let (reader, writer) = stream.into_split();
+    let (out_tx, out_rx) = mpsc::unbounded_channel();
+    tokio::spawn(write_events(writer, out_rx));
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
         let line = line.trim();
         if line.is_empty() {
             continue;
@@ -133,29 +215,21 @@ fn handle_connection(
         let request: AgentRequest = match serde_json::from_str(line) {
             Ok(r) => r,
             Err(e) => {
-                let error = AgentEvent::error("unknown", "parse_error", &e.to_string());
-                let _ = writer.write_all(error.to_ndjson().as_bytes());
+                let _ = out_tx.send(AgentEvent::error("unknown", "parse_error", &e.to_string()));
                 continue;
             }
         };
 
         let request_id = request.id.clone();
 
-        // Handle cancellation requests specially
+        // Cancel stops the session's running turns, wherever they started
         if request.method == AgentMethod::Cancel {
-            let mut flights = in_flight.lock().unwrap();
-            if let Some(cancel_tx) = flights.remove(&request.session_id) {
-                let _ = cancel_tx.send(());
-                let event = AgentEvent::done(
-                    &request_id,
-                    crate::protocol::internal::UsageStats::default(),
-                );
-                let _ = writer.write_all(event.to_ndjson().as_bytes());
+            let event = if cancel_session(&in_flight, &request.session_id) > 0 {
+                AgentEvent::done(&request_id, UsageStats::default())
             } else {
-                let event =
-                    AgentEvent::error(&request_id, "not_found", "No in-flight request to cancel");
-                let _ = writer.write_all(event.to_ndjson().as_bytes());
-            }
+                AgentEvent::error(&request_id, "not_found", "No in-flight request to cancel")
+            };
+            let _ = out_tx.send(event);
             continue;
         }
 
@@ -163,34 +237,53 @@ fn handle_connection(
         let worker_config = WorkerConfig {
             gateway_mode,
             turn_store: Arc::clone(&turn_store),
-            persona: persona.to_string(),
+            persona: request.persona.clone().unwrap_or_else(|| persona.clone()),
+            cancel: CancellationToken::new(),
         };
 
-        // Spawn worker and collect events
-        let handle = worker::spawn_worker_with_config(request, worker_config);
-
-        // Stream events back to the client
-        for event in handle.events {
-            let json = event.to_ndjson();
-            if let Err(e) = writer.write_all(json.as_bytes()) {
-                eprintln!("[agent] Write error: {}", e);
-                break;
-            }
-            if let Err(e) = writer.flush() {
-                eprintln!("[agent] Flush error: {}", e);
-                break;
-            }
+        // Register turns so a Cancel request can stop them
+        let is_turn = matches!(
+            request.method,
+            AgentMethod::RunTurn | AgentMethod::ResumeTurn
+        );
+        let flight_id = uuid::Uuid::new_v4().to_string();
+        if is_turn {
+            in_flight.lock().unwrap().insert(
+                flight_id.clone(),
+                InFlight {
+                    session_id: request.session_id.clone(),
+                    cancel: worker_config.cancel.clone(),
+                },
+            );
+        }
+        let in_turn = is_turn.then(metrics::track_turn);
+        let session_id = request.session_id.clone();
+        let lock = is_turn.then(|| session_lock(&session_locks, &session_id));
 
-            // Check if this was the terminal event
-            match &event.event {
-                crate::protocol::internal::AgentEventType::Done { .. }
-                | crate::protocol::internal::AgentEventType::Error { .. }
-                | crate::protocol::internal::AgentEventType::Yield { .. } => {
+        // Wait for the session's earlier turns, then stream events back
+        // until the worker finishes
+        let out_tx = out_tx.clone();
+        let in_flight = Arc::clone(&in_flight);
+        let session_locks = Arc::clone(&session_locks);
+        tokio::spawn(async move {
+            let _in_turn = in_turn;
+            let turn = match &lock {
+                Some(lock) => Some(Arc::clone(lock).lock_owned().await),
+                None => None,
+            };
+            let mut handle = worker::spawn_worker_with_config(request, worker_config);
+            while let Some(event) = handle.events.recv().await {
+                if out_tx.send(event).is_err() {
                     break;
                 }
-                _ => {}
             }
-        }
+            in_flight.lock().unwrap().remove(&flight_id);
+            if lock.is_some() {
+                drop(turn);
+                drop(lock);
+                release_session_lock(&session_locks, &session_id);
+            }
+        });
     }
 
     Ok(())
@@ -229,3 +322,56 @@ pub fn run_with_persona(
     });
     server.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flight(in_flight: &InFlightMap, key: &str, session_id: &str) -> CancellationToken {
+        let cancel = CancellationToken::new();
+        in_flight.lock().unwrap().insert(
+            key.to_string(),
+            InFlight {
+                session_id: session_id.to_string(),
+                cancel: cancel.clone(),
+            },
+        );
+        cancel
+    }
+
+    #[test]
+    fn test_cancel_reaches_every_turn_of_the_session() {
+        let in_flight: InFlightMap = Arc::new(Mutex::new(HashMap::new()));
+        let first = flight(&in_flight, "a", "s1");
+        let second = flight(&in_flight, "b", "s1");
+        let other = flight(&in_flight, "c", "s2");
+
+        // One turn finishing leaves the other registered
+        in_flight.lock().unwrap().remove("a");
+        assert_eq!(cancel_session(&in_flight, "s1"), 1);
+        assert!(!first.is_cancelled());
+        assert!(second.is_cancelled());
+        assert!(!other.is_cancelled());
+        assert_eq!(cancel_session(&in_flight, "missing"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_turns_run_one_at_a_time() {
+        let locks: SessionLocks = Arc::new(Mutex::new(HashMap::new()));
+        let first = session_lock(&locks, "s1").lock_owned().await;
+
+        // A second turn of the session waits; another session doesn't
+        let second = session_lock(&locks, "s1");
+        assert!(second.try_lock().is_err());
+        assert!(session_lock(&locks, "s2").try_lock().is_ok());
+
+        drop(first);
+        assert!(second.try_lock().is_ok());
+
+        // The lock is forgotten once nobody holds it
+        drop(second);
+        release_session_lock(&locks, "s1");
+        release_session_lock(&locks, "s2");
+        assert!(locks.lock().unwrap().is_empty());
+    }
+}
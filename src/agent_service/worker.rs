@@ -1,17 +1,29 @@
 //! Worker that wraps agent.rs to emit streaming NDJSON events.
 //!
-//! In gateway mode, the worker yields when tool approval is needed,
-//! saving state for later resumption.
+//! Requests run as tasks on the daemon's tokio runtime. Gateway turns await
+//! the async LLM client, so a turn waiting on a backend holds no thread;
+//! tools, compaction and non-gateway turns still block and run through
+//! `block_in_place`.
+//!
+//! In gateway mode, the worker yields when tool approval is needed or a
+//! node tool has to run on a gateway client, saving state for later
+//! resumption.
 
 use crate::agent_service::turn_state::{PendingToolCall, TurnState, TurnStateStore};
+use crate::cli::Context;
+use crate::config::{Config, Target};
 use crate::protocol::internal::{AgentEvent, AgentMethod, AgentRequest, UsageStats, YieldReason};
 use serde_json::{json, Value};
-use std::sync::{mpsc, Arc};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 /// Result of running a worker task
 pub struct WorkerHandle {
     /// Channel to receive streaming events
-    pub events: mpsc::Receiver<AgentEvent>,
+    pub events: mpsc::UnboundedReceiver<AgentEvent>,
+    /// Token that cancels the worker's in-flight work
+    pub cancel: CancellationToken,
 }
 
 /// Configuration for the worker
@@ -22,6 +34,8 @@ pub struct WorkerConfig {
     pub turn_store: Arc<TurnStateStore>,
     /// Persona to use (mrcode or mrbot)
     pub persona: String,
+    /// Cancels this request's in-flight LLM calls and remaining iterations
+    pub cancel: CancellationToken,
 }
 
 impl Default for WorkerConfig {
@@ -30,23 +44,23 @@ impl Default for WorkerConfig {
             gateway_mode: false,
             turn_store: Arc::new(TurnStateStore::default()),
             persona: "mrbot".to_string(),
+            cancel: CancellationToken::new(),
         }
     }
 }
 
-/// Run the agent in a blocking fashion, sending events to a channel.
-/// This wraps the existing synchronous agent::run_turn function.
-pub fn run_agent_task(
+/// Run a request, sending its events to a channel
+pub async fn run_agent_task(
     request: AgentRequest,
-    event_tx: mpsc::Sender<AgentEvent>,
+    event_tx: mpsc::UnboundedSender<AgentEvent>,
 ) -> Result<(), String> {
-    run_agent_task_with_config(request, event_tx, &WorkerConfig::default())
+    run_agent_task_with_config(request, event_tx, &WorkerConfig::default()).await
 }
 
 /// Run agent task with explicit configuration
-pub fn run_agent_task_with_config(
+pub async fn run_agent_task_with_config(
     request: AgentRequest,
-    event_tx: mpsc::Sender<AgentEvent>,
+    event_tx: mpsc::UnboundedSender<AgentEvent>,
     config: &WorkerConfig,
 ) -> Result<(), String> {
     let id = &request.id;
@@ -56,6 +70,27 @@ pub fn run_agent_task_with_config(
             let _ = event_tx.send(AgentEvent::pong(id));
             Ok(())
         }
+        AgentMethod::Metrics => {
+            let _ = event_tx.send(AgentEvent::metrics(id, crate::metrics::prometheus()));
+            Ok(())
+        }
+        AgentMethod::SessionGet => {
+            let event = match crate::session::load_session(&request.session_id) {
+                Ok(saved) => AgentEvent::session_history(
+                    id,
+                    &saved.session_id,
+                    saved.messages,
+                    saved.turn_count,
+                ),
+                Err(_) => AgentEvent::error(
+                    id,
+                    "session_not_found",
+                    &format!("No history for session {}", request.session_id),
+                ),
+            };
+            let _ = event_tx.send(event);
+            Ok(())
+        }
         AgentMethod::Cancel => {
             // Cancel is handled at the server level
             let _ = event_tx.send(AgentEvent::error(
@@ -67,17 +102,22 @@ pub fn run_agent_task_with_config(
         }
         AgentMethod::RunTurn => {
             if config.gateway_mode {
-                run_turn_gateway_mode(request, event_tx, config)
+                run_turn_gateway_mode(request, event_tx, config).await
             } else {
-                run_turn_task(request, event_tx)
+                // The original agent loop is synchronous
+                tokio::task::block_in_place(|| run_turn_task(request, event_tx, config))
             }
         }
-        AgentMethod::ResumeTurn => run_resume_task(request, event_tx, config),
+        AgentMethod::ResumeTurn => run_resume_task(request, event_tx, config).await,
     }
 }
 
 /// Run a turn in non-gateway mode (original behavior)
-fn run_turn_task(request: AgentRequest, event_tx: mpsc::Sender<AgentEvent>) -> Result<(), String> {
+fn run_turn_task(
+    request: AgentRequest,
+    event_tx: mpsc::UnboundedSender<AgentEvent>,
+    config: &WorkerConfig,
+) -> Result<(), String> {
     use crate::context_factory::{
         build_context, load_config_with_defaults, parse_working_dir, resolve_target,
     };
@@ -103,15 +143,18 @@ fn run_turn_task(request: AgentRequest, event_tx: mpsc::Sender<AgentEvent>) -> R
 
     // Build context
     let ctx = match build_context(&cfg, root, request.session_id.clone(), target) {
-        Ok(c) => c,
+        Ok(mut c) => {
+            c.cancel = config.cancel.clone();
+            c
+        }
         Err(e) => {
             let _ = event_tx.send(AgentEvent::error(id, "context_error", &e));
             return Ok(());
         }
     };
 
-    // Convert request messages to mutable vec
-    let mut messages: Vec<Value> = request.messages;
+    // Convert request messages to mutable vec, picking up earlier turns
+    let mut messages: Vec<Value> = with_history(&request.session_id, request.messages);
 
     // Extract user message from the last message if it exists
     let user_input = messages
@@ -146,23 +189,7 @@ fn run_turn_task(request: AgentRequest, event_tx: mpsc::Sender<AgentEvent>) -> R
 
             // Check for pending questions
             if let Some(pending) = result.pending_question {
-                let questions: Vec<Value> = pending
-                    .questions
-                    .iter()
-                    .map(|q| {
-                        json!({
-                            "question": q.question,
-                            "header": q.header,
-                            "options": q.options.iter().map(|o| {
-                                json!({
-                                    "label": o.label,
-                                    "description": o.description
-                                })
-                            }).collect::<Vec<_>>(),
-                            "multi_select": q.multi_select
-                        })
-                    })
-                    .collect();
+                let questions = questions_json(&pending.questions);
 
                 let _ = event_tx.send(AgentEvent::awaiting_input(
                     id,
@@ -171,14 +198,20 @@ fn run_turn_task(request: AgentRequest, event_tx: mpsc::Sender<AgentEvent>) -> R
                 ));
             }
 
+            save_history(&ctx.session_id, &messages, request.ephemeral);
+
             // Send done event with usage stats
             let usage = UsageStats {
                 input_tokens: result.stats.input_tokens,
                 output_tokens: result.stats.output_tokens,
                 tool_uses: result.stats.tool_uses,
+                cost_usd: ctx.session_costs.borrow().total_cost(),
             };
             let _ = event_tx.send(AgentEvent::done(id, usage));
         }
+        Err(e) if crate::llm::is_cancelled(&e) => {
+            let _ = event_tx.send(AgentEvent::error(id, "cancelled", "Request cancelled"));
+        }
         Err(e) => {
             let _ = event_tx.send(AgentEvent::error(id, "agent_error", &e.to_string()));
         }
@@ -187,343 +220,615 @@ fn run_turn_task(request: AgentRequest, event_tx: mpsc::Sender<AgentEvent>) -> R
     Ok(())
 }
 
-/// Run a turn in gateway mode with yield/resume semantics
-fn run_turn_gateway_mode(
-    request: AgentRequest,
-    event_tx: mpsc::Sender<AgentEvent>,
-    config: &WorkerConfig,
-) -> Result<(), String> {
-    use crate::context_factory::{
-        build_context, load_config_with_defaults, parse_working_dir, resolve_target,
-    };
-    use crate::llm::{self, LlmClient};
-    use crate::policy::Decision;
-    use crate::tools;
-
-    let id = &request.id;
-    let turn_id = uuid::Uuid::new_v4().to_string();
-
-    // Parse working directory
-    let root = parse_working_dir(request.working_dir.as_ref());
-
-    // Load config with defaults applied
-    let cfg = load_config_with_defaults()?;
-
-    // Resolve target
-    let target = resolve_target(request.target.as_ref(), &cfg);
-    if target.is_none() {
-        let _ = event_tx.send(AgentEvent::error(id, "no_target", "No target configured"));
-        return Ok(());
-    }
-    let target = target.unwrap();
-
-    // Extract user message
-    let mut messages: Vec<Value> = request.messages.clone();
-    let user_input = messages
-        .last()
-        .and_then(|m| m.get("content"))
-        .and_then(|c| c.as_str())
-        .unwrap_or("")
-        .to_string();
-
-    if user_input.is_empty() {
-        let _ = event_tx.send(AgentEvent::error(
-            id,
-            "no_input",
-            "No user message provided",
-        ));
-        return Ok(());
-    }
-
-    // Build system prompt
-    let system_prompt = r#"You are an agentic coding assistant running locally.
+/// System prompt for turns run through the gateway
+const GATEWAY_SYSTEM_PROMPT: &str = r#"You are an agentic coding assistant running locally.
 You can only access files via tools. All paths are relative to the project root.
 Use Glob/Grep to find files before Read. Before Edit/Write, explain what you will change.
 Use Bash for running builds, tests, formatters, and git operations.
 Never use curl or wget - they are blocked by policy.
 Keep edits minimal and precise."#;
 
-    // Get tool schemas
-    let schema_opts = tools::SchemaOptions::new(false);
-    let tool_schemas = tools::schemas_with_task(&schema_opts);
+/// Most LLM calls a gateway turn makes before it stops, counted afresh
+/// each time the turn resumes
+const MAX_ITERATIONS: usize = 12;
 
-    let bash_config = cfg.bash.clone();
+/// Tool call id used for the synthetic call that carries a budget yield
+const BUDGET_CALL_ID: &str = "budget";
 
-    // Build context for tool execution
-    let ctx = match build_context(
-        &cfg,
-        root.clone(),
-        request.session_id.clone(),
-        Some(target.clone()),
-    ) {
-        Ok(c) => c,
-        Err(e) => {
-            let _ = event_tx.send(AgentEvent::error(id, "context_error", &e));
-            return Ok(());
-        }
-    };
+/// Questions of an AskUserQuestion call in the form clients receive them
+fn questions_json(questions: &[crate::tools::ask_user::Question]) -> Vec<Value> {
+    questions
+        .iter()
+        .map(|q| {
+            json!({
+                "question": q.question,
+                "header": q.header,
+                "options": q.options.iter().map(|o| {
+                    json!({
+                        "label": o.label,
+                        "description": o.description
+                    })
+                }).collect::<Vec<_>>(),
+                "multi_select": q.multi_select
+            })
+        })
+        .collect()
+}
 
-    // Build messages for LLM
-    let mut req_messages = vec![json!({
-        "role": "system",
-        "content": system_prompt
-    })];
-    req_messages.extend(messages.clone());
-
-    // Token stats
-    let mut input_tokens = 0u64;
-    let mut output_tokens = 0u64;
-    let mut tool_uses = 0u64;
-
-    const MAX_ITERATIONS: usize = 12;
-
-    for _iteration in 1..=MAX_ITERATIONS {
-        // Get client for target's backend
-        let response = {
-            let mut backends = ctx.backends.borrow_mut();
-            let client = backends
-                .get_client(&target.backend)
-                .map_err(|e| format!("Backend error: {}", e))?;
+/// The synthetic call a turn yields on once a cost budget is spent, so the
+/// client can approve running over it
+fn budget_call(ctx: &Context) -> Option<PendingToolCall> {
+    let status = crate::agent::core::exceeded_budget(ctx)?;
+    Some(PendingToolCall {
+        tool_call_id: BUDGET_CALL_ID.to_string(),
+        tool_name: "CostBudget".to_string(),
+        tool_args: json!({
+            "budget": status.kind.as_str(),
+            "period": status.period,
+            "limit_usd": status.limit_usd,
+            "spent_usd": status.spent_usd,
+        }),
+        policy_rule: None,
+        questions: None,
+    })
+}
 
-            let request = llm::ChatRequest {
-                model: target.model.clone(),
-                messages: req_messages.clone(),
-                tools: Some(tool_schemas.clone()),
-                tool_choice: Some("auto".to_string()),
-            };
+/// Whether a tool runs on a gateway node rather than in the daemon
+fn is_node_tool(name: &str) -> bool {
+    name.starts_with("node.")
+}
 
-            client
-                .chat(&request)
-                .map_err(|e| format!("LLM error: {}", e))?
-        };
+/// Node tool schemas the gateway offered for this turn. Anything else is
+/// dropped so a request can't shadow the built-in tools.
+fn node_tool_schemas(request: &AgentRequest) -> Vec<Value> {
+    request
+        .tools
+        .iter()
+        .flatten()
+        .filter(|schema| {
+            schema
+                .pointer("/function/name")
+                .and_then(|n| n.as_str())
+                .is_some_and(is_node_tool)
+        })
+        .cloned()
+        .collect()
+}
 
-        // Track usage
-        if let Some(usage) = &response.usage {
-            input_tokens += usage.prompt_tokens;
-            output_tokens += usage.completion_tokens;
+/// Add an operator-approved allow rule to this turn's policy and save it to
+/// the local allow list of the project the turn works in
+fn save_allow_rule(ctx: &Context, rule: &str) {
+    ctx.policy
+        .borrow_mut()
+        .config_mut()
+        .approved
+        .push(rule.to_string());
+    let path = ctx.root.join(".brainpro").join("config.local.toml");
+    let local = if path.exists() {
+        Config::load_from(&path)
+    } else {
+        Ok(Config::default())
+    };
+    let saved = local.and_then(|mut local| {
+        if !local.permissions.allow.iter().any(|r| r == rule) {
+            local.permissions.allow.push(rule.to_string());
         }
+        local.save_local_permissions(&ctx.root)
+    });
+    if let Err(e) = saved {
+        eprintln!("[agent] Failed to save permission rule {}: {}", rule, e);
+    }
+}
 
-        if response.choices.is_empty() {
-            break;
+/// Point the assistant's tool call at the arguments that actually ran, so
+/// the history matches the result the model sees
+fn replace_tool_call_args(messages: &mut [Value], tool_call_id: &str, args: &Value) {
+    let arguments = Value::String(args.to_string());
+    for message in messages.iter_mut().rev() {
+        let Some(calls) = message.get_mut("tool_calls").and_then(|c| c.as_array_mut()) else {
+            continue;
+        };
+        if let Some(call) = calls
+            .iter_mut()
+            .find(|c| c.get("id").and_then(|i| i.as_str()) == Some(tool_call_id))
+        {
+            call["function"]["arguments"] = arguments;
+            return;
         }
+    }
+}
 
-        let choice = &response.choices[0];
-        let msg = &choice.message;
+/// A gateway turn's context and conversation. New turns and resumed ones
+/// both run the agent loop through [`GatewayTurn::run`].
+struct GatewayTurn {
+    /// Request id the turn's events are sent under
+    id: String,
+    cfg: Config,
+    ctx: Context,
+    target: Target,
+    sampling: crate::llm::SamplingParams,
+    tool_schemas: Vec<Value>,
+    /// Conversation without the system prompt, as it is stored and yielded
+    messages: Vec<Value>,
+    span: crate::spans::SpanGuard,
+}
+
+impl GatewayTurn {
+    /// Build the context a turn runs in. A missing target or a context
+    /// error is reported to the client and gives `None`.
+    fn start(
+        request: &AgentRequest,
+        config: &WorkerConfig,
+        event_tx: &mpsc::UnboundedSender<AgentEvent>,
+        session_id: &str,
+        working_dir: Option<&String>,
+        target: Option<&String>,
+    ) -> Result<Option<Self>, String> {
+        use crate::agent::core::start_turn_span;
+        use crate::context_factory::{
+            build_context, load_config_with_defaults, parse_working_dir, resolve_target,
+        };
 
-        // Send thinking/content event
-        if let Some(content) = &msg.content {
-            if !content.is_empty() {
-                let _ = event_tx.send(AgentEvent::content(id, content));
+        let id = &request.id;
+        let cfg = load_config_with_defaults()?;
+        let Some(target) = resolve_target(target, &cfg) else {
+            let _ = event_tx.send(AgentEvent::error(id, "no_target", "No target configured"));
+            return Ok(None);
+        };
+        let root = parse_working_dir(working_dir);
+        let ctx = match build_context(&cfg, root, session_id.to_string(), Some(target.clone())) {
+            Ok(mut c) => {
+                c.cancel = config.cancel.clone();
+                c
             }
+            Err(e) => {
+                let _ = event_tx.send(AgentEvent::error(id, "context_error", &e));
+                return Ok(None);
+            }
+        };
+        ctx.policy
+            .borrow_mut()
+            .config_mut()
+            .approved
+            .extend(request.allow_rules.iter().cloned());
+        let span = start_turn_span(&ctx, Some(&config.persona));
+
+        let schema_opts = crate::tools::SchemaOptions::new(false);
+        let mut tool_schemas = crate::tools::schemas_with_task(&schema_opts);
+        tool_schemas.extend(node_tool_schemas(request));
+
+        Ok(Some(Self {
+            id: id.clone(),
+            sampling: persona_sampling(&cfg, &target, &config.persona),
+            cfg,
+            ctx,
+            target,
+            tool_schemas,
+            messages: Vec::new(),
+            span,
+        }))
+    }
+
+    /// Switch to another target along with its sampling parameters
+    fn set_target(&mut self, target: Target, persona: &str) {
+        if target != self.target {
+            self.sampling = persona_sampling(&self.cfg, &target, persona);
+            self.target = target;
         }
+    }
 
-        let tool_calls = match &msg.tool_calls {
-            Some(tc) if !tc.is_empty() => tc,
-            _ => {
-                // No tool calls, we're done
-                break;
+    /// The conversation as sent to the LLM, system prompt first
+    fn request_messages(&self) -> Vec<Value> {
+        let mut messages = vec![json!({
+            "role": "system",
+            "content": GATEWAY_SYSTEM_PROMPT
+        })];
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// Add a tool's result to the conversation
+    fn push_tool_result(&mut self, tool_call_id: &str, result: &Value) {
+        self.messages.push(json!({
+            "role": "tool",
+            "tool_call_id": tool_call_id,
+            "content": serde_json::to_string(result).unwrap_or_default()
+        }));
+    }
+
+    /// Run a tool in the daemon and report its result
+    fn run_tool(
+        &self,
+        event_tx: &mpsc::UnboundedSender<AgentEvent>,
+        call: &PendingToolCall,
+    ) -> Result<Value, String> {
+        let tool_start = std::time::Instant::now();
+        let result = tokio::task::block_in_place(|| {
+            execute_tool(
+                &self.ctx,
+                &call.tool_name,
+                call.tool_args.clone(),
+                &self.cfg.bash,
+            )
+        })?;
+        let duration_ms = tool_start.elapsed().as_millis() as u64;
+
+        let ok = result.get("error").is_none();
+        let _ = event_tx.send(AgentEvent::tool_result(
+            &self.id,
+            &call.tool_name,
+            &call.tool_call_id,
+            result.clone(),
+            ok,
+            duration_ms,
+        ));
+        Ok(result)
+    }
+
+    /// Refuse a tool call the policy denies and report the refusal
+    fn deny_tool(
+        &self,
+        event_tx: &mpsc::UnboundedSender<AgentEvent>,
+        call: &PendingToolCall,
+        message: &str,
+        rule: Option<&str>,
+    ) -> Value {
+        let result = json!({
+            "error": {
+                "code": "permission_denied",
+                "message": format!("{}{}", message,
+                    rule.map(|r| format!(" (rule: {})", r)).unwrap_or_default())
+            }
+        });
+        let _ = event_tx.send(AgentEvent::tool_result(
+            &self.id,
+            &call.tool_name,
+            &call.tool_call_id,
+            result.clone(),
+            false,
+            0,
+        ));
+        result
+    }
+
+    /// Save the turn with `call` pending and yield it to the gateway
+    fn yield_call(
+        &self,
+        config: &WorkerConfig,
+        event_tx: &mpsc::UnboundedSender<AgentEvent>,
+        call: PendingToolCall,
+        reason: YieldReason,
+    ) {
+        let turn_id = uuid::Uuid::new_v4().to_string();
+        let id = &self.id;
+        let event = match reason {
+            YieldReason::AwaitingApproval => AgentEvent::yield_approval(
+                id,
+                &turn_id,
+                &call.tool_call_id,
+                &call.tool_name,
+                call.tool_args.clone(),
+                call.policy_rule.clone(),
+            ),
+            YieldReason::AwaitingInput => AgentEvent::yield_input(
+                id,
+                &turn_id,
+                &call.tool_call_id,
+                call.questions.clone().unwrap_or_default(),
+            ),
+            YieldReason::BudgetExceeded => {
+                AgentEvent::yield_budget(id, &turn_id, &call.tool_call_id, call.tool_args.clone())
             }
+            YieldReason::NodeTool => AgentEvent::yield_node_tool(
+                id,
+                &turn_id,
+                &call.tool_call_id,
+                &call.tool_name,
+                call.tool_args.clone(),
+            ),
         };
+        let state = TurnState::new(
+            turn_id,
+            self.ctx.session_id.clone(),
+            id.clone(),
+            self.messages.clone(),
+            call,
+            reason,
+            Some(self.target.to_string()),
+            Some(self.ctx.root.to_string_lossy().to_string()),
+        );
+        let _ = config.turn_store.save(state);
+        let _ = event_tx.send(event);
+    }
 
-        // Add assistant message to conversation
-        let assistant_msg = json!({
-            "role": "assistant",
-            "content": msg.content,
-            "tool_calls": tool_calls
-        });
-        req_messages.push(assistant_msg.clone());
-        messages.push(assistant_msg);
-
-        // Process each tool call
-        for tc in tool_calls {
-            let name = &tc.function.name;
-            let args: Value = serde_json::from_str(&tc.function.arguments).unwrap_or(json!({}));
-
-            tool_uses += 1;
-
-            // Send tool call event
-            let _ = event_tx.send(AgentEvent::tool_call(id, name, args.clone(), &tc.id));
-
-            // Check policy decision
-            let (decision, matched_rule) = ctx.policy.borrow().decide(name, &args);
-
-            match decision {
-                Decision::Allow => {
-                    // Execute the tool
-                    let tool_start = std::time::Instant::now();
-                    let result = execute_tool(&ctx, name, args.clone(), &bash_config)?;
-                    let duration_ms = tool_start.elapsed().as_millis() as u64;
-
-                    let ok = result.get("error").is_none();
-                    let _ = event_tx.send(AgentEvent::tool_result(
-                        id,
-                        name,
-                        &tc.id,
-                        result.clone(),
-                        ok,
-                        duration_ms,
-                    ));
-
-                    // Add tool result to conversation
-                    let tool_msg = json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "content": serde_json::to_string(&result).unwrap_or_default()
+    /// Run the agent loop until the turn completes, fails or yields. With
+    /// `compact_history`, the history is compacted once the privacy level
+    /// has picked the target, if it has grown too big.
+    ///
+    /// The turn owns its context and only borrows it between awaits, so the
+    /// loop can run as a task on the daemon's multi-threaded runtime.
+    async fn run(
+        mut self,
+        request: &AgentRequest,
+        config: &WorkerConfig,
+        event_tx: &mpsc::UnboundedSender<AgentEvent>,
+        mut compact_history: bool,
+    ) -> Result<(), String> {
+        use crate::agent::core::{
+            admit_target, auto_compact, end_turn_span, enforce_privacy, failover_target,
+            record_call_outcome, record_cost, recover_context_overflow, MAX_FAILOVERS,
+            MAX_OVERFLOW_RECOVERIES,
+        };
+        use crate::llm;
+        use crate::policy::Decision;
+
+        let id = self.id.clone();
+        let persona = config.persona.as_str();
+        let mut stats = crate::agent::CommandStats::default();
+        let mut overflow_recoveries = 0;
+        let mut failovers = 0;
+
+        for _iteration in 1..=MAX_ITERATIONS {
+            if self.ctx.cancel.is_cancelled() {
+                let _ = event_tx.send(AgentEvent::error(&id, "cancelled", "Request cancelled"));
+                return Ok(());
+            }
+
+            // Pause for approval once a cost budget is spent
+            if let Some(call) = budget_call(&self.ctx) {
+                self.yield_call(config, event_tx, call, YieldReason::BudgetExceeded);
+                return Ok(());
+            }
+
+            // Skip backends whose circuit is open, then apply the privacy level
+            let mut req_messages = self.request_messages();
+            let admitted = admit_target(&self.ctx, &self.target, None)
+                .map_err(|e| format!("Backend error: {}", e))?;
+            match enforce_privacy(&self.ctx, &admitted, &req_messages) {
+                Ok(admitted) => self.set_target(admitted, persona),
+                Err(e) => {
+                    let _ =
+                        event_tx.send(AgentEvent::error(&id, "privacy_refused", &e.to_string()));
+                    return Ok(());
+                }
+            }
+            if std::mem::take(&mut compact_history) {
+                tokio::task::block_in_place(|| {
+                    auto_compact(
+                        &self.ctx,
+                        &self.target,
+                        GATEWAY_SYSTEM_PROMPT,
+                        &self.tool_schemas,
+                        &mut self.messages,
+                    )
+                });
+                req_messages = self.request_messages();
+            }
+
+            let client = self
+                .ctx
+                .backends
+                .borrow_mut()
+                .get_client(&self.target.backend)
+                .map_err(|e| format!("Backend error: {}", e))?
+                .as_async()
+                .clone();
+            let chat = llm::ChatRequest {
+                model: self.target.model.clone(),
+                messages: req_messages,
+                tools: Some(self.tool_schemas.clone()),
+                tool_choice: Some("auto".to_string()),
+                sampling: self.sampling.clone(),
+            };
+            let cancel = self.ctx.cancel.clone();
+
+            let started = std::time::Instant::now();
+            let response = client.chat(&chat, &cancel).await;
+            record_call_outcome(&self.ctx, &self.target, started, &response);
+            let response = match response {
+                Ok(response) => response,
+                Err(e)
+                    if llm::is_context_overflow(&e)
+                        && overflow_recoveries < MAX_OVERFLOW_RECOVERIES =>
+                {
+                    overflow_recoveries += 1;
+                    let next = tokio::task::block_in_place(|| {
+                        recover_context_overflow(
+                            &self.ctx,
+                            &self.target,
+                            GATEWAY_SYSTEM_PROMPT,
+                            &self.tool_schemas,
+                            &mut self.messages,
+                            &e,
+                            overflow_recoveries,
+                        )
                     });
-                    req_messages.push(tool_msg.clone());
-                    messages.push(tool_msg);
+                    match next {
+                        Some(next) => {
+                            self.set_target(next, persona);
+                            continue;
+                        }
+                        None => return Err(format!("LLM error: {}", e)),
+                    }
                 }
-                Decision::Deny => {
-                    // Tool is denied, add error result
-                    let result = json!({
-                        "error": {
-                            "code": "permission_denied",
-                            "message": format!("Denied by policy{}",
-                                matched_rule.as_ref().map(|r| format!(" (rule: {})", r)).unwrap_or_default())
+                Err(e) if llm::is_cancelled(&e) => {
+                    let _ = event_tx.send(AgentEvent::error(&id, "cancelled", "Request cancelled"));
+                    return Ok(());
+                }
+                Err(e) if llm::is_backend_failure(&e) && failovers < MAX_FAILOVERS => {
+                    failovers += 1;
+                    match failover_target(&self.ctx, &self.target, None, &format!("failed: {}", e))
+                    {
+                        Some(next) => {
+                            self.set_target(next, persona);
+                            continue;
                         }
-                    });
-                    let _ = event_tx.send(AgentEvent::tool_result(
-                        id,
-                        name,
-                        &tc.id,
-                        result.clone(),
-                        false,
-                        0,
-                    ));
-
-                    let tool_msg = json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "content": serde_json::to_string(&result).unwrap_or_default()
-                    });
-                    req_messages.push(tool_msg.clone());
-                    messages.push(tool_msg);
+                        None => return Err(format!("LLM error: {}", e)),
+                    }
                 }
-                Decision::Ask => {
-                    // Need to yield for approval
-                    // Check if this is AskUserQuestion (special case)
-                    if name == "AskUserQuestion" {
-                        match crate::tools::ask_user::validate(&args) {
+                Err(e) => return Err(format!("LLM error: {}", e)),
+            };
+
+            // Track usage
+            if let Some(usage) = &response.usage {
+                stats.input_tokens += usage.prompt_tokens;
+                stats.output_tokens += usage.completion_tokens;
+                record_cost(
+                    &self.ctx,
+                    &self.target,
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    None,
+                );
+            }
+
+            let Some(choice) = response.choices.first() else {
+                break;
+            };
+            let msg = &choice.message;
+
+            if let Some(content) = &msg.content {
+                if !content.is_empty() {
+                    let _ = event_tx.send(AgentEvent::content(&id, content));
+                }
+            }
+
+            let tool_calls = match &msg.tool_calls {
+                Some(tc) if !tc.is_empty() => tc,
+                _ => break,
+            };
+
+            self.messages.push(json!({
+                "role": "assistant",
+                "content": msg.content,
+                "tool_calls": tool_calls
+            }));
+
+            for tc in tool_calls {
+                let name = &tc.function.name;
+                let args: Value = serde_json::from_str(&tc.function.arguments).unwrap_or(json!({}));
+
+                stats.tool_uses += 1;
+                let _ = event_tx.send(AgentEvent::tool_call(&id, name, args.clone(), &tc.id));
+
+                let (decision, matched_rule) = self.ctx.policy.borrow().decide(name, &args);
+                let mut call = PendingToolCall {
+                    tool_call_id: tc.id.clone(),
+                    tool_name: name.clone(),
+                    tool_args: args,
+                    policy_rule: matched_rule,
+                    questions: None,
+                };
+
+                let result = match decision {
+                    Decision::Allow if is_node_tool(name) => {
+                        self.yield_call(config, event_tx, call, YieldReason::NodeTool);
+                        return Ok(());
+                    }
+                    Decision::Allow => self.run_tool(event_tx, &call)?,
+                    Decision::Deny => self.deny_tool(
+                        event_tx,
+                        &call,
+                        "Denied by policy",
+                        call.policy_rule.as_deref(),
+                    ),
+                    Decision::Ask if name == "AskUserQuestion" => {
+                        match crate::tools::ask_user::validate(&call.tool_args) {
                             Ok(questions) => {
-                                let q_json: Vec<Value> = questions
-                                    .iter()
-                                    .map(|q| {
-                                        json!({
-                                            "question": q.question,
-                                            "header": q.header,
-                                            "options": q.options.iter().map(|o| {
-                                                json!({
-                                                    "label": o.label,
-                                                    "description": o.description
-                                                })
-                                            }).collect::<Vec<_>>(),
-                                            "multi_select": q.multi_select
-                                        })
-                                    })
-                                    .collect();
-
-                                // Save state
-                                let state = TurnState::new(
-                                    turn_id.clone(),
-                                    request.session_id.clone(),
-                                    id.to_string(),
-                                    messages.clone(),
-                                    PendingToolCall {
-                                        tool_call_id: tc.id.clone(),
-                                        tool_name: name.clone(),
-                                        tool_args: args.clone(),
-                                        policy_rule: None,
-                                        questions: Some(q_json.clone()),
-                                    },
-                                    YieldReason::AwaitingInput,
-                                    Some(target.to_string()),
-                                    request.working_dir.clone(),
-                                );
-                                let _ = config.turn_store.save(state);
-
-                                // Send yield event
-                                let _ = event_tx
-                                    .send(AgentEvent::yield_input(id, &turn_id, &tc.id, q_json));
+                                call.policy_rule = None;
+                                call.questions = Some(questions_json(&questions));
+                                self.yield_call(config, event_tx, call, YieldReason::AwaitingInput);
                                 return Ok(());
                             }
                             Err(error) => {
-                                // Invalid questions, return error
                                 let _ = event_tx.send(AgentEvent::tool_result(
-                                    id,
+                                    &id,
                                     name,
                                     &tc.id,
                                     error.clone(),
                                     false,
                                     0,
                                 ));
-                                let tool_msg = json!({
-                                    "role": "tool",
-                                    "tool_call_id": tc.id,
-                                    "content": serde_json::to_string(&error).unwrap_or_default()
-                                });
-                                req_messages.push(tool_msg.clone());
-                                messages.push(tool_msg);
+                                error
                             }
                         }
-                    } else {
-                        // Regular tool needing approval - save state and yield
-                        let state = TurnState::new(
-                            turn_id.clone(),
-                            request.session_id.clone(),
-                            id.to_string(),
-                            messages.clone(),
-                            PendingToolCall {
-                                tool_call_id: tc.id.clone(),
-                                tool_name: name.clone(),
-                                tool_args: args.clone(),
-                                policy_rule: matched_rule.clone(),
-                                questions: None,
-                            },
-                            YieldReason::AwaitingApproval,
-                            Some(target.to_string()),
-                            request.working_dir.clone(),
-                        );
-                        let _ = config.turn_store.save(state);
-
-                        // Send yield event for approval
-                        let _ = event_tx.send(AgentEvent::yield_approval(
-                            id,
-                            &turn_id,
-                            &tc.id,
-                            name,
-                            args.clone(),
-                            matched_rule,
-                        ));
+                    }
+                    Decision::Ask => {
+                        self.yield_call(config, event_tx, call, YieldReason::AwaitingApproval);
                         return Ok(());
                     }
-                }
+                };
+                self.push_tool_result(&tc.id, &result);
             }
         }
+
+        end_turn_span(&mut self.span, &self.target, &stats);
+        save_history(&self.ctx.session_id, &self.messages, request.ephemeral);
+        let usage = UsageStats {
+            input_tokens: stats.input_tokens,
+            output_tokens: stats.output_tokens,
+            tool_uses: stats.tool_uses,
+            cost_usd: self.ctx.session_costs.borrow().total_cost(),
+        };
+        let _ = event_tx.send(AgentEvent::done(&id, usage));
+
+        Ok(())
+    }
+}
+
+/// Run a turn in gateway mode with yield/resume semantics
+async fn run_turn_gateway_mode(
+    request: AgentRequest,
+    event_tx: mpsc::UnboundedSender<AgentEvent>,
+    config: &WorkerConfig,
+) -> Result<(), String> {
+    let id = &request.id;
+
+    let messages = request.messages.clone();
+    let has_input = messages
+        .last()
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .is_some_and(|c| !c.is_empty());
+    if !has_input {
+        let _ = event_tx.send(AgentEvent::error(
+            id,
+            "no_input",
+            "No user message provided",
+        ));
+        return Ok(());
     }
 
-    // Send done event
-    let usage = UsageStats {
-        input_tokens,
-        output_tokens,
-        tool_uses,
+    let Some(mut turn) = GatewayTurn::start(
+        &request,
+        config,
+        &event_tx,
+        &request.session_id,
+        request.working_dir.as_ref(),
+        request.target.as_ref(),
+    )?
+    else {
+        return Ok(());
     };
-    let _ = event_tx.send(AgentEvent::done(id, usage));
 
-    Ok(())
+    // Continue from the stored history when the client sent only the new message
+    let compact_history = messages.len() == 1;
+    turn.messages = if compact_history {
+        with_history(&turn.ctx.session_id, messages)
+    } else {
+        messages
+    };
+
+    turn.run(&request, config, &event_tx, compact_history).await
 }
 
 /// Resume a yielded turn
-fn run_resume_task(
+async fn run_resume_task(
     request: AgentRequest,
-    event_tx: mpsc::Sender<AgentEvent>,
+    event_tx: mpsc::UnboundedSender<AgentEvent>,
     config: &WorkerConfig,
 ) -> Result<(), String> {
-    use crate::context_factory::{
-        build_context, load_config_with_defaults, parse_working_dir, resolve_target,
-    };
-    use crate::llm::{self, LlmClient};
     use crate::policy::Decision;
-    use crate::tools;
 
     let id = &request.id;
 
@@ -555,341 +860,156 @@ fn run_resume_task(
     // Remove state from store (we're consuming it)
     config.turn_store.remove(&resume_data.turn_id);
 
-    let _turn_id = state.turn_id.clone();
-
-    // Parse working directory
-    let root = parse_working_dir(state.working_dir.as_ref());
-
-    // Load config with defaults applied
-    let cfg = load_config_with_defaults()?;
-
-    // Resolve target
-    let target = resolve_target(state.target.as_ref(), &cfg);
-    if target.is_none() {
-        let _ = event_tx.send(AgentEvent::error(id, "no_target", "No target configured"));
+    let Some(mut turn) = GatewayTurn::start(
+        &request,
+        config,
+        &event_tx,
+        &state.session_id,
+        state.working_dir.as_ref(),
+        state.target.as_ref(),
+    )?
+    else {
         return Ok(());
-    }
-    let target = target.unwrap();
-
-    let bash_config = cfg.bash.clone();
-
-    // Build system prompt
-    let system_prompt = r#"You are an agentic coding assistant running locally.
-You can only access files via tools. All paths are relative to the project root.
-Use Glob/Grep to find files before Read. Before Edit/Write, explain what you will change.
-Use Bash for running builds, tests, formatters, and git operations.
-Never use curl or wget - they are blocked by policy.
-Keep edits minimal and precise."#;
-
-    // Get tool schemas
-    let schema_opts = tools::SchemaOptions::new(false);
-    let tool_schemas = tools::schemas_with_task(&schema_opts);
-
-    // Build context
-    let ctx = match build_context(
-        &cfg,
-        root.clone(),
-        state.session_id.clone(),
-        Some(target.clone()),
-    ) {
-        Ok(c) => c,
-        Err(e) => {
-            let _ = event_tx.send(AgentEvent::error(id, "context_error", &e));
-            return Ok(());
-        }
     };
-
-    // Restore messages
-    let mut messages = state.messages.clone();
+    turn.span.set("turn.resumed", true);
+    turn.messages = state.messages.clone();
     let pending = &state.pending_tool_call;
 
-    // Process the response based on yield reason
+    // Process the response based on yield reason. A budget approval adds no
+    // tool message; the loop simply carries on.
     let tool_result = match state.yield_reason {
-        YieldReason::AwaitingApproval => {
-            if resume_data.approved == Some(true) {
-                // Execute the tool
-                let tool_start = std::time::Instant::now();
-                let result = execute_tool(
-                    &ctx,
-                    &pending.tool_name,
-                    pending.tool_args.clone(),
-                    &bash_config,
-                )?;
-                let duration_ms = tool_start.elapsed().as_millis() as u64;
-
-                let ok = result.get("error").is_none();
-                let _ = event_tx.send(AgentEvent::tool_result(
-                    id,
-                    &pending.tool_name,
-                    &pending.tool_call_id,
-                    result.clone(),
-                    ok,
-                    duration_ms,
-                ));
-                result
-            } else {
-                // User denied
-                json!({
-                    "error": {
-                        "code": "permission_denied",
-                        "message": "User denied permission"
-                    }
-                })
+        YieldReason::AwaitingApproval if resume_data.approved == Some(true) => {
+            if let Some(rule) = &resume_data.allow_rule {
+                save_allow_rule(&turn.ctx, rule);
+            }
+
+            // Run the operator's edited arguments in place of the agent's.
+            // The edited call goes through policy again: a deny rule refuses
+            // it, and an ask rule the original call didn't hit asks again.
+            let mut call = pending.clone();
+            let mut edited_decision = None;
+            if let Some(args) = &resume_data.tool_args {
+                call.tool_args = args.clone();
+                replace_tool_call_args(&mut turn.messages, &call.tool_call_id, args);
+                edited_decision = Some(turn.ctx.policy.borrow().decide(&call.tool_name, args));
+            }
+            match edited_decision {
+                Some((Decision::Deny, rule)) => Some(turn.deny_tool(
+                    &event_tx,
+                    &call,
+                    "Edited call denied by policy",
+                    rule.as_deref(),
+                )),
+                Some((Decision::Ask, rule)) if rule.is_some() && rule != pending.policy_rule => {
+                    call.policy_rule = rule;
+                    turn.yield_call(config, &event_tx, call, YieldReason::AwaitingApproval);
+                    return Ok(());
+                }
+                _ if is_node_tool(&call.tool_name) => {
+                    // Approved node tools still have to run on the node
+                    turn.yield_call(config, &event_tx, call, YieldReason::NodeTool);
+                    return Ok(());
+                }
+                _ => Some(turn.run_tool(&event_tx, &call)?),
             }
         }
+        YieldReason::AwaitingApproval => {
+            // User denied
+            Some(json!({
+                "error": {
+                    "code": "permission_denied",
+                    "message": "User denied permission"
+                }
+            }))
+        }
         YieldReason::AwaitingInput => {
             // Process user's answers
             let answers = resume_data.answers.clone().unwrap_or(json!({}));
-            json!({
+            Some(json!({
                 "ok": true,
                 "answers": answers
-            })
-        }
-    };
-
-    // Add tool result to messages
-    let tool_msg = json!({
-        "role": "tool",
-        "tool_call_id": pending.tool_call_id,
-        "content": serde_json::to_string(&tool_result).unwrap_or_default()
-    });
-    messages.push(tool_msg);
-
-    // Build messages for LLM with system prompt
-    let mut req_messages = vec![json!({
-        "role": "system",
-        "content": system_prompt
-    })];
-    req_messages.extend(messages.clone());
-
-    // Token stats
-    let mut input_tokens = 0u64;
-    let mut output_tokens = 0u64;
-    let mut tool_uses = 0u64;
-
-    const MAX_ITERATIONS: usize = 12;
-
-    // Continue the agent loop
-    for _iteration in 1..=MAX_ITERATIONS {
-        let response = {
-            let mut backends = ctx.backends.borrow_mut();
-            let client = backends
-                .get_client(&target.backend)
-                .map_err(|e| format!("Backend error: {}", e))?;
-
-            let request = llm::ChatRequest {
-                model: target.model.clone(),
-                messages: req_messages.clone(),
-                tools: Some(tool_schemas.clone()),
-                tool_choice: Some("auto".to_string()),
-            };
-
-            client
-                .chat(&request)
-                .map_err(|e| format!("LLM error: {}", e))?
-        };
-
-        if let Some(usage) = &response.usage {
-            input_tokens += usage.prompt_tokens;
-            output_tokens += usage.completion_tokens;
+            }))
         }
-
-        if response.choices.is_empty() {
-            break;
+        YieldReason::NodeTool => {
+            let result = resume_data.result.clone().unwrap_or_else(|| {
+                json!({
+                    "error": {
+                        "code": "node_unavailable",
+                        "message": "No result from the node"
+                    }
+                })
+            });
+            let ok = result.get("error").is_none();
+            let _ = event_tx.send(AgentEvent::tool_result(
+                id,
+                &pending.tool_name,
+                &pending.tool_call_id,
+                result.clone(),
+                ok,
+                resume_data.duration_ms.unwrap_or(0),
+            ));
+            Some(result)
         }
-
-        let choice = &response.choices[0];
-        let msg = &choice.message;
-
-        if let Some(content) = &msg.content {
-            if !content.is_empty() {
-                let _ = event_tx.send(AgentEvent::content(id, content));
+        YieldReason::BudgetExceeded => {
+            if resume_data.approved != Some(true) {
+                let _ = event_tx.send(AgentEvent::error(
+                    id,
+                    "budget_exceeded",
+                    "Cost budget exceeded; turn stopped",
+                ));
+                return Ok(());
+            }
+            if let Some(period) = pending.tool_args.get("period").and_then(|p| p.as_str()) {
+                crate::agent::core::approve_budget_overrun(period);
             }
+            None
         }
+    };
 
-        let tool_calls = match &msg.tool_calls {
-            Some(tc) if !tc.is_empty() => tc,
-            _ => break,
-        };
+    if let Some(tool_result) = tool_result {
+        turn.push_tool_result(&pending.tool_call_id, &tool_result);
+    }
 
-        let assistant_msg = json!({
-            "role": "assistant",
-            "content": msg.content,
-            "tool_calls": tool_calls
-        });
-        req_messages.push(assistant_msg.clone());
-        messages.push(assistant_msg);
-
-        for tc in tool_calls {
-            let name = &tc.function.name;
-            let args: Value = serde_json::from_str(&tc.function.arguments).unwrap_or(json!({}));
-
-            tool_uses += 1;
-
-            let _ = event_tx.send(AgentEvent::tool_call(id, name, args.clone(), &tc.id));
-
-            let (decision, matched_rule) = ctx.policy.borrow().decide(name, &args);
-
-            match decision {
-                Decision::Allow => {
-                    let tool_start = std::time::Instant::now();
-                    let result = execute_tool(&ctx, name, args.clone(), &bash_config)?;
-                    let duration_ms = tool_start.elapsed().as_millis() as u64;
-
-                    let ok = result.get("error").is_none();
-                    let _ = event_tx.send(AgentEvent::tool_result(
-                        id,
-                        name,
-                        &tc.id,
-                        result.clone(),
-                        ok,
-                        duration_ms,
-                    ));
-
-                    let tool_msg = json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "content": serde_json::to_string(&result).unwrap_or_default()
-                    });
-                    req_messages.push(tool_msg.clone());
-                    messages.push(tool_msg);
-                }
-                Decision::Deny => {
-                    let result = json!({
-                        "error": {
-                            "code": "permission_denied",
-                            "message": format!("Denied by policy{}",
-                                matched_rule.as_ref().map(|r| format!(" (rule: {})", r)).unwrap_or_default())
-                        }
-                    });
-                    let _ = event_tx.send(AgentEvent::tool_result(
-                        id,
-                        name,
-                        &tc.id,
-                        result.clone(),
-                        false,
-                        0,
-                    ));
-
-                    let tool_msg = json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "content": serde_json::to_string(&result).unwrap_or_default()
-                    });
-                    req_messages.push(tool_msg.clone());
-                    messages.push(tool_msg);
-                }
-                Decision::Ask => {
-                    // Handle AskUserQuestion specially
-                    if name == "AskUserQuestion" {
-                        match crate::tools::ask_user::validate(&args) {
-                            Ok(questions) => {
-                                let q_json: Vec<Value> = questions
-                                    .iter()
-                                    .map(|q| {
-                                        json!({
-                                            "question": q.question,
-                                            "header": q.header,
-                                            "options": q.options.iter().map(|o| {
-                                                json!({
-                                                    "label": o.label,
-                                                    "description": o.description
-                                                })
-                                            }).collect::<Vec<_>>(),
-                                            "multi_select": q.multi_select
-                                        })
-                                    })
-                                    .collect();
-
-                                let new_turn_id = uuid::Uuid::new_v4().to_string();
-                                let state = TurnState::new(
-                                    new_turn_id.clone(),
-                                    ctx.session_id.clone(),
-                                    id.to_string(),
-                                    messages.clone(),
-                                    PendingToolCall {
-                                        tool_call_id: tc.id.clone(),
-                                        tool_name: name.clone(),
-                                        tool_args: args.clone(),
-                                        policy_rule: None,
-                                        questions: Some(q_json.clone()),
-                                    },
-                                    YieldReason::AwaitingInput,
-                                    Some(target.to_string()),
-                                    ctx.root.to_string_lossy().to_string().into(),
-                                );
-                                let _ = config.turn_store.save(state);
-
-                                let _ = event_tx.send(AgentEvent::yield_input(
-                                    id,
-                                    &new_turn_id,
-                                    &tc.id,
-                                    q_json,
-                                ));
-                                return Ok(());
-                            }
-                            Err(error) => {
-                                let _ = event_tx.send(AgentEvent::tool_result(
-                                    id,
-                                    name,
-                                    &tc.id,
-                                    error.clone(),
-                                    false,
-                                    0,
-                                ));
-                                let tool_msg = json!({
-                                    "role": "tool",
-                                    "tool_call_id": tc.id,
-                                    "content": serde_json::to_string(&error).unwrap_or_default()
-                                });
-                                req_messages.push(tool_msg.clone());
-                                messages.push(tool_msg);
-                            }
-                        }
-                    } else {
-                        // Regular tool needing approval
-                        let new_turn_id = uuid::Uuid::new_v4().to_string();
-                        let state = TurnState::new(
-                            new_turn_id.clone(),
-                            ctx.session_id.clone(),
-                            id.to_string(),
-                            messages.clone(),
-                            PendingToolCall {
-                                tool_call_id: tc.id.clone(),
-                                tool_name: name.clone(),
-                                tool_args: args.clone(),
-                                policy_rule: matched_rule.clone(),
-                                questions: None,
-                            },
-                            YieldReason::AwaitingApproval,
-                            Some(target.to_string()),
-                            ctx.root.to_string_lossy().to_string().into(),
-                        );
-                        let _ = config.turn_store.save(state);
-
-                        let _ = event_tx.send(AgentEvent::yield_approval(
-                            id,
-                            &new_turn_id,
-                            &tc.id,
-                            name,
-                            args.clone(),
-                            matched_rule,
-                        ));
-                        return Ok(());
-                    }
-                }
-            }
+    turn.run(&request, config, &event_tx, false).await
+}
+
+/// Prepend the session's stored history when the client sent only the new
+/// user message. Clients that send the full conversation are left alone.
+fn with_history(session_id: &str, messages: Vec<Value>) -> Vec<Value> {
+    if messages.len() != 1 || !crate::session::is_valid_session_id(session_id) {
+        return messages;
+    }
+    match crate::session::load_session(session_id) {
+        Ok(saved) => {
+            let mut history = saved.messages;
+            history.extend(messages);
+            history
         }
+        Err(_) => messages,
     }
+}
 
-    let usage = UsageStats {
-        input_tokens,
-        output_tokens,
-        tool_uses,
-    };
-    let _ = event_tx.send(AgentEvent::done(id, usage));
+/// Store the session's history after a completed turn, unless the caller
+/// keeps the conversation itself
+fn save_history(session_id: &str, messages: &[Value], ephemeral: bool) {
+    if ephemeral || !crate::session::is_valid_session_id(session_id) {
+        return;
+    }
+    if let Err(e) = crate::session::append_turn(session_id, messages) {
+        eprintln!("[worker] Failed to save session {}: {}", session_id, e);
+    }
+}
 
-    Ok(())
+/// Sampling parameters for a target, overlaid with the persona manifest's
+fn persona_sampling(
+    cfg: &crate::config::Config,
+    target: &crate::config::Target,
+    persona: &str,
+) -> crate::llm::SamplingParams {
+    let persona_sampling = crate::persona::load_persona(persona)
+        .map(|p| p.sampling)
+        .unwrap_or_default();
+    cfg.sampling_for(target).merged(&persona_sampling)
 }
 
 /// Execute a tool and return the result
@@ -902,20 +1022,24 @@ fn execute_tool(
     crate::agent::execute_simple(ctx, name, args, bash_config)
 }
 
-/// Spawn a worker task in a new thread
+/// Spawn a worker task on the current tokio runtime
 pub fn spawn_worker(request: AgentRequest) -> WorkerHandle {
     spawn_worker_with_config(request, WorkerConfig::default())
 }
 
-/// Spawn a worker task with configuration
+/// Spawn a worker task with configuration. A request that fails outright
+/// ends with an `agent_error` event, so the caller always sees it finish.
 pub fn spawn_worker_with_config(request: AgentRequest, config: WorkerConfig) -> WorkerHandle {
-    let (tx, rx) = mpsc::channel();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let cancel = config.cancel.clone();
 
-    std::thread::spawn(move || {
-        if let Err(e) = run_agent_task_with_config(request, tx, &config) {
+    tokio::spawn(async move {
+        let id = request.id.clone();
+        if let Err(e) = run_agent_task_with_config(request, tx.clone(), &config).await {
             eprintln!("[worker] Error: {}", e);
+            let _ = tx.send(AgentEvent::error(&id, "agent_error", &e));
         }
     });
 
-    WorkerHandle { events: rx }
+    WorkerHandle { events: rx, cancel }
 }
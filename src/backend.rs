@@ -1,7 +1,18 @@
 use crate::config::{BackendConfig, Config};
 use crate::llm::{Client, StreamingClient};
+use crate::rate_limit::{self, RateLimiter};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Shared rate limiter for a backend, sized from its config
+fn rate_limiter(backend: &str, config: &BackendConfig) -> Arc<RateLimiter> {
+    rate_limit::limiter_for(
+        backend,
+        config.requests_per_minute,
+        config.tokens_per_minute,
+    )
+}
 
 /// Registry of backends with lazy-loaded clients.
 /// API keys are stored securely using secrecy::Secret.
@@ -43,7 +54,8 @@ impl BackendRegistry {
             })?;
 
             // Create client with the secret API key
-            let client = Client::new(&config.base_url, api_key);
+            let client = Client::new(&config.base_url, api_key)
+                .with_rate_limiter(rate_limiter(backend, config));
             self.clients.insert(backend.to_string(), client);
         }
 
@@ -69,7 +81,8 @@ impl BackendRegistry {
                 )
             })?;
 
-            let client = StreamingClient::new(&config.base_url, api_key);
+            let client = StreamingClient::new(&config.base_url, api_key)
+                .with_rate_limiter(rate_limiter(backend, config));
             self.streaming_clients.insert(backend.to_string(), client);
         }
 
@@ -85,4 +98,12 @@ impl BackendRegistry {
     pub fn has_backend(&self, name: &str) -> bool {
         self.backends.contains_key(name)
     }
+
+    /// Check if a backend exists and has an API key we can use
+    pub fn is_available(&self, name: &str) -> bool {
+        self.backends
+            .get(name)
+            .map(|config| config.resolve_api_key().is_ok())
+            .unwrap_or(false)
+    }
 }
@@ -4,13 +4,18 @@
 //! and routes requests to the agent daemon.
 //!
 //! Usage:
-//!   brainpro-gateway [--port 18789] [--agent-socket /path/to/socket]
+//!   brainpro-gateway [--port 18789] [--agent-socket /path/to/socket] [--lane main=4]...
 //!
 //! Environment variables:
 //!   BRAINPRO_GATEWAY_PORT - Port to listen on (default: 18789)
 //!   BRAINPRO_AGENT_SOCKET - Path to agent Unix socket (default: /run/brainpro.sock)
 //!   BRAINPRO_GATEWAY_TOKEN - Auth token for client connections (optional)
+//!   BRAINPRO_LANE_CRON / _MAIN / _SUBAGENT / _BATCH - Per-lane concurrency limits
+//!   BRAINPRO_LANE_MAX_QUEUE - Requests a lane may queue before rejecting (default: 100)
+//!   BRAINPRO_OPENAI_ASK - Answer to approval prompts on /v1/chat/completions: deny or allow (default: deny)
 
+use brainpro::gateway::lanes::LaneType;
+use brainpro::gateway::openai::AskPolicy;
 use brainpro::gateway::server::{run, GatewayConfig};
 use std::env;
 
@@ -25,6 +30,16 @@ async fn main() {
     eprintln!("brainpro-gateway starting...");
     eprintln!("Port: {}", config.port);
     eprintln!("Agent socket: {}", config.agent_socket);
+    eprintln!(
+        "Lanes: cron={} main={} subagent={} batch={} (queue {})",
+        config.lanes.cron_concurrency,
+        config.lanes.main_concurrency,
+        config.lanes.subagent_concurrency,
+        config.lanes.batch_concurrency,
+        config.lanes.max_queue_depth
+    );
+
+    eprintln!("OpenAI API approvals: {}", config.openai_ask.as_str());
 
     // Run the server
     if let Err(e) = run(config).await {
@@ -50,6 +65,16 @@ fn parse_config() -> GatewayConfig {
                 config.agent_socket = args[i + 1].clone();
                 i += 2;
             }
+            "--lane" if i + 1 < args.len() => {
+                let parsed = args[i + 1]
+                    .split_once('=')
+                    .and_then(|(name, limit)| Some((LaneType::parse(name)?, limit.parse().ok()?)));
+                match parsed {
+                    Some((lane, limit)) => config.lanes.set_concurrency(lane, limit),
+                    None => eprintln!("Ignoring invalid --lane {}", args[i + 1]),
+                }
+                i += 2;
+            }
             _ => i += 1,
         }
     }
@@ -69,5 +94,31 @@ fn parse_config() -> GatewayConfig {
         config.auth_token = Some(token);
     }
 
+    for lane in [
+        LaneType::Cron,
+        LaneType::Main,
+        LaneType::Subagent,
+        LaneType::Batch,
+    ] {
+        let var = format!("BRAINPRO_LANE_{}", lane.to_string().to_uppercase());
+        if let Some(limit) = env::var(&var).ok().and_then(|v| v.parse().ok()) {
+            config.lanes.set_concurrency(lane, limit);
+        }
+    }
+
+    if let Some(depth) = env::var("BRAINPRO_LANE_MAX_QUEUE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        config.lanes.max_queue_depth = depth;
+    }
+
+    if let Ok(ask) = env::var("BRAINPRO_OPENAI_ASK") {
+        match AskPolicy::parse(&ask) {
+            Some(policy) => config.openai_ask = policy,
+            None => eprintln!("Ignoring invalid BRAINPRO_OPENAI_ASK {}", ask),
+        }
+    }
+
     config
 }
@@ -20,7 +20,9 @@ use brainpro::persona::mrcode::MrCode;
 use brainpro::persona::Persona;
 use brainpro::plan::PlanModeState;
 use brainpro::policy::PolicyEngine;
+use brainpro::redact;
 use brainpro::skillpacks::{ActiveSkills, SkillIndex};
+use brainpro::tokens::TokenCounter;
 use brainpro::tools::{ask_user, todo::TodoState};
 use brainpro::transcript::Transcript;
 
@@ -175,10 +177,13 @@ fn main() -> Result<()> {
         resume: None,
         gateway: None,
         dump_prompt: args.dump_prompt,
+        privacy_level: None,
+        command: None,
     };
 
     // Initialize components
     let print_mode = args.prompt.is_some();
+    redact::configure(&session_id, &cfg.privacy);
     let policy = PolicyEngine::new(cfg.permissions.clone(), print_mode, args.yes);
     let hooks = HookManager::new(cfg.hooks.clone(), session_id.clone(), root.clone());
     let skill_index = SkillIndex::build(&root);
@@ -207,6 +212,8 @@ fn main() -> Result<()> {
         turn_counter: RefCell::new(0),
         command_index: RefCell::new(command_index),
         todo_state: RefCell::new(TodoState::new()),
+        token_counter: RefCell::new(TokenCounter::new()),
+        cancel: tokio_util::sync::CancellationToken::new(),
     };
 
     // Get MrCode persona
@@ -0,0 +1,285 @@
+//! Cascade routing: run a turn on a cheap target and escalate to stronger
+//! ones when the conversation shows signs of failure.
+//!
+//! The escalation chain comes from `[model_routing.cascade.chains]` for the
+//! turn's route category. The conversation is kept as-is when moving up, so
+//! the stronger model picks up where the cheaper one got stuck.
+
+use crate::config::Target;
+use crate::model_routing::{CascadeConfig, RouteCategory};
+use serde_json::Value;
+use std::fmt;
+
+/// Commands whose failure means the work doesn't verify
+const VERIFY_COMMANDS: &[&str] = &[
+    "cargo test",
+    "cargo check",
+    "cargo clippy",
+    "cargo build",
+    "npm test",
+    "npm run test",
+    "yarn test",
+    "pnpm test",
+    "pytest",
+    "go test",
+    "go vet",
+    "make test",
+    "make check",
+    "tsc",
+    "mvn test",
+    "gradle test",
+];
+
+/// Phrases a model uses when it has given up on the task
+const HELP_PHRASES: &[&str] = &[
+    "i'm stuck",
+    "i am stuck",
+    "i need help",
+    "i need your help",
+    "i'm unable to",
+    "i am unable to",
+    "i can't figure out",
+    "i cannot figure out",
+    "i'm not sure how to proceed",
+    "i don't know how to proceed",
+];
+
+/// Why a cascade moved to a stronger target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscalationSignal {
+    /// The same tool call was repeated with identical arguments
+    DoomLoop { tool: String },
+    /// Several tool calls in a row returned errors
+    ToolErrors { count: usize },
+    /// A verify/test command exited non-zero
+    FailedVerification { command: String },
+    /// The model said it was stuck or asked for help
+    AskedForHelp,
+}
+
+impl EscalationSignal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EscalationSignal::DoomLoop { .. } => "doom_loop",
+            EscalationSignal::ToolErrors { .. } => "tool_errors",
+            EscalationSignal::FailedVerification { .. } => "failed_verification",
+            EscalationSignal::AskedForHelp => "asked_for_help",
+        }
+    }
+}
+
+impl fmt::Display for EscalationSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscalationSignal::DoomLoop { tool } => write!(f, "repeated identical {} calls", tool),
+            EscalationSignal::ToolErrors { count } => write!(f, "{} tool errors in a row", count),
+            EscalationSignal::FailedVerification { command } => {
+                write!(f, "verification failed: {}", command)
+            }
+            EscalationSignal::AskedForHelp => write!(f, "model asked for help"),
+        }
+    }
+}
+
+/// Escalation state for one turn or subagent run
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    category: RouteCategory,
+    chain: Vec<Target>,
+    position: usize,
+    tool_errors: usize,
+    tool_error_threshold: usize,
+}
+
+impl Cascade {
+    /// Start a cascade for `category`, if cascading is enabled and the
+    /// category has a chain configured
+    pub fn new(config: &CascadeConfig, category: RouteCategory) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let chain: Vec<Target> = config
+            .chains
+            .get(&category)?
+            .iter()
+            .filter_map(|s| Target::parse(s))
+            .collect();
+        if chain.is_empty() {
+            return None;
+        }
+        Some(Self {
+            category,
+            chain,
+            position: 0,
+            tool_errors: 0,
+            tool_error_threshold: config.tool_error_threshold.max(1),
+        })
+    }
+
+    pub fn category(&self) -> RouteCategory {
+        self.category
+    }
+
+    /// Target the cascade is currently on
+    pub fn current(&self) -> &Target {
+        &self.chain[self.position]
+    }
+
+    /// Whether there is a stronger target left to move to
+    pub fn can_escalate(&self) -> bool {
+        self.position + 1 < self.chain.len()
+    }
+
+    /// Move to the next target in the chain
+    pub fn escalate(&mut self) -> Option<Target> {
+        if !self.can_escalate() {
+            return None;
+        }
+        self.position += 1;
+        self.tool_errors = 0;
+        Some(self.current().clone())
+    }
+
+    /// Look at a tool result for failure signals
+    pub fn observe_tool_result(
+        &mut self,
+        tool: &str,
+        args: &Value,
+        result: &Value,
+    ) -> Option<EscalationSignal> {
+        let exit_failed = result
+            .get("exit_code")
+            .map(|c| c.as_i64() != Some(0))
+            .unwrap_or(false);
+        if tool == "Bash" && exit_failed {
+            if let Some(command) = args.get("command").and_then(|c| c.as_str()) {
+                if is_verify_command(command) {
+                    return Some(EscalationSignal::FailedVerification {
+                        command: command.to_string(),
+                    });
+                }
+            }
+        }
+
+        if result.get("error").is_some() || exit_failed {
+            self.tool_errors += 1;
+            if self.tool_errors >= self.tool_error_threshold {
+                return Some(EscalationSignal::ToolErrors {
+                    count: self.tool_errors,
+                });
+            }
+        } else {
+            self.tool_errors = 0;
+        }
+        None
+    }
+
+    /// Look at assistant text for the model giving up
+    pub fn observe_response(&self, text: &str) -> Option<EscalationSignal> {
+        let lower = text.to_lowercase();
+        HELP_PHRASES
+            .iter()
+            .any(|p| lower.contains(p))
+            .then_some(EscalationSignal::AskedForHelp)
+    }
+}
+
+fn is_verify_command(command: &str) -> bool {
+    command
+        .split(['&', ';', '|'])
+        .map(|part| part.trim())
+        .any(|part| VERIFY_COMMANDS.iter().any(|v| part.starts_with(v)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> CascadeConfig {
+        let mut config = CascadeConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        config.chains.insert(
+            RouteCategory::Coding,
+            vec![
+                "gpt-4o-mini@chatgpt".to_string(),
+                "claude-3-5-sonnet-latest@claude".to_string(),
+            ],
+        );
+        config
+    }
+
+    #[test]
+    fn test_cascade_requires_enabled_chain() {
+        assert!(Cascade::new(&config(), RouteCategory::Testing).is_none());
+        let disabled = CascadeConfig {
+            enabled: false,
+            ..config()
+        };
+        assert!(Cascade::new(&disabled, RouteCategory::Coding).is_none());
+
+        let mut cascade = Cascade::new(&config(), RouteCategory::Coding).unwrap();
+        assert_eq!(cascade.current().model, "gpt-4o-mini");
+        assert_eq!(cascade.escalate().unwrap().backend, "claude");
+        assert!(cascade.escalate().is_none());
+    }
+
+    #[test]
+    fn test_failed_verification_escalates_immediately() {
+        let mut cascade = Cascade::new(&config(), RouteCategory::Coding).unwrap();
+        let signal = cascade.observe_tool_result(
+            "Bash",
+            &json!({ "command": "cd app && cargo test --workspace" }),
+            &json!({ "exit_code": 101, "stdout": "", "stderr": "failed" }),
+        );
+        assert!(matches!(
+            signal,
+            Some(EscalationSignal::FailedVerification { .. })
+        ));
+
+        // A failing non-verify command only counts as a tool error
+        let signal = cascade.observe_tool_result(
+            "Bash",
+            &json!({ "command": "ls missing" }),
+            &json!({ "exit_code": 2 }),
+        );
+        assert!(signal.is_none());
+    }
+
+    #[test]
+    fn test_consecutive_tool_errors() {
+        let mut cascade = Cascade::new(&config(), RouteCategory::Coding).unwrap();
+        let error = json!({ "error": { "code": "not_found", "message": "nope" } });
+        assert!(cascade
+            .observe_tool_result("Read", &json!({}), &error)
+            .is_none());
+        assert!(cascade
+            .observe_tool_result("Read", &json!({}), &error)
+            .is_none());
+        // A success breaks the streak
+        assert!(cascade
+            .observe_tool_result("Read", &json!({}), &json!({ "content": "ok" }))
+            .is_none());
+        for _ in 0..2 {
+            assert!(cascade
+                .observe_tool_result("Read", &json!({}), &error)
+                .is_none());
+        }
+        assert_eq!(
+            cascade.observe_tool_result("Read", &json!({}), &error),
+            Some(EscalationSignal::ToolErrors { count: 3 })
+        );
+    }
+
+    #[test]
+    fn test_help_request_detection() {
+        let cascade = Cascade::new(&config(), RouteCategory::Coding).unwrap();
+        assert_eq!(
+            cascade.observe_response("I'm stuck on this borrow checker error."),
+            Some(EscalationSignal::AskedForHelp)
+        );
+        assert!(cascade.observe_response("Done, all tests pass.").is_none());
+    }
+}
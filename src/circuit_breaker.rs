@@ -7,6 +7,8 @@
 
 #![allow(dead_code)]
 
+use crate::events::{self, Event};
+use crate::metrics;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -145,6 +147,10 @@ impl CircuitBreaker {
                             "[circuit_breaker:{}] Transitioning to half-open after {}s recovery",
                             self.name, self.config.recovery_timeout_secs
                         );
+                        events::emit(Event::circuit_half_open(
+                            &self.name,
+                            self.config.half_open_probes,
+                        ));
                         return CircuitBreakerDecision::Probe;
                     }
                 }
@@ -179,6 +185,10 @@ impl CircuitBreaker {
                         "[circuit_breaker:{}] Circuit closed after {} successful probes",
                         self.name, self.config.half_open_probes
                     );
+                    events::emit(Event::circuit_closed(
+                        &self.name,
+                        self.config.half_open_probes,
+                    ));
                 }
             }
             CircuitState::Open => {
@@ -208,6 +218,12 @@ impl CircuitBreaker {
                         "[circuit_breaker:{}] Circuit opened after {} consecutive failures",
                         self.name, state.consecutive_failures
                     );
+                    metrics::record_circuit_trip(&self.name);
+                    events::emit(Event::circuit_opened(
+                        &self.name,
+                        state.consecutive_failures,
+                        self.config.recovery_timeout_secs as u32,
+                    ));
                 }
             }
             CircuitState::HalfOpen => {
@@ -217,6 +233,12 @@ impl CircuitBreaker {
                     "[circuit_breaker:{}] Circuit reopened after probe failure",
                     self.name
                 );
+                metrics::record_circuit_trip(&self.name);
+                events::emit(Event::circuit_opened(
+                    &self.name,
+                    state.consecutive_failures,
+                    self.config.recovery_timeout_secs as u32,
+                ));
             }
             CircuitState::Open => {
                 // Already open, just track the failure
@@ -0,0 +1,195 @@
+//! Prompt classification for model routing.
+//!
+//! A fast target labels each user prompt with a `RouteCategory` and a rough
+//! complexity. Results are cached by prompt hash for the life of the process,
+//! and keyword matching takes over whenever the model call or its answer
+//! fails.
+
+use crate::config::Target;
+use crate::llm::{ChatRequest, SamplingParams};
+use crate::model_routing::RouteCategory;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Cached classifications kept before the cache is cleared
+const CACHE_CAPACITY: usize = 1024;
+
+const CLASSIFIER_PROMPT: &str = r#"Classify the user's request for a coding assistant.
+Reply with only a JSON object: {"category": "...", "complexity": "..."}
+
+category is one of:
+- planning: design, architecture or a plan before any code changes
+- coding: writing, changing, fixing or refactoring code
+- exploration: finding, reading or explaining existing code
+- testing: writing or running tests, or verifying behaviour
+- documentation: READMEs, docs or code comments
+- fast: a quick question or tiny task needing no tools
+- default: anything else
+
+complexity is one of: low, medium, high.
+Judge by what the user wants done, not by words they mention in passing
+("don't touch the tests, implement X" is coding)."#;
+
+/// Rough size of the work a prompt asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Complexity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Complexity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Complexity::Low => "low",
+            Complexity::Medium => "medium",
+            Complexity::High => "high",
+        }
+    }
+}
+
+/// Where a classification came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassificationSource {
+    Llm,
+    Cache,
+    Keywords,
+}
+
+impl ClassificationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClassificationSource::Llm => "llm",
+            ClassificationSource::Cache => "cache",
+            ClassificationSource::Keywords => "keywords",
+        }
+    }
+}
+
+/// Category and complexity assigned to a prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub category: RouteCategory,
+    /// Unknown when classified by keywords
+    pub complexity: Option<Complexity>,
+    pub source: ClassificationSource,
+}
+
+impl Classification {
+    /// Keyword fallback when the classifier can't be used
+    pub fn from_keywords(prompt: &str) -> Self {
+        Self {
+            category: RouteCategory::from_prompt(prompt),
+            complexity: None,
+            source: ClassificationSource::Keywords,
+        }
+    }
+}
+
+/// Cache key for a prompt
+pub fn prompt_hash(prompt: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(prompt.trim().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn cache() -> &'static Mutex<HashMap<String, (RouteCategory, Complexity)>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, (RouteCategory, Complexity)>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Previously classified result for this prompt, if any
+pub fn cached(prompt: &str) -> Option<Classification> {
+    let cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .get(&prompt_hash(prompt))
+        .map(|&(category, complexity)| Classification {
+            category,
+            complexity: Some(complexity),
+            source: ClassificationSource::Cache,
+        })
+}
+
+/// Remember a model classification for this prompt
+pub fn remember(prompt: &str, category: RouteCategory, complexity: Complexity) {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(prompt_hash(prompt), (category, complexity));
+}
+
+/// Build the classification request for `target`
+pub fn request(target: &Target, prompt: &str) -> ChatRequest {
+    ChatRequest {
+        model: target.model.clone(),
+        messages: vec![
+            json!({ "role": "system", "content": CLASSIFIER_PROMPT }),
+            json!({ "role": "user", "content": prompt }),
+        ],
+        tools: None,
+        tool_choice: None,
+        sampling: SamplingParams {
+            temperature: Some(0.0),
+            max_tokens: Some(64),
+            ..Default::default()
+        },
+    }
+}
+
+/// Parse the classifier's reply, tolerating text around the JSON object
+pub fn parse_reply(reply: &str) -> Option<(RouteCategory, Complexity)> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    let value: Value = serde_json::from_str(reply.get(start..=end)?).ok()?;
+    let category = RouteCategory::parse(value.get("category")?.as_str()?)?;
+    let complexity = serde_json::from_value(value.get("complexity")?.clone()).ok()?;
+    Some((category, complexity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(
+            parse_reply(r#"{"category": "coding", "complexity": "high"}"#),
+            Some((RouteCategory::Coding, Complexity::High))
+        );
+        assert_eq!(
+            parse_reply("Sure!\n```json\n{\"category\": \"Fast\", \"complexity\": \"low\"}\n```"),
+            Some((RouteCategory::Fast, Complexity::Low))
+        );
+        assert!(parse_reply(r#"{"category": "cooking", "complexity": "low"}"#).is_none());
+        assert!(parse_reply("coding").is_none());
+    }
+
+    #[test]
+    fn test_cache_by_prompt_hash() {
+        let prompt = "classifier cache test: add a retry to the uploader";
+        assert!(cached(prompt).is_none());
+        remember(prompt, RouteCategory::Coding, Complexity::Medium);
+
+        let hit = cached(&format!("  {}\n", prompt)).unwrap();
+        assert_eq!(hit.category, RouteCategory::Coding);
+        assert_eq!(hit.complexity, Some(Complexity::Medium));
+        assert_eq!(hit.source, ClassificationSource::Cache);
+    }
+
+    #[test]
+    fn test_keyword_fallback_produces_fast() {
+        assert_eq!(
+            Classification::from_keywords("What does this crate do?").category,
+            RouteCategory::Fast
+        );
+        assert_eq!(
+            Classification::from_keywords("Refactor the session store to use sqlite").category,
+            RouteCategory::Coding
+        );
+    }
+}
@@ -6,23 +6,25 @@ use crate::{
     config::Config,
     config::PermissionMode,
     config::Target,
-    cost::{format_cost, SessionCosts},
+    cost::{format_cost, format_tokens, SessionCosts},
     hooks::HookManager,
     model_routing::ModelRouter,
     plan::{self, PlanModeState},
     policy::PolicyEngine,
     session,
     skillpacks::{ActiveSkills, SkillIndex},
-    tools::{ask_user, todo::TodoState},
+    tokens::TokenCounter,
+    tools::{self, ask_user, todo::TodoState},
     transcript::Transcript,
 };
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 use std::cell::RefCell;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
 /// Command line arguments for brainpro
 #[derive(Parser, Clone)]
@@ -113,6 +115,23 @@ pub struct Args {
 
     #[arg(long, help = "Dump assembled system prompt before LLM call")]
     pub dump_prompt: bool,
+
+    #[arg(
+        long = "privacy-level",
+        value_name = "LEVEL",
+        help = "Privacy level for outgoing requests (standard|sensitive|strict)"
+    )]
+    pub privacy_level: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands that run instead of an agent session
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Report token usage and cost from past sessions
+    Usage(crate::usage::UsageArgs),
 }
 
 impl Default for Args {
@@ -139,6 +158,8 @@ impl Default for Args {
             resume: None,
             gateway: None,
             dump_prompt: false,
+            privacy_level: None,
+            command: None,
         }
     }
 }
@@ -173,6 +194,10 @@ pub struct Context {
     pub command_index: RefCell<CommandIndex>,
     // Todo list for task tracking
     pub todo_state: RefCell<TodoState>,
+    // Token estimation, calibrated from backend usage reports
+    pub token_counter: RefCell<TokenCounter>,
+    // Cancels in-flight LLM calls for this context (e.g. gateway cancel requests)
+    pub cancel: CancellationToken,
 }
 
 /// Print command stats to stderr
@@ -452,15 +477,17 @@ async fn handle_command_async(
             println!("  /trace          - toggle tracing");
             println!("  /backends       - list configured backends");
             println!("  /target [t]     - show/set current target (model@backend)");
+            println!("  /privacy [level] - show/set privacy level (standard|sensitive|strict)");
             println!("Permissions:");
             println!("  /mode [name]    - get/set permission mode (default|acceptEdits|bypassPermissions)");
             println!("  /permissions    - show permission rules");
             println!("  /permissions add allow|ask|deny \"pattern\"");
-            println!("  /permissions rm allow|ask|deny <index>");
+            println!("  /permissions rm allow|ask|deny|approved <index>");
             println!("Context:");
             println!("  /context        - show context usage stats");
             println!("  /compact        - compact conversation history");
             println!("  /cost           - show session cost breakdown");
+            println!("  /usage [opts]   - usage across sessions (--by day|model|backend|project|agent, --days N, --format table|json|csv)");
             println!("Subagents:");
             println!("  /agents                - list available subagents");
             println!("  /task <agent> <prompt> - run a subagent with the given prompt");
@@ -498,11 +525,33 @@ async fn handle_command_async(
             println!("Tracing: {}", if *t { "on" } else { "off" });
         }
         "/backends" => {
+            let health = crate::agent::core::provider_health(ctx);
+            let backends = ctx.backends.borrow();
+            let mut list = backends.list_backends();
+            list.sort_by(|a, b| a.0.cmp(b.0));
             println!("Configured backends:");
-            for (name, backend) in ctx.backends.borrow().list_backends() {
-                println!("  {}: {}", name, backend.base_url);
+            for (name, backend) in list {
+                let status = health.get_status(name);
+                let info = health.get_health_info(name);
+                print!(
+                    "  {}: {} [{:?}, circuit {:?}",
+                    name, backend.base_url, status.health, status.circuit_state
+                );
+                if info.total_requests > 0 {
+                    print!(
+                        ", {}/{} ok, avg {:.0}ms",
+                        info.successful_requests, info.total_requests, info.avg_latency_ms
+                    );
+                }
+                if info.consecutive_failures > 0 {
+                    print!(", {} consecutive failures", info.consecutive_failures);
+                }
+                println!("]");
             }
         }
+        "/privacy" => {
+            handle_privacy_command(ctx, parts.get(1).map(|s| s.trim()).unwrap_or(""));
+        }
         "/target" => {
             if parts.len() > 1 {
                 let target_str = parts[1].trim();
@@ -551,19 +600,7 @@ async fn handle_command_async(
             handle_permissions_command(ctx, if parts.len() > 1 { parts[1] } else { "" });
         }
         "/context" => {
-            let total_chars: usize = messages
-                .iter()
-                .map(|m| serde_json::to_string(m).map(|s| s.len()).unwrap_or(0))
-                .sum();
-            let max_chars = ctx.config.borrow().context.max_chars;
-            let usage_pct = (total_chars as f64 / max_chars as f64) * 100.0;
-            println!("Context usage:");
-            println!("  Messages: {} ({} chars)", messages.len(), total_chars);
-            println!("  Max: {} chars", max_chars);
-            println!("  Usage: {:.1}%", usage_pct);
-            if compact::needs_compaction(messages, &ctx.config.borrow().context) {
-                println!("  ⚠️  Compaction recommended. Run /compact");
-            }
+            handle_context_command(ctx, messages);
         }
         "/compact" => {
             handle_compact_command(ctx, messages);
@@ -571,6 +608,9 @@ async fn handle_command_async(
         "/cost" => {
             handle_cost_command(ctx);
         }
+        "/usage" => {
+            handle_usage_command(ctx, parts.get(1).copied().unwrap_or(""));
+        }
         "/commands" => {
             handle_commands_list(ctx);
         }
@@ -618,6 +658,12 @@ fn handle_permissions_command(ctx: &Context, args: &str) {
         for (i, rule) in config.ask.iter().enumerate() {
             println!("  [{}] {}", i, rule);
         }
+        if !config.approved.is_empty() {
+            println!("\nApproved rules (override ask):");
+            for (i, rule) in config.approved.iter().enumerate() {
+                println!("  [{}] {}", i, rule);
+            }
+        }
         println!("\nDeny rules:");
         for (i, rule) in config.deny.iter().enumerate() {
             println!("  [{}] {}", i, rule);
@@ -656,7 +702,7 @@ fn handle_permissions_command(ctx: &Context, args: &str) {
             drop(policy);
 
             // Save to local config
-            if let Err(e) = ctx.config.borrow().save_local_permissions() {
+            if let Err(e) = ctx.config.borrow().save_local_permissions(&ctx.root) {
                 eprintln!("Warning: failed to save permissions: {}", e);
             }
         }
@@ -670,13 +716,14 @@ fn handle_permissions_command(ctx: &Context, args: &str) {
                     "allow" if idx < config.allow.len() => Some(config.allow.remove(idx)),
                     "ask" if idx < config.ask.len() => Some(config.ask.remove(idx)),
                     "deny" if idx < config.deny.len() => Some(config.deny.remove(idx)),
+                    "approved" if idx < config.approved.len() => Some(config.approved.remove(idx)),
                     _ => None,
                 };
 
                 if let Some(rule) = removed {
                     println!("Removed {} rule: {}", decision_type, rule);
                     drop(policy);
-                    if let Err(e) = ctx.config.borrow().save_local_permissions() {
+                    if let Err(e) = ctx.config.borrow().save_local_permissions(&ctx.root) {
                         eprintln!("Warning: failed to save permissions: {}", e);
                     }
                 } else {
@@ -690,7 +737,7 @@ fn handle_permissions_command(ctx: &Context, args: &str) {
             println!("Usage:");
             println!("  /permissions                    - show current rules");
             println!("  /permissions add allow|ask|deny \"pattern\"");
-            println!("  /permissions rm allow|ask|deny <index>");
+            println!("  /permissions rm allow|ask|deny|approved <index>");
         }
     }
 }
@@ -753,6 +800,50 @@ fn handle_cost_command(ctx: &Context) {
             );
         }
     }
+
+    // Budgets, from the persistent ledger so other sessions count too
+    let budgets = {
+        let config = ctx.config.borrow();
+        crate::cost::ledger()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .budgets(
+                &config.cost_tracking,
+                &ctx.session_id,
+                &ctx.root.display().to_string(),
+            )
+    };
+    if !budgets.is_empty() {
+        println!("\nBudgets:");
+        for budget in budgets {
+            println!(
+                "  {}: {} of {} ({:.0}%, {} left){}",
+                budget.kind.label(),
+                format_cost(budget.spent_usd),
+                format_cost(budget.limit_usd),
+                budget.fraction() * 100.0,
+                format_cost(budget.remaining_usd()),
+                if budget.exceeded() { " - exceeded" } else { "" }
+            );
+        }
+    }
+}
+
+fn handle_usage_command(ctx: &Context, args: &str) {
+    let mut args = match crate::usage::parse_command(args) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if args.transcripts.is_none() {
+        args.transcripts = ctx.args.transcripts_dir.clone();
+    }
+    match crate::usage::report(&args, &ctx.root) {
+        Ok(report) => print!("{}", crate::usage::render(&report, args.format)),
+        Err(e) => println!("Error: {}", e),
+    }
 }
 
 fn handle_agents_command(ctx: &Context) {
@@ -1346,6 +1437,149 @@ fn handle_plan_delete(ctx: &Context, name: &str) {
     }
 }
 
+fn handle_privacy_command(ctx: &Context, arg: &str) {
+    use crate::privacy::{self, PrivacyAuditLog, PrivacyLevel};
+
+    if !arg.is_empty() {
+        match PrivacyLevel::parse(arg) {
+            Some(level) => {
+                ctx.config.borrow_mut().privacy.default_level = level;
+                println!("Privacy level set: {}", level.as_str());
+            }
+            None => println!("Invalid privacy level. Use: standard, sensitive, strict"),
+        }
+        return;
+    }
+
+    let config = ctx.config.borrow();
+    println!("Privacy level: {}", config.privacy.default_level.as_str());
+    println!(
+        "Sensitive patterns: {} (matches escalate requests to strict)",
+        config.privacy.strict_patterns.len()
+    );
+
+    let mut zdr: Vec<&String> = config
+        .backends
+        .iter()
+        .filter(|(_, b)| b.zdr)
+        .map(|(name, _)| name)
+        .collect();
+    zdr.sort();
+    println!(
+        "ZDR backends: {}",
+        if zdr.is_empty() {
+            "(none)".to_string()
+        } else {
+            zdr.iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+
+    let target = ctx
+        .current_target
+        .borrow()
+        .clone()
+        .or_else(|| config.get_default_target());
+    if let Some(t) = target {
+        let is_zdr = config
+            .backends
+            .get(&t.backend)
+            .map(|b| b.zdr)
+            .unwrap_or(false);
+        println!(
+            "Current target: {} ({})",
+            t,
+            if is_zdr { "ZDR" } else { "non-ZDR" }
+        );
+    }
+
+    let Some(path) = privacy::audit_log_path() else {
+        return;
+    };
+    let violations = PrivacyAuditLog::load(&path);
+    println!(
+        "Audit file: {} ({} violations)",
+        path.display(),
+        violations.len()
+    );
+    for v in violations.iter().rev().take(5) {
+        let when = chrono::DateTime::from_timestamp(v.timestamp as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!(
+            "  {} {} data sent to {} [{}]",
+            when,
+            v.privacy_level.as_str(),
+            v.backend,
+            v.matched_patterns.join(", ")
+        );
+    }
+}
+
+fn handle_context_command(ctx: &Context, messages: &[serde_json::Value]) {
+    let target = ctx
+        .current_target
+        .borrow()
+        .clone()
+        .or_else(|| ctx.config.borrow().get_default_target());
+    let Some(target) = target else {
+        println!("No target configured. Use /target to set one.");
+        return;
+    };
+
+    let in_planning_mode = ctx.plan_mode.borrow().phase == plan::PlanPhase::Planning;
+    let system_prompt = agent::build_system_prompt(ctx, in_planning_mode);
+    let tool_schemas = tools::schemas_with_task(&tools::SchemaOptions::new(ctx.args.optimize));
+    let breakdown = ctx.token_counter.borrow().breakdown(
+        &target.model,
+        &system_prompt,
+        &tool_schemas,
+        messages,
+    );
+
+    let context_config = ctx.config.borrow().context.clone();
+    let context_window = ctx
+        .model_router
+        .borrow()
+        .get_capabilities(&target.model)
+        .context_window;
+    let limit = compact::context_limit(&context_config, context_window);
+    let total = breakdown.total();
+    let usage_pct = if limit > 0 {
+        (total as f64 / limit as f64) * 100.0
+    } else {
+        0.0
+    };
+
+    let tok = |n: usize| format_tokens(n as u64);
+    let counter = ctx.token_counter.borrow();
+    let calibration = if counter.is_calibrated(&target.model) {
+        "calibrated from backend usage"
+    } else {
+        "not yet calibrated"
+    };
+    println!("Context usage ({}):", target);
+    println!(
+        "  Heuristic estimate, not the model's tokenizer ({})",
+        calibration
+    );
+    println!("  System prompt: ~{}", tok(breakdown.system));
+    println!("  Tools:         ~{}", tok(breakdown.tools));
+    println!(
+        "  History:       ~{} ({} messages)",
+        tok(breakdown.history),
+        messages.len()
+    );
+    println!("  Tool results:  ~{}", tok(breakdown.tool_results));
+    println!("  Total:         ~{} / {} tokens", tok(total), tok(limit));
+    println!("  Usage: {:.1}%", usage_pct);
+    if compact::needs_compaction(total, &context_config, context_window) {
+        println!("  ⚠️  Compaction recommended. Run /compact");
+    }
+}
+
 fn handle_compact_command(ctx: &Context, messages: &mut Vec<serde_json::Value>) {
     if messages.is_empty() {
         println!("No messages to compact.");
@@ -1384,7 +1618,8 @@ fn handle_compact_command(ctx: &Context, messages: &mut Vec<serde_json::Value>)
             }
         };
 
-        compact::compact_messages(messages, &context_config, client, &target.model)
+        let counter = ctx.token_counter.borrow();
+        compact::compact_messages(messages, &context_config, client, &target.model, &counter)
     };
 
     match compact_result {
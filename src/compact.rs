@@ -4,28 +4,29 @@
 //! to reclaim space while preserving essential information.
 
 use crate::config::ContextConfig;
+use crate::cost::format_tokens;
 use crate::llm::{ChatRequest, Client, LlmClient};
+use crate::spans;
+use crate::tokens::TokenCounter;
 use anyhow::Result;
 use serde_json::{json, Value};
 
-/// Estimate character count for a message
-fn estimate_chars(msg: &Value) -> usize {
-    serde_json::to_string(msg).map(|s| s.len()).unwrap_or(0)
+/// Token budget for a model: the context window, capped by `context.max_tokens`
+pub fn context_limit(config: &ContextConfig, context_window: usize) -> usize {
+    config
+        .max_tokens
+        .map(|max| max.min(context_window))
+        .unwrap_or(context_window)
 }
 
-/// Calculate total context size in characters
-pub fn context_size(messages: &[Value]) -> usize {
-    messages.iter().map(estimate_chars).sum()
-}
-
-/// Check if compaction is needed based on config thresholds
-pub fn needs_compaction(messages: &[Value], config: &ContextConfig) -> bool {
+/// Check if compaction is needed for a request of `used_tokens` prompt tokens
+pub fn needs_compaction(used_tokens: usize, config: &ContextConfig, context_window: usize) -> bool {
     if !config.auto_compact_enabled {
         return false;
     }
-    let current_size = context_size(messages);
-    let threshold = (config.max_chars as f64 * config.auto_compact_threshold) as usize;
-    current_size > threshold
+    let threshold =
+        (context_limit(config, context_window) as f64 * config.auto_compact_threshold) as usize;
+    used_tokens > threshold
 }
 
 /// Result of compaction
@@ -33,8 +34,8 @@ pub fn needs_compaction(messages: &[Value], config: &ContextConfig) -> bool {
 pub struct CompactionResult {
     pub original_count: usize,
     pub compacted_count: usize,
-    pub original_chars: usize,
-    pub compacted_chars: usize,
+    pub original_tokens: usize,
+    pub compacted_tokens: usize,
     pub summary: String,
 }
 
@@ -49,9 +50,32 @@ pub fn compact_messages(
     config: &ContextConfig,
     llm_client: &Client,
     model: &str,
+    counter: &TokenCounter,
+) -> Result<(Vec<Value>, CompactionResult)> {
+    let mut span = spans::start("compaction");
+    span.set("gen_ai.request.model", model);
+    let compacted = compact_with_summary(messages, config, llm_client, model, counter);
+    match &compacted {
+        Ok((_, result)) => {
+            span.set("compaction.original_messages", result.original_count);
+            span.set("compaction.compacted_messages", result.compacted_count);
+            span.set("compaction.original_tokens", result.original_tokens);
+            span.set("compaction.compacted_tokens", result.compacted_tokens);
+        }
+        Err(e) => span.error(&e.to_string()),
+    }
+    compacted
+}
+
+fn compact_with_summary(
+    messages: &[Value],
+    config: &ContextConfig,
+    llm_client: &Client,
+    model: &str,
+    counter: &TokenCounter,
 ) -> Result<(Vec<Value>, CompactionResult)> {
     let original_count = messages.len();
-    let original_chars = context_size(messages);
+    let original_tokens = counter.count_messages(model, messages);
 
     // If we have fewer messages than keep_last_turns, nothing to compact
     if messages.len() <= config.keep_last_turns * 2 {
@@ -60,8 +84,8 @@ pub fn compact_messages(
             CompactionResult {
                 original_count,
                 compacted_count: messages.len(),
-                original_chars,
-                compacted_chars: original_chars,
+                original_tokens,
+                compacted_tokens: original_tokens,
                 summary: String::new(),
             },
         ));
@@ -90,7 +114,7 @@ pub fn compact_messages(
     // Add the recent messages
     compacted.extend(to_keep.iter().cloned());
 
-    let compacted_chars = context_size(&compacted);
+    let compacted_tokens = counter.count_messages(model, &compacted);
     let compacted_count = compacted.len();
 
     Ok((
@@ -98,8 +122,8 @@ pub fn compact_messages(
         CompactionResult {
             original_count,
             compacted_count,
-            original_chars,
-            compacted_chars,
+            original_tokens,
+            compacted_tokens,
             summary,
         },
     ))
@@ -159,6 +183,7 @@ Be brief but complete. Focus on facts and outcomes."
         ],
         tools: None,
         tool_choice: None,
+        sampling: Default::default(),
     };
 
     let response = client.chat(&request)?;
@@ -174,18 +199,18 @@ Be brief but complete. Focus on facts and outcomes."
 
 /// Format compaction result for display
 pub fn format_result(result: &CompactionResult) -> String {
-    let reduction = if result.original_chars > 0 {
-        100.0 - (result.compacted_chars as f64 / result.original_chars as f64 * 100.0)
+    let reduction = if result.original_tokens > 0 {
+        100.0 - (result.compacted_tokens as f64 / result.original_tokens as f64 * 100.0)
     } else {
         0.0
     };
 
     format!(
-        "Compacted: {} → {} messages, {} → {} chars ({:.0}% reduction)",
+        "Compacted: {} → {} messages, ~{} → ~{} tokens ({:.0}% reduction)",
         result.original_count,
         result.compacted_count,
-        result.original_chars,
-        result.compacted_chars,
+        format_tokens(result.original_tokens as u64),
+        format_tokens(result.compacted_tokens as u64),
         reduction
     )
 }
@@ -194,37 +219,43 @@ pub fn format_result(result: &CompactionResult) -> String {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_context_size() {
-        let messages = vec![
-            json!({"role": "user", "content": "hello"}),
-            json!({"role": "assistant", "content": "hi there"}),
-        ];
-        let size = context_size(&messages);
-        assert!(size > 0);
-    }
-
     #[test]
     fn test_needs_compaction() {
         let config = ContextConfig {
             max_chars: 100,
+            max_tokens: Some(100),
             auto_compact_threshold: 0.8,
             auto_compact_enabled: true,
             keep_last_turns: 2,
         };
+        let counter = TokenCounter::new();
 
         // Small context - no compaction needed
         let small_messages = vec![json!({"role": "user", "content": "hi"})];
-        assert!(!needs_compaction(&small_messages, &config));
+        let small = counter.count_messages("gpt-4o", &small_messages);
+        assert!(!needs_compaction(small, &config, 128_000));
+
+        // Large context over the max_tokens cap - compaction needed
+        let large_messages: Vec<Value> = (0..100)
+            .map(|i| json!({"role": "user", "content": format!("message {}", i)}))
+            .collect();
+        let large = counter.count_messages("gpt-4o", &large_messages);
+        assert!(needs_compaction(large, &config, 128_000));
 
         // Disabled - no compaction
         let disabled_config = ContextConfig {
             auto_compact_enabled: false,
             ..config
         };
-        let large_messages: Vec<Value> = (0..100)
-            .map(|i| json!({"role": "user", "content": format!("message {}", i)}))
-            .collect();
-        assert!(!needs_compaction(&large_messages, &disabled_config));
+        assert!(!needs_compaction(large, &disabled_config, 128_000));
+    }
+
+    #[test]
+    fn test_context_limit_uses_smaller_of_window_and_cap() {
+        let mut config = ContextConfig::default();
+        assert_eq!(context_limit(&config, 8192), 8192);
+        config.max_tokens = Some(4000);
+        assert_eq!(context_limit(&config, 8192), 4000);
+        assert_eq!(context_limit(&config, 2048), 2048);
     }
 }
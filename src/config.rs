@@ -6,8 +6,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::llm::SamplingParams;
 use crate::privacy::PrivacyConfig;
 use crate::provider_health::HealthConfig;
+use crate::spans::TelemetryConfig;
 
 /// A validation error in the configuration
 #[derive(Debug, Clone)]
@@ -90,6 +92,11 @@ pub struct PermissionsConfig {
     pub ask: Vec<String>,
     #[serde(default)]
     pub deny: Vec<String>,
+    /// Allow rules an operator approved while answering a prompt in this
+    /// session. Unlike `allow`, these win over `ask` rules, or the prompt
+    /// would never stop. Never read from or saved to config files.
+    #[serde(skip)]
+    pub approved: Vec<String>,
 }
 
 /// Configuration for the Bash tool
@@ -117,6 +124,9 @@ pub struct AgentSpec {
     pub max_turns: usize,
     #[serde(default)]
     pub system_prompt: Option<String>,
+    /// Sampling overrides for this subagent (temperature, seed, ...)
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
 }
 
 fn default_allowed_tools() -> Vec<String> {
@@ -179,8 +189,12 @@ pub fn load_agents_from_dir(dir: &Path) -> HashMap<String, AgentSpec> {
 /// Configuration for context management
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ContextConfig {
+    /// Legacy character budget; compaction is now driven by token counts
     #[serde(default = "default_max_chars")]
     pub max_chars: usize,
+    /// Cap on context tokens, below the model's context window (None = use the window)
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
     #[serde(default = "default_auto_compact_threshold")]
     pub auto_compact_threshold: f64,
     #[serde(default = "default_true")]
@@ -206,6 +220,7 @@ impl Default for ContextConfig {
     fn default() -> Self {
         Self {
             max_chars: default_max_chars(),
+            max_tokens: None,
             auto_compact_threshold: default_auto_compact_threshold(),
             auto_compact_enabled: default_true(),
             keep_last_turns: default_keep_last_turns(),
@@ -214,7 +229,7 @@ impl Default for ContextConfig {
 }
 
 /// A parsed target: model@backend
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Target {
     pub model: String,
     pub backend: String,
@@ -252,6 +267,20 @@ pub struct BackendConfig {
     /// Whether this backend supports Zero Data Retention
     #[serde(default)]
     pub zdr: bool,
+    /// Client-side request budget, shared by every caller in this process
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Client-side token budget (estimated prompt tokens, corrected by usage)
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Per-target settings from `[targets."model@backend"]`
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct TargetConfig {
+    /// Default sampling parameters for requests sent to this target
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
 }
 
 /// Fallback chain configuration for automatic failover
@@ -365,6 +394,9 @@ pub struct Config {
     pub backends: HashMap<String, BackendConfig>,
     #[serde(default)]
     pub default_target: Option<String>,
+    /// Per-target settings keyed by "model@backend"
+    #[serde(default)]
+    pub targets: HashMap<String, TargetConfig>,
     #[serde(default)]
     pub permissions: PermissionsConfig,
     #[serde(default)]
@@ -387,6 +419,8 @@ pub struct Config {
     pub health: HealthConfig,
     #[serde(default)]
     pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
     #[serde(skip)]
     pub agents: HashMap<String, AgentSpec>,
 }
@@ -404,6 +438,8 @@ impl Config {
                 api_key_env: Some("VENICE_API_KEY".to_string()),
                 api_key: std::env::var("venice_api_key").ok(), // fallback to lowercase
                 zdr: true,                                     // Venice has ZDR policy
+                requests_per_minute: None,
+                tokens_per_minute: None,
             },
         );
 
@@ -415,6 +451,8 @@ impl Config {
                 api_key_env: Some("OPENAI_API_KEY".to_string()),
                 api_key: None,
                 zdr: false,
+                requests_per_minute: None,
+                tokens_per_minute: None,
             },
         );
 
@@ -426,6 +464,8 @@ impl Config {
                 api_key_env: Some("ANTHROPIC_API_KEY".to_string()),
                 api_key: None,
                 zdr: true,
+                requests_per_minute: None,
+                tokens_per_minute: None,
             },
         );
 
@@ -437,12 +477,15 @@ impl Config {
                 api_key_env: None,
                 api_key: None,
                 zdr: true,
+                requests_per_minute: None,
+                tokens_per_minute: None,
             },
         );
 
         Config {
             backends,
             default_target: None,
+            targets: HashMap::new(),
             permissions: PermissionsConfig::default(),
             bash: BashConfig::default(),
             context: ContextConfig::default(),
@@ -454,6 +497,7 @@ impl Config {
             fallback_chains: FallbackChainsConfig::default(),
             health: HealthConfig::default(),
             privacy: PrivacyConfig::default(),
+            telemetry: TelemetryConfig::default(),
             agents: HashMap::new(),
         }
     }
@@ -524,6 +568,12 @@ impl Config {
             self.default_target = other.default_target;
         }
 
+        // Merge per-target settings (other takes priority per field)
+        for (name, target) in other.targets {
+            let entry = self.targets.entry(name).or_default();
+            entry.sampling = entry.sampling.merged(&target.sampling);
+        }
+
         // Merge permissions: concatenate arrays, override mode if non-default
         self.permissions.allow.extend(other.permissions.allow);
         self.permissions.ask.extend(other.permissions.ask);
@@ -548,6 +598,17 @@ impl Config {
         // Merge hooks (concatenate)
         self.hooks.extend(other.hooks);
 
+        // Merge model routing (other takes priority per category/model)
+        self.model_routing.routes.extend(other.model_routing.routes);
+        self.model_routing
+            .model_caps
+            .extend(other.model_routing.model_caps);
+        let cascade = other.model_routing.cascade;
+        self.model_routing.cascade.chains.extend(cascade.chains);
+        self.model_routing.cascade.enabled = cascade.enabled;
+        self.model_routing.cascade.tool_error_threshold = cascade.tool_error_threshold;
+        self.model_routing.classifier = other.model_routing.classifier;
+
         // Merge cost tracking (take other's values)
         self.cost_tracking = other.cost_tracking;
 
@@ -580,6 +641,9 @@ impl Config {
 
         // Merge privacy config (take other's values)
         self.privacy = other.privacy;
+
+        // Merge telemetry config (take other's values)
+        self.telemetry = other.telemetry;
     }
 
     /// Get the default target
@@ -587,6 +651,14 @@ impl Config {
         self.default_target.as_ref().and_then(|s| Target::parse(s))
     }
 
+    /// Sampling parameters configured for a target, or defaults if none
+    pub fn sampling_for(&self, target: &Target) -> SamplingParams {
+        self.targets
+            .get(&target.to_string())
+            .map(|t| t.sampling.clone())
+            .unwrap_or_default()
+    }
+
     /// Create config from CLI arguments, starting with built-in backends
     /// The CLI-provided API key is applied to the backend matching the base_url
     pub fn from_cli_args(model: &str, base_url: &str, api_key: &str) -> Self {
@@ -617,6 +689,8 @@ impl Config {
                 api_key: Some(api_key.to_string()),
                 api_key_env: None,
                 zdr,
+                requests_per_minute: None,
+                tokens_per_minute: None,
             },
         );
 
@@ -659,8 +733,45 @@ impl Config {
             });
         }
 
+        // Validate per-target settings
+        for (name, target) in &self.targets {
+            if Target::parse(name).is_none() {
+                errors.push(ValidationError {
+                    field: format!("targets.{}", name),
+                    message: format!(
+                        "Invalid target format '{}', expected 'model@backend'",
+                        name
+                    ),
+                });
+            }
+            validate_sampling(&format!("targets.{}", name), &target.sampling, &mut errors);
+        }
+
+        // Validate per-backend rate limits
+        for (name, backend) in &self.backends {
+            for (key, value) in [
+                ("requests_per_minute", backend.requests_per_minute),
+                ("tokens_per_minute", backend.tokens_per_minute),
+            ] {
+                if value == Some(0) {
+                    errors.push(ValidationError {
+                        field: format!("backends.{}.{}", name, key),
+                        message: "Must be greater than 0".to_string(),
+                    });
+                }
+            }
+        }
+
+        if self.context.max_tokens == Some(0) {
+            errors.push(ValidationError {
+                field: "context.max_tokens".to_string(),
+                message: "Must be greater than 0".to_string(),
+            });
+        }
+
         // Validate agent specs
         for (name, spec) in &self.agents {
+            validate_sampling(&format!("agents.{}", name), &spec.sampling, &mut errors);
             if spec.max_turns == 0 {
                 errors.push(ValidationError {
                     field: format!("agents.{}.max_turns", name),
@@ -702,29 +813,53 @@ impl Config {
         }
     }
 
-    /// Save permissions to local config file (.brainpro/config.local.toml)
-    /// Creates the .brainpro directory if it doesn't exist
-    pub fn save_local_permissions(&self) -> Result<()> {
-        let brainpro_dir = Path::new(".brainpro");
-        if !brainpro_dir.exists() {
-            std::fs::create_dir_all(brainpro_dir)?;
-        }
-
-        // Create a minimal config with just permissions
-        let local_config = LocalPermissionsConfig {
-            permissions: self.permissions.clone(),
+    /// Save permissions to `<root>/.brainpro/config.local.toml`, keeping the
+    /// file's other settings. Creates the .brainpro directory if it doesn't
+    /// exist
+    pub fn save_local_permissions(&self, root: &Path) -> Result<()> {
+        let brainpro_dir = root.join(".brainpro");
+        std::fs::create_dir_all(&brainpro_dir)?;
+        let path = brainpro_dir.join("config.local.toml");
+
+        let mut local: toml::Table = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e.into()),
         };
+        local.insert(
+            "permissions".to_string(),
+            toml::Value::try_from(&self.permissions)?,
+        );
 
-        let content = toml::to_string_pretty(&local_config)?;
-        std::fs::write(brainpro_dir.join("config.local.toml"), content)?;
+        std::fs::write(&path, toml::to_string_pretty(&local)?)?;
         Ok(())
     }
 }
 
-/// Minimal config for saving just permissions to local file
-#[derive(Debug, Clone, Serialize)]
-struct LocalPermissionsConfig {
-    permissions: PermissionsConfig,
+/// Check sampling parameters are within the ranges providers accept
+fn validate_sampling(field: &str, sampling: &SamplingParams, errors: &mut Vec<ValidationError>) {
+    if let Some(temperature) = sampling.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            errors.push(ValidationError {
+                field: format!("{}.temperature", field),
+                message: format!("Must be between 0.0 and 2.0, got {}", temperature),
+            });
+        }
+    }
+    if let Some(top_p) = sampling.top_p {
+        if !(0.0..=1.0).contains(&top_p) {
+            errors.push(ValidationError {
+                field: format!("{}.top_p", field),
+                message: format!("Must be between 0.0 and 1.0, got {}", top_p),
+            });
+        }
+    }
+    if sampling.max_tokens == Some(0) {
+        errors.push(ValidationError {
+            field: format!("{}.max_tokens", field),
+            message: "Must be greater than 0".to_string(),
+        });
+    }
 }
 
 #[cfg(test)]
@@ -799,6 +934,52 @@ mod tests {
         assert!(errors[0].message.contains("Invalid regex"));
     }
 
+    #[test]
+    fn test_targets_sampling_parsed() {
+        let config: Config = toml::from_str(
+            r#"
+[targets."gpt-4o@chatgpt"]
+temperature = 0
+seed = 1234
+stop = ["</done>"]
+"#,
+        )
+        .unwrap();
+        let target = Target::parse("gpt-4o@chatgpt").unwrap();
+        let sampling = config.sampling_for(&target);
+        assert_eq!(sampling.temperature, Some(0.0));
+        assert_eq!(sampling.seed, Some(1234));
+        assert_eq!(sampling.stop, Some(vec!["</done>".to_string()]));
+
+        let other = Target::parse("gpt-4o-mini@chatgpt").unwrap();
+        assert!(config.sampling_for(&other).is_empty());
+    }
+
+    #[test]
+    fn test_agent_spec_sampling_parsed() {
+        let spec: AgentSpec = toml::from_str(
+            r#"
+name = "planner"
+temperature = 0.9
+parallel_tool_calls = false
+"#,
+        )
+        .unwrap();
+        assert_eq!(spec.sampling.temperature, Some(0.9));
+        assert_eq!(spec.sampling.parallel_tool_calls, Some(false));
+    }
+
+    #[test]
+    fn test_validate_invalid_sampling() {
+        let mut config = Config::with_builtin_backends();
+        let mut target = TargetConfig::default();
+        target.sampling.temperature = Some(3.0);
+        config.targets.insert("gpt-4o@chatgpt".to_string(), target);
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].field.ends_with(".temperature"));
+    }
+
     #[test]
     fn test_validate_empty_hook_command() {
         let mut config = Config::with_builtin_backends();
@@ -812,4 +993,33 @@ mod tests {
         assert_eq!(errors.len(), 1);
         assert!(errors[0].message.contains("empty"));
     }
+
+    #[test]
+    fn test_save_local_permissions_keeps_existing_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".brainpro").join("config.local.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            "default_target = \"gpt-4o@chatgpt\"\n[permissions]\nask = [\"Bash(git push:*)\"]\n",
+        )
+        .unwrap();
+
+        let mut local = Config::load_from(&path).unwrap();
+        local
+            .permissions
+            .allow
+            .push("Bash(cargo test:*)".to_string());
+        local
+            .permissions
+            .approved
+            .push("Bash(git push:*)".to_string());
+        local.save_local_permissions(dir.path()).unwrap();
+
+        let saved = Config::load_from(&path).unwrap();
+        assert_eq!(saved.default_target.as_deref(), Some("gpt-4o@chatgpt"));
+        assert_eq!(saved.permissions.ask, vec!["Bash(git push:*)"]);
+        assert_eq!(saved.permissions.allow, vec!["Bash(cargo test:*)"]);
+        assert!(saved.permissions.approved.is_empty());
+    }
 }
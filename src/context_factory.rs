@@ -13,7 +13,9 @@ use crate::hooks::HookManager;
 use crate::model_routing::ModelRouter;
 use crate::plan::PlanModeState;
 use crate::policy::PolicyEngine;
+use crate::redact;
 use crate::skillpacks::{ActiveSkills, SkillIndex};
+use crate::tokens::TokenCounter;
 use crate::tools::todo::TodoState;
 use crate::transcript::Transcript;
 use anyhow::Result;
@@ -78,6 +80,7 @@ pub fn build_context(
         .map_err(|e| format!("Failed to create transcript: {}", e))?;
 
     // Initialize components
+    redact::configure(&session_id, &cfg.privacy);
     let policy = PolicyEngine::new(cfg.permissions.clone(), false, false);
     let hooks = HookManager::new(cfg.hooks.clone(), session_id.clone(), root.clone());
     let skill_index = SkillIndex::build(&root);
@@ -106,6 +109,8 @@ pub fn build_context(
         turn_counter: RefCell::new(0),
         command_index: RefCell::new(command_index),
         todo_state: RefCell::new(TodoState::new()),
+        token_counter: RefCell::new(TokenCounter::new()),
+        cancel: tokio_util::sync::CancellationToken::new(),
     })
 }
 
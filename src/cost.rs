@@ -2,9 +2,16 @@
 //!
 //! Tracks token usage and costs across operations, turns, and sessions.
 //! Supports per-model pricing configuration with sensible defaults.
+//! Every priced call is also appended to a persistent ledger under
+//! `~/.brainpro/costs/`, which backs the session, daily and project budgets.
 
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 /// Pricing for a single model (per 1M tokens in USD)
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -40,6 +47,15 @@ pub struct CostConfig {
     /// Show cost in the stats line after each turn
     #[serde(default = "default_true")]
     pub display_in_stats: bool,
+    /// Hard limit on spend for a single session (USD)
+    #[serde(default)]
+    pub session_max_usd: Option<f64>,
+    /// Hard limit on spend per local calendar day, across sessions (USD)
+    #[serde(default)]
+    pub daily_max_usd: Option<f64>,
+    /// Hard limit on spend per project root per calendar month (USD)
+    #[serde(default)]
+    pub project_monthly_max_usd: Option<f64>,
 }
 
 fn default_true() -> bool {
@@ -52,6 +68,9 @@ impl Default for CostConfig {
             enabled: true,
             warn_threshold_usd: None,
             display_in_stats: true,
+            session_max_usd: None,
+            daily_max_usd: None,
+            project_monthly_max_usd: None,
         }
     }
 }
@@ -107,12 +126,10 @@ impl TurnCost {
         self.operations.iter().map(|op| op.cost_usd).sum()
     }
 
-    #[allow(dead_code)] // For future detailed reporting
     pub fn input_tokens(&self) -> u64 {
         self.operations.iter().map(|op| op.input_tokens).sum()
     }
 
-    #[allow(dead_code)] // For future detailed reporting
     pub fn output_tokens(&self) -> u64 {
         self.operations.iter().map(|op| op.output_tokens).sum()
     }
@@ -159,8 +176,7 @@ impl SessionCosts {
         op
     }
 
-    /// Merge costs from a subagent into the current turn
-    #[allow(dead_code)] // For future parallel subagent support
+    /// Merge already-priced operations into a turn
     pub fn merge_operations(&mut self, turn_number: u32, ops: Vec<OperationCost>) {
         if let Some(turn) = self.turns.iter_mut().find(|t| t.turn_number == turn_number) {
             for op in ops {
@@ -188,16 +204,19 @@ impl SessionCosts {
         self.turns.iter().map(|t| t.total_tokens()).sum()
     }
 
+    /// Number of LLM calls recorded
+    pub fn operation_count(&self) -> usize {
+        self.turns.iter().map(|t| t.operations.len()).sum()
+    }
+
     pub fn total_cost(&self) -> f64 {
         self.turns.iter().map(|t| t.total_cost()).sum()
     }
 
-    #[allow(dead_code)] // For future detailed reporting
     pub fn input_tokens(&self) -> u64 {
         self.turns.iter().map(|t| t.input_tokens()).sum()
     }
 
-    #[allow(dead_code)] // For future detailed reporting
     pub fn output_tokens(&self) -> u64 {
         self.turns.iter().map(|t| t.output_tokens()).sum()
     }
@@ -367,6 +386,372 @@ impl PricingTable {
     }
 }
 
+/// Fractions of a budget at which a threshold warning is emitted
+pub const BUDGET_WARN_FRACTIONS: [f64; 3] = [0.5, 0.8, 1.0];
+
+/// Which spending window a budget applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetKind {
+    Session,
+    Daily,
+    ProjectMonthly,
+}
+
+impl BudgetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetKind::Session => "session",
+            BudgetKind::Daily => "daily",
+            BudgetKind::ProjectMonthly => "project_monthly",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BudgetKind::Session => "Session",
+            BudgetKind::Daily => "Daily",
+            BudgetKind::ProjectMonthly => "Project (month)",
+        }
+    }
+}
+
+/// Spend against one configured budget
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    pub kind: BudgetKind,
+    /// Identifies the budget window, e.g. `daily:2026-10-18`
+    pub period: String,
+    pub limit_usd: f64,
+    pub spent_usd: f64,
+}
+
+impl BudgetStatus {
+    pub fn fraction(&self) -> f64 {
+        if self.limit_usd <= 0.0 {
+            return 1.0;
+        }
+        self.spent_usd / self.limit_usd
+    }
+
+    pub fn remaining_usd(&self) -> f64 {
+        (self.limit_usd - self.spent_usd).max(0.0)
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.spent_usd >= self.limit_usd
+    }
+}
+
+/// One priced LLM call as stored in the ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub ts: DateTime<Utc>,
+    pub session_id: String,
+    /// Project root the session was working in
+    pub project: String,
+    pub model: String,
+    pub backend: String,
+    /// Subagent that made the call, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl LedgerEntry {
+    /// Local calendar day the call was made on
+    pub fn local_date(&self) -> NaiveDate {
+        self.ts.with_timezone(&Local).date_naive()
+    }
+}
+
+/// Spend summed from one day's ledger file
+#[derive(Debug, Default)]
+struct DayTotals {
+    /// Bytes of the file already summed
+    offset: u64,
+    total: f64,
+    /// Spend per project root
+    projects: HashMap<String, f64>,
+}
+
+impl DayTotals {
+    fn add(&mut self, entry: &LedgerEntry) {
+        self.total += entry.cost_usd;
+        *self.projects.entry(entry.project.clone()).or_default() += entry.cost_usd;
+    }
+}
+
+/// A session's spend and the last day it spent anything
+#[derive(Debug)]
+struct SessionTotal {
+    spent: f64,
+    last_date: NaiveDate,
+}
+
+/// Persistent cost ledger, one JSONL file per local day.
+///
+/// Keeps running totals rather than entries. Before every budget check the
+/// current month's day files are read from where the last read stopped, so
+/// spend by other processes (CLI and daemon) counts as soon as it is written.
+#[derive(Debug, Default)]
+pub struct CostLedger {
+    dir: Option<PathBuf>,
+    /// Spend per local day of the current month
+    days: BTreeMap<NaiveDate, DayTotals>,
+    /// Spend per session, kept across month boundaries
+    sessions: HashMap<String, SessionTotal>,
+    /// Budget thresholds already announced, keyed by period and percentage
+    warned: HashSet<String>,
+    /// Budget periods the user has approved running over
+    approved: HashSet<String>,
+}
+
+impl CostLedger {
+    /// Default ledger directory (~/.brainpro/costs)
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|h| h.join(".brainpro").join("costs"))
+    }
+
+    /// Open a ledger, summing the current month's spend from `dir`.
+    /// Without a directory the ledger is kept in memory only.
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let mut ledger = Self {
+            dir,
+            ..Default::default()
+        };
+        ledger.refresh(Local::now().date_naive());
+        ledger
+    }
+
+    fn day_file(dir: &Path, date: NaiveDate) -> PathBuf {
+        dir.join(format!("{}.jsonl", date.format("%Y-%m-%d")))
+    }
+
+    /// Load all ledger entries between two local dates (inclusive)
+    pub fn load_entries(dir: &Path, from: NaiveDate, to: NaiveDate) -> Vec<LedgerEntry> {
+        let mut entries = Vec::new();
+        let mut date = from;
+        while date <= to {
+            if let Ok(file) = fs::File::open(Self::day_file(dir, date)) {
+                entries.extend(
+                    BufReader::new(file)
+                        .lines()
+                        .map_while(Result::ok)
+                        .filter_map(|line| serde_json::from_str::<LedgerEntry>(&line).ok()),
+                );
+            }
+            match date.succ_opt() {
+                Some(next) => date = next,
+                None => break,
+            }
+        }
+        entries
+    }
+
+    /// Append an entry to its day's file and update the totals
+    pub fn record(&mut self, entry: LedgerEntry) {
+        let Some(dir) = &self.dir else {
+            self.add(&entry);
+            return;
+        };
+        let path = Self::day_file(dir, entry.local_date());
+        let result = fs::create_dir_all(dir).and_then(|_| {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let line = serde_json::to_string(&entry).unwrap_or_default();
+            writeln!(file, "{}", line)
+        });
+        match result {
+            // Read back with whatever other processes appended meanwhile
+            Ok(()) => self.refresh(Local::now().date_naive()),
+            Err(e) => {
+                eprintln!("[cost] Failed to write ledger {}: {}", path.display(), e);
+                self.add(&entry);
+            }
+        }
+    }
+
+    fn add(&mut self, entry: &LedgerEntry) {
+        let date = entry.local_date();
+        self.days.entry(date).or_default().add(entry);
+        let session = self
+            .sessions
+            .entry(entry.session_id.clone())
+            .or_insert(SessionTotal {
+                spent: 0.0,
+                last_date: date,
+            });
+        session.spent += entry.cost_usd;
+        session.last_date = session.last_date.max(date);
+    }
+
+    /// Catch up with the current month's day files and drop older totals.
+    /// Sessions idle since before last month are forgotten.
+    fn refresh(&mut self, today: NaiveDate) {
+        let first = today.with_day(1).unwrap_or(today);
+        let forget_before = first
+            .pred_opt()
+            .and_then(|d| d.with_day(1))
+            .unwrap_or(first);
+        self.days.retain(|date, _| *date >= first);
+        self.sessions.retain(|_, s| s.last_date >= forget_before);
+
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        let mut date = first;
+        while date <= today {
+            self.read_day(&Self::day_file(&dir, date), date);
+            match date.succ_opt() {
+                Some(next) => date = next,
+                None => break,
+            }
+        }
+    }
+
+    /// Sum the complete lines appended to a day file since the last read
+    fn read_day(&mut self, path: &Path, date: NaiveDate) {
+        let Ok(len) = fs::metadata(path).map(|m| m.len()) else {
+            return;
+        };
+        let offset = self.days.get(&date).map_or(0, |d| d.offset);
+        if len == offset {
+            return;
+        }
+        if len < offset {
+            // The file was truncated or replaced; start over
+            self.days.remove(&date);
+            return self.read_day(path, date);
+        }
+
+        let mut appended = Vec::new();
+        let read = fs::File::open(path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_to_end(&mut appended)
+        });
+        if read.is_err() {
+            return;
+        }
+        // A line still being written is picked up next time
+        let Some(complete) = appended.iter().rposition(|b| *b == b'\n').map(|i| i + 1) else {
+            return;
+        };
+        let entries: Vec<LedgerEntry> = appended[..complete]
+            .split(|b| *b == b'\n')
+            .filter_map(|line| serde_json::from_slice(line).ok())
+            .collect();
+        for entry in &entries {
+            self.add(entry);
+        }
+        self.days.entry(date).or_default().offset = offset + complete as u64;
+    }
+
+    pub fn session_total(&self, session_id: &str) -> f64 {
+        self.sessions.get(session_id).map_or(0.0, |s| s.spent)
+    }
+
+    pub fn daily_total(&self, date: NaiveDate) -> f64 {
+        self.days.get(&date).map_or(0.0, |d| d.total)
+    }
+
+    pub fn project_month_total(&self, project: &str, year: i32, month: u32) -> f64 {
+        self.days
+            .iter()
+            .filter(|(date, _)| date.year() == year && date.month() == month)
+            .filter_map(|(_, day)| day.projects.get(project))
+            .sum()
+    }
+
+    /// Current spend against every configured budget
+    pub fn budgets(
+        &mut self,
+        config: &CostConfig,
+        session_id: &str,
+        project: &str,
+    ) -> Vec<BudgetStatus> {
+        let today = Local::now().date_naive();
+        self.refresh(today);
+        let mut statuses = Vec::new();
+        if let Some(limit) = config.session_max_usd {
+            statuses.push(BudgetStatus {
+                kind: BudgetKind::Session,
+                period: format!("session:{}", session_id),
+                limit_usd: limit,
+                spent_usd: self.session_total(session_id),
+            });
+        }
+        if let Some(limit) = config.daily_max_usd {
+            statuses.push(BudgetStatus {
+                kind: BudgetKind::Daily,
+                period: format!("daily:{}", today.format("%Y-%m-%d")),
+                limit_usd: limit,
+                spent_usd: self.daily_total(today),
+            });
+        }
+        if let Some(limit) = config.project_monthly_max_usd {
+            statuses.push(BudgetStatus {
+                kind: BudgetKind::ProjectMonthly,
+                period: format!("project:{}:{}", project, today.format("%Y-%m")),
+                limit_usd: limit,
+                spent_usd: self.project_month_total(project, today.year(), today.month()),
+            });
+        }
+        statuses
+    }
+
+    /// Budget thresholds crossed since the last call, each reported once.
+    /// Returns the budget together with the fraction that was crossed.
+    pub fn crossed_thresholds(
+        &mut self,
+        config: &CostConfig,
+        session_id: &str,
+        project: &str,
+    ) -> Vec<(BudgetStatus, f64)> {
+        let mut crossed = Vec::new();
+        for status in self.budgets(config, session_id, project) {
+            // Only the highest newly crossed threshold is worth announcing
+            let mut highest = None;
+            for fraction in BUDGET_WARN_FRACTIONS {
+                let key = format!("{}:{}", status.period, (fraction * 100.0) as u32);
+                if status.fraction() >= fraction && self.warned.insert(key) {
+                    highest = Some(fraction);
+                }
+            }
+            if let Some(fraction) = highest {
+                crossed.push((status, fraction));
+            }
+        }
+        crossed
+    }
+
+    /// First exceeded budget the user hasn't approved running over
+    pub fn exceeded(
+        &mut self,
+        config: &CostConfig,
+        session_id: &str,
+        project: &str,
+    ) -> Option<BudgetStatus> {
+        let budgets = self.budgets(config, session_id, project);
+        budgets
+            .into_iter()
+            .find(|s| s.exceeded() && !self.approved.contains(&s.period))
+    }
+
+    /// Allow spending past a budget for the rest of its period
+    pub fn approve_overrun(&mut self, period: &str) {
+        self.approved.insert(period.to_string());
+    }
+}
+
+static LEDGER: OnceLock<Mutex<CostLedger>> = OnceLock::new();
+
+/// Process-wide ledger shared by all sessions (and gateway turns)
+pub fn ledger() -> &'static Mutex<CostLedger> {
+    LEDGER.get_or_init(|| Mutex::new(CostLedger::open(CostLedger::default_dir())))
+}
+
 /// Format a cost value for display
 pub fn format_cost(cost: f64) -> String {
     if cost < 0.01 {
@@ -392,6 +777,7 @@ pub fn format_tokens(tokens: u64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn test_model_pricing_calculation() {
@@ -438,4 +824,124 @@ mod tests {
         assert_eq!(format_tokens(1500), "1.5k");
         assert_eq!(format_tokens(1_500_000), "1.5M");
     }
+
+    fn entry(session: &str, project: &str, cost: f64) -> LedgerEntry {
+        LedgerEntry {
+            ts: Utc::now(),
+            session_id: session.to_string(),
+            project: project.to_string(),
+            model: "gpt-4o-mini".to_string(),
+            backend: "chatgpt".to_string(),
+            agent: None,
+            input_tokens: 1000,
+            output_tokens: 500,
+            cost_usd: cost,
+        }
+    }
+
+    #[test]
+    fn test_ledger_persists_and_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = CostLedger::open(Some(dir.path().to_path_buf()));
+        ledger.record(entry("s1", "/proj", 0.25));
+        ledger.record(entry("s2", "/proj", 0.50));
+
+        let reopened = CostLedger::open(Some(dir.path().to_path_buf()));
+        assert!((reopened.session_total("s1") - 0.25).abs() < 1e-9);
+        assert!((reopened.daily_total(Local::now().date_naive()) - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_budgets_see_spend_from_other_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CostConfig {
+            daily_max_usd: Some(1.0),
+            ..Default::default()
+        };
+        let mut cli = CostLedger::open(Some(dir.path().to_path_buf()));
+        let mut daemon = CostLedger::open(Some(dir.path().to_path_buf()));
+
+        daemon.record(entry("d1", "/proj", 0.75));
+        assert!(cli.exceeded(&config, "c1", "/proj").is_none());
+        daemon.record(entry("d1", "/proj", 0.5));
+        let status = cli.exceeded(&config, "c1", "/proj").unwrap();
+        assert_eq!(status.kind, BudgetKind::Daily);
+        assert!((status.spent_usd - 1.25).abs() < 1e-9);
+
+        // A half-written line waits until it is complete
+        let path = CostLedger::day_file(dir.path(), Local::now().date_naive());
+        let line = serde_json::to_string(&entry("d2", "/proj", 2.0)).unwrap();
+        let (head, tail) = line.split_at(10);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{}", head).unwrap();
+        cli.budgets(&config, "c1", "/proj");
+        assert!((cli.session_total("d2")).abs() < 1e-9);
+        writeln!(file, "{}", tail).unwrap();
+        cli.budgets(&config, "c1", "/proj");
+        assert!((cli.session_total("d2") - 2.0).abs() < 1e-9);
+        assert!((cli.daily_total(Local::now().date_naive()) - 3.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_session_spend_survives_month_boundary() {
+        let mut ledger = CostLedger::open(None);
+        let mut last_month = entry("s1", "/proj", 0.4);
+        last_month.ts = Local
+            .with_ymd_and_hms(2026, 9, 30, 23, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        ledger.record(last_month);
+        let day = NaiveDate::from_ymd_opt(2026, 9, 30).unwrap();
+        assert!((ledger.daily_total(day) - 0.4).abs() < 1e-9);
+
+        ledger.refresh(NaiveDate::from_ymd_opt(2026, 10, 1).unwrap());
+        assert_eq!(ledger.daily_total(day), 0.0);
+        assert_eq!(ledger.project_month_total("/proj", 2026, 9), 0.0);
+        assert!((ledger.session_total("s1") - 0.4).abs() < 1e-9);
+
+        // Sessions idle for more than a month are forgotten
+        ledger.refresh(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap());
+        assert_eq!(ledger.session_total("s1"), 0.0);
+    }
+
+    #[test]
+    fn test_budget_thresholds_reported_once() {
+        let config = CostConfig {
+            session_max_usd: Some(1.0),
+            daily_max_usd: Some(10.0),
+            ..Default::default()
+        };
+        let mut ledger = CostLedger::open(None);
+
+        ledger.record(entry("s1", "/proj", 0.6));
+        let crossed = ledger.crossed_thresholds(&config, "s1", "/proj");
+        assert_eq!(crossed.len(), 1);
+        assert_eq!(crossed[0].0.kind, BudgetKind::Session);
+        assert_eq!(crossed[0].1, 0.5);
+        assert!(ledger.crossed_thresholds(&config, "s1", "/proj").is_empty());
+
+        // Jumping past 80% and 100% at once reports only the highest
+        ledger.record(entry("s1", "/proj", 0.5));
+        let crossed = ledger.crossed_thresholds(&config, "s1", "/proj");
+        assert_eq!(crossed.len(), 1);
+        assert_eq!(crossed[0].1, 1.0);
+    }
+
+    #[test]
+    fn test_exceeded_budget_until_approved() {
+        let config = CostConfig {
+            project_monthly_max_usd: Some(1.0),
+            ..Default::default()
+        };
+        let mut ledger = CostLedger::open(None);
+        ledger.record(entry("s1", "/proj", 1.5));
+
+        assert!(ledger.exceeded(&config, "s2", "/other").is_none());
+        let status = ledger.exceeded(&config, "s2", "/proj").unwrap();
+        assert_eq!(status.kind, BudgetKind::ProjectMonthly);
+        assert_eq!(status.remaining_usd(), 0.0);
+
+        ledger.approve_overrun(&status.period);
+        assert!(ledger.exceeded(&config, "s2", "/proj").is_none());
+    }
 }
@@ -118,6 +118,11 @@ pub enum EventType {
         error_code: String,
         error_message: String,
     },
+    ModelRateLimitWait {
+        backend: String,
+        wait_ms: u64,
+        reason: String,
+    },
     ModelStreamStart {
         backend: String,
         model: String,
@@ -403,6 +408,17 @@ impl Event {
         )
     }
 
+    pub fn rate_limit_wait(backend: &str, wait_ms: u64, reason: &str) -> Self {
+        Self::new(
+            Subsystem::Model,
+            EventType::ModelRateLimitWait {
+                backend: backend.to_string(),
+                wait_ms,
+                reason: reason.to_string(),
+            },
+        )
+    }
+
     pub fn tool_invoked(
         session_id: &str,
         tool_name: &str,
@@ -488,6 +504,28 @@ impl Event {
         )
     }
 
+    pub fn queue_request_enqueued(lane: &str, request_id: &str, position: u32) -> Self {
+        Self::new(
+            Subsystem::Queue,
+            EventType::QueueRequestEnqueued {
+                lane: lane.to_string(),
+                request_id: request_id.to_string(),
+                position,
+            },
+        )
+    }
+
+    pub fn queue_request_dequeued(lane: &str, request_id: &str, wait_time_ms: u64) -> Self {
+        Self::new(
+            Subsystem::Queue,
+            EventType::QueueRequestDequeued {
+                lane: lane.to_string(),
+                request_id: request_id.to_string(),
+                wait_time_ms,
+            },
+        )
+    }
+
     pub fn run_attempt(session_id: &str, turn_number: u32, iteration: u32) -> Self {
         Self::new(
             Subsystem::Run,
@@ -546,6 +584,16 @@ impl Event {
         )
     }
 
+    pub fn circuit_half_open(backend: &str, probes_remaining: u32) -> Self {
+        Self::new(
+            Subsystem::Circuit,
+            EventType::CircuitHalfOpen {
+                backend: backend.to_string(),
+                probes_remaining,
+            },
+        )
+    }
+
     pub fn circuit_closed(backend: &str, success_probes: u32) -> Self {
         Self::new(
             Subsystem::Circuit,
@@ -598,6 +646,17 @@ impl Event {
             },
         )
     }
+
+    pub fn cost_budget_exceeded(session_id: &str, budget_usd: f64, actual_usd: f64) -> Self {
+        Self::new(
+            Subsystem::Cost,
+            EventType::CostBudgetExceeded {
+                session_id: session_id.to_string(),
+                budget_usd,
+                actual_usd,
+            },
+        )
+    }
 }
 
 /// Event listener callback type
@@ -1,6 +1,7 @@
 //! Agent connection - Unix socket client to communicate with agent daemon.
 
-use crate::protocol::internal::{AgentEvent, AgentRequest};
+use crate::protocol::internal::{AgentEvent, AgentEventType, AgentRequest};
+use serde_json::Value;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::path::Path;
@@ -60,14 +61,72 @@ impl AgentConnection {
 
         // Parse response
         if let Ok(event) = serde_json::from_str::<AgentEvent>(&line) {
-            Ok(matches!(
-                event.event,
-                crate::protocol::internal::AgentEventType::Pong
-            ))
+            Ok(matches!(event.event, AgentEventType::Pong))
         } else {
             Ok(false)
         }
     }
+
+    /// Fetch the agent daemon's Prometheus metrics
+    pub fn metrics(&self) -> Result<String, std::io::Error> {
+        let request = AgentRequest::metrics(&uuid::Uuid::new_v4().to_string());
+        match self.request_one(&request)?.event {
+            AgentEventType::Metrics { text } => Ok(text),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unexpected response to metrics request",
+            )),
+        }
+    }
+
+    /// Fetch a session's stored history as `(messages, turn_count)`.
+    /// Returns `None` when the daemon has no history for the session.
+    pub fn session(&self, session_id: &str) -> Result<Option<(Vec<Value>, u32)>, std::io::Error> {
+        let request = AgentRequest::session_get(&uuid::Uuid::new_v4().to_string(), session_id);
+        match self.request_one(&request)?.event {
+            AgentEventType::SessionHistory {
+                messages,
+                turn_count,
+                ..
+            } => Ok(Some((messages, turn_count))),
+            AgentEventType::Error { .. } => Ok(None),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unexpected response to session request",
+            )),
+        }
+    }
+
+    /// Cancel the session's running turns. Returns `false` when none was
+    /// running.
+    pub fn cancel(&self, session_id: &str) -> Result<bool, std::io::Error> {
+        let request = AgentRequest::cancel(&uuid::Uuid::new_v4().to_string(), session_id);
+        match self.request_one(&request)?.event {
+            AgentEventType::Done { .. } => Ok(true),
+            AgentEventType::Error { .. } => Ok(false),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unexpected response to cancel request",
+            )),
+        }
+    }
+
+    /// Send a request that is answered with a single event
+    fn request_one(&self, request: &AgentRequest) -> Result<AgentEvent, std::io::Error> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+
+        let json = serde_json::to_string(request)?;
+        writeln!(stream, "{}", json)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        serde_json::from_str(&line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
 }
 
 fn send_request_blocking(
@@ -174,4 +233,33 @@ impl AsyncAgentConnection {
         .await
         .map_err(|e| std::io::Error::other(e.to_string()))?
     }
+
+    /// Fetch the agent daemon's Prometheus metrics
+    pub async fn metrics(&self) -> Result<String, std::io::Error> {
+        let socket_path = self.inner.socket_path.clone();
+        tokio::task::spawn_blocking(move || AgentConnection::new(&socket_path).metrics())
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+    }
+
+    /// Cancel the session's running turns
+    pub async fn cancel(&self, session_id: &str) -> Result<bool, std::io::Error> {
+        let socket_path = self.inner.socket_path.clone();
+        let session_id = session_id.to_string();
+        tokio::task::spawn_blocking(move || AgentConnection::new(&socket_path).cancel(&session_id))
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+    }
+
+    /// Fetch a session's stored history
+    pub async fn session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<(Vec<Value>, u32)>, std::io::Error> {
+        let socket_path = self.inner.socket_path.clone();
+        let session_id = session_id.to_string();
+        tokio::task::spawn_blocking(move || AgentConnection::new(&socket_path).session(&session_id))
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+    }
 }
@@ -0,0 +1,203 @@
+//! Gateway handshake authentication.
+//!
+//! The gateway answers `hello` with a random nonce. Clients prove themselves
+//! either with an HMAC-SHA256 of the challenge keyed by the shared
+//! `BRAINPRO_GATEWAY_TOKEN`, or with an Ed25519 signature from a paired
+//! device key. Both sides sign the same challenge message, which binds the
+//! nonce to the device id from `hello`.
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use ring::{digest, hmac};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Random bytes in a challenge nonce
+const NONCE_LEN: usize = 32;
+
+/// Fresh base64 nonce for a challenge
+pub fn new_nonce() -> String {
+    let mut bytes = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system randomness unavailable");
+    BASE64.encode(bytes)
+}
+
+/// The bytes both HMAC and device signatures are computed over
+pub fn challenge_message(nonce: &str, device_id: &str) -> Vec<u8> {
+    format!("brainpro-auth-v1:{}:{}", nonce, device_id).into_bytes()
+}
+
+/// HMAC-SHA256 of the challenge keyed with the shared token (base64)
+pub fn token_signature(token: &str, nonce: &str, device_id: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes());
+    let tag = hmac::sign(&key, &challenge_message(nonce, device_id));
+    BASE64.encode(tag.as_ref())
+}
+
+/// Check a token HMAC in constant time
+pub fn verify_token(token: &str, nonce: &str, device_id: &str, signature: &str) -> bool {
+    let Ok(signature) = BASE64.decode(signature) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes());
+    hmac::verify(&key, &challenge_message(nonce, device_id), &signature).is_ok()
+}
+
+/// Compare a presented bearer token with the shared token in constant time
+pub fn verify_bearer(token: &str, presented: &str) -> bool {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes());
+    let tag = hmac::sign(&key, presented.as_bytes());
+    hmac::verify(&key, token.as_bytes(), tag.as_ref()).is_ok()
+}
+
+/// Check an Ed25519 signature over the challenge. Key and signature are base64.
+pub fn verify_device(public_key: &str, nonce: &str, device_id: &str, signature: &str) -> bool {
+    let (Ok(public_key), Ok(signature)) = (BASE64.decode(public_key), BASE64.decode(signature))
+    else {
+        return false;
+    };
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&challenge_message(nonce, device_id), &signature)
+        .is_ok()
+}
+
+/// Short fingerprint of a device key, for operators comparing keys out of band
+pub fn fingerprint(public_key: &str) -> String {
+    let hash = digest::digest(&digest::SHA256, public_key.as_bytes());
+    hash.as_ref()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// A client's persistent device id and Ed25519 key pair
+pub struct DeviceIdentity {
+    pub device_id: String,
+    key_pair: Ed25519KeyPair,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredIdentity {
+    device_id: String,
+    /// PKCS#8 document, base64
+    private_key: String,
+}
+
+impl DeviceIdentity {
+    /// Generate a new identity
+    pub fn generate() -> Result<(Self, Vec<u8>)> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| anyhow!("Failed to generate device key"))?;
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .map_err(|_| anyhow!("Invalid generated device key"))?;
+        let identity = Self {
+            device_id: uuid::Uuid::new_v4().to_string(),
+            key_pair,
+        };
+        Ok((identity, pkcs8.as_ref().to_vec()))
+    }
+
+    /// Load the identity from `path`, creating it on first use
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if let Ok(content) = fs::read_to_string(path) {
+            let stored: StoredIdentity = serde_json::from_str(&content)?;
+            let pkcs8 = BASE64.decode(&stored.private_key)?;
+            let key_pair = Ed25519KeyPair::from_pkcs8(&pkcs8)
+                .map_err(|_| anyhow!("Invalid device key in {}", path.display()))?;
+            return Ok(Self {
+                device_id: stored.device_id,
+                key_pair,
+            });
+        }
+
+        let (identity, pkcs8) = Self::generate()?;
+        let stored = StoredIdentity {
+            device_id: identity.device_id.clone(),
+            private_key: BASE64.encode(pkcs8),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&stored)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(identity)
+    }
+
+    /// Default identity file: `$BRAINPRO_DATA_DIR/device.json` or
+    /// `~/.brainpro/device.json`
+    pub fn default_path() -> PathBuf {
+        if let Ok(data_dir) = std::env::var("BRAINPRO_DATA_DIR") {
+            return PathBuf::from(data_dir).join("device.json");
+        }
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".brainpro")
+            .join("device.json")
+    }
+
+    /// Public key, base64
+    pub fn public_key(&self) -> String {
+        BASE64.encode(self.key_pair.public_key().as_ref())
+    }
+
+    /// Sign a challenge nonce (base64)
+    pub fn sign(&self, nonce: &str) -> String {
+        let signature = self
+            .key_pair
+            .sign(&challenge_message(nonce, &self.device_id));
+        BASE64.encode(signature.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_signature_round_trip() {
+        let nonce = new_nonce();
+        let signature = token_signature("secret", &nonce, "laptop");
+        assert!(verify_token("secret", &nonce, "laptop", &signature));
+        assert!(!verify_token("wrong", &nonce, "laptop", &signature));
+        assert!(!verify_token("secret", &new_nonce(), "laptop", &signature));
+        assert!(!verify_token("secret", &nonce, "phone", &signature));
+        assert!(!verify_token("secret", &nonce, "laptop", "not base64!"));
+
+        assert!(verify_bearer("secret", "secret"));
+        assert!(!verify_bearer("secret", "secre"));
+    }
+
+    #[test]
+    fn test_device_signature_round_trip() {
+        let (identity, _) = DeviceIdentity::generate().unwrap();
+        let (other, _) = DeviceIdentity::generate().unwrap();
+        let nonce = new_nonce();
+        let signature = identity.sign(&nonce);
+
+        let key = identity.public_key();
+        assert!(verify_device(&key, &nonce, &identity.device_id, &signature));
+        assert!(!verify_device(
+            &key,
+            &new_nonce(),
+            &identity.device_id,
+            &signature
+        ));
+        assert!(!verify_device(
+            &other.public_key(),
+            &nonce,
+            &identity.device_id,
+            &signature
+        ));
+    }
+}
@@ -4,6 +4,7 @@ use crate::protocol::client::{ClientCapabilities, ClientRole};
 use dashmap::DashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 /// Information about a connected client
 #[derive(Debug, Clone)]
@@ -33,6 +34,8 @@ pub struct ClientManager {
     senders: DashMap<String, ClientSender>,
     /// Map of session ID to client IDs (for broadcasting)
     sessions: DashMap<String, Vec<String>>,
+    /// Map of client ID to the token that closes its connection
+    closers: DashMap<String, CancellationToken>,
 }
 
 impl ClientManager {
@@ -41,10 +44,12 @@ impl ClientManager {
             clients: DashMap::new(),
             senders: DashMap::new(),
             sessions: DashMap::new(),
+            closers: DashMap::new(),
         })
     }
 
-    /// Register a new client
+    /// Register a new client. The returned token is cancelled when the
+    /// connection should be closed.
     pub fn register(
         &self,
         client_id: &str,
@@ -52,7 +57,7 @@ impl ClientManager {
         device_id: &str,
         caps: ClientCapabilities,
         sender: ClientSender,
-    ) {
+    ) -> CancellationToken {
         let info = ClientInfo {
             id: client_id.to_string(),
             role,
@@ -63,6 +68,16 @@ impl ClientManager {
         };
         self.clients.insert(client_id.to_string(), info);
         self.senders.insert(client_id.to_string(), sender);
+        let closer = CancellationToken::new();
+        self.closers.insert(client_id.to_string(), closer.clone());
+        closer
+    }
+
+    /// Close a client's connection
+    pub fn close(&self, client_id: &str) {
+        if let Some(closer) = self.closers.get(client_id) {
+            closer.cancel();
+        }
     }
 
     /// Unregister a client
@@ -77,13 +92,24 @@ impl ClientManager {
             }
         }
         self.senders.remove(client_id);
+        self.closers.remove(client_id);
     }
 
-    /// Associate a client with a session
+    /// Associate a client with a session, leaving its previous one
     pub fn join_session(&self, client_id: &str, session_id: &str) {
         // Update client info
-        if let Some(mut info) = self.clients.get_mut(client_id) {
-            info.session_id = Some(session_id.to_string());
+        let previous = self
+            .clients
+            .get_mut(client_id)
+            .and_then(|mut info| info.session_id.replace(session_id.to_string()));
+        if let Some(previous) = previous {
+            if previous == session_id {
+                return;
+            }
+            self.sessions.alter(&previous, |_, mut clients| {
+                clients.retain(|id| id != client_id);
+                clients
+            });
         }
 
         // Add to session's client list
@@ -115,6 +141,14 @@ impl ClientManager {
         }
     }
 
+    /// Clients currently in a session
+    pub fn session_members(&self, session_id: &str) -> Vec<ClientInfo> {
+        self.sessions
+            .get(session_id)
+            .map(|ids| ids.iter().filter_map(|id| self.get_client(id)).collect())
+            .unwrap_or_default()
+    }
+
     /// Get client info
     pub fn get_client(&self, client_id: &str) -> Option<ClientInfo> {
         self.clients.get(client_id).map(|r| r.clone())
@@ -155,6 +189,7 @@ impl Default for ClientManager {
             clients: DashMap::new(),
             senders: DashMap::new(),
             sessions: DashMap::new(),
+            closers: DashMap::new(),
         }
     }
 }
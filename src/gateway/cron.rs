@@ -0,0 +1,440 @@
+//! Scheduled prompts for the gateway.
+//!
+//! A job runs its prompt as an agent turn in a target session, either on a
+//! five-field cron expression (`minute hour day-of-month month day-of-week`,
+//! evaluated in local time) or at a fixed interval. Jobs persist to
+//! `cron.json` in the gateway data directory.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Shortest interval a job may repeat at
+const MIN_INTERVAL_SECS: u64 = 60;
+
+/// Longest interval a job may repeat at
+const MAX_INTERVAL_SECS: u64 = 366 * 86400;
+
+/// How far ahead to look for the next cron match before giving up
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 4;
+
+/// When a job fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Schedule {
+    /// Cron expression, e.g. `0 2 * * *` or `@daily`
+    Cron { expr: String },
+    /// Fixed interval in seconds
+    Every { seconds: u64 },
+}
+
+impl Schedule {
+    /// Parse and validate a cron expression
+    pub fn cron(expr: &str) -> Result<Self> {
+        CronExpr::parse(expr)?;
+        Ok(Schedule::Cron {
+            expr: expr.trim().to_string(),
+        })
+    }
+
+    /// Parse an interval such as `90s`, `15m`, `2h`, `1d` or plain seconds
+    pub fn every(interval: &str) -> Result<Self> {
+        let interval = interval.trim();
+        let (digits, unit) = match interval.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => interval.split_at(i),
+            None => (interval, "s"),
+        };
+        let count: u64 = digits
+            .parse()
+            .map_err(|_| anyhow!("Invalid interval: {}", interval))?;
+        let multiplier = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            _ => bail!("Invalid interval unit in '{}' (use s, m, h or d)", interval),
+        };
+        let seconds = count
+            .checked_mul(multiplier)
+            .filter(|s| *s <= MAX_INTERVAL_SECS)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Interval must be at most {} days",
+                    MAX_INTERVAL_SECS / 86400
+                )
+            })?;
+        if seconds < MIN_INTERVAL_SECS {
+            bail!("Interval must be at least {} seconds", MIN_INTERVAL_SECS);
+        }
+        Ok(Schedule::Every { seconds })
+    }
+
+    /// First fire time strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every { seconds } => {
+                let interval = Duration::try_seconds(i64::try_from(*seconds).ok()?)?;
+                after.checked_add_signed(interval)
+            }
+            Schedule::Cron { expr } => CronExpr::parse(expr)
+                .ok()?
+                .next_after(after.with_timezone(&Local)),
+        }
+    }
+}
+
+/// Parsed cron expression, one bit per allowed value
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Day-of-month field was `*`
+    any_day_of_month: bool,
+    /// Day-of-week field was `*`
+    any_day_of_week: bool,
+}
+
+impl CronExpr {
+    fn parse(expr: &str) -> Result<Self> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!("Cron expression needs 5 fields, got {}", fields.len());
+        }
+
+        // Day-of-week accepts 7 for Sunday
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+
+    /// Standard cron rule: when both day fields are restricted, either may match
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let dom = self.days_of_month & (1 << time.day()) != 0;
+        let dow = self.days_of_week & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(MAX_LOOKAHEAD_DAYS);
+        let mut time = start;
+
+        while time < limit {
+            if self.months & (1 << time.month()) == 0 || !self.matches_day(&time) {
+                time = (time.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += Duration::minutes(1);
+                continue;
+            }
+            // Skip local times that don't exist (DST gaps)
+            if let Some(local) = Local.from_local_datetime(&time).earliest() {
+                return Some(local.with_timezone(&Utc));
+            }
+            time += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// Parse one cron field (`*`, `*/n`, `a`, `a-b`, `a-b/n`, comma lists) into a bitset
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid step in cron field '{}'", field))?;
+                if step == 0 {
+                    bail!("Step must be positive in cron field '{}'", field);
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, field)?, parse_value(b, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // `5/15` means from 5 to the end in steps of 15
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            bail!("Cron field '{}' out of range ({}-{})", field, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid value '{}' in cron field '{}'", value, field))
+}
+
+/// A scheduled prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CronJob {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub prompt: String,
+    pub schedule: Schedule,
+    /// Session the turn runs in; clients in it receive the results
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    /// Device id of the operator who added the job
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<DateTime<Utc>>,
+    /// None once the schedule has no further matches
+    #[serde(default)]
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// Persistent set of cron jobs
+pub struct CronStore {
+    path: PathBuf,
+    jobs: Mutex<Vec<CronJob>>,
+}
+
+impl CronStore {
+    /// Open the store at `path`, starting empty if it doesn't exist
+    pub fn open(path: PathBuf) -> Self {
+        let jobs = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    /// Store in the gateway data directory
+    pub fn with_default_path() -> Self {
+        Self::open(super::data_dir().join("cron.json"))
+    }
+
+    /// Add a job, computing its first run
+    pub fn add(&self, mut job: CronJob) -> std::io::Result<CronJob> {
+        job.next_run = job.schedule.next_after(Utc::now());
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push(job.clone());
+        self.save(&jobs)?;
+        Ok(job)
+    }
+
+    /// Remove a job. Returns false if there was none with this id.
+    pub fn remove(&self, id: &str) -> std::io::Result<bool> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let before = jobs.len();
+        jobs.retain(|j| j.id != id);
+        if jobs.len() == before {
+            return Ok(false);
+        }
+        self.save(&jobs)?;
+        Ok(true)
+    }
+
+    pub fn list(&self) -> Vec<CronJob> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    /// Jobs due at `now`, with their next run already advanced
+    pub fn take_due(&self, now: DateTime<Utc>) -> Vec<CronJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut due = Vec::new();
+        for job in jobs.iter_mut() {
+            if job.next_run.is_some_and(|next| next <= now) {
+                job.last_run = Some(now);
+                job.next_run = job.schedule.next_after(now);
+                due.push(job.clone());
+            }
+        }
+        if !due.is_empty() {
+            if let Err(e) = self.save(&jobs) {
+                eprintln!("[gateway] Failed to save cron jobs: {}", e);
+            }
+        }
+        due
+    }
+
+    fn save(&self, jobs: &[CronJob]) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(jobs)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(s: &str) -> DateTime<Utc> {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_cron_fields() {
+        let expr = CronExpr::parse("*/15 2-4 * * 1,7").unwrap();
+        assert_eq!(expr.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(expr.hours, 1 << 2 | 1 << 3 | 1 << 4);
+        assert_eq!(expr.days_of_week, 1 | 1 << 1);
+        assert!(CronExpr::parse("0 2 * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(Schedule::cron("@daily").is_ok());
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let nightly = Schedule::cron("0 2 * * *").unwrap();
+        assert_eq!(
+            nightly.next_after(local("2026-03-10 01:30")),
+            Some(local("2026-03-10 02:00"))
+        );
+        assert_eq!(
+            nightly.next_after(local("2026-03-10 02:00")),
+            Some(local("2026-03-11 02:00"))
+        );
+
+        // 2026-03-13 is a Friday; the next weekday run is Monday
+        let weekdays = Schedule::cron("30 9 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(local("2026-03-13 10:00")),
+            Some(local("2026-03-16 09:30"))
+        );
+
+        let never = Schedule::cron("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(local("2026-01-01 00:00")), None);
+    }
+
+    #[test]
+    fn test_every_intervals() {
+        assert_eq!(
+            Schedule::every("15m").unwrap(),
+            Schedule::Every { seconds: 900 }
+        );
+        assert_eq!(
+            Schedule::every("3600").unwrap(),
+            Schedule::Every { seconds: 3600 }
+        );
+        assert!(Schedule::every("10s").is_err());
+        assert!(Schedule::every("2w").is_err());
+    }
+
+    #[test]
+    fn test_every_rejects_out_of_range_intervals() {
+        assert!(Schedule::every("0").is_err());
+        assert!(Schedule::every("0d").is_err());
+        assert!(Schedule::every("367d").is_err());
+        assert!(Schedule::every("18446744073709551615").is_err());
+        assert!(Schedule::every("18446744073709551615d").is_err());
+        assert!(Schedule::every("99999999999999999999").is_err());
+        assert_eq!(
+            Schedule::every("366d").unwrap(),
+            Schedule::Every {
+                seconds: MAX_INTERVAL_SECS
+            }
+        );
+    }
+
+    #[test]
+    fn test_every_next_after_never_wraps() {
+        let now = Utc::now();
+        // Intervals that only arrive through a hand-edited cron.json
+        let huge = Schedule::Every { seconds: u64::MAX };
+        assert_eq!(huge.next_after(now), None);
+        let beyond_chrono = Schedule::Every {
+            seconds: i64::MAX as u64,
+        };
+        assert_eq!(beyond_chrono.next_after(now), None);
+
+        let hourly = Schedule::every("1h").unwrap();
+        assert_eq!(hourly.next_after(now), Some(now + Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_store_take_due() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cron.json");
+        let store = CronStore::open(path.clone());
+        let job = store
+            .add(CronJob {
+                id: "job-1".to_string(),
+                name: None,
+                prompt: "summarize yesterday's commits".to_string(),
+                schedule: Schedule::every("1h").unwrap(),
+                session_id: "cron-session".to_string(),
+                persona: None,
+                working_dir: None,
+                created_by: "laptop".to_string(),
+                created_at: Utc::now(),
+                last_run: None,
+                next_run: None,
+            })
+            .unwrap();
+        let next = job.next_run.unwrap();
+
+        assert!(store.take_due(next - Duration::seconds(1)).is_empty());
+        let due = store.take_due(next);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].next_run, Some(next + Duration::hours(1)));
+
+        let reopened = CronStore::open(path);
+        assert_eq!(reopened.list()[0].last_run, Some(next));
+        assert!(reopened.remove("job-1").unwrap());
+        assert!(!reopened.remove("job-1").unwrap());
+    }
+}
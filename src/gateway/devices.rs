@@ -0,0 +1,175 @@
+//! Paired device registry.
+//!
+//! Devices authenticate with an Ed25519 key. A key is trusted once an
+//! operator approves its pairing request; approvals and revocations are
+//! persisted to `devices.json` so they survive gateway restarts.
+
+use crate::protocol::client::ClientRole;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+/// A device whose key an operator approved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairedDevice {
+    pub device_id: String,
+    /// Ed25519 public key, base64
+    pub public_key: String,
+    pub role: ClientRole,
+    pub paired_at: DateTime<Utc>,
+    /// Device id of the operator who approved the pairing
+    pub approved_by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl PairedDevice {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+/// How a presented device key relates to the registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceStatus {
+    /// Paired with this key and not revoked
+    Paired(ClientRole),
+    /// Paired with a different key
+    KeyMismatch,
+    Revoked,
+    Unknown,
+}
+
+/// Persistent registry of paired devices
+pub struct DeviceStore {
+    path: PathBuf,
+    devices: RwLock<HashMap<String, PairedDevice>>,
+}
+
+impl DeviceStore {
+    /// Open the registry at `path`, starting empty if it doesn't exist
+    pub fn open(path: PathBuf) -> Self {
+        let devices = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Vec<PairedDevice>>(&content).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|d| (d.device_id.clone(), d))
+            .collect();
+        Self {
+            path,
+            devices: RwLock::new(devices),
+        }
+    }
+
+    /// Registry in the default data directory
+    pub fn with_default_path() -> Self {
+        Self::open(super::data_dir().join("devices.json"))
+    }
+
+    /// Look up a device by id and presented key
+    pub fn status(&self, device_id: &str, public_key: &str) -> DeviceStatus {
+        let devices = self.devices.read().unwrap();
+        match devices.get(device_id) {
+            None => DeviceStatus::Unknown,
+            Some(d) if d.is_revoked() => DeviceStatus::Revoked,
+            Some(d) if d.public_key != public_key => DeviceStatus::KeyMismatch,
+            Some(d) => DeviceStatus::Paired(d.role),
+        }
+    }
+
+    /// Whether the device was paired and has been revoked
+    pub fn is_revoked(&self, device_id: &str) -> bool {
+        self.devices
+            .read()
+            .unwrap()
+            .get(device_id)
+            .is_some_and(|d| d.is_revoked())
+    }
+
+    /// Record an approved pairing, replacing any earlier (revoked) entry
+    pub fn pair(
+        &self,
+        device_id: &str,
+        public_key: &str,
+        role: ClientRole,
+        approved_by: &str,
+    ) -> std::io::Result<()> {
+        let device = PairedDevice {
+            device_id: device_id.to_string(),
+            public_key: public_key.to_string(),
+            role,
+            paired_at: Utc::now(),
+            approved_by: approved_by.to_string(),
+            revoked_at: None,
+        };
+        self.devices
+            .write()
+            .unwrap()
+            .insert(device_id.to_string(), device);
+        self.save()
+    }
+
+    /// Revoke a device. Returns false if it isn't paired.
+    pub fn revoke(&self, device_id: &str) -> std::io::Result<bool> {
+        {
+            let mut devices = self.devices.write().unwrap();
+            match devices.get_mut(device_id) {
+                Some(d) if !d.is_revoked() => d.revoked_at = Some(Utc::now()),
+                _ => return Ok(false),
+            }
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// All known devices, revoked ones included
+    pub fn list(&self) -> Vec<PairedDevice> {
+        let mut devices: Vec<_> = self.devices.read().unwrap().values().cloned().collect();
+        devices.sort_by_key(|d| d.paired_at);
+        devices
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&self.list())?;
+        fs::write(&self.path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pair_and_revoke_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devices.json");
+        let store = DeviceStore::open(path.clone());
+
+        assert_eq!(store.status("phone", "key-a"), DeviceStatus::Unknown);
+        store
+            .pair("phone", "key-a", ClientRole::Operator, "laptop")
+            .unwrap();
+        assert_eq!(
+            store.status("phone", "key-a"),
+            DeviceStatus::Paired(ClientRole::Operator)
+        );
+        assert_eq!(store.status("phone", "key-b"), DeviceStatus::KeyMismatch);
+        assert!(!store.is_revoked("phone"));
+
+        assert!(store.revoke("phone").unwrap());
+        assert!(!store.revoke("phone").unwrap());
+
+        let reopened = DeviceStore::open(path);
+        assert_eq!(reopened.status("phone", "key-a"), DeviceStatus::Revoked);
+        assert!(reopened.is_revoked("phone"));
+        assert!(!reopened.is_revoked("tablet"));
+        assert_eq!(reopened.list().len(), 1);
+    }
+}
@@ -0,0 +1,301 @@
+//! Per-session event log, so reconnecting clients can replay what they missed.
+//!
+//! Every event the gateway pushes to a session gets the session's next
+//! sequence number and is kept in a bounded ring. Yields still waiting on a
+//! client are also kept apart, so they survive eviction from the ring until
+//! the turn is resumed. The log also remembers who answered each yield, so
+//! when several operators share a session only the first answer counts.
+
+use crate::protocol::client::{events, ClientEvent};
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Events kept per session
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Sessions kept before the least recently active one is dropped
+pub const DEFAULT_MAX_SESSIONS: usize = 256;
+
+/// How long a yield stays pending; matches the daemon's turn state TTL
+const PENDING_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// A yield that has been answered
+struct Answer {
+    turn_id: String,
+    /// Device id of the client that answered
+    by: String,
+    /// The pending yield, restored if the answer is released
+    event: Option<(Instant, ClientEvent)>,
+}
+
+struct SessionLog {
+    next_seq: u64,
+    events: VecDeque<ClientEvent>,
+    /// Yields waiting on a client, with when they were recorded
+    pending: Vec<(Instant, ClientEvent)>,
+    answered: VecDeque<Answer>,
+    updated: Instant,
+}
+
+impl SessionLog {
+    fn new() -> Self {
+        Self {
+            next_seq: 1,
+            events: VecDeque::new(),
+            pending: Vec::new(),
+            answered: VecDeque::new(),
+            updated: Instant::now(),
+        }
+    }
+}
+
+/// What a client missed since a sequence number
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// Logged events after the requested sequence number, oldest first
+    pub events: Vec<ClientEvent>,
+    /// Yields still waiting for an answer
+    pub pending: Vec<ClientEvent>,
+    /// Sequence number of the session's latest event
+    pub last_seq: u64,
+    /// Whether events after the requested sequence number were evicted
+    pub truncated: bool,
+}
+
+/// Bounded event logs for all sessions
+pub struct EventLog {
+    sessions: DashMap<String, SessionLog>,
+    capacity: usize,
+    max_sessions: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_MAX_SESSIONS)
+    }
+}
+
+impl EventLog {
+    pub fn new(capacity: usize, max_sessions: usize) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            capacity: capacity.max(1),
+            max_sessions: max_sessions.max(1),
+        }
+    }
+
+    /// Number `event` and append it to the session's log. Returns the
+    /// numbered event to deliver.
+    pub fn record(&self, session_id: &str, mut event: ClientEvent) -> ClientEvent {
+        if !self.sessions.contains_key(session_id) {
+            self.evict_idle();
+        }
+        let mut log = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(SessionLog::new);
+
+        event.seq = Some(log.next_seq);
+        log.next_seq += 1;
+        log.updated = Instant::now();
+
+        if is_pending_yield(&event) {
+            log.pending.push((Instant::now(), event.clone()));
+        }
+        if log.events.len() == self.capacity {
+            log.events.pop_front();
+        }
+        log.events.push_back(event.clone());
+        event
+    }
+
+    /// Claim the answer to a yield for the device `by`, returning the yield
+    /// event if it was logged. Fails with the device that answered first if
+    /// the yield was already answered.
+    pub fn claim(
+        &self,
+        session_id: &str,
+        turn_id: &str,
+        by: &str,
+    ) -> Result<Option<ClientEvent>, String> {
+        let Some(mut log) = self.sessions.get_mut(session_id) else {
+            return Ok(None);
+        };
+        if let Some(answer) = log.answered.iter().find(|a| a.turn_id == turn_id) {
+            return Err(answer.by.clone());
+        }
+        let event = log
+            .pending
+            .iter()
+            .position(|(_, e)| event_turn_id(e) == Some(turn_id))
+            .map(|i| log.pending.remove(i));
+        if log.answered.len() == self.capacity {
+            log.answered.pop_front();
+        }
+        let yielded = event.as_ref().map(|(_, e)| e.clone());
+        log.answered.push_back(Answer {
+            turn_id: turn_id.to_string(),
+            by: by.to_string(),
+            event,
+        });
+        Ok(yielded)
+    }
+
+    /// Undo a claim whose answer never reached the agent, so the yield is
+    /// pending again
+    pub fn release(&self, session_id: &str, turn_id: &str) {
+        let Some(mut log) = self.sessions.get_mut(session_id) else {
+            return;
+        };
+        if let Some(i) = log.answered.iter().position(|a| a.turn_id == turn_id) {
+            if let Some(answer) = log.answered.remove(i) {
+                log.pending.extend(answer.event);
+            }
+        }
+    }
+
+    /// Whether the session has a log
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.sessions.contains_key(session_id)
+    }
+
+    /// Events after `since_seq` plus pending yields, or `None` for a session
+    /// with no log
+    pub fn replay(&self, session_id: &str, since_seq: u64) -> Option<Replay> {
+        let mut log = self.sessions.get_mut(session_id)?;
+        log.pending.retain(|(at, _)| at.elapsed() < PENDING_TTL);
+
+        let oldest = log
+            .events
+            .front()
+            .and_then(|e| e.seq)
+            .unwrap_or(log.next_seq);
+        Some(Replay {
+            events: log
+                .events
+                .iter()
+                .filter(|e| e.seq.is_some_and(|seq| seq > since_seq))
+                .cloned()
+                .collect(),
+            pending: log.pending.iter().map(|(_, e)| e.clone()).collect(),
+            last_seq: log.next_seq - 1,
+            truncated: since_seq.saturating_add(1) < oldest,
+        })
+    }
+
+    /// Drop the least recently active session when at the session limit
+    fn evict_idle(&self) {
+        if self.sessions.len() < self.max_sessions {
+            return;
+        }
+        let oldest = self
+            .sessions
+            .iter()
+            .min_by_key(|entry| entry.updated)
+            .map(|entry| entry.key().clone());
+        if let Some(session_id) = oldest {
+            self.sessions.remove(&session_id);
+        }
+    }
+}
+
+/// Yield events a client still has to answer
+fn is_pending_yield(event: &ClientEvent) -> bool {
+    matches!(
+        event.event.as_str(),
+        events::AGENT_AWAITING_APPROVAL
+            | events::AGENT_AWAITING_INPUT
+            | events::AGENT_BUDGET_EXCEEDED
+    ) && event_turn_id(event).is_some()
+}
+
+fn event_turn_id(event: &ClientEvent) -> Option<&str> {
+    event.data.get("turn_id").and_then(|t| t.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(name: &str, data: serde_json::Value) -> ClientEvent {
+        ClientEvent::new(name, data, Some("s1".to_string()))
+    }
+
+    #[test]
+    fn test_replay_since_seq() {
+        let log = EventLog::new(3, 10);
+        for i in 0..5 {
+            let recorded = log.record("s1", event(events::AGENT_MESSAGE, json!({ "i": i })));
+            assert_eq!(recorded.seq, Some(i + 1));
+        }
+
+        let replay = log.replay("s1", 3).unwrap();
+        assert_eq!(replay.last_seq, 5);
+        assert!(!replay.truncated);
+        let seqs: Vec<_> = replay.events.iter().filter_map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![4, 5]);
+
+        // Event 2 was evicted, so a client that saw only event 1 missed some
+        let replay = log.replay("s1", 1).unwrap();
+        assert!(replay.truncated);
+        assert_eq!(replay.events.len(), 3);
+
+        assert!(log.replay("other", 0).is_none());
+    }
+
+    #[test]
+    fn test_pending_yields_survive_eviction() {
+        let log = EventLog::new(2, 10);
+        log.record(
+            "s1",
+            event(
+                events::AGENT_AWAITING_APPROVAL,
+                json!({ "turn_id": "t1", "tool_name": "Bash" }),
+            ),
+        );
+        for _ in 0..3 {
+            log.record("s1", event(events::HEALTH_TICK, json!({})));
+        }
+
+        let replay = log.replay("s1", 4).unwrap();
+        assert!(replay.events.is_empty());
+        assert_eq!(replay.pending.len(), 1);
+        assert_eq!(replay.pending[0].seq, Some(1));
+
+        log.claim("s1", "t1", "laptop").unwrap();
+        assert!(log.replay("s1", 0).unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn test_first_claim_wins() {
+        let log = EventLog::default();
+        log.record(
+            "s1",
+            event(events::AGENT_AWAITING_APPROVAL, json!({ "turn_id": "t1" })),
+        );
+
+        let yielded = log.claim("s1", "t1", "lead").unwrap();
+        assert_eq!(yielded.unwrap().event, events::AGENT_AWAITING_APPROVAL);
+        assert_eq!(log.claim("s1", "t1", "junior").unwrap_err(), "lead");
+
+        // A released claim makes the yield pending and answerable again
+        log.release("s1", "t1");
+        assert_eq!(log.replay("s1", 0).unwrap().pending.len(), 1);
+        assert!(log.claim("s1", "t1", "junior").is_ok());
+    }
+
+    #[test]
+    fn test_idle_sessions_evicted() {
+        let log = EventLog::new(10, 2);
+        log.record("a", event(events::AGENT_MESSAGE, json!({})));
+        log.record("b", event(events::AGENT_MESSAGE, json!({})));
+        log.record("a", event(events::AGENT_MESSAGE, json!({})));
+        log.record("c", event(events::AGENT_MESSAGE, json!({})));
+
+        assert!(log.replay("a", 0).is_some());
+        assert!(log.replay("b", 0).is_none());
+        assert!(log.replay("c", 0).is_some());
+    }
+}
@@ -1,8 +1,6 @@
 //! Lane-based concurrency for gateway request processing.
 //!
 //! This module provides infrastructure for prioritized request processing.
-
-#![allow(dead_code)]
 //!
 //! Lanes provide prioritized, concurrent request processing with:
 //! - Priority ordering (Cron > Main > Subagent > Batch)
@@ -33,12 +31,18 @@
 //!        ▼
 //!   Worker Pool
 //! ```
+//!
+//! Callers take a [`LaneTicket`] with [`LaneManager::acquire`] and await
+//! [`LaneTicket::granted`]. The [`LaneWorker`] grants slots in priority order
+//! within each lane's concurrency limit; the [`LaneSlot`] holds its place
+//! until dropped.
 
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
 
 /// Lane types for request categorization
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, PartialOrd, Ord)]
@@ -202,12 +206,51 @@ pub struct LaneStats {
     pub avg_wait_time_ms: f64,
 }
 
+impl LaneConfig {
+    /// Set a lane's concurrency limit
+    pub fn set_concurrency(&mut self, lane_type: LaneType, limit: usize) {
+        let limit = limit.max(1);
+        match lane_type {
+            LaneType::Cron => self.cron_concurrency = limit,
+            LaneType::Main => self.main_concurrency = limit,
+            LaneType::Subagent => self.subagent_concurrency = limit,
+            LaneType::Batch => self.batch_concurrency = limit,
+        }
+    }
+}
+
+/// A queued request waiting for its lane slot
+pub struct LaneTicket {
+    pub id: String,
+    pub lane: LaneType,
+    /// 1-based position in the lane's queue when enqueued
+    pub position: usize,
+    slot: oneshot::Receiver<LaneSlot>,
+}
+
+impl LaneTicket {
+    /// Wait until the lane has room. None if the manager dropped the request.
+    pub async fn granted(self) -> Option<LaneSlot> {
+        self.slot.await.ok()
+    }
+}
+
+/// A running request's place in its lane; dropping it frees the slot
+pub struct LaneSlot {
+    pub id: String,
+    pub lane: LaneType,
+    pub wait_time_ms: u64,
+    _release: oneshot::Sender<()>,
+}
+
 /// Lane manager for request routing and scheduling
 pub struct LaneManager {
     lanes: Mutex<[Lane; 4]>,
     config: LaneConfig,
     /// Global request counter for IDs
     request_counter: AtomicU64,
+    /// Tickets waiting for a slot, by request ID
+    waiters: Mutex<HashMap<String, oneshot::Sender<LaneSlot>>>,
 }
 
 impl LaneManager {
@@ -221,9 +264,47 @@ impl LaneManager {
             ]),
             config,
             request_counter: AtomicU64::new(0),
+            waiters: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Queue a request and get a ticket to wait on
+    pub fn acquire(
+        &self,
+        lane_type: LaneType,
+        session_id: String,
+    ) -> Result<LaneTicket, LaneError> {
+        // Hold the waiter lock so a worker can't dequeue before we register
+        let mut waiters = self.waiters.lock().unwrap();
+        let (id, position) = self.enqueue(lane_type, session_id, serde_json::Value::Null)?;
+        let (tx, rx) = oneshot::channel();
+        waiters.insert(id.clone(), tx);
+        Ok(LaneTicket {
+            id,
+            lane: lane_type,
+            position,
+            slot: rx,
+        })
+    }
+
+    /// Hand a dequeued request its slot and wait until the holder releases it
+    pub async fn grant(&self, request: QueuedRequest) {
+        let Some(waiter) = self.waiters.lock().unwrap().remove(&request.id) else {
+            return;
+        };
+        let (release_tx, release_rx) = oneshot::channel();
+        let slot = LaneSlot {
+            id: request.id,
+            lane: request.lane,
+            wait_time_ms: request.queued_at.elapsed().as_millis() as u64,
+            _release: release_tx,
+        };
+        if waiter.send(slot).is_ok() {
+            // Resolves with an error once the slot is dropped
+            let _ = release_rx.await;
+        }
+    }
+
     /// Enqueue a request to the appropriate lane
     ///
     /// Returns Ok(position) if queued, Err if queue is full
@@ -359,11 +440,13 @@ impl LaneWorker {
         Self { manager, shutdown }
     }
 
-    /// Run the worker, processing requests until shutdown
+    /// Run the worker, processing requests until shutdown. Each request's
+    /// handler runs on its own task; the lane's concurrency limit bounds how
+    /// many run at once.
     pub async fn run<F, Fut>(self, handler: F)
     where
         F: Fn(QueuedRequest) -> Fut + Send + Sync + 'static,
-        Fut: std::future::Future<Output = ()> + Send,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
     {
         let mut shutdown = self.shutdown;
         let poll_interval = Duration::from_millis(10);
@@ -376,10 +459,14 @@ impl LaneWorker {
                     }
                 }
                 _ = tokio::time::sleep(poll_interval) => {
-                    if let Some(request) = self.manager.try_dequeue() {
+                    while let Some(request) = self.manager.try_dequeue() {
                         let lane = request.lane;
-                        handler(request).await;
-                        self.manager.complete(lane);
+                        let manager = Arc::clone(&self.manager);
+                        let task = handler(request);
+                        tokio::spawn(async move {
+                            task.await;
+                            manager.complete(lane);
+                        });
                     }
                 }
             }
@@ -499,6 +586,39 @@ mod tests {
         assert!(matches!(result, Err(LaneError::QueueFull { .. })));
     }
 
+    #[tokio::test]
+    async fn test_tickets_respect_concurrency() {
+        let config = LaneConfig {
+            main_concurrency: 1,
+            ..LaneConfig::default()
+        };
+        let manager = LaneManager::new(config);
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let worker = LaneWorker::new(Arc::clone(&manager), shutdown_rx);
+        let granter = Arc::clone(&manager);
+        tokio::spawn(worker.run(move |request| {
+            let manager = Arc::clone(&granter);
+            async move { manager.grant(request).await }
+        }));
+
+        let first = manager.acquire(LaneType::Main, "s1".to_string()).unwrap();
+        let second = manager.acquire(LaneType::Main, "s2".to_string()).unwrap();
+        assert_eq!((first.position, second.position), (1, 2));
+
+        let slot = first.granted().await.unwrap();
+        let mut second = tokio::spawn(second.granted());
+        let waited = tokio::time::timeout(Duration::from_millis(100), &mut second).await;
+        assert!(waited.is_err(), "second ticket granted while lane was full");
+
+        drop(slot);
+        let slot = tokio::time::timeout(Duration::from_secs(2), second)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(slot.lane, LaneType::Main);
+    }
+
     #[test]
     fn test_lane_stats() {
         let manager = LaneManager::new(LaneConfig::default());
@@ -4,6 +4,27 @@
 #![allow(dead_code)]
 
 pub mod agent_conn;
+pub mod auth;
 pub mod client_mgr;
+pub mod cron;
+pub mod devices;
+pub mod event_log;
 pub mod lanes;
+pub mod nodes;
+pub mod openai;
 pub mod server;
+pub mod ui;
+
+use std::path::PathBuf;
+
+/// Where the gateway keeps paired devices and cron jobs:
+/// `$BRAINPRO_DATA_DIR` or `~/.brainpro`
+pub fn data_dir() -> PathBuf {
+    std::env::var("BRAINPRO_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".brainpro")
+        })
+}
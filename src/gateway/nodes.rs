@@ -0,0 +1,304 @@
+//! Remote tool execution on node clients.
+//!
+//! Nodes advertise tools in `hello`. The agent sees each one as
+//! `node.<device_id>.<tool>`; when it calls one, the gateway sends the node a
+//! `node.invoke` request and waits for the matching `res` frame.
+
+use crate::gateway::client_mgr::{ClientInfo, ClientManager};
+use crate::protocol::client::{methods, ClientRequest, ErrorInfo, ResponsePayload, ToolSchema};
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+const NODE_TOOL_PREFIX: &str = "node.";
+
+/// Name the agent sees for a node's tool
+pub fn tool_name(device_id: &str, tool: &str) -> String {
+    format!("{}{}.{}", NODE_TOOL_PREFIX, device_id, tool)
+}
+
+/// Split `node.<device_id>.<tool>` into device id and tool
+pub fn parse_tool_name(name: &str) -> Option<(&str, &str)> {
+    let rest = name.strip_prefix(NODE_TOOL_PREFIX)?;
+    let (device_id, tool) = rest.split_once('.')?;
+    (!device_id.is_empty() && !tool.is_empty()).then_some((device_id, tool))
+}
+
+/// Device ids and tool names end up in a dotted function name, so keep them
+/// to characters that parse back unambiguously
+fn is_valid_part(part: &str) -> bool {
+    !part.is_empty()
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Function schemas for every tool the connected nodes advertise. Tools
+/// listed without a schema take arbitrary object arguments.
+pub fn tool_schemas(nodes: &[ClientInfo]) -> Vec<Value> {
+    let mut seen = HashSet::new();
+    let mut schemas = Vec::new();
+    for node in nodes {
+        if !is_valid_part(&node.device_id) {
+            continue;
+        }
+        let listed = node.caps.tools.iter().map(|name| ToolSchema {
+            name: name.clone(),
+            description: String::new(),
+            parameters: json!({ "type": "object", "properties": {} }),
+        });
+        for tool in node.caps.tool_schemas.iter().cloned().chain(listed) {
+            if !is_valid_part(&tool.name) {
+                continue;
+            }
+            let name = tool_name(&node.device_id, &tool.name);
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let description = if tool.description.is_empty() {
+                format!("Run {} on the node {}", tool.name, node.device_id)
+            } else {
+                format!("{} (runs on the node {})", tool.description, node.device_id)
+            };
+            schemas.push(json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": description,
+                    "parameters": tool.parameters,
+                }
+            }));
+        }
+    }
+    schemas
+}
+
+/// A node tool call waiting for the node's response
+struct PendingCall {
+    client_id: String,
+    result: oneshot::Sender<Result<Value, ErrorInfo>>,
+}
+
+/// Node tool calls in flight, by request id
+#[derive(Default)]
+pub struct NodeCalls {
+    pending: DashMap<String, PendingCall>,
+}
+
+impl NodeCalls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `tool` on the node `device_id` and wait up to `timeout` for its result
+    pub async fn call(
+        &self,
+        clients: &ClientManager,
+        device_id: &str,
+        tool: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> Result<Value, ErrorInfo> {
+        let node = clients
+            .list_nodes()
+            .into_iter()
+            .find(|n| {
+                n.device_id == device_id
+                    && (n.caps.tools.iter().any(|t| t == tool)
+                        || n.caps.tool_schemas.iter().any(|t| t.name == tool))
+            })
+            .ok_or_else(|| {
+                error(
+                    "node_unavailable",
+                    &format!("Node {} with tool {} is not connected", device_id, tool),
+                )
+            })?;
+
+        let call_id = uuid::Uuid::new_v4().to_string();
+        let request = ClientRequest::new(
+            &call_id,
+            methods::NODE_INVOKE,
+            json!({ "tool": tool, "args": args }),
+        );
+        let (result_tx, result_rx) = oneshot::channel();
+        self.pending.insert(
+            call_id.clone(),
+            PendingCall {
+                client_id: node.id.clone(),
+                result: result_tx,
+            },
+        );
+
+        let sent = serde_json::to_string(&request)
+            .map(|json| clients.send_to_client(&node.id, &json))
+            .unwrap_or(false);
+        if !sent {
+            self.pending.remove(&call_id);
+            return Err(error("node_unavailable", "Failed to reach the node"));
+        }
+
+        match tokio::time::timeout(timeout, result_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(error(
+                "node_disconnected",
+                &format!("Node {} disconnected", device_id),
+            )),
+            Err(_) => {
+                self.pending.remove(&call_id);
+                Err(error(
+                    "node_timeout",
+                    &format!("Node {} did not answer within {:?}", device_id, timeout),
+                ))
+            }
+        }
+    }
+
+    /// Deliver a node's response. Returns false if no call from this client
+    /// is waiting on it.
+    pub fn complete(&self, client_id: &str, response: ResponsePayload) -> bool {
+        let Some((_, call)) = self
+            .pending
+            .remove_if(&response.id, |_, call| call.client_id == client_id)
+        else {
+            return false;
+        };
+        let result = if response.ok {
+            Ok(response.payload.unwrap_or(Value::Null))
+        } else {
+            Err(response
+                .error
+                .unwrap_or_else(|| error("node_error", "Node tool failed")))
+        };
+        let _ = call.result.send(result);
+        true
+    }
+
+    /// Fail the calls waiting on a client that went away
+    pub fn abandon(&self, client_id: &str) {
+        self.pending.retain(|_, call| call.client_id != client_id);
+    }
+}
+
+fn error(code: &str, message: &str) -> ErrorInfo {
+    ErrorInfo {
+        code: code.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::client_mgr::ClientMessage;
+    use crate::protocol::client::{ClientCapabilities, ClientRole};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    fn register_node(
+        clients: &ClientManager,
+        tools: &[&str],
+    ) -> mpsc::UnboundedReceiver<ClientMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let caps = ClientCapabilities {
+            tools: tools.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        clients.register("client-1", ClientRole::Node, "laptop", caps, tx);
+        rx
+    }
+
+    #[test]
+    fn test_tool_names_and_schemas() {
+        assert_eq!(tool_name("laptop", "clipboard"), "node.laptop.clipboard");
+        assert_eq!(
+            parse_tool_name("node.laptop.clipboard"),
+            Some(("laptop", "clipboard"))
+        );
+        assert_eq!(parse_tool_name("mcp.laptop.clipboard"), None);
+        assert_eq!(parse_tool_name("node.laptop"), None);
+
+        let clients = ClientManager::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let caps = ClientCapabilities {
+            tools: vec!["clipboard".into(), "bad.name".into()],
+            tool_schemas: vec![ToolSchema {
+                name: "clipboard".into(),
+                description: "Read the clipboard".into(),
+                parameters: json!({ "type": "object" }),
+            }],
+            ..Default::default()
+        };
+        clients.register("c1", ClientRole::Node, "laptop", caps, tx);
+
+        let schemas = tool_schemas(&clients.list_nodes());
+        assert_eq!(schemas.len(), 1);
+        assert_eq!(schemas[0]["function"]["name"], "node.laptop.clipboard");
+        assert!(schemas[0]["function"]["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("Read the clipboard"));
+    }
+
+    #[tokio::test]
+    async fn test_call_round_trip_and_timeout() {
+        let clients = ClientManager::new();
+        let calls = Arc::new(NodeCalls::new());
+        let mut frames = register_node(&clients, &["clipboard"]);
+
+        // The node answers only the first request it receives
+        let responder = Arc::clone(&calls);
+        tokio::spawn(async move {
+            let frame: Value = serde_json::from_str(&frames.recv().await.unwrap().json).unwrap();
+            assert_eq!(frame["method"], methods::NODE_INVOKE);
+            assert_eq!(frame["params"]["tool"], "clipboard");
+            let response = ResponsePayload {
+                id: frame["id"].as_str().unwrap().to_string(),
+                ok: true,
+                payload: Some(json!({ "text": "copied" })),
+                error: None,
+            };
+            assert!(!responder.complete("someone-else", response.clone()));
+            assert!(responder.complete("client-1", response));
+            // Stay connected but never answer again
+            while frames.recv().await.is_some() {}
+        });
+
+        let result = calls
+            .call(
+                &clients,
+                "laptop",
+                "clipboard",
+                json!({}),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["text"], "copied");
+
+        let err = calls
+            .call(
+                &clients,
+                "laptop",
+                "clipboard",
+                json!({}),
+                Duration::from_millis(20),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "node_timeout");
+
+        let err = calls
+            .call(
+                &clients,
+                "laptop",
+                "browser",
+                json!({}),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "node_unavailable");
+    }
+}
@@ -0,0 +1,571 @@
+//! OpenAI-compatible HTTP facade over the agent.
+//!
+//! `/v1/chat/completions` runs a full agent turn, tools included, for any
+//! client that speaks the OpenAI API. Personas and targets are offered as
+//! models. Nobody is around to answer yields on this path, so approvals
+//! follow the gateway's [`AskPolicy`], questions get no answers and budgets
+//! are never extended.
+
+use crate::config::Target;
+use crate::gateway::auth;
+use crate::gateway::lanes::LaneType;
+use crate::gateway::server::{is_open_to, GatewayState};
+use crate::metrics;
+use crate::persona;
+use crate::protocol::internal::{
+    AgentEventType, AgentRequest, ResumeData, UsageStats, YieldReason,
+};
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Model that runs the daemon's default persona and target
+pub const DEFAULT_MODEL: &str = "brainpro";
+
+/// How tool calls the policy asks about are answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AskPolicy {
+    #[default]
+    Deny,
+    Allow,
+}
+
+impl AskPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "deny" => Some(Self::Deny),
+            "allow" => Some(Self::Allow),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deny => "deny",
+            Self::Allow => "allow",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    #[serde(default)]
+    model: String,
+    messages: Vec<Value>,
+    #[serde(default)]
+    stream: bool,
+}
+
+/// Persona and target a model id selects: `brainpro`, `<persona>`,
+/// `<model>@<backend>` or `<persona>/<model>@<backend>`
+pub fn parse_model(model: &str) -> Result<(Option<String>, Option<String>), String> {
+    let model = model.trim();
+    if model.is_empty() || model == DEFAULT_MODEL {
+        return Ok((None, None));
+    }
+    let (persona, target) = match model.split_once('/') {
+        Some((p, t)) if persona::get_persona(p).is_some() => (Some(p), Some(t)),
+        _ if persona::get_persona(model).is_some() => (Some(model), None),
+        _ => (None, Some(model)),
+    };
+    if target.is_some_and(|t| Target::parse(t).is_none()) {
+        return Err(format!("The model `{}` does not exist", model));
+    }
+    Ok((persona.map(String::from), target.map(String::from)))
+}
+
+/// Model ids to list: the default, each persona, then configured targets
+pub fn model_ids(default_target: Option<&str>, targets: &[String]) -> Vec<String> {
+    let mut ids = vec![DEFAULT_MODEL.to_string()];
+    ids.extend(persona::NAMES.iter().map(|p| p.to_string()));
+    let mut targets: Vec<_> = targets.iter().map(String::as_str).collect();
+    targets.sort_unstable();
+    for target in default_target.into_iter().chain(targets) {
+        if !ids.iter().any(|id| id == target) {
+            ids.push(target.to_string());
+        }
+    }
+    ids
+}
+
+/// Messages as the daemon expects them: text content, no developer role
+fn normalize_messages(messages: Vec<Value>) -> Vec<Value> {
+    messages
+        .into_iter()
+        .map(|mut message| {
+            if message.get("role").and_then(|r| r.as_str()) == Some("developer") {
+                message["role"] = json!("system");
+            }
+            if let Some(parts) = message.get("content").and_then(|c| c.as_array()) {
+                let text: Vec<&str> = parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect();
+                message["content"] = json!(text.join("\n"));
+            }
+            message
+        })
+        .collect()
+}
+
+/// What a turn produced, in order
+#[derive(Debug)]
+enum TurnOutput {
+    Text(String),
+    /// Tool calls, results and automatic answers to yields
+    Activity(Value),
+    Done(UsageStats),
+    Failed {
+        code: String,
+        message: String,
+    },
+}
+
+/// `GET /v1/models`
+pub async fn list_models(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<GatewayState>>,
+    headers: HeaderMap,
+) -> Response {
+    if !is_authorized(&state, &peer, &headers) {
+        return error_response(
+            StatusCode::UNAUTHORIZED,
+            "invalid_api_key",
+            "Missing or invalid bearer token",
+        );
+    }
+    let (default_target, targets) = match crate::context_factory::load_config_with_defaults() {
+        Ok(cfg) => (
+            cfg.default_target.clone(),
+            cfg.targets.keys().cloned().collect(),
+        ),
+        Err(_) => (None, Vec::new()),
+    };
+    let data: Vec<Value> = model_ids(default_target.as_deref(), &targets)
+        .into_iter()
+        .map(|id| json!({ "id": id, "object": "model", "created": 0, "owned_by": "brainpro" }))
+        .collect();
+    Json(json!({ "object": "list", "data": data })).into_response()
+}
+
+/// `POST /v1/chat/completions`
+pub async fn chat_completions(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<GatewayState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !is_authorized(&state, &peer, &headers) {
+        return error_response(
+            StatusCode::UNAUTHORIZED,
+            "invalid_api_key",
+            "Missing or invalid bearer token",
+        );
+    }
+    let request: ChatCompletionRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &format!("Invalid request body: {}", e),
+            )
+        }
+    };
+    let (persona, target) = match parse_model(&request.model) {
+        Ok(selected) => selected,
+        Err(message) => {
+            return error_response(StatusCode::NOT_FOUND, "model_not_found", &message);
+        }
+    };
+    let messages = normalize_messages(request.messages);
+    if messages
+        .last()
+        .and_then(|m| m.get("role"))
+        .and_then(|r| r.as_str())
+        != Some("user")
+    {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "The last message must be from the user",
+        );
+    }
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    let session_id = format!("openai-{}", uuid::Uuid::new_v4());
+    let model = if request.model.is_empty() {
+        DEFAULT_MODEL.to_string()
+    } else {
+        request.model
+    };
+    // Clients resend the conversation every time, so nothing is kept per call
+    let agent_request = AgentRequest::run_turn(&id, &session_id, messages, target)
+        .with_persona(persona)
+        .ephemeral();
+    let output = run_turn(Arc::clone(&state), agent_request);
+
+    let completion = Completion {
+        id,
+        model,
+        session_id,
+        created: chrono::Utc::now().timestamp(),
+    };
+    if request.stream {
+        stream_completion(completion, output).into_response()
+    } else {
+        collect_completion(completion, output).await
+    }
+}
+
+/// Whether the request carries the gateway token. Without a configured
+/// token only local requests are authorized.
+fn is_authorized(state: &GatewayState, peer: &SocketAddr, headers: &HeaderMap) -> bool {
+    let Some(token) = &state.config.auth_token else {
+        return is_open_to(state, peer);
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    auth::verify_bearer(token, presented)
+}
+
+fn error_response(status: StatusCode, code: &str, message: &str) -> Response {
+    (status, Json(error_body(code, message))).into_response()
+}
+
+fn error_body(code: &str, message: &str) -> Value {
+    let kind = match code {
+        "invalid_api_key" => "authentication_error",
+        "invalid_request_error" | "model_not_found" => "invalid_request_error",
+        _ => "server_error",
+    };
+    json!({ "error": { "message": message, "type": kind, "code": code } })
+}
+
+/// Run a turn on the Main lane, answering its yields without a client
+fn run_turn(
+    state: Arc<GatewayState>,
+    request: AgentRequest,
+) -> mpsc::UnboundedReceiver<TurnOutput> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let req_id = request.id.clone();
+        let session_id = request.session_id.clone();
+        let fail = |code: &str, message: String| {
+            let _ = tx.send(TurnOutput::Failed {
+                code: code.to_string(),
+                message,
+            });
+        };
+
+        let turn = async {
+            let _queued = metrics::track_queue("main");
+            let _slot = match super::server::acquire_lane(&state, LaneType::Main, &session_id).await
+            {
+                Ok(slot) => slot,
+                Err(message) => return fail("overloaded", message),
+            };
+            let mut event_rx = match state.agent.send_request(state.prepare(request)).await {
+                Ok(rx) => rx,
+                Err(e) => return fail("agent_unavailable", e.to_string()),
+            };
+
+            while let Some(agent_event) = event_rx.recv().await {
+                let event = agent_event.event;
+                if let Some(resumed) =
+                    super::server::run_node_tool(&state, &session_id, &req_id, &event, true).await
+                {
+                    match resumed {
+                        Ok(resumed_rx) => event_rx = resumed_rx,
+                        Err(e) => return fail("agent_unavailable", e.to_string()),
+                    }
+                    continue;
+                }
+                let output = match event {
+                    AgentEventType::Content { text } => TurnOutput::Text(text),
+                    AgentEventType::ToolCall { name, args, .. } => TurnOutput::Activity(
+                        json!({ "type": "tool_call", "name": name, "args": args }),
+                    ),
+                    AgentEventType::ToolResult {
+                        name,
+                        ok,
+                        duration_ms,
+                        ..
+                    } => TurnOutput::Activity(json!({
+                        "type": "tool_result",
+                        "name": name,
+                        "ok": ok,
+                        "duration_ms": duration_ms,
+                    })),
+                    AgentEventType::Yield {
+                        turn_id,
+                        reason,
+                        tool_call_id,
+                        tool_name,
+                        ..
+                    } => {
+                        let (resume_data, activity) = auto_answer(
+                            state.config.openai_ask,
+                            turn_id,
+                            reason,
+                            tool_call_id,
+                            tool_name,
+                        );
+                        let _ = tx.send(TurnOutput::Activity(activity));
+                        let resume = AgentRequest::resume_turn(&req_id, &session_id, resume_data)
+                            .ephemeral();
+                        match state.agent.send_request(state.prepare(resume)).await {
+                            Ok(resumed_rx) => event_rx = resumed_rx,
+                            Err(e) => return fail("agent_unavailable", e.to_string()),
+                        }
+                        continue;
+                    }
+                    AgentEventType::Done { usage } => TurnOutput::Done(usage),
+                    AgentEventType::Error { code, message } => TurnOutput::Failed { code, message },
+                    _ => continue,
+                };
+                let finished = matches!(output, TurnOutput::Done(_) | TurnOutput::Failed { .. });
+                if tx.send(output).is_err() || finished {
+                    return;
+                }
+            }
+            fail(
+                "agent_unavailable",
+                "Agent connection closed before the turn finished".to_string(),
+            );
+        };
+
+        // A client that hangs up stops the daemon's turn too
+        tokio::select! {
+            _ = turn => {}
+            _ = tx.closed() => {
+                if let Err(e) = state.agent.cancel(&session_id).await {
+                    eprintln!("[gateway] Failed to cancel {}: {}", session_id, e);
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Resume data for a yield nobody is around to answer, and the activity
+/// that reports it
+fn auto_answer(
+    policy: AskPolicy,
+    turn_id: String,
+    reason: YieldReason,
+    tool_call_id: String,
+    tool_name: String,
+) -> (ResumeData, Value) {
+    let mut resume_data = ResumeData {
+        turn_id,
+        tool_call_id,
+        ..Default::default()
+    };
+    let activity = match reason {
+        YieldReason::AwaitingInput => {
+            resume_data.answers = Some(json!({}));
+            json!({ "type": "question", "answered": false })
+        }
+        YieldReason::BudgetExceeded => {
+            resume_data.approved = Some(false);
+            json!({ "type": "budget_exceeded", "approved": false })
+        }
+        YieldReason::AwaitingApproval | YieldReason::NodeTool => {
+            let approved = policy == AskPolicy::Allow;
+            resume_data.approved = Some(approved);
+            json!({ "type": "approval", "name": tool_name, "approved": approved })
+        }
+    };
+    (resume_data, activity)
+}
+
+/// Fields every response for one completion shares
+struct Completion {
+    id: String,
+    model: String,
+    session_id: String,
+    created: i64,
+}
+
+impl Completion {
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    }
+}
+
+fn usage_json(usage: &UsageStats) -> Value {
+    json!({
+        "prompt_tokens": usage.input_tokens,
+        "completion_tokens": usage.output_tokens,
+        "total_tokens": usage.input_tokens + usage.output_tokens,
+    })
+}
+
+/// Wait for the turn and answer with one message. Tool activity goes in
+/// the `brainpro` extension field.
+async fn collect_completion(
+    completion: Completion,
+    mut output: mpsc::UnboundedReceiver<TurnOutput>,
+) -> Response {
+    let mut texts = Vec::new();
+    let mut activity = Vec::new();
+    let mut usage = UsageStats::default();
+    while let Some(item) = output.recv().await {
+        match item {
+            TurnOutput::Text(text) => texts.push(text),
+            TurnOutput::Activity(a) => activity.push(a),
+            TurnOutput::Done(u) => usage = u,
+            TurnOutput::Failed { code, message } => {
+                let status = match code.as_str() {
+                    "overloaded" | "agent_unavailable" => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                return error_response(status, &code, &message);
+            }
+        }
+    }
+    Json(json!({
+        "id": completion.id,
+        "object": "chat.completion",
+        "created": completion.created,
+        "model": completion.model,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": texts.join("\n\n") },
+            "finish_reason": "stop",
+        }],
+        "usage": usage_json(&usage),
+        "brainpro": { "session_id": completion.session_id, "activity": activity },
+    }))
+    .into_response()
+}
+
+/// Stream the turn as SSE chunks. Tool activity arrives as chunks with an
+/// empty delta and a `brainpro` extension field.
+fn stream_completion(
+    completion: Completion,
+    mut output: mpsc::UnboundedReceiver<TurnOutput>,
+) -> Sse<impl futures_util::Stream<Item = Result<Event, Infallible>>> {
+    let stream = async_stream::stream! {
+        yield Ok(Event::default().data(completion.chunk(json!({ "role": "assistant" }), None).to_string()));
+        let mut wrote_text = false;
+        while let Some(item) = output.recv().await {
+            match item {
+                TurnOutput::Text(text) => {
+                    let text = if wrote_text { format!("\n\n{}", text) } else { text };
+                    wrote_text = true;
+                    let chunk = completion.chunk(json!({ "content": text }), None);
+                    yield Ok(Event::default().data(chunk.to_string()));
+                }
+                TurnOutput::Activity(activity) => {
+                    let mut chunk = completion.chunk(json!({}), None);
+                    chunk["brainpro"] = activity;
+                    yield Ok(Event::default().data(chunk.to_string()));
+                }
+                TurnOutput::Done(usage) => {
+                    let mut chunk = completion.chunk(json!({}), Some("stop"));
+                    chunk["usage"] = usage_json(&usage);
+                    yield Ok(Event::default().data(chunk.to_string()));
+                    break;
+                }
+                TurnOutput::Failed { code, message } => {
+                    yield Ok(Event::default().data(error_body(&code, &message).to_string()));
+                    break;
+                }
+            }
+        }
+        yield Ok(Event::default().data("[DONE]"));
+    };
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model() {
+        assert_eq!(parse_model("brainpro").unwrap(), (None, None));
+        assert_eq!(parse_model("").unwrap(), (None, None));
+        assert_eq!(
+            parse_model("mrbot").unwrap(),
+            (Some("mrbot".to_string()), None)
+        );
+        assert_eq!(
+            parse_model("gpt-4o@openai").unwrap(),
+            (None, Some("gpt-4o@openai".to_string()))
+        );
+        assert_eq!(
+            parse_model("mrcode/meta/llama-3@together").unwrap(),
+            (
+                Some("mrcode".to_string()),
+                Some("meta/llama-3@together".to_string())
+            )
+        );
+        assert!(parse_model("gpt-4o").is_err());
+    }
+
+    #[test]
+    fn test_model_ids_and_messages() {
+        let targets = vec!["b@x".to_string(), "a@x".to_string()];
+        assert_eq!(
+            model_ids(Some("b@x"), &targets),
+            vec!["brainpro", "mrcode", "mrbot", "b@x", "a@x"]
+        );
+
+        let messages = normalize_messages(vec![
+            json!({ "role": "developer", "content": "Be brief" }),
+            json!({ "role": "user", "content": [
+                { "type": "text", "text": "Hello" },
+                { "type": "text", "text": "there" },
+            ] }),
+        ]);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"], "Hello\nthere");
+    }
+
+    #[test]
+    fn test_auto_answer() {
+        let (resume, activity) = auto_answer(
+            AskPolicy::Deny,
+            "t1".into(),
+            YieldReason::AwaitingApproval,
+            "c1".into(),
+            "Bash".into(),
+        );
+        assert_eq!(resume.approved, Some(false));
+        assert_eq!(activity["name"], "Bash");
+
+        let (resume, _) = auto_answer(
+            AskPolicy::Allow,
+            "t1".into(),
+            YieldReason::BudgetExceeded,
+            "c1".into(),
+            String::new(),
+        );
+        assert_eq!(resume.approved, Some(false));
+    }
+}
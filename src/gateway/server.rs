@@ -1,30 +1,71 @@
 //! Gateway WebSocket server using axum.
 
+use crate::events::{self as bus, Event};
 use crate::gateway::agent_conn::AsyncAgentConnection;
-use crate::gateway::client_mgr::{ClientManager, ClientMessage};
+use crate::gateway::auth;
+use crate::gateway::client_mgr::{ClientInfo, ClientManager, ClientMessage};
+use crate::gateway::cron::{CronJob, CronStore, Schedule};
+use crate::gateway::devices::{DeviceStatus, DeviceStore};
+use crate::gateway::event_log::EventLog;
+use crate::gateway::lanes::{LaneConfig, LaneManager, LaneSlot, LaneType, LaneWorker};
+use crate::gateway::nodes::{self, NodeCalls};
+use crate::gateway::openai::{self, AskPolicy};
+use crate::gateway::ui;
+use crate::metrics;
+use crate::plan;
+use crate::policy::PolicyEngine;
 use crate::protocol::client::{
-    events, methods, ClientEvent, ClientRequest, ClientResponse, Hello, PolicyInfo, Welcome,
+    events, methods, Auth, ClientEvent, ClientRequest, ClientResponse, ClientRole, ErrorInfo,
+    Hello, PolicyInfo, ResponsePayload, Welcome,
 };
-use crate::protocol::internal::{AgentEventType, AgentRequest, ResumeData, YieldReason};
+use crate::protocol::internal::{
+    AgentEvent, AgentEventType, AgentRequest, ResumeData, YieldReason,
+};
+use crate::tool_display;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ConnectInfo, State, WebSocketUpgrade,
     },
-    response::{Html, IntoResponse},
-    routing::get,
+    http::header,
+    response::IntoResponse,
+    routing::{get, post},
     Router,
 };
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Time a client has to send `hello` and answer the challenge
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time an operator has to approve a device pairing
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often the scheduler checks for due cron jobs
+const CRON_TICK: Duration = Duration::from_secs(15);
+
+/// Time a node has to return a tool result
+const NODE_CALL_TIMEOUT: Duration = Duration::from_secs(120);
 
 /// Gateway server configuration
 pub struct GatewayConfig {
     pub port: u16,
     pub agent_socket: String,
+    /// Shared token that admits operators
     pub auth_token: Option<String>,
+    /// Shared token that admits nodes only
+    pub node_token: Option<String>,
+    /// Per-lane concurrency limits and queue depth
+    pub lanes: LaneConfig,
+    /// How the OpenAI-compatible endpoints answer approval prompts
+    pub openai_ask: AskPolicy,
 }
 
 impl Default for GatewayConfig {
@@ -33,24 +74,91 @@ impl Default for GatewayConfig {
             port: 18789,
             agent_socket: "/run/brainpro.sock".to_string(),
             auth_token: std::env::var("BRAINPRO_GATEWAY_TOKEN").ok(),
+            node_token: std::env::var("BRAINPRO_NODE_TOKEN").ok(),
+            lanes: LaneConfig::default(),
+            openai_ask: AskPolicy::default(),
         }
     }
 }
 
+/// A device waiting for an operator to approve its pairing
+pub struct PendingPairing {
+    pub public_key: String,
+    pub role: ClientRole,
+    /// Identifies the waiting connection, so it only clears its own request
+    nonce: String,
+    decision: oneshot::Sender<bool>,
+}
+
+/// How a client proved who it is during the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthMethod {
+    /// No gateway token configured
+    Open,
+    /// HMAC over the shared token
+    Token,
+    /// Signature from a paired device key
+    Device,
+}
+
 /// Shared state for the gateway
 pub struct GatewayState {
     pub config: GatewayConfig,
     pub clients: Arc<ClientManager>,
     pub agent: AsyncAgentConnection,
+    pub devices: DeviceStore,
+    /// Pairing requests by device id
+    pub pairings: DashMap<String, PendingPairing>,
+    pub cron: CronStore,
+    pub lanes: Arc<LaneManager>,
+    /// Tool calls waiting on node clients
+    pub node_calls: NodeCalls,
+    /// Recent events per session, for clients that reconnect
+    pub events: EventLog,
+    /// Allow rules operators approved for the rest of a session
+    pub session_rules: DashMap<String, Vec<String>>,
 }
 
 impl GatewayState {
+    /// Schemas of the tools connected nodes offer the agent
+    fn node_tools(&self) -> Vec<Value> {
+        nodes::tool_schemas(&self.clients.list_nodes())
+    }
+
+    /// Attach node tools and the session's approval rules to a request
+    pub(super) fn prepare(&self, request: AgentRequest) -> AgentRequest {
+        let rules = self
+            .session_rules
+            .get(&request.session_id)
+            .map(|r| r.clone())
+            .unwrap_or_default();
+        request
+            .with_tools(self.node_tools())
+            .with_allow_rules(rules)
+    }
+
+    /// Log a session event and push it to the session's clients
+    fn publish(&self, session_id: &str, event: ClientEvent) {
+        let event = self.events.record(session_id, event);
+        if let Ok(json) = serde_json::to_string(&event) {
+            self.clients.broadcast_to_session(session_id, &json);
+        }
+    }
+
     pub fn new(config: GatewayConfig) -> Arc<Self> {
         let agent = AsyncAgentConnection::new(&config.agent_socket);
+        let lanes = LaneManager::new(config.lanes.clone());
         Arc::new(Self {
             config,
             clients: ClientManager::new(),
             agent,
+            devices: DeviceStore::with_default_path(),
+            pairings: DashMap::new(),
+            cron: CronStore::with_default_path(),
+            lanes,
+            node_calls: NodeCalls::new(),
+            session_rules: DashMap::new(),
+            events: EventLog::default(),
         })
     }
 }
@@ -60,40 +168,42 @@ pub async fn run(config: GatewayConfig) -> Result<(), Box<dyn std::error::Error>
     let state = GatewayState::new(config);
     let port = state.config.port;
 
+    if state.config.auth_token.is_none() {
+        eprintln!(
+            "[gateway] WARNING: BRAINPRO_GATEWAY_TOKEN is not set; only local clients can connect, without authentication"
+        );
+    }
+
+    // All agent work waits for a lane slot; the sender keeps the worker alive
+    let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let worker = LaneWorker::new(Arc::clone(&state.lanes), shutdown_rx);
+    let lanes = Arc::clone(&state.lanes);
+    tokio::spawn(worker.run(move |request| {
+        let lanes = Arc::clone(&lanes);
+        async move { lanes.grant(request).await }
+    }));
+    tokio::spawn(run_cron_scheduler(Arc::clone(&state)));
+
     let app = Router::new()
-        .route("/", get(index_handler))
+        .route("/", get(ui::index))
+        .route("/ui/app.js", get(ui::app_js))
+        .route("/ui/style.css", get(ui::style_css))
         .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
         .route("/ws", get(ws_handler))
+        .route("/v1/models", get(openai::list_models))
+        .route("/v1/chat/completions", post(openai::chat_completions))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
     eprintln!("[gateway] Listening on port {}", port);
 
-    axum::serve(listener, app).await?;
-    Ok(())
-}
-
-async fn index_handler() -> Html<&'static str> {
-    Html(
-        r#"<!DOCTYPE html>
-<html>
-<head>
-    <title>Brainpro Gateway</title>
-    <style>
-        body { font-family: system-ui, sans-serif; max-width: 800px; margin: 2rem auto; padding: 1rem; }
-        h1 { color: #333; }
-        .status { padding: 1rem; background: #f0f0f0; border-radius: 4px; }
-    </style>
-</head>
-<body>
-    <h1>Brainpro Gateway</h1>
-    <div class="status">
-        <p>WebSocket endpoint: <code>ws://localhost:18789/ws</code></p>
-        <p>Health check: <code>GET /health</code></p>
-    </div>
-</body>
-</html>"#,
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .await?;
+    Ok(())
 }
 
 async fn health_handler(State(state): State<Arc<GatewayState>>) -> impl IntoResponse {
@@ -106,17 +216,43 @@ async fn health_handler(State(state): State<Arc<GatewayState>>) -> impl IntoResp
         "status": status,
         "agent_available": agent_ok,
         "connected_clients": client_count,
+        "lanes": state.lanes.stats(),
     }))
 }
 
+/// Gateway metrics followed by the agent daemon's, in Prometheus text format
+async fn metrics_handler(State(state): State<Arc<GatewayState>>) -> impl IntoResponse {
+    let mut body = metrics::prometheus();
+    if state.agent.is_available() {
+        match state.agent.metrics().await {
+            Ok(agent_metrics) => body.push_str(&agent_metrics),
+            Err(e) => eprintln!("[gateway] Failed to fetch agent metrics: {}", e),
+        }
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+}
+
 async fn ws_handler(
     ws: WebSocketUpgrade,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(state): State<Arc<GatewayState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_websocket(socket, state))
+    ws.on_upgrade(move |socket| handle_websocket(socket, peer, state))
+}
+
+/// Whether a request may skip authentication: only when no gateway token is
+/// configured, and only from the gateway's own host
+pub(crate) fn is_open_to(state: &GatewayState, peer: &SocketAddr) -> bool {
+    state.config.auth_token.is_none() && peer.ip().to_canonical().is_loopback()
 }
 
-async fn handle_websocket(socket: WebSocket, state: Arc<GatewayState>) {
+async fn handle_websocket(socket: WebSocket, peer: SocketAddr, state: Arc<GatewayState>) {
     let (mut sender, mut receiver) = socket.split();
 
     // Generate client ID
@@ -143,8 +279,23 @@ async fn handle_websocket(socket: WebSocket, state: Arc<GatewayState>) {
         }
     };
 
+    // Challenge the client before it gets any access
+    let auth_method = match authenticate(&state, &hello, &peer, &mut receiver, &tx).await {
+        Ok(method) => method,
+        Err((code, message)) => {
+            eprintln!("[gateway] Client {} rejected: {}", client_id, message);
+            send_frame(
+                &tx,
+                json!({ "type": "error", "code": code, "message": message }),
+            );
+            drop(tx);
+            let _ = send_task.await;
+            return;
+        }
+    };
+
     // Register client
-    state.clients.register(
+    let closed = state.clients.register(
         &client_id,
         hello.role,
         &hello.device_id,
@@ -175,15 +326,25 @@ async fn handle_websocket(socket: WebSocket, state: Arc<GatewayState>) {
     }
 
     // Join session
-    state.clients.join_session(&client_id, &session_id);
+    switch_session(&state, &client_id, &session_id);
 
     eprintln!(
-        "[gateway] Client {} connected (role={:?}, session={})",
-        client_id, hello.role, session_id
+        "[gateway] Client {} connected (role={:?}, auth={:?}, session={})",
+        client_id, hello.role, auth_method, session_id
     );
 
-    // Handle incoming messages
-    while let Some(msg) = receiver.next().await {
+    // Handle incoming messages until the client leaves or is closed
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = closed.cancelled() => {
+                eprintln!("[gateway] Client {} closed: device revoked", client_id);
+                break;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
         match msg {
             Ok(Message::Text(text)) => {
                 if let Err(e) =
@@ -202,7 +363,7 @@ async fn handle_websocket(socket: WebSocket, state: Arc<GatewayState>) {
     }
 
     // Cleanup
-    state.clients.unregister(&client_id);
+    disconnect_client(&state, &client_id);
     send_task.abort();
     eprintln!("[gateway] Client {} disconnected", client_id);
 }
@@ -210,14 +371,21 @@ async fn handle_websocket(socket: WebSocket, state: Arc<GatewayState>) {
 async fn wait_for_hello(
     receiver: &mut futures_util::stream::SplitStream<WebSocket>,
 ) -> Option<Hello> {
-    // Wait up to 10 seconds for hello
-    let timeout = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+    wait_for_frame(receiver, "hello").await
+}
+
+/// Wait for the next frame of `frame_type`, skipping anything else
+async fn wait_for_frame<T: DeserializeOwned>(
+    receiver: &mut futures_util::stream::SplitStream<WebSocket>,
+    frame_type: &str,
+) -> Option<T> {
+    let timeout = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
         while let Some(msg) = receiver.next().await {
             if let Ok(Message::Text(text)) = msg {
                 if let Ok(value) = serde_json::from_str::<Value>(&text) {
-                    if value.get("type").and_then(|t| t.as_str()) == Some("hello") {
-                        if let Ok(hello) = serde_json::from_value::<Hello>(value) {
-                            return Some(hello);
+                    if value.get("type").and_then(|t| t.as_str()) == Some(frame_type) {
+                        if let Ok(frame) = serde_json::from_value::<T>(value) {
+                            return Some(frame);
                         }
                     }
                 }
@@ -229,6 +397,157 @@ async fn wait_for_hello(
     timeout.await.ok().flatten()
 }
 
+/// Challenge the client and check its answer. With no gateway token
+/// configured only local clients are let in, without a challenge.
+async fn authenticate(
+    state: &Arc<GatewayState>,
+    hello: &Hello,
+    peer: &SocketAddr,
+    receiver: &mut futures_util::stream::SplitStream<WebSocket>,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) -> Result<AuthMethod, (&'static str, String)> {
+    let Some(token) = &state.config.auth_token else {
+        if is_open_to(state, peer) {
+            return Ok(AuthMethod::Open);
+        }
+        return Err((
+            "auth_required",
+            "Set BRAINPRO_GATEWAY_TOKEN to accept clients from other hosts".to_string(),
+        ));
+    };
+
+    let nonce = auth::new_nonce();
+    send_frame(tx, json!({ "type": "challenge", "nonce": nonce }));
+
+    let response: Auth = wait_for_frame(receiver, "auth")
+        .await
+        .ok_or(("auth_failed", "No auth response".to_string()))?;
+
+    // The token decides the role, not the hello. A revoked device stays
+    // out even with the token.
+    if !response.signature.is_empty() {
+        let verifies =
+            |token: &str| auth::verify_token(token, &nonce, &hello.device_id, &response.signature);
+        let granted = if verifies(token) {
+            Some(ClientRole::Operator)
+        } else if state.config.node_token.as_deref().is_some_and(verifies) {
+            Some(ClientRole::Node)
+        } else {
+            None
+        };
+        match granted {
+            Some(_) if state.devices.is_revoked(&hello.device_id) => {
+                return Err(("device_revoked", "Device has been revoked".to_string()))
+            }
+            Some(role) if role == hello.role => return Ok(AuthMethod::Token),
+            Some(role) => {
+                return Err((
+                    "auth_failed",
+                    match role {
+                        ClientRole::Operator => "Token only admits operators".to_string(),
+                        ClientRole::Node => "Token only admits nodes".to_string(),
+                    },
+                ))
+            }
+            None => {}
+        }
+    }
+
+    let (Some(key), Some(signature)) = (&response.device_key, &response.device_signature) else {
+        return Err(("auth_failed", "Invalid token signature".to_string()));
+    };
+    if !auth::verify_device(key, &nonce, &hello.device_id, signature) {
+        return Err(("auth_failed", "Invalid device signature".to_string()));
+    }
+
+    match state.devices.status(&hello.device_id, key) {
+        DeviceStatus::Paired(role) if role == hello.role => Ok(AuthMethod::Device),
+        DeviceStatus::Paired(_) => Err((
+            "auth_failed",
+            "Device is paired with a different role".to_string(),
+        )),
+        DeviceStatus::KeyMismatch => Err((
+            "auth_failed",
+            "Device key does not match the paired key".to_string(),
+        )),
+        DeviceStatus::Revoked => Err(("device_revoked", "Device has been revoked".to_string())),
+        DeviceStatus::Unknown => request_pairing(state, hello, key, tx).await,
+    }
+}
+
+/// Ask connected operators to approve a new device key and wait for the answer
+async fn request_pairing(
+    state: &Arc<GatewayState>,
+    hello: &Hello,
+    public_key: &str,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) -> Result<AuthMethod, (&'static str, String)> {
+    let operators = state.clients.list_operators();
+    if operators.is_empty() {
+        return Err((
+            "pairing_unavailable",
+            "No operator is connected to approve pairing".to_string(),
+        ));
+    }
+
+    let fingerprint = auth::fingerprint(public_key);
+    let nonce = auth::new_nonce();
+    let (decision_tx, decision_rx) = oneshot::channel();
+    // A second connection must not swap its key in after operators saw the first
+    match state.pairings.entry(hello.device_id.clone()) {
+        Entry::Occupied(_) => {
+            return Err((
+                "pairing_pending",
+                "A pairing request for this device is already pending".to_string(),
+            ));
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(PendingPairing {
+                public_key: public_key.to_string(),
+                role: hello.role,
+                nonce: nonce.clone(),
+                decision: decision_tx,
+            });
+        }
+    }
+
+    send_frame(
+        tx,
+        json!({
+            "type": "pairing",
+            "device_id": hello.device_id,
+            "fingerprint": fingerprint,
+        }),
+    );
+    let event = ClientEvent::new(
+        events::DEVICE_PAIR_REQUESTED,
+        json!({
+            "device_id": hello.device_id,
+            "role": hello.role,
+            "fingerprint": fingerprint,
+        }),
+        None,
+    );
+    if let Ok(event_json) = serde_json::to_string(&event) {
+        for operator in operators {
+            state.clients.send_to_client(&operator.id, &event_json);
+        }
+    }
+
+    let decision = tokio::time::timeout(PAIRING_TIMEOUT, decision_rx).await;
+    state
+        .pairings
+        .remove_if(&hello.device_id, |_, pending| pending.nonce == nonce);
+    match decision {
+        Ok(Ok(true)) => Ok(AuthMethod::Device),
+        Ok(_) => Err(("pairing_denied", "Pairing was not approved".to_string())),
+        Err(_) => Err((
+            "pairing_timeout",
+            "No operator answered the pairing request".to_string(),
+        )),
+    }
+}
+
 async fn handle_client_message(
     state: &Arc<GatewayState>,
     client_id: &str,
@@ -242,8 +561,20 @@ async fn handle_client_message(
     let msg_type = value.get("type").and_then(|t| t.as_str()).unwrap_or("");
 
     if msg_type == "req" {
+        // The client may have moved to another session since connecting
+        let current = state
+            .clients
+            .get_client(client_id)
+            .and_then(|c| c.session_id)
+            .unwrap_or_else(|| session_id.to_string());
         let request: ClientRequest = serde_json::from_value(value)?;
-        handle_request(state, client_id, session_id, request, tx).await?;
+        handle_request(state, client_id, &current, request, tx).await?;
+    } else if msg_type == "res" {
+        // A node answering a tool call
+        let response: ResponsePayload = serde_json::from_value(value)?;
+        if !state.node_calls.complete(client_id, response) {
+            eprintln!("[gateway] Client {} sent an unexpected response", client_id);
+        }
     }
 
     Ok(())
@@ -265,18 +596,27 @@ async fn handle_request(
                 json!({
                     "agent_available": state.agent.is_available(),
                     "connected_clients": state.clients.client_count(),
+                    "lanes": state.lanes.stats(),
                 }),
             );
             send_response(tx, &response);
         }
 
         methods::CHAT_SEND => {
-            handle_chat_send(state, session_id, &req_id, request.params, tx).await?;
+            let (state, session_id, tx) = (Arc::clone(state), session_id.to_string(), tx.clone());
+            spawn_turn(async move {
+                handle_chat_send(&state, &session_id, &req_id, request.params, &tx).await
+            });
+        }
+
+        methods::CHAT_CANCEL => {
+            let response = handle_chat_cancel(state, client_id, session_id, &req_id).await;
+            send_response(tx, &response);
         }
 
         methods::SESSION_CREATE => {
             let new_session_id = uuid::Uuid::new_v4().to_string();
-            state.clients.join_session(client_id, &new_session_id);
+            switch_session(state, client_id, &new_session_id);
             let response = ClientResponse::ok(&req_id, json!({ "session_id": new_session_id }));
             send_response(tx, &response);
         }
@@ -287,8 +627,57 @@ async fn handle_request(
             send_response(tx, &response);
         }
 
-        methods::TURN_RESUME => {
-            handle_turn_resume(state, session_id, &req_id, request.params, tx).await?;
+        methods::SESSION_GET => {
+            let response =
+                handle_session_get(state, client_id, session_id, &req_id, &request.params).await;
+            send_response(tx, &response);
+        }
+
+        methods::SESSION_ATTACH => {
+            let response = handle_session_attach(state, client_id, &req_id, &request.params).await;
+            send_response(tx, &response);
+        }
+
+        methods::DEVICE_PAIR | methods::DEVICE_LIST | methods::DEVICE_REVOKE => {
+            let response = handle_device_request(state, client_id, &req_id, &request);
+            send_response(tx, &response);
+        }
+
+        methods::CRON_ADD | methods::CRON_REMOVE | methods::CRON_LIST => {
+            let response = handle_cron_request(state, client_id, session_id, &req_id, &request);
+            send_response(tx, &response);
+        }
+
+        methods::TURN_RESUME | methods::TOOL_APPROVE => {
+            let (state, client_id, session_id, tx) = (
+                Arc::clone(state),
+                client_id.to_string(),
+                session_id.to_string(),
+                tx.clone(),
+            );
+            spawn_turn(async move {
+                if request.method == methods::TURN_RESUME {
+                    handle_turn_resume(
+                        &state,
+                        &client_id,
+                        &session_id,
+                        &req_id,
+                        request.params,
+                        &tx,
+                    )
+                    .await
+                } else {
+                    handle_tool_approve(
+                        &state,
+                        &client_id,
+                        &session_id,
+                        &req_id,
+                        request.params,
+                        &tx,
+                    )
+                    .await
+                }
+            });
         }
 
         _ => {
@@ -304,6 +693,542 @@ async fn handle_request(
     Ok(())
 }
 
+/// Run a request that drives a turn in the background, so the client's
+/// next requests (such as `chat.cancel`) are handled while the turn runs
+fn spawn_turn<F>(handler: F)
+where
+    F: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>
+        + Send
+        + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(e) = handler.await {
+            eprintln!("[gateway] Error handling message: {}", e);
+        }
+    });
+}
+
+/// Whether a client is connected as an operator
+fn is_operator(state: &GatewayState, client_id: &str) -> bool {
+    state
+        .clients
+        .get_client(client_id)
+        .is_some_and(|c| c.role == ClientRole::Operator)
+}
+
+/// Stop the turns running in the client's session. Only operators may
+/// cancel.
+async fn handle_chat_cancel(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    session_id: &str,
+    req_id: &str,
+) -> ClientResponse {
+    if !is_operator(state, client_id) {
+        return ClientResponse::error(req_id, "forbidden", "Operator role required");
+    }
+    match state.agent.cancel(session_id).await {
+        Ok(true) => ClientResponse::ok(
+            req_id,
+            json!({ "session_id": session_id, "cancelled": true }),
+        ),
+        Ok(false) => ClientResponse::error(
+            req_id,
+            "not_found",
+            &format!("No turn is running in session {}", session_id),
+        ),
+        Err(e) => ClientResponse::error(req_id, "agent_unavailable", &e.to_string()),
+    }
+}
+
+/// Cron job management. Only operators may add, remove or list jobs.
+fn handle_cron_request(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    session_id: &str,
+    req_id: &str,
+    request: &ClientRequest,
+) -> ClientResponse {
+    let Some(client) = state
+        .clients
+        .get_client(client_id)
+        .filter(|c| c.role == ClientRole::Operator)
+    else {
+        return ClientResponse::error(req_id, "forbidden", "Operator role required");
+    };
+    let params = &request.params;
+    let param = |key: &str| params.get(key).and_then(|v| v.as_str());
+
+    match request.method.as_str() {
+        methods::CRON_LIST => ClientResponse::ok(req_id, json!({ "jobs": state.cron.list() })),
+        methods::CRON_REMOVE => {
+            let Some(id) = param("id") else {
+                return ClientResponse::error(req_id, "invalid_params", "Missing id");
+            };
+            match state.cron.remove(id) {
+                Ok(true) => ClientResponse::ok(req_id, json!({ "id": id, "removed": true })),
+                Ok(false) => {
+                    ClientResponse::error(req_id, "not_found", &format!("No cron job {}", id))
+                }
+                Err(e) => ClientResponse::error(req_id, "storage_error", &e.to_string()),
+            }
+        }
+        _ => {
+            let Some(prompt) = param("prompt").filter(|p| !p.trim().is_empty()) else {
+                return ClientResponse::error(req_id, "invalid_params", "Missing prompt");
+            };
+            let schedule = match (param("cron"), param("every")) {
+                (Some(expr), None) => Schedule::cron(expr),
+                (None, Some(interval)) => Schedule::every(interval),
+                _ => {
+                    return ClientResponse::error(
+                        req_id,
+                        "invalid_params",
+                        "Give exactly one of cron or every",
+                    )
+                }
+            };
+            let schedule = match schedule {
+                Ok(schedule) => schedule,
+                Err(e) => return ClientResponse::error(req_id, "invalid_schedule", &e.to_string()),
+            };
+            let target_session = param("session_id").unwrap_or(session_id);
+            if !crate::session::is_valid_session_id(target_session) {
+                return ClientResponse::error(req_id, "invalid_params", "Invalid session_id");
+            }
+            let persona = param("persona").map(str::to_string);
+            if let Some(name) = &persona {
+                if crate::persona::get_persona(name).is_none() {
+                    return ClientResponse::error(
+                        req_id,
+                        "invalid_params",
+                        &format!("Unknown persona: {}", name),
+                    );
+                }
+            }
+
+            let job = CronJob {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: param("name").map(str::to_string),
+                prompt: prompt.to_string(),
+                schedule,
+                session_id: target_session.to_string(),
+                persona,
+                working_dir: param("working_dir").map(str::to_string),
+                created_by: client.device_id,
+                created_at: chrono::Utc::now(),
+                last_run: None,
+                next_run: None,
+            };
+            match state.cron.add(job) {
+                Ok(job) => ClientResponse::ok(req_id, json!({ "job": job })),
+                Err(e) => ClientResponse::error(req_id, "storage_error", &e.to_string()),
+            }
+        }
+    }
+}
+
+/// Queue due cron jobs on the Cron lane
+async fn run_cron_scheduler(state: Arc<GatewayState>) {
+    let mut tick = tokio::time::interval(CRON_TICK);
+    loop {
+        tick.tick().await;
+        for job in state.cron.take_due(chrono::Utc::now()) {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move { run_cron_job(&state, job).await });
+        }
+    }
+}
+
+/// Wait for a slot on `lane`, telling the session its place in the queue.
+/// Fails when the lane's queue is full.
+pub(super) async fn acquire_lane(
+    state: &Arc<GatewayState>,
+    lane: LaneType,
+    session_id: &str,
+) -> Result<LaneSlot, String> {
+    let lane_name = lane.to_string();
+    let ticket = state
+        .lanes
+        .acquire(lane, session_id.to_string())
+        .map_err(|e| format!("{}; try again later", e))?;
+
+    bus::emit(Event::queue_request_enqueued(
+        &lane_name,
+        &ticket.id,
+        ticket.position as u32,
+    ));
+    state.publish(
+        session_id,
+        ClientEvent::new(
+            events::QUEUE_ENQUEUED,
+            json!({
+                "lane": lane_name,
+                "request_id": ticket.id,
+                "position": ticket.position,
+            }),
+            Some(session_id.to_string()),
+        ),
+    );
+
+    let slot = ticket
+        .granted()
+        .await
+        .ok_or_else(|| format!("Request dropped from lane '{}'", lane_name))?;
+
+    bus::emit(Event::queue_request_dequeued(
+        &lane_name,
+        &slot.id,
+        slot.wait_time_ms,
+    ));
+    state.publish(
+        session_id,
+        ClientEvent::new(
+            events::QUEUE_DEQUEUED,
+            json!({
+                "lane": lane_name,
+                "request_id": slot.id,
+                "wait_time_ms": slot.wait_time_ms,
+            }),
+            Some(session_id.to_string()),
+        ),
+    );
+    Ok(slot)
+}
+
+/// Run a cron job's prompt as a turn in its session and broadcast the
+/// results to the clients in that session
+async fn run_cron_job(state: &Arc<GatewayState>, job: CronJob) {
+    let session_id = job.session_id.as_str();
+    let broadcast = |event: ClientEvent| state.publish(session_id, event);
+
+    broadcast(ClientEvent::new(
+        events::CRON_FIRED,
+        json!({
+            "job_id": job.id,
+            "name": job.name,
+            "prompt": job.prompt,
+            "fired_at": job.last_run,
+        }),
+        Some(session_id.to_string()),
+    ));
+
+    let _queued = metrics::track_queue("cron");
+    let _slot = match acquire_lane(state, LaneType::Cron, session_id).await {
+        Ok(slot) => slot,
+        Err(message) => {
+            eprintln!("[gateway] Cron job {} not run: {}", job.id, message);
+            broadcast(ClientEvent::new(
+                events::AGENT_ERROR,
+                json!({ "code": "overloaded", "message": message }),
+                Some(session_id.to_string()),
+            ));
+            return;
+        }
+    };
+
+    let req_id = format!("cron-{}", uuid::Uuid::new_v4());
+    let request = state.prepare(
+        AgentRequest::run_turn(
+            &req_id,
+            session_id,
+            vec![json!({ "role": "user", "content": job.prompt })],
+            None,
+        )
+        .with_persona(job.persona.clone())
+        .with_working_dir(job.working_dir.clone()),
+    );
+
+    let mut event_rx = match state.agent.send_request(request).await {
+        Ok(rx) => rx,
+        Err(e) => {
+            eprintln!("[gateway] Cron job {} failed: {}", job.id, e);
+            broadcast(ClientEvent::new(
+                events::AGENT_ERROR,
+                json!({ "code": "agent_unavailable", "message": e.to_string() }),
+                Some(session_id.to_string()),
+            ));
+            return;
+        }
+    };
+    while let Some(agent_event) = event_rx.recv().await {
+        if let Some(resumed) =
+            run_node_tool(state, session_id, &req_id, &agent_event.event, false).await
+        {
+            match resumed {
+                Ok(rx) => {
+                    event_rx = rx;
+                    continue;
+                }
+                Err(e) => {
+                    broadcast(ClientEvent::new(
+                        events::AGENT_ERROR,
+                        json!({ "code": "agent_unavailable", "message": e.to_string() }),
+                        Some(session_id.to_string()),
+                    ));
+                    return;
+                }
+            }
+        }
+        if let AgentEventType::Yield { reason, .. } = &agent_event.event {
+            metrics::record_yield(yield_reason_str(reason));
+        }
+        if let Some(event) = client_event(agent_event.event, session_id) {
+            broadcast(event);
+        }
+    }
+}
+
+/// Move a client into `session_id`, announcing the move to the clients of
+/// both the old and the new session
+fn switch_session(state: &Arc<GatewayState>, client_id: &str, session_id: &str) {
+    let Some(client) = state.clients.get_client(client_id) else {
+        return;
+    };
+    if client.session_id.as_deref() == Some(session_id) {
+        return;
+    }
+    state.clients.join_session(client_id, session_id);
+    if let Some(previous) = &client.session_id {
+        publish_presence(state, previous, &client, "left");
+    }
+    publish_presence(state, session_id, &client, "joined");
+}
+
+/// Drop a client, failing its node calls and telling its session it left
+fn disconnect_client(state: &Arc<GatewayState>, client_id: &str) {
+    let client = state.clients.get_client(client_id);
+    state.clients.unregister(client_id);
+    state.node_calls.abandon(client_id);
+    if let Some(client) = client {
+        if let Some(session_id) = &client.session_id {
+            publish_presence(state, session_id, &client, "left");
+        }
+    }
+}
+
+/// Send a session the change in its membership and who is in it now
+fn publish_presence(
+    state: &Arc<GatewayState>,
+    session_id: &str,
+    client: &ClientInfo,
+    change: &str,
+) {
+    let members: Vec<Value> = state
+        .clients
+        .session_members(session_id)
+        .iter()
+        .map(|c| json!({ "client_id": c.id, "device_id": c.device_id, "role": c.role }))
+        .collect();
+    state.publish(
+        session_id,
+        ClientEvent::new(
+            events::PRESENCE_UPDATE,
+            json!({
+                "change": change,
+                "client_id": client.id,
+                "device_id": client.device_id,
+                "role": client.role,
+                "clients": members,
+            }),
+            Some(session_id.to_string()),
+        ),
+    );
+}
+
+/// Move an operator into an existing session and replay the events it
+/// missed since `since_seq`, along with yields still waiting for an answer
+/// Fetch a session's history. Clients in the session may read it; other
+/// sessions need the operator role.
+async fn handle_session_get(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    session_id: &str,
+    req_id: &str,
+    params: &Value,
+) -> ClientResponse {
+    let requested = params
+        .get("session_id")
+        .and_then(|s| s.as_str())
+        .unwrap_or(session_id);
+    let is_member = state
+        .clients
+        .session_members(requested)
+        .iter()
+        .any(|c| c.id == client_id);
+    if !is_member && !is_operator(state, client_id) {
+        return ClientResponse::error(req_id, "forbidden", "Operator role required");
+    }
+    match state.agent.session(requested).await {
+        Ok(history) => {
+            let (messages, turn_count) = history.unwrap_or_default();
+            ClientResponse::ok(
+                req_id,
+                json!({
+                    "session_id": requested,
+                    "messages": messages,
+                    "turn_count": turn_count,
+                }),
+            )
+        }
+        Err(e) => ClientResponse::error(req_id, "agent_unavailable", &e.to_string()),
+    }
+}
+
+async fn handle_session_attach(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    req_id: &str,
+    params: &Value,
+) -> ClientResponse {
+    if !is_operator(state, client_id) {
+        return ClientResponse::error(req_id, "forbidden", "Operator role required");
+    }
+    let Some(session_id) = params.get("session_id").and_then(|s| s.as_str()) else {
+        return ClientResponse::error(req_id, "invalid_params", "Missing session_id");
+    };
+    let since_seq = params
+        .get("since_seq")
+        .and_then(|s| s.as_u64())
+        .unwrap_or(0);
+
+    // Sessions the gateway has no log for (e.g. after a restart) can still be
+    // rejoined if the daemon has their history
+    let known = state.events.contains(session_id)
+        || !state.clients.session_members(session_id).is_empty()
+        || matches!(state.agent.session(session_id).await, Ok(Some(_)));
+    if !known {
+        return ClientResponse::error(
+            req_id,
+            "session_not_found",
+            &format!("Session {} not found", session_id),
+        );
+    }
+
+    // Join before replaying so nothing published in between is lost;
+    // clients drop duplicates by seq
+    switch_session(state, client_id, session_id);
+    let replay = state
+        .events
+        .replay(session_id, since_seq)
+        .unwrap_or_default();
+    ClientResponse::ok(
+        req_id,
+        json!({
+            "session_id": session_id,
+            "last_seq": replay.last_seq,
+            "truncated": replay.truncated,
+            "events": replay.events,
+            "pending": replay.pending,
+        }),
+    )
+}
+
+/// Device management. Only operators may pair, list or revoke devices.
+fn handle_device_request(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    req_id: &str,
+    request: &ClientRequest,
+) -> ClientResponse {
+    let Some(client) = state
+        .clients
+        .get_client(client_id)
+        .filter(|c| c.role == ClientRole::Operator)
+    else {
+        return ClientResponse::error(req_id, "forbidden", "Operator role required");
+    };
+
+    if request.method == methods::DEVICE_LIST {
+        let pending: Vec<Value> = state
+            .pairings
+            .iter()
+            .map(|p| {
+                json!({
+                    "device_id": p.key(),
+                    "role": p.role,
+                    "fingerprint": auth::fingerprint(&p.public_key),
+                })
+            })
+            .collect();
+        return ClientResponse::ok(
+            req_id,
+            json!({ "devices": state.devices.list(), "pending": pending }),
+        );
+    }
+
+    let Some(device_id) = request.params.get("device_id").and_then(|d| d.as_str()) else {
+        return ClientResponse::error(req_id, "invalid_params", "Missing device_id");
+    };
+
+    if request.method == methods::DEVICE_REVOKE {
+        return match state.devices.revoke(device_id) {
+            Ok(true) => {
+                // Close the device's live connections, however they
+                // authenticated
+                for id in state.clients.list_clients() {
+                    if state
+                        .clients
+                        .get_client(&id)
+                        .is_some_and(|c| c.device_id == device_id)
+                    {
+                        state.clients.close(&id);
+                        disconnect_client(state, &id);
+                    }
+                }
+                ClientResponse::ok(req_id, json!({ "device_id": device_id, "revoked": true }))
+            }
+            Ok(false) => ClientResponse::error(
+                req_id,
+                "not_found",
+                &format!("Device {} is not paired", device_id),
+            ),
+            Err(e) => ClientResponse::error(req_id, "storage_error", &e.to_string()),
+        };
+    }
+
+    let approve = request
+        .params
+        .get("approve")
+        .and_then(|a| a.as_bool())
+        .unwrap_or(true);
+    let fingerprint = request.params.get("fingerprint").and_then(|f| f.as_str());
+    if approve && fingerprint.is_none() {
+        return ClientResponse::error(
+            req_id,
+            "invalid_params",
+            "Missing fingerprint of the key to approve",
+        );
+    }
+    // Only decide for the key the operator actually looked at
+    let Some((_, pending)) = state.pairings.remove_if(device_id, |_, pending| {
+        fingerprint.is_none_or(|f| f == auth::fingerprint(&pending.public_key))
+    }) else {
+        let message = if state.pairings.contains_key(device_id) {
+            format!(
+                "Fingerprint does not match the pending key for device {}",
+                device_id
+            )
+        } else {
+            format!("No pairing request from device {}", device_id)
+        };
+        return ClientResponse::error(req_id, "not_found", &message);
+    };
+    if approve {
+        if let Err(e) = state.devices.pair(
+            device_id,
+            &pending.public_key,
+            pending.role,
+            &client.device_id,
+        ) {
+            let _ = pending.decision.send(false);
+            return ClientResponse::error(req_id, "storage_error", &e.to_string());
+        }
+    }
+    let _ = pending.decision.send(approve);
+    ClientResponse::ok(req_id, json!({ "device_id": device_id, "paired": approve }))
+}
+
 async fn handle_chat_send(
     state: &Arc<GatewayState>,
     session_id: &str,
@@ -319,8 +1244,25 @@ async fn handle_chat_send(
         return Ok(());
     }
 
+    // Interactive turns use the Main lane; clients may send background work to Batch
+    let lane = match params.get("lane").and_then(|l| l.as_str()) {
+        None => LaneType::Main,
+        Some(name) => match LaneType::parse(name) {
+            Some(lane @ (LaneType::Main | LaneType::Batch)) => lane,
+            _ => {
+                let response = ClientResponse::error(
+                    req_id,
+                    "invalid_params",
+                    &format!("Invalid lane: {}", name),
+                );
+                send_response(tx, &response);
+                return Ok(());
+            }
+        },
+    };
+
     // Build agent request
-    let agent_request = AgentRequest::run_turn(
+    let agent_request = state.prepare(AgentRequest::run_turn(
         req_id,
         session_id,
         vec![json!({
@@ -328,172 +1270,37 @@ async fn handle_chat_send(
             "content": message
         })],
         None, // Use default target
-    );
-
-    // Send to agent and stream events back
-    let mut event_rx = state.agent.send_request(agent_request).await?;
-
-    // Forward agent events as client events
-    while let Some(agent_event) = event_rx.recv().await {
-        let client_event = match agent_event.event {
-            AgentEventType::Thinking { content } => Some(ClientEvent::new(
-                events::AGENT_THINKING,
-                json!({ "content": content }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::ToolCall {
-                name,
-                args,
-                tool_call_id,
-            } => Some(ClientEvent::new(
-                events::AGENT_TOOL_CALL,
-                json!({ "name": name, "args": args, "tool_call_id": tool_call_id }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::ToolResult {
-                name,
-                tool_call_id,
-                result,
-                ok,
-                duration_ms,
-            } => Some(ClientEvent::new(
-                events::AGENT_TOOL_RESULT,
-                json!({
-                    "name": name,
-                    "tool_call_id": tool_call_id,
-                    "result": result,
-                    "ok": ok,
-                    "duration_ms": duration_ms
-                }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Content { text } => Some(ClientEvent::new(
-                events::AGENT_MESSAGE,
-                json!({ "text": text }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Done { usage } => {
-                // Send done event
-                let event = ClientEvent::new(
-                    events::AGENT_DONE,
-                    json!({
-                        "input_tokens": usage.input_tokens,
-                        "output_tokens": usage.output_tokens,
-                        "tool_uses": usage.tool_uses
-                    }),
-                    Some(session_id.to_string()),
-                );
-                send_event(tx, &event);
-
-                // Send success response
-                let response = ClientResponse::ok(
-                    req_id,
-                    json!({
-                        "status": "completed",
-                        "usage": {
-                            "input_tokens": usage.input_tokens,
-                            "output_tokens": usage.output_tokens,
-                        }
-                    }),
-                );
-                send_response(tx, &response);
-                None
-            }
-            AgentEventType::Error { code, message } => {
-                // Send error event
-                let event = ClientEvent::new(
-                    events::AGENT_ERROR,
-                    json!({ "code": code, "message": message }),
-                    Some(session_id.to_string()),
-                );
-                send_event(tx, &event);
-
-                // Send error response
-                let response = ClientResponse::error(req_id, &code, &message);
-                send_response(tx, &response);
-                None
-            }
-            AgentEventType::AwaitingInput {
-                tool_call_id,
-                questions,
-            } => Some(ClientEvent::new(
-                events::AGENT_AWAITING_INPUT,
-                json!({ "tool_call_id": tool_call_id, "questions": questions }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Yield {
-                turn_id,
-                reason,
-                tool_call_id,
-                tool_name,
-                tool_args,
-                questions,
-                policy_rule,
-            } => {
-                // Map yield events to appropriate client events
-                let (event_name, data) = match reason {
-                    YieldReason::AwaitingApproval => (
-                        events::AGENT_AWAITING_APPROVAL,
-                        json!({
-                            "turn_id": turn_id,
-                            "tool_call_id": tool_call_id,
-                            "tool_name": tool_name,
-                            "tool_args": tool_args,
-                            "policy_rule": policy_rule
-                        }),
-                    ),
-                    YieldReason::AwaitingInput => (
-                        events::AGENT_AWAITING_INPUT,
-                        json!({
-                            "turn_id": turn_id,
-                            "tool_call_id": tool_call_id,
-                            "questions": questions
-                        }),
-                    ),
-                };
-
-                // Send yield event but don't send response yet (turn is paused)
-                let event = ClientEvent::new(event_name, data, Some(session_id.to_string()));
-                send_event(tx, &event);
-
-                // Send "yielded" response to indicate turn paused
-                let response = ClientResponse::ok(
-                    req_id,
-                    json!({
-                        "status": "yielded",
-                        "turn_id": turn_id,
-                        "reason": match reason {
-                            YieldReason::AwaitingApproval => "awaiting_approval",
-                            YieldReason::AwaitingInput => "awaiting_input",
-                        }
-                    }),
-                );
-                send_response(tx, &response);
-                None
-            }
-            AgentEventType::TokenDelta { text } => Some(ClientEvent::new(
-                events::AGENT_TOKEN_DELTA,
-                json!({ "text": text }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Pong => None,
-        };
-
-        if let Some(event) = client_event {
-            send_event(tx, &event);
+    ));
+
+    // Wait for a lane slot, then send to agent and stream events back
+    let _queued = metrics::track_queue(&lane.to_string());
+    let _slot = match acquire_lane(state, lane, session_id).await {
+        Ok(slot) => slot,
+        Err(message) => {
+            send_response(tx, &ClientResponse::error(req_id, "overloaded", &message));
+            return Ok(());
         }
-    }
+    };
+    let event_rx = state.agent.send_request(agent_request).await?;
+
+    forward_agent_events(state, event_rx, session_id, req_id, tx).await;
 
     Ok(())
 }
 
 async fn handle_turn_resume(
     state: &Arc<GatewayState>,
+    client_id: &str,
     session_id: &str,
     req_id: &str,
     params: Value,
     tx: &mpsc::UnboundedSender<ClientMessage>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !is_operator(state, client_id) {
+        let response = ClientResponse::error(req_id, "forbidden", "Operator role required");
+        send_response(tx, &response);
+        return Ok(());
+    }
     let turn_id = params.get("turn_id").and_then(|t| t.as_str()).unwrap_or("");
 
     if turn_id.is_empty() {
@@ -524,7 +1331,7 @@ async fn handle_turn_resume(
                 turn_id: turn_id.to_string(),
                 tool_call_id,
                 approved: Some(approved),
-                answers: None,
+                ..Default::default()
             }
         }
         "answers" => {
@@ -532,8 +1339,8 @@ async fn handle_turn_resume(
             ResumeData {
                 turn_id: turn_id.to_string(),
                 tool_call_id,
-                approved: None,
                 answers: Some(answers),
+                ..Default::default()
             }
         }
         _ => {
@@ -547,157 +1354,464 @@ async fn handle_turn_resume(
         }
     };
 
-    // Build agent request
-    let agent_request = AgentRequest::resume_turn(req_id, session_id, resume_data);
+    let Some((answered_by, _)) = claim_yield(state, client_id, session_id, req_id, turn_id, tx)
+    else {
+        return Ok(());
+    };
+    let resolved = json!({
+        "turn_id": turn_id,
+        "tool_call_id": resume_data.tool_call_id,
+        "approved": resume_data.approved,
+        "by": answered_by,
+    });
+    resume_claimed(state, session_id, req_id, resume_data, resolved, tx).await
+}
 
-    // Send to agent and stream events back
-    let mut event_rx = state.agent.send_request(agent_request).await?;
+async fn handle_tool_approve(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    session_id: &str,
+    req_id: &str,
+    params: Value,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !is_operator(state, client_id) {
+        let response = ClientResponse::error(req_id, "forbidden", "Operator role required");
+        send_response(tx, &response);
+        return Ok(());
+    }
+    let turn_id = params.get("turn_id").and_then(|t| t.as_str()).unwrap_or("");
+    if turn_id.is_empty() {
+        let response = ClientResponse::error(req_id, "invalid_params", "Missing turn_id parameter");
+        send_response(tx, &response);
+        return Ok(());
+    }
+    let tool_call_id = params
+        .get("tool_call_id")
+        .and_then(|t| t.as_str())
+        .unwrap_or("")
+        .to_string();
+    let approved = params
+        .get("approved")
+        .and_then(|a| a.as_bool())
+        .unwrap_or(true);
+    let edited_args = params.get("args").filter(|a| !a.is_null()).cloned();
+    if edited_args.as_ref().is_some_and(|a| !a.is_object()) {
+        let response = ClientResponse::error(req_id, "invalid_params", "args must be an object");
+        send_response(tx, &response);
+        return Ok(());
+    }
+    let scope = params
+        .get("scope")
+        .and_then(|s| s.as_str())
+        .unwrap_or("once");
+    if !matches!(scope, "once" | "session" | "project") {
+        let response = ClientResponse::error(
+            req_id,
+            "invalid_params",
+            &format!("Invalid scope: {}", scope),
+        );
+        send_response(tx, &response);
+        return Ok(());
+    }
+    let explicit_rule = params
+        .get("rule")
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty());
+
+    let Some((answered_by, yielded)) =
+        claim_yield(state, client_id, session_id, req_id, turn_id, tx)
+    else {
+        return Ok(());
+    };
+    let reject = |message: &str| {
+        state.events.release(session_id, turn_id);
+        send_response(
+            tx,
+            &ClientResponse::error(req_id, "invalid_params", message),
+        );
+    };
+    if yielded
+        .as_ref()
+        .is_some_and(|e| e.event != events::AGENT_AWAITING_APPROVAL)
+    {
+        reject("Turn is not awaiting tool approval");
+        return Ok(());
+    }
+
+    // Rules are derived from the call as it will run, edits included
+    let tool_name = yielded
+        .as_ref()
+        .and_then(|e| e.data.get("tool_name"))
+        .and_then(|t| t.as_str())
+        .map(String::from);
+    let tool_args = edited_args.clone().unwrap_or_else(|| {
+        yielded
+            .as_ref()
+            .and_then(|e| e.data.get("tool_args"))
+            .cloned()
+            .unwrap_or(json!({}))
+    });
+    let rule = if approved && scope != "once" {
+        let rule = explicit_rule.map(String::from).or_else(|| {
+            tool_name
+                .as_deref()
+                .and_then(|tool| PolicyEngine::suggest_rule(tool, &tool_args))
+        });
+        let Some(rule) = rule else {
+            reject("Can't derive an allow rule for this call; pass one as rule");
+            return Ok(());
+        };
+        Some(rule)
+    } else {
+        None
+    };
 
-    // Forward agent events as client events (same as chat_send)
+    if let (Some(rule), "session") = (&rule, scope) {
+        let mut rules = state
+            .session_rules
+            .entry(session_id.to_string())
+            .or_default();
+        if !rules.contains(rule) {
+            rules.push(rule.clone());
+        }
+    }
+    let resolved = json!({
+        "turn_id": turn_id,
+        "tool_call_id": tool_call_id,
+        "approved": approved,
+        "by": answered_by,
+        "scope": scope,
+        "rule": rule,
+        "edited": edited_args.is_some(),
+    });
+    let resume_data = ResumeData {
+        turn_id: turn_id.to_string(),
+        tool_call_id,
+        approved: Some(approved),
+        tool_args: edited_args.filter(|_| approved),
+        allow_rule: rule.filter(|_| scope == "project"),
+        ..Default::default()
+    };
+    resume_claimed(state, session_id, req_id, resume_data, resolved, tx).await
+}
+
+/// Claim the answer to a yield for a client. Operators sharing the session
+/// all see the yield; the first answer wins. Returns the answering device
+/// and the yield event if the gateway logged it, or `None` after telling the
+/// client it lost.
+fn claim_yield(
+    state: &Arc<GatewayState>,
+    client_id: &str,
+    session_id: &str,
+    req_id: &str,
+    turn_id: &str,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) -> Option<(String, Option<ClientEvent>)> {
+    let answered_by = state
+        .clients
+        .get_client(client_id)
+        .map(|c| c.device_id)
+        .unwrap_or_default();
+    match state.events.claim(session_id, turn_id, &answered_by) {
+        Ok(yielded) => Some((answered_by, yielded)),
+        Err(first) => {
+            let response = ClientResponse::error(
+                req_id,
+                "already_resolved",
+                &format!("Already answered by {}", first),
+            );
+            send_response(tx, &response);
+            None
+        }
+    }
+}
+
+/// Resume a claimed yield on the Main lane, announce the answer to the
+/// session and stream the rest of the turn
+async fn resume_claimed(
+    state: &Arc<GatewayState>,
+    session_id: &str,
+    req_id: &str,
+    resume_data: ResumeData,
+    resolved: Value,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let turn_id = resume_data.turn_id.clone();
+    let agent_request = state.prepare(AgentRequest::resume_turn(req_id, session_id, resume_data));
+
+    // Resumed turns go back on the Main lane
+    let _queued = metrics::track_queue("main");
+    let _slot = match acquire_lane(state, LaneType::Main, session_id).await {
+        Ok(slot) => slot,
+        Err(message) => {
+            state.events.release(session_id, &turn_id);
+            send_response(tx, &ClientResponse::error(req_id, "overloaded", &message));
+            return Ok(());
+        }
+    };
+    state.publish(
+        session_id,
+        ClientEvent::new(
+            events::APPROVAL_RESOLVED,
+            resolved,
+            Some(session_id.to_string()),
+        ),
+    );
+    let event_rx = state.agent.send_request(agent_request).await?;
+
+    forward_agent_events(state, event_rx, session_id, req_id, tx).await;
+
+    Ok(())
+}
+
+/// Stream a turn's agent events to the session, answering the requesting
+/// client when the turn completes, fails or yields
+async fn forward_agent_events(
+    state: &Arc<GatewayState>,
+    mut event_rx: mpsc::UnboundedReceiver<AgentEvent>,
+    session_id: &str,
+    req_id: &str,
+    tx: &mpsc::UnboundedSender<ClientMessage>,
+) {
     while let Some(agent_event) = event_rx.recv().await {
-        let client_event = match agent_event.event {
-            AgentEventType::Thinking { content } => Some(ClientEvent::new(
-                events::AGENT_THINKING,
-                json!({ "content": content }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::ToolCall {
-                name,
-                args,
-                tool_call_id,
-            } => Some(ClientEvent::new(
-                events::AGENT_TOOL_CALL,
-                json!({ "name": name, "args": args, "tool_call_id": tool_call_id }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::ToolResult {
-                name,
-                tool_call_id,
-                result,
-                ok,
-                duration_ms,
-            } => Some(ClientEvent::new(
-                events::AGENT_TOOL_RESULT,
+        if let Some(resumed) =
+            run_node_tool(state, session_id, req_id, &agent_event.event, false).await
+        {
+            match resumed {
+                Ok(rx) => {
+                    event_rx = rx;
+                    continue;
+                }
+                Err(e) => {
+                    let response =
+                        ClientResponse::error(req_id, "agent_unavailable", &e.to_string());
+                    send_response(tx, &response);
+                    return;
+                }
+            }
+        }
+        let response = match &agent_event.event {
+            AgentEventType::Done { usage } => Some(ClientResponse::ok(
+                req_id,
                 json!({
-                    "name": name,
-                    "tool_call_id": tool_call_id,
-                    "result": result,
-                    "ok": ok,
-                    "duration_ms": duration_ms
-                }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Content { text } => Some(ClientEvent::new(
-                events::AGENT_MESSAGE,
-                json!({ "text": text }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Done { usage } => {
-                let event = ClientEvent::new(
-                    events::AGENT_DONE,
-                    json!({
+                    "status": "completed",
+                    "usage": {
                         "input_tokens": usage.input_tokens,
                         "output_tokens": usage.output_tokens,
-                        "tool_uses": usage.tool_uses
-                    }),
-                    Some(session_id.to_string()),
-                );
-                send_event(tx, &event);
-
-                let response = ClientResponse::ok(
-                    req_id,
-                    json!({
-                        "status": "completed",
-                        "usage": {
-                            "input_tokens": usage.input_tokens,
-                            "output_tokens": usage.output_tokens,
-                        }
-                    }),
-                );
-                send_response(tx, &response);
-                None
-            }
+                        "cost_usd": usage.cost_usd,
+                    }
+                }),
+            )),
             AgentEventType::Error { code, message } => {
-                let event = ClientEvent::new(
-                    events::AGENT_ERROR,
-                    json!({ "code": code, "message": message }),
-                    Some(session_id.to_string()),
-                );
-                send_event(tx, &event);
-
-                let response = ClientResponse::error(req_id, &code, &message);
-                send_response(tx, &response);
-                None
+                Some(ClientResponse::error(req_id, code, message))
             }
-            AgentEventType::AwaitingInput {
-                tool_call_id,
-                questions,
-            } => Some(ClientEvent::new(
-                events::AGENT_AWAITING_INPUT,
-                json!({ "tool_call_id": tool_call_id, "questions": questions }),
-                Some(session_id.to_string()),
-            )),
             AgentEventType::Yield {
-                turn_id,
-                reason,
-                tool_call_id,
-                tool_name,
-                tool_args,
-                questions,
-                policy_rule,
+                turn_id, reason, ..
             } => {
-                let (event_name, data) = match reason {
-                    YieldReason::AwaitingApproval => (
-                        events::AGENT_AWAITING_APPROVAL,
-                        json!({
-                            "turn_id": turn_id,
-                            "tool_call_id": tool_call_id,
-                            "tool_name": tool_name,
-                            "tool_args": tool_args,
-                            "policy_rule": policy_rule
-                        }),
-                    ),
-                    YieldReason::AwaitingInput => (
-                        events::AGENT_AWAITING_INPUT,
-                        json!({
-                            "turn_id": turn_id,
-                            "tool_call_id": tool_call_id,
-                            "questions": questions
-                        }),
-                    ),
-                };
-
-                let event = ClientEvent::new(event_name, data, Some(session_id.to_string()));
-                send_event(tx, &event);
-
-                let response = ClientResponse::ok(
+                metrics::record_yield(yield_reason_str(reason));
+                // Turn is paused until the client resumes it
+                Some(ClientResponse::ok(
                     req_id,
                     json!({
                         "status": "yielded",
                         "turn_id": turn_id,
-                        "reason": match reason {
-                            YieldReason::AwaitingApproval => "awaiting_approval",
-                            YieldReason::AwaitingInput => "awaiting_input",
-                        }
+                        "reason": yield_reason_str(reason)
                     }),
-                );
-                send_response(tx, &response);
-                None
+                ))
             }
-            AgentEventType::TokenDelta { text } => Some(ClientEvent::new(
-                events::AGENT_TOKEN_DELTA,
-                json!({ "text": text }),
-                Some(session_id.to_string()),
-            )),
-            AgentEventType::Pong => None,
+            _ => None,
         };
 
-        if let Some(event) = client_event {
-            send_event(tx, &event);
+        if let Some(event) = client_event(agent_event.event, session_id) {
+            state.publish(session_id, event);
+        }
+        if let Some(response) = response {
+            send_response(tx, &response);
         }
     }
+}
 
-    Ok(())
+/// If the turn yielded for a node tool, run the call on the node and resume
+/// the turn with its result. Returns the resumed turn's events.
+pub(super) async fn run_node_tool(
+    state: &Arc<GatewayState>,
+    session_id: &str,
+    req_id: &str,
+    event: &AgentEventType,
+    ephemeral: bool,
+) -> Option<std::io::Result<mpsc::UnboundedReceiver<AgentEvent>>> {
+    let AgentEventType::Yield {
+        turn_id,
+        reason: YieldReason::NodeTool,
+        tool_call_id,
+        tool_name,
+        tool_args,
+        ..
+    } = event
+    else {
+        return None;
+    };
+
+    let started = std::time::Instant::now();
+    let result = match nodes::parse_tool_name(tool_name) {
+        Some((device_id, tool)) => {
+            state
+                .node_calls
+                .call(
+                    &state.clients,
+                    device_id,
+                    tool,
+                    tool_args.clone(),
+                    NODE_CALL_TIMEOUT,
+                )
+                .await
+        }
+        None => Err(ErrorInfo {
+            code: "invalid_tool".to_string(),
+            message: format!("Not a node tool: {}", tool_name),
+        }),
+    };
+    let result =
+        result.unwrap_or_else(|e| json!({ "error": { "code": e.code, "message": e.message } }));
+
+    let resume_data = ResumeData {
+        turn_id: turn_id.clone(),
+        tool_call_id: tool_call_id.clone(),
+        result: Some(result),
+        duration_ms: Some(started.elapsed().as_millis() as u64),
+        ..Default::default()
+    };
+    let mut request = AgentRequest::resume_turn(req_id, session_id, resume_data);
+    request.ephemeral = ephemeral;
+    let request = state.prepare(request);
+    Some(state.agent.send_request(request).await)
+}
+
+/// Client event for an agent event, if clients see it
+fn client_event(event: AgentEventType, session_id: &str) -> Option<ClientEvent> {
+    let (name, data) = match event {
+        AgentEventType::Thinking { content } => {
+            (events::AGENT_THINKING, json!({ "content": content }))
+        }
+        AgentEventType::ToolCall {
+            name,
+            args,
+            tool_call_id,
+        } => (
+            events::AGENT_TOOL_CALL,
+            json!({
+                "name": name,
+                "display": tool_display::format_tool_call(&name, &args),
+                "args": args,
+                "tool_call_id": tool_call_id
+            }),
+        ),
+        AgentEventType::ToolResult {
+            name,
+            tool_call_id,
+            result,
+            ok,
+            duration_ms,
+        } => (
+            events::AGENT_TOOL_RESULT,
+            json!({
+                "display": tool_display::format_tool_result(&name, &result),
+                "name": name,
+                "tool_call_id": tool_call_id,
+                "result": result,
+                "ok": ok,
+                "duration_ms": duration_ms
+            }),
+        ),
+        AgentEventType::Content { text } => {
+            // Plans come back as text; send their steps along for rich clients
+            let plan = text
+                .contains("```plan")
+                .then(|| plan::parse_plan_output(&text, "").ok())
+                .flatten();
+            let data = match plan {
+                Some(plan) => json!({
+                    "text": text,
+                    "plan": { "summary": plan.summary, "steps": plan.steps }
+                }),
+                None => json!({ "text": text }),
+            };
+            (events::AGENT_MESSAGE, data)
+        }
+        AgentEventType::Done { usage } => (
+            events::AGENT_DONE,
+            json!({
+                "input_tokens": usage.input_tokens,
+                "output_tokens": usage.output_tokens,
+                "tool_uses": usage.tool_uses,
+                "cost_usd": usage.cost_usd
+            }),
+        ),
+        AgentEventType::Error { code, message } => (
+            events::AGENT_ERROR,
+            json!({ "code": code, "message": message }),
+        ),
+        AgentEventType::AwaitingInput {
+            tool_call_id,
+            questions,
+        } => (
+            events::AGENT_AWAITING_INPUT,
+            json!({ "tool_call_id": tool_call_id, "questions": questions }),
+        ),
+        AgentEventType::Yield {
+            turn_id,
+            reason,
+            tool_call_id,
+            tool_name,
+            tool_args,
+            questions,
+            policy_rule,
+        } => match reason {
+            YieldReason::AwaitingApproval => (
+                events::AGENT_AWAITING_APPROVAL,
+                json!({
+                    "turn_id": turn_id,
+                    "tool_call_id": tool_call_id,
+                    "tool_name": tool_name,
+                    "tool_args": tool_args,
+                    "policy_rule": policy_rule
+                }),
+            ),
+            YieldReason::AwaitingInput => (
+                events::AGENT_AWAITING_INPUT,
+                json!({
+                    "turn_id": turn_id,
+                    "tool_call_id": tool_call_id,
+                    "questions": questions
+                }),
+            ),
+            YieldReason::BudgetExceeded => (
+                events::AGENT_BUDGET_EXCEEDED,
+                json!({
+                    "turn_id": turn_id,
+                    "tool_call_id": tool_call_id,
+                    "budget": tool_args
+                }),
+            ),
+            // The gateway runs node tools itself; clients only see the result
+            YieldReason::NodeTool => return None,
+        },
+        AgentEventType::TokenDelta { text } => (events::AGENT_TOKEN_DELTA, json!({ "text": text })),
+        AgentEventType::Pong
+        | AgentEventType::Metrics { .. }
+        | AgentEventType::SessionHistory { .. } => return None,
+    };
+    Some(ClientEvent::new(name, data, Some(session_id.to_string())))
+}
+
+fn yield_reason_str(reason: &YieldReason) -> &'static str {
+    match reason {
+        YieldReason::AwaitingApproval => "awaiting_approval",
+        YieldReason::AwaitingInput => "awaiting_input",
+        YieldReason::BudgetExceeded => "budget_exceeded",
+        YieldReason::NodeTool => "node_tool",
+    }
 }
 
 fn send_response(tx: &mpsc::UnboundedSender<ClientMessage>, response: &ClientResponse) {
@@ -706,8 +1820,8 @@ fn send_response(tx: &mpsc::UnboundedSender<ClientMessage>, response: &ClientRes
     }
 }
 
-fn send_event(tx: &mpsc::UnboundedSender<ClientMessage>, event: &ClientEvent) {
-    if let Ok(json) = serde_json::to_string(event) {
-        let _ = tx.send(ClientMessage { json });
-    }
+fn send_frame(tx: &mpsc::UnboundedSender<ClientMessage>, frame: Value) {
+    let _ = tx.send(ClientMessage {
+        json: frame.to_string(),
+    });
 }
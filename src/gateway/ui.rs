@@ -0,0 +1,46 @@
+//! Browser chat UI.
+//!
+//! A single page served from assets embedded in the binary, so the gateway
+//! needs no build step or network to serve it. The page speaks the same
+//! WebSocket protocol as `yo --gateway`.
+
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+};
+
+const INDEX_HTML: &str = include_str!("ui/index.html");
+const APP_JS: &str = include_str!("ui/app.js");
+const STYLE_CSS: &str = include_str!("ui/style.css");
+
+/// `GET /`
+pub async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+/// `GET /ui/app.js`
+pub async fn app_js() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+        APP_JS,
+    )
+}
+
+/// `GET /ui/style.css`
+pub async fn style_css() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/css; charset=utf-8")],
+        STYLE_CSS,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_links_embedded_assets() {
+        assert!(INDEX_HTML.contains("/ui/app.js"));
+        assert!(INDEX_HTML.contains("/ui/style.css"));
+    }
+}
@@ -0,0 +1,639 @@
+// Brainpro browser client. Speaks the gateway WebSocket protocol: `hello`,
+// an optional token challenge, then `req`/`res` frames and session events.
+'use strict';
+
+const TOKEN_KEY = 'brainpro.token';
+const DEVICE_KEY = 'brainpro.device_id';
+const SESSION_KEY = 'brainpro.session';
+
+const $ = (id) => document.getElementById(id);
+
+const state = {
+    ws: null,
+    sessionId: null,
+    // Sequence numbers already rendered, so replays don't duplicate events
+    seen: new Set(),
+    lastSeq: 0,
+    busy: false,
+    // Response callbacks by request id
+    pending: new Map(),
+    // Tool call elements by tool_call_id
+    tools: new Map(),
+    // Yields waiting for an answer, shown one dialog at a time
+    yields: [],
+    activeYield: null,
+    // Assistant message receiving token deltas
+    streaming: null,
+    tokenPrompt: null,
+    totals: { input: 0, output: 0, cost: 0, turns: 0 },
+};
+
+function randomId() {
+    if (crypto.randomUUID) return crypto.randomUUID();
+    return Array.from({ length: 16 }, () => Math.floor(Math.random() * 256).toString(16).padStart(2, '0')).join('');
+}
+
+const deviceId = localStorage.getItem(DEVICE_KEY) || `browser-${randomId().slice(0, 8)}`;
+localStorage.setItem(DEVICE_KEY, deviceId);
+
+// ---------------------------------------------------------------------------
+// Connection
+// ---------------------------------------------------------------------------
+
+function connect() {
+    const scheme = location.protocol === 'https:' ? 'wss' : 'ws';
+    const ws = new WebSocket(`${scheme}://${location.host}/ws`);
+    state.ws = ws;
+    setStatus('connecting…');
+
+    ws.onopen = () => sendFrame({
+        type: 'hello',
+        role: 'operator',
+        device_id: deviceId,
+        caps: { tools: [], protocol_version: 1 },
+    });
+    ws.onmessage = (e) => {
+        let frame;
+        try {
+            frame = JSON.parse(e.data);
+        } catch {
+            return;
+        }
+        handleFrame(frame);
+    };
+    ws.onclose = () => {
+        if (state.ws !== ws) return;
+        state.pending.clear();
+        setBusy(false);
+        setStatus('disconnected, retrying…', 'error');
+        setTimeout(connect, 2000);
+    };
+}
+
+function sendFrame(frame) {
+    if (state.ws && state.ws.readyState === WebSocket.OPEN) {
+        state.ws.send(JSON.stringify(frame));
+        return true;
+    }
+    return false;
+}
+
+function request(method, params, onResponse) {
+    const id = randomId();
+    if (onResponse) state.pending.set(id, onResponse);
+    if (!sendFrame({ type: 'req', id, method, params })) {
+        state.pending.delete(id);
+        onResponse?.({ ok: false, error: { code: 'disconnected', message: 'Not connected' } });
+    }
+}
+
+function handleFrame(frame) {
+    switch (frame.type) {
+        case 'challenge':
+            answerChallenge(frame.nonce);
+            break;
+        case 'pairing':
+            setStatus(`waiting for an operator to approve ${deviceId}…`);
+            break;
+        case 'error':
+            if (frame.code === 'auth_failed') localStorage.removeItem(TOKEN_KEY);
+            setStatus(frame.message || 'connection rejected', 'error');
+            break;
+        case 'welcome':
+            onWelcome(frame);
+            break;
+        case 'res': {
+            const callback = state.pending.get(frame.id);
+            state.pending.delete(frame.id);
+            callback?.(frame);
+            break;
+        }
+        case 'event':
+            handleEvent(frame);
+            break;
+    }
+}
+
+async function answerChallenge(nonce) {
+    if (!crypto.subtle) {
+        setStatus('token auth needs https or localhost', 'error');
+        return;
+    }
+    const ws = state.ws;
+    let token = localStorage.getItem(TOKEN_KEY);
+    if (!token) {
+        // Reconnects while the dialog is open share one prompt
+        state.tokenPrompt = state.tokenPrompt || promptToken().finally(() => {
+            state.tokenPrompt = null;
+        });
+        token = await state.tokenPrompt;
+        localStorage.setItem(TOKEN_KEY, token);
+    }
+    const signature = await hmacBase64(token, `brainpro-auth-v1:${nonce}:${deviceId}`);
+    // The handshake may have timed out while the user typed
+    if (state.ws === ws) sendFrame({ type: 'auth', signature });
+}
+
+async function hmacBase64(secret, message) {
+    const encoder = new TextEncoder();
+    const key = await crypto.subtle.importKey(
+        'raw', encoder.encode(secret), { name: 'HMAC', hash: 'SHA-256' }, false, ['sign']);
+    const tag = await crypto.subtle.sign('HMAC', key, encoder.encode(message));
+    return btoa(String.fromCharCode(...new Uint8Array(tag)));
+}
+
+function promptToken() {
+    return new Promise((resolve) => {
+        openDialog($('token-dialog'), () => {
+            resolve($('token-input').value);
+        });
+    });
+}
+
+function onWelcome(frame) {
+    setStatus('connected', 'connected');
+    // Pick up where we left off: after a reconnect, or after a page reload
+    const previous = state.sessionId || sessionStorage.getItem(SESSION_KEY);
+    if (previous && previous !== frame.session_id) {
+        attach(previous, { fresh: !state.sessionId, fallback: frame.session_id });
+    } else {
+        setSession(frame.session_id);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sessions
+// ---------------------------------------------------------------------------
+
+function setSession(sessionId) {
+    if (state.sessionId !== sessionId) {
+        state.seen.clear();
+        state.lastSeq = 0;
+    }
+    state.sessionId = sessionId;
+    sessionStorage.setItem(SESSION_KEY, sessionId);
+    $('session-input').value = sessionId;
+}
+
+function resetTranscript() {
+    $('transcript').replaceChildren();
+    state.tools.clear();
+    state.streaming = null;
+    state.yields = [];
+    $('plan-panel').hidden = true;
+    $('todo-panel').hidden = true;
+}
+
+// Join a session and replay what this page hasn't shown yet
+function attach(sessionId, { fresh, fallback }) {
+    const previous = state.sessionId;
+    const sinceSeq = fresh || previous !== sessionId ? 0 : state.lastSeq;
+    if (sinceSeq === 0) resetTranscript();
+    // Live events for the new session can arrive before the response
+    setSession(sessionId);
+
+    request('session.attach', { session_id: sessionId, since_seq: sinceSeq }, (res) => {
+        if (!res.ok) {
+            note(`Could not attach to ${sessionId}: ${res.error.message}`);
+            setSession(fallback || previous);
+            return;
+        }
+        if (res.payload.truncated) note('Some earlier events are no longer available.');
+        res.payload.events.forEach(handleEvent);
+        res.payload.pending.forEach(queueYield);
+    });
+}
+
+$('attach-form').addEventListener('submit', (e) => {
+    e.preventDefault();
+    const sessionId = $('session-input').value.trim();
+    if (sessionId && sessionId !== state.sessionId) attach(sessionId, { fresh: true, fallback: state.sessionId });
+});
+
+$('new-session').addEventListener('click', () => {
+    request('session.create', {}, (res) => {
+        if (!res.ok) return addMessage('error', res.error.message);
+        resetTranscript();
+        setSession(res.payload.session_id);
+    });
+});
+
+// ---------------------------------------------------------------------------
+// Events
+// ---------------------------------------------------------------------------
+
+function handleEvent(frame) {
+    if (frame.session_id && state.sessionId && frame.session_id !== state.sessionId) return;
+    if (frame.seq != null) {
+        if (state.seen.has(frame.seq)) return;
+        state.seen.add(frame.seq);
+        state.lastSeq = Math.max(state.lastSeq, frame.seq);
+    }
+
+    const data = frame.data || {};
+    switch (frame.event) {
+        case 'agent.thinking':
+            addMessage('thinking', data.content);
+            break;
+        case 'agent.token_delta':
+            appendDelta(data.text);
+            break;
+        case 'agent.message':
+            finishMessage(data.text);
+            if (data.plan) renderPlan(data.plan);
+            break;
+        case 'agent.tool_call':
+            addToolCall(data);
+            break;
+        case 'agent.tool_result':
+            addToolResult(data);
+            break;
+        case 'agent.awaiting_approval':
+        case 'agent.awaiting_input':
+        case 'agent.budget_exceeded':
+            queueYield(frame);
+            break;
+        case 'approval.resolved':
+            resolveYield(data);
+            break;
+        case 'agent.done':
+            addUsage(data);
+            setBusy(false);
+            break;
+        case 'agent.error':
+            addMessage('error', `${data.code}: ${data.message}`);
+            setBusy(false);
+            break;
+        case 'presence.update':
+            renderPresence(data);
+            break;
+        case 'queue.enqueued':
+            if (data.position > 1) note(`Queued on the ${data.lane} lane (position ${data.position})`);
+            break;
+        case 'cron.fired':
+            note(`Cron job ${data.job_id || ''} fired`);
+            break;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Transcript
+// ---------------------------------------------------------------------------
+
+function append(el) {
+    const transcript = $('transcript');
+    const atBottom = transcript.scrollHeight - transcript.scrollTop - transcript.clientHeight < 80;
+    transcript.append(el);
+    if (atBottom) transcript.scrollTop = transcript.scrollHeight;
+}
+
+// Text with fenced code blocks rendered as <pre>
+function renderText(el, text) {
+    el.replaceChildren();
+    const fence = /```[\w-]*\n?([\s\S]*?)```/g;
+    let last = 0;
+    for (const match of text.matchAll(fence)) {
+        el.append(text.slice(last, match.index));
+        const pre = document.createElement('pre');
+        const code = document.createElement('code');
+        code.textContent = match[1];
+        pre.append(code);
+        el.append(pre);
+        last = match.index + match[0].length;
+    }
+    el.append(text.slice(last));
+}
+
+function addMessage(kind, text) {
+    const el = document.createElement('div');
+    el.className = `msg ${kind}`;
+    renderText(el, text || '');
+    append(el);
+    return el;
+}
+
+function note(text) {
+    addMessage('note', text);
+}
+
+function appendDelta(text) {
+    if (!state.streaming) {
+        state.streaming = addMessage('assistant', '');
+        state.streaming.dataset.text = '';
+    }
+    state.streaming.dataset.text += text;
+    renderText(state.streaming, state.streaming.dataset.text);
+}
+
+function finishMessage(text) {
+    if (state.streaming) {
+        renderText(state.streaming, text);
+        state.streaming = null;
+    } else {
+        addMessage('assistant', text);
+    }
+}
+
+function addToolCall(data) {
+    state.streaming = null;
+    const el = document.createElement('details');
+    el.className = 'tool';
+    const summary = document.createElement('summary');
+    const call = document.createElement('span');
+    call.textContent = data.display || `⏺ ${data.name}`;
+    summary.append(call);
+    const args = document.createElement('pre');
+    args.textContent = JSON.stringify(data.args, null, 2);
+    el.append(summary, args);
+    append(el);
+    state.tools.set(data.tool_call_id, el);
+
+    if (data.name === 'TodoWrite' && Array.isArray(data.args?.todos)) renderTodos(data.args.todos);
+}
+
+function addToolResult(data) {
+    if (!state.tools.has(data.tool_call_id)) {
+        addToolCall({ name: data.name, args: {}, tool_call_id: data.tool_call_id });
+    }
+    const el = state.tools.get(data.tool_call_id);
+    el.classList.add(data.ok ? 'ok' : 'failed');
+
+    // The last line is the summary; anything before it (command output) goes in the body
+    const display = (data.display || '').replace(/\s+$/, '');
+    const lines = display.split('\n');
+    const status = document.createElement('div');
+    status.className = 'result';
+    status.textContent = lines.pop();
+    el.querySelector('summary').append(status);
+
+    if (lines.length) {
+        const output = document.createElement('pre');
+        output.textContent = lines.join('\n');
+        el.append(output);
+    }
+    const raw = document.createElement('details');
+    const rawSummary = document.createElement('summary');
+    rawSummary.textContent = 'Raw result';
+    const rawBody = document.createElement('pre');
+    rawBody.textContent = JSON.stringify(data.result, null, 2);
+    raw.append(rawSummary, rawBody);
+    el.append(raw);
+}
+
+function renderTodos(todos) {
+    const list = $('todo-list');
+    list.replaceChildren(...todos.map((todo) => {
+        const li = document.createElement('li');
+        li.className = todo.status;
+        const icon = { completed: '☑', in_progress: '▶' }[todo.status] || '☐';
+        li.textContent = `${icon} ${todo.status === 'in_progress' ? todo.activeForm || todo.content : todo.content}`;
+        return li;
+    }));
+    $('todo-panel').hidden = todos.length === 0;
+}
+
+function renderPlan(plan) {
+    $('plan-summary').textContent = plan.summary || '';
+    $('plan-steps').replaceChildren(...plan.steps.map((step) => {
+        const li = document.createElement('li');
+        li.value = step.number;
+        li.textContent = step.title;
+        if (step.description) {
+            const desc = document.createElement('span');
+            desc.className = 'desc';
+            desc.textContent = step.description;
+            li.append(desc);
+        }
+        return li;
+    }));
+    $('plan-panel').hidden = plan.steps.length === 0;
+}
+
+function renderPresence(data) {
+    const clients = data.clients || [];
+    const others = clients.filter((c) => c.device_id !== deviceId).map((c) => c.device_id);
+    $('presence').textContent = others.length ? `with ${others.join(', ')}` : '';
+    if (data.device_id !== deviceId) note(`${data.device_id} ${data.change}`);
+}
+
+function addUsage(data) {
+    const totals = state.totals;
+    totals.input += data.input_tokens || 0;
+    totals.output += data.output_tokens || 0;
+    totals.cost += data.cost_usd || 0;
+    totals.turns += 1;
+    const n = (v) => v.toLocaleString();
+    $('cost').textContent =
+        `Last: ${n(data.input_tokens || 0)} in · ${n(data.output_tokens || 0)} out · ` +
+        `${data.tool_uses || 0} tools · $${(data.cost_usd || 0).toFixed(4)}` +
+        `   |   This page: ${n(totals.input)} in · ${n(totals.output)} out · $${totals.cost.toFixed(4)}`;
+}
+
+function setStatus(text, kind) {
+    const el = $('status');
+    el.textContent = text;
+    el.className = `status ${kind || ''}`;
+}
+
+function setBusy(busy) {
+    state.busy = busy;
+    $('send').disabled = busy;
+}
+
+// ---------------------------------------------------------------------------
+// Yields: approvals, questions and budgets
+// ---------------------------------------------------------------------------
+
+function openDialog(dialog, onSubmit) {
+    const form = dialog.querySelector('form');
+    form.onsubmit = (e) => {
+        e.preventDefault();
+        if (onSubmit(e.submitter?.value) !== false) dialog.close();
+    };
+    // Every dialog needs an answer
+    dialog.oncancel = (e) => e.preventDefault();
+    dialog.showModal();
+}
+
+function queueYield(frame) {
+    const turnId = frame.data?.turn_id;
+    if (!turnId) return;
+    const queued = (y) => y.data.turn_id === turnId;
+    if ((state.activeYield && queued(state.activeYield)) || state.yields.some(queued)) return;
+    state.yields.push(frame);
+    nextYield();
+}
+
+function nextYield() {
+    if (state.activeYield || state.yields.length === 0) return;
+    const frame = state.yields.shift();
+    state.activeYield = frame;
+    const show = {
+        'agent.awaiting_approval': showApproval,
+        'agent.awaiting_input': showQuestions,
+        'agent.budget_exceeded': showBudget,
+    }[frame.event];
+    show(frame.data);
+}
+
+function finishYield() {
+    state.activeYield = null;
+    nextYield();
+}
+
+// Another operator answered first, or our own answer came back
+function resolveYield(data) {
+    state.yields = state.yields.filter((y) => y.data.turn_id !== data.turn_id);
+    if (state.activeYield && state.activeYield.data.turn_id === data.turn_id) {
+        for (const dialog of document.querySelectorAll('dialog[open]')) dialog.close();
+        finishYield();
+    }
+    const verdict = data.approved === true ? 'Approved' : data.approved === false ? 'Denied' : 'Answered';
+    let text = `${verdict} by ${data.by || 'unknown'}`;
+    if (data.edited) text += ' (edited)';
+    if (data.rule) text += `; allowing ${data.rule} for this ${data.scope}`;
+    note(text);
+}
+
+function answer(method, params) {
+    setBusy(true);
+    request(method, params, (res) => {
+        if (!res.ok) {
+            if (res.error.code !== 'already_resolved') addMessage('error', res.error.message);
+            setBusy(false);
+        }
+    });
+}
+
+function showApproval(data) {
+    const args = data.tool_args || {};
+    const isBash = data.tool_name === 'Bash' && typeof args.command === 'string';
+    $('approval-summary').textContent = data.tool_name;
+    $('approval-rule').textContent = data.policy_rule ? `Asked by rule ${data.policy_rule}` : '';
+    $('approval-edit-label').textContent = isBash ? 'Command' : 'Arguments (JSON)';
+    $('approval-edit').value = isBash ? args.command : JSON.stringify(args, null, 2);
+    $('approval-error').textContent = '';
+
+    openDialog($('approval-dialog'), (choice) => {
+        const params = { turn_id: data.turn_id, tool_call_id: data.tool_call_id };
+        if (choice === 'deny') {
+            answer('tool.approve', { ...params, approved: false });
+            finishYield();
+            return;
+        }
+
+        let edited = null;
+        const text = $('approval-edit').value;
+        if (isBash) {
+            if (text.trim() !== args.command.trim()) edited = { ...args, command: text.trim() };
+        } else {
+            let parsed;
+            try {
+                parsed = JSON.parse(text);
+            } catch {
+                parsed = null;
+            }
+            if (!parsed || typeof parsed !== 'object' || Array.isArray(parsed)) {
+                $('approval-error').textContent = 'Arguments must be a JSON object.';
+                return false;
+            }
+            if (JSON.stringify(parsed) !== JSON.stringify(args)) edited = parsed;
+        }
+        answer('tool.approve', { ...params, approved: true, args: edited, scope: choice });
+        finishYield();
+    });
+}
+
+function showQuestions(data) {
+    const container = $('questions');
+    container.replaceChildren();
+    const questions = data.questions || [];
+    questions.forEach((q, i) => {
+        const fieldset = document.createElement('fieldset');
+        const legend = document.createElement('legend');
+        legend.textContent = q.header ? `[${q.header}] ${q.question}` : q.question;
+        fieldset.append(legend);
+        const type = q.multi_select ? 'checkbox' : 'radio';
+        for (const option of q.options || []) {
+            const label = document.createElement('label');
+            const input = document.createElement('input');
+            input.type = type;
+            input.name = `q${i}`;
+            input.value = option.label;
+            label.append(input, ` ${option.label}`);
+            if (option.description) {
+                const desc = document.createElement('span');
+                desc.className = 'muted';
+                desc.textContent = ` — ${option.description}`;
+                label.append(desc);
+            }
+            fieldset.append(label);
+        }
+        const other = document.createElement('input');
+        other.type = 'text';
+        other.name = `q${i}-other`;
+        other.placeholder = 'Other answer';
+        fieldset.append(other);
+        container.append(fieldset);
+    });
+
+    openDialog($('question-dialog'), () => {
+        const answers = {};
+        questions.forEach((q, i) => {
+            const checked = [...container.querySelectorAll(`input[name="q${i}"]:checked`)].map((el) => el.value);
+            const other = container.querySelector(`input[name="q${i}-other"]`).value.trim();
+            if (other) checked.push(other);
+            if (q.multi_select) answers[`q${i}`] = checked;
+            else if (checked.length) answers[`q${i}`] = checked[checked.length - 1];
+        });
+        answer('turn.resume', {
+            turn_id: data.turn_id,
+            tool_call_id: data.tool_call_id,
+            response_type: 'answers',
+            answers,
+        });
+        finishYield();
+    });
+}
+
+function showBudget(data) {
+    $('budget-details').textContent = JSON.stringify(data.budget, null, 2);
+    openDialog($('budget-dialog'), (choice) => {
+        answer('turn.resume', {
+            turn_id: data.turn_id,
+            tool_call_id: data.tool_call_id,
+            response_type: 'approval',
+            approved: choice === 'continue',
+        });
+        finishYield();
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Composer
+// ---------------------------------------------------------------------------
+
+$('composer').addEventListener('submit', (e) => {
+    e.preventDefault();
+    const input = $('message');
+    const message = input.value.trim();
+    if (!message || state.busy) return;
+    input.value = '';
+    addMessage('user', message);
+    setBusy(true);
+    request('chat.send', { message }, (res) => {
+        if (!res.ok) {
+            addMessage('error', `${res.error.code}: ${res.error.message}`);
+            setBusy(false);
+        }
+    });
+});
+
+$('message').addEventListener('keydown', (e) => {
+    if (e.key === 'Enter' && !e.shiftKey) {
+        e.preventDefault();
+        $('composer').requestSubmit();
+    }
+});
+
+connect();
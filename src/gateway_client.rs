@@ -1,6 +1,8 @@
 //! Gateway client for connecting CLI to gateway via WebSocket.
 
-use crate::protocol::client::methods;
+use crate::cost::format_cost;
+use crate::gateway::auth::{self, DeviceIdentity};
+use crate::protocol::client::{events, methods};
 use anyhow::{anyhow, Result};
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
@@ -29,12 +31,20 @@ async fn run_gateway_async(
 
     let (mut write, mut read) = ws_stream.split();
 
+    // Persistent device identity, so the gateway can recognise paired keys
+    let identity = match DeviceIdentity::load_or_create(&DeviceIdentity::default_path()) {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("[gateway-client] Using a temporary device key: {}", e);
+            DeviceIdentity::generate()?.0
+        }
+    };
+
     // Send Hello
-    let device_id = uuid::Uuid::new_v4().to_string();
     let hello = json!({
         "type": "hello",
         "role": "operator",
-        "device_id": device_id,
+        "device_id": identity.device_id,
         "caps": {
             "tools": [],
             "protocol_version": 1
@@ -46,13 +56,47 @@ async fn run_gateway_async(
         .await
         .map_err(|e| anyhow!("Failed to send hello: {}", e))?;
 
-    // Wait for Welcome
+    // Answer the challenge (if any) and wait for Welcome
     let welcome = loop {
         match read.next().await {
             Some(Ok(Message::Text(text))) => {
                 let msg: Value = serde_json::from_str(&text)?;
-                if msg.get("type").and_then(|t| t.as_str()) == Some("welcome") {
-                    break msg;
+                match msg.get("type").and_then(|t| t.as_str()) {
+                    Some("welcome") => break msg,
+                    Some("challenge") => {
+                        let nonce = msg.get("nonce").and_then(|n| n.as_str()).unwrap_or("");
+                        let signature = std::env::var("BRAINPRO_GATEWAY_TOKEN")
+                            .map(|token| auth::token_signature(&token, nonce, &identity.device_id))
+                            .unwrap_or_default();
+                        let auth = json!({
+                            "type": "auth",
+                            "signature": signature,
+                            "device_key": identity.public_key(),
+                            "device_signature": identity.sign(nonce),
+                        });
+                        write
+                            .send(Message::Text(auth.to_string()))
+                            .await
+                            .map_err(|e| anyhow!("Failed to send auth: {}", e))?;
+                    }
+                    Some("pairing") => {
+                        let fingerprint = msg
+                            .get("fingerprint")
+                            .and_then(|f| f.as_str())
+                            .unwrap_or("?");
+                        eprintln!(
+                            "[gateway-client] Waiting for an operator to approve device {} (key {})",
+                            identity.device_id, fingerprint
+                        );
+                    }
+                    Some("error") => {
+                        let message = msg
+                            .get("message")
+                            .and_then(|m| m.as_str())
+                            .unwrap_or("Authentication failed");
+                        return Err(anyhow!("Gateway rejected connection: {}", message));
+                    }
+                    _ => {}
                 }
             }
             Some(Err(e)) => return Err(anyhow!("WebSocket error: {}", e)),
@@ -115,6 +159,46 @@ async fn run_repl(write: &mut WsWrite, read: &mut WsRead, auto_approve: bool) ->
             break;
         }
 
+        if let Some(command) = input.strip_prefix('/') {
+            let mut parts = command.split_whitespace();
+            let (method, params) = match (parts.next(), parts.next()) {
+                (Some("attach"), Some(id)) => {
+                    let since_seq = parts.next().and_then(|s| s.parse::<u64>().ok());
+                    (
+                        methods::SESSION_ATTACH,
+                        json!({ "session_id": id, "since_seq": since_seq.unwrap_or(0) }),
+                    )
+                }
+                (Some("devices"), None) => (methods::DEVICE_LIST, json!({})),
+                (Some("pair"), Some(id)) => match parts.next() {
+                    Some(fingerprint) => (
+                        methods::DEVICE_PAIR,
+                        json!({ "device_id": id, "fingerprint": fingerprint }),
+                    ),
+                    None => {
+                        eprintln!("Usage: /pair <device_id> <fingerprint>");
+                        continue;
+                    }
+                },
+                (Some("deny"), Some(id)) => (
+                    methods::DEVICE_PAIR,
+                    json!({ "device_id": id, "approve": false }),
+                ),
+                (Some("revoke"), Some(id)) => (methods::DEVICE_REVOKE, json!({ "device_id": id })),
+                _ => {
+                    eprintln!(
+                        "Commands: /attach <session> [seq], /devices, /pair <id> <fingerprint>, /deny <id>, /revoke <id>, /exit"
+                    );
+                    continue;
+                }
+            };
+            match send_request(write, read, method, params).await {
+                Ok(payload) => println!("{}", serde_json::to_string_pretty(&payload)?),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            continue;
+        }
+
         if let Err(e) = send_chat_and_stream(write, read, input, auto_approve).await {
             eprintln!("Error: {}", e);
         }
@@ -123,6 +207,78 @@ async fn run_repl(write: &mut WsWrite, read: &mut WsRead, auto_approve: bool) ->
     Ok(())
 }
 
+/// Send a request and wait for its response payload
+async fn send_request(
+    write: &mut WsWrite,
+    read: &mut WsRead,
+    method: &str,
+    params: Value,
+) -> Result<Value> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let request = json!({
+        "type": "req",
+        "id": req_id,
+        "method": method,
+        "params": params,
+    });
+    write
+        .send(Message::Text(request.to_string()))
+        .await
+        .map_err(|e| anyhow!("Failed to send request: {}", e))?;
+
+    loop {
+        match read.next().await {
+            Some(Ok(Message::Text(text))) => {
+                let msg: Value = serde_json::from_str(&text)?;
+                if msg.get("type").and_then(|t| t.as_str()) == Some("event") {
+                    print_pair_request(&msg);
+                    continue;
+                }
+                if msg.get("id").and_then(|i| i.as_str()) != Some(req_id.as_str()) {
+                    continue;
+                }
+                if msg.get("ok").and_then(|o| o.as_bool()) == Some(true) {
+                    return Ok(msg.get("payload").cloned().unwrap_or(Value::Null));
+                }
+                let message = msg
+                    .get("error")
+                    .and_then(|e| e.get("message"))
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("Request failed");
+                return Err(anyhow!("{}", message));
+            }
+            Some(Err(e)) => return Err(anyhow!("WebSocket error: {}", e)),
+            None => return Err(anyhow!("Connection closed")),
+            _ => {}
+        }
+    }
+}
+
+/// Tell the operator about a device waiting to be paired
+fn print_pair_request(msg: &Value) {
+    if msg.get("event").and_then(|e| e.as_str()) != Some(events::DEVICE_PAIR_REQUESTED) {
+        return;
+    }
+    let data = msg.get("data").cloned().unwrap_or(json!({}));
+    let device_id = data
+        .get("device_id")
+        .and_then(|d| d.as_str())
+        .unwrap_or("?");
+    let fingerprint = data
+        .get("fingerprint")
+        .and_then(|f| f.as_str())
+        .unwrap_or("?");
+    eprintln!(
+        "⚠ Device {} ({}) requests pairing, key {}. Approve with /pair {} {} or /deny {}",
+        device_id,
+        data.get("role").and_then(|r| r.as_str()).unwrap_or("?"),
+        fingerprint,
+        device_id,
+        fingerprint,
+        device_id
+    );
+}
+
 async fn send_chat_and_stream(
     write: &mut WsWrite,
     read: &mut WsRead,
@@ -171,6 +327,7 @@ async fn stream_response(
 
                 match msg_type {
                     "event" => {
+                        print_pair_request(&msg);
                         let event_name = msg.get("event").and_then(|e| e.as_str()).unwrap_or("");
                         let data = msg.get("data").cloned().unwrap_or(json!({}));
 
@@ -183,6 +340,39 @@ async fn stream_response(
                                     }
                                 }
                             }
+                            "presence.update" => {
+                                let device = data
+                                    .get("device_id")
+                                    .and_then(|d| d.as_str())
+                                    .unwrap_or("?");
+                                let change =
+                                    data.get("change").and_then(|c| c.as_str()).unwrap_or("?");
+                                eprintln!("• {} {} the session", device, change);
+                            }
+                            "approval.resolved" => {
+                                let by = data.get("by").and_then(|b| b.as_str()).unwrap_or("?");
+                                match data.get("approved").and_then(|a| a.as_bool()) {
+                                    Some(true) => eprintln!("• Approved by {}", by),
+                                    Some(false) => eprintln!("• Denied by {}", by),
+                                    None => eprintln!("• Answered by {}", by),
+                                }
+                                if let Some(rule) = data.get("rule").and_then(|r| r.as_str()) {
+                                    let scope =
+                                        data.get("scope").and_then(|s| s.as_str()).unwrap_or("");
+                                    eprintln!("  Allowing {} for this {}", rule, scope);
+                                }
+                            }
+                            "queue.enqueued" => {
+                                let position =
+                                    data.get("position").and_then(|p| p.as_u64()).unwrap_or(1);
+                                if position > 1 {
+                                    eprintln!(
+                                        "⏳ Queued on {} lane (position {})",
+                                        data.get("lane").and_then(|l| l.as_str()).unwrap_or("?"),
+                                        position
+                                    );
+                                }
+                            }
                             "agent.tool_call" => {
                                 tool_uses += 1;
                                 let name = data.get("name").and_then(|n| n.as_str()).unwrap_or("?");
@@ -233,13 +423,13 @@ async fn stream_response(
                                     .unwrap_or("?");
                                 let tool_args = data.get("tool_args").cloned().unwrap_or(json!({}));
 
-                                let approved = if auto_approve {
+                                let answer = if auto_approve {
                                     eprintln!(
                                         "⚠ Auto-approved: {}({})",
                                         tool_name,
                                         format_args_brief(&tool_args)
                                     );
-                                    true
+                                    ToolApproval::default()
                                 } else {
                                     eprintln!(
                                         "⚠ Permission required: {}({})",
@@ -262,20 +452,21 @@ async fn stream_response(
                                         }
                                     }
 
-                                    prompt_approval()
+                                    prompt_tool_approval(tool_name, &tool_args)
                                 };
 
-                                // Send resume request
+                                // Send approval
                                 let resume_req_id = uuid::Uuid::new_v4().to_string();
                                 let resume_request = json!({
                                     "type": "req",
                                     "id": resume_req_id,
-                                    "method": methods::TURN_RESUME,
+                                    "method": methods::TOOL_APPROVE,
                                     "params": {
                                         "turn_id": turn_id,
                                         "tool_call_id": tool_call_id,
-                                        "response_type": "approval",
-                                        "approved": approved
+                                        "approved": answer.approved,
+                                        "args": answer.args,
+                                        "scope": answer.scope,
                                     }
                                 });
 
@@ -287,6 +478,44 @@ async fn stream_response(
                                 // Continue streaming with new request ID
                                 // (The loop will continue processing events)
                             }
+                            "agent.budget_exceeded" => {
+                                // Budgets are never auto-approved, even with --yes
+                                let turn_id =
+                                    data.get("turn_id").and_then(|t| t.as_str()).unwrap_or("");
+                                let tool_call_id = data
+                                    .get("tool_call_id")
+                                    .and_then(|t| t.as_str())
+                                    .unwrap_or("");
+                                let budget = data.get("budget").cloned().unwrap_or(json!({}));
+                                let amount = |key: &str| {
+                                    budget.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0)
+                                };
+                                eprintln!(
+                                    "⚠ Cost budget exceeded: {} ({} spent of {})",
+                                    budget.get("budget").and_then(|b| b.as_str()).unwrap_or("?"),
+                                    format_cost(amount("spent_usd")),
+                                    format_cost(amount("limit_usd"))
+                                );
+                                let approved = prompt_approval();
+
+                                let resume_req_id = uuid::Uuid::new_v4().to_string();
+                                let resume_request = json!({
+                                    "type": "req",
+                                    "id": resume_req_id,
+                                    "method": methods::TURN_RESUME,
+                                    "params": {
+                                        "turn_id": turn_id,
+                                        "tool_call_id": tool_call_id,
+                                        "response_type": "approval",
+                                        "approved": approved
+                                    }
+                                });
+
+                                write
+                                    .send(Message::Text(resume_request.to_string()))
+                                    .await
+                                    .map_err(|e| anyhow!("Failed to send resume: {}", e))?;
+                            }
                             "agent.awaiting_input" => {
                                 // Prompt user for answers
                                 let turn_id =
@@ -342,6 +571,16 @@ async fn stream_response(
                             continue;
                         }
 
+                        // Another operator in the session answered first; the
+                        // turn carries on with their answer
+                        let code = msg
+                            .get("error")
+                            .and_then(|e| e.get("code"))
+                            .and_then(|c| c.as_str());
+                        if code == Some("already_resolved") {
+                            continue;
+                        }
+
                         // Response received (completed or error), we're done
                         let duration = start.elapsed();
                         let total_tokens = input_tokens + output_tokens;
@@ -391,6 +630,96 @@ fn prompt_approval() -> bool {
     }
 }
 
+/// Answer to a tool approval prompt
+struct ToolApproval {
+    approved: bool,
+    /// Edited arguments to run the tool with
+    args: Option<Value>,
+    /// "once", "session" or "project"
+    scope: &'static str,
+}
+
+impl Default for ToolApproval {
+    fn default() -> Self {
+        Self {
+            approved: true,
+            args: None,
+            scope: "once",
+        }
+    }
+}
+
+/// Prompt user for tool approval, with options to edit the call or allow
+/// similar calls for the session or the project
+fn prompt_tool_approval(tool_name: &str, tool_args: &Value) -> ToolApproval {
+    print!("Allow? [y]es / [N]o / [e]dit / [s]ession / [a]lways: ");
+    io::stdout().flush().ok();
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        return ToolApproval {
+            approved: false,
+            ..Default::default()
+        };
+    }
+    match input.trim().to_lowercase().as_str() {
+        "y" | "yes" => ToolApproval::default(),
+        "s" | "session" => ToolApproval {
+            scope: "session",
+            ..Default::default()
+        },
+        "a" | "always" => ToolApproval {
+            scope: "project",
+            ..Default::default()
+        },
+        "e" | "edit" => match prompt_edited_args(tool_name, tool_args) {
+            Some(args) => ToolApproval {
+                args: Some(args),
+                ..Default::default()
+            },
+            None => ToolApproval {
+                approved: false,
+                ..Default::default()
+            },
+        },
+        _ => ToolApproval {
+            approved: false,
+            ..Default::default()
+        },
+    }
+}
+
+/// Read replacement arguments: a new command for Bash, JSON otherwise.
+/// Returns `None` if the input is empty or invalid.
+fn prompt_edited_args(tool_name: &str, tool_args: &Value) -> Option<Value> {
+    if tool_name == "Bash" {
+        print!("New command: ");
+    } else {
+        eprintln!("  Current: {}", tool_args);
+        print!("New arguments (JSON): ");
+    }
+    io::stdout().flush().ok();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).ok()?;
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    if tool_name == "Bash" {
+        let mut args = tool_args.clone();
+        args["command"] = json!(input);
+        return Some(args);
+    }
+    match serde_json::from_str::<Value>(input) {
+        Ok(args) if args.is_object() => Some(args),
+        _ => {
+            eprintln!("Invalid JSON object; denying");
+            None
+        }
+    }
+}
+
 /// Prompt user for question answers
 fn prompt_questions(questions: &[Value]) -> Value {
     let mut answers = json!({});
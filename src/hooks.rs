@@ -4,6 +4,7 @@
 //! Exit codes: 0 = allow, 2 = block, other = warn (continue with warning).
 
 use crate::config::{HookConfig, HookEvent};
+use crate::spans;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -165,6 +166,20 @@ impl HookManager {
 
     /// Execute a single hook with the given JSON input
     fn execute_hook<T: Serialize>(&self, hook: &HookConfig, input: &T) -> HookResult {
+        let mut span = spans::start("hook");
+        if span.is_recording() {
+            span.set("hook.event", format!("{:?}", hook.event));
+            span.set("hook.command", hook.command.join(" "));
+        }
+        let result = self.run_hook_process(hook, input);
+        match result.exit_code {
+            Some(code) => span.set("hook.exit_code", code),
+            None => span.error(&result.stderr),
+        }
+        result
+    }
+
+    fn run_hook_process<T: Serialize>(&self, hook: &HookConfig, input: &T) -> HookResult {
         let input_json = match serde_json::to_string(input) {
             Ok(json) => json,
             Err(e) => {
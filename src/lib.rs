@@ -8,7 +8,9 @@ mod agent_impl;
 pub mod agent_policy;
 pub mod agent_service;
 pub mod backend;
+pub mod cascade;
 pub mod circuit_breaker;
+pub mod classifier;
 pub mod cli;
 pub mod commands;
 pub mod compact;
@@ -28,13 +30,18 @@ pub mod policy;
 pub mod privacy;
 pub mod protocol;
 pub mod provider_health;
+pub mod rate_limit;
+pub mod redact;
 pub mod session;
 pub mod skillpacks;
+pub mod spans;
 pub mod subagent;
+pub mod tokens;
 pub mod tool_display;
 pub mod tool_filter;
 pub mod tools;
 pub mod transcript;
+pub mod usage;
 pub mod vendors;
 
 // Re-export Args for the binaries
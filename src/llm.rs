@@ -1,17 +1,22 @@
 //! LLM client with retry logic, jittered backoff, and connection pooling.
 //!
-//! Uses reqwest::blocking for synchronous HTTP calls with built-in
-//! connection pooling and timeout handling.
+//! `AsyncClient` does the HTTP work on tokio and honors cancellation tokens.
+//! The agent daemon awaits it directly; `Client` is a blocking wrapper
+//! around it for the CLI and other synchronous callers.
 
 #![allow(dead_code)]
 
+use crate::rate_limit::{self, RateLimitHeaders, RateLimiter};
+use crate::tokens::TokenCounter;
 use anyhow::{anyhow, Result};
 use rand::Rng;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::sync::{Arc, OnceLock};
 use std::thread;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 // Retry configuration for rate limiting and transient errors
 const MAX_RETRIES: u32 = 5;
@@ -32,6 +37,185 @@ fn jittered_backoff(base_ms: u64) -> u64 {
     (jittered as u64).min(MAX_BACKOFF_MS)
 }
 
+/// Substrings backends use when a request exceeds the model's context window
+const CONTEXT_OVERFLOW_MARKERS: &[&str] = &[
+    "context_length_exceeded",
+    "maximum context length",
+    "context length",
+    "context window",
+    "prompt is too long",
+    "too many tokens",
+    "input is too long",
+    "reduce the length of the messages",
+];
+
+/// Typed errors from a chat backend.
+///
+/// Returned wrapped in `anyhow::Error`; callers that care about the kind use
+/// `downcast_ref::<BackendError>()` or helpers like [`is_context_overflow`].
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    /// The request does not fit in the model's context window
+    ContextOverflow { status: u16, body: String },
+    /// Non-retryable HTTP error
+    Api { status: u16, body: String },
+    /// Retryable HTTP error that kept failing
+    RetriesExhausted {
+        status: u16,
+        retries: u32,
+        body: String,
+    },
+    /// Connection or network failure that kept failing
+    Connection { retries: u32, message: String },
+    /// The call was cancelled before it completed
+    Cancelled,
+}
+
+impl BackendError {
+    /// Classify a failed HTTP response
+    pub fn from_response(status: u16, body: String) -> Self {
+        if matches!(status, 400 | 413 | 422) && is_context_overflow_body(&body) {
+            BackendError::ContextOverflow { status, body }
+        } else {
+            BackendError::Api { status, body }
+        }
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::ContextOverflow { status, body } => {
+                write!(f, "Context length exceeded ({}): {}", status, body)
+            }
+            BackendError::Api { status, body } => write!(f, "API error {}: {}", status, body),
+            BackendError::RetriesExhausted {
+                status,
+                retries,
+                body,
+            } => write!(
+                f,
+                "API error {} after {} retries: {}",
+                status, retries, body
+            ),
+            BackendError::Connection { retries, message } => {
+                write!(f, "Connection error after {} retries: {}", retries, message)
+            }
+            BackendError::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// Check whether an error body describes a context-length rejection
+fn is_context_overflow_body(body: &str) -> bool {
+    let lower = body.to_lowercase();
+    CONTEXT_OVERFLOW_MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// Check whether an error comes from a cancelled call
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<BackendError>(),
+        Some(BackendError::Cancelled)
+    )
+}
+
+/// Check whether an error is a backend context-length rejection
+pub fn is_context_overflow(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<BackendError>(),
+        Some(BackendError::ContextOverflow { .. })
+    )
+}
+
+/// Check whether an error says the backend itself is failing, as opposed to
+/// the request being bad or the caller giving up. These count against the
+/// backend's health and circuit breaker.
+pub fn is_backend_failure(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<BackendError>() {
+        Some(BackendError::ContextOverflow { .. } | BackendError::Cancelled) => false,
+        Some(BackendError::Api { status, .. }) => {
+            matches!(status, 401 | 403 | 408 | 429) || *status >= 500
+        }
+        Some(BackendError::RetriesExhausted { .. } | BackendError::Connection { .. }) => true,
+        None => true,
+    }
+}
+
+/// Rough prompt size of a request, for reserving rate-limit capacity
+fn estimate_request_tokens(request: &ChatRequest) -> u64 {
+    TokenCounter::new().count_request(
+        &request.model,
+        &request.messages,
+        request.tools.as_deref().unwrap_or(&[]),
+    ) as u64
+}
+
+/// Sleep for a rate-limit or backoff wait, reporting it on the events bus.
+/// Returns early with `BackendError::Cancelled` if `cancel` fires.
+async fn limited_wait(
+    backend: &str,
+    wait: Duration,
+    reason: &str,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    if wait.is_zero() {
+        return Ok(());
+    }
+    rate_limit::report_wait(backend, wait, reason);
+    tokio::select! {
+        _ = cancel.cancelled() => Err(BackendError::Cancelled.into()),
+        _ = tokio::time::sleep(wait) => Ok(()),
+    }
+}
+
+/// Sampling parameters sent alongside a chat request.
+///
+/// Every field is optional so that layers (target, persona, subagent) can be
+/// merged with more specific layers overriding only what they set.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+}
+
+impl SamplingParams {
+    /// Overlay `other` on top of `self`; fields set in `other` win.
+    pub fn merged(&self, other: &SamplingParams) -> SamplingParams {
+        SamplingParams {
+            temperature: other.temperature.or(self.temperature),
+            top_p: other.top_p.or(self.top_p),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            stop: other.stop.clone().or_else(|| self.stop.clone()),
+            seed: other.seed.or(self.seed),
+            parallel_tool_calls: other.parallel_tool_calls.or(self.parallel_tool_calls),
+        }
+    }
+
+    /// True if no parameter is set
+    pub fn is_empty(&self) -> bool {
+        *self == SamplingParams::default()
+    }
+
+    /// Drop parameters that only make sense when tools are offered
+    pub fn without_tool_params(mut self) -> Self {
+        self.parallel_tool_calls = None;
+        self
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ChatRequest {
     pub model: String,
@@ -40,6 +224,8 @@ pub struct ChatRequest {
     pub tools: Option<Vec<Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<String>,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
 }
 
 /// Token usage statistics from the API response
@@ -183,36 +369,73 @@ pub trait LlmClient {
     }
 }
 
-pub struct Client {
+/// Async LLM client with retry logic and cancellation.
+///
+/// Backoff sleeps and in-flight requests both stop as soon as the
+/// `CancellationToken` passed to a call is cancelled. Clones share the
+/// connection pool and rate limiter.
+#[derive(Clone)]
+pub struct AsyncClient {
     base_url: String,
     /// API key wrapped in SecretString for secure memory handling.
     /// Will be zeroized on drop and won't leak via Debug/Display.
     api_key: SecretString,
-    http_client: reqwest::blocking::Client,
+    http_client: reqwest::Client,
+    /// Shared per-backend limiter, also fed by provider rate-limit headers
+    limiter: Option<Arc<RateLimiter>>,
 }
 
-impl Client {
-    /// Create a new LLM client.
-    /// The API key is stored as a SecretString for secure memory handling.
+impl AsyncClient {
+    /// Create a new async LLM client.
     pub fn new(base_url: &str, api_key: SecretString) -> Self {
-        let http_client = reqwest::blocking::Client::builder()
+        let http_client = reqwest::Client::builder()
             .timeout(Duration::from_secs(120))
             .pool_max_idle_per_host(10)
             .build()
-            .expect("Failed to create HTTP client");
+            .expect("Failed to create async HTTP client");
 
         Self {
             base_url: base_url.trim_end_matches('/').to_string(),
             api_key,
             http_client,
+            limiter: None,
         }
     }
 
-    /// Internal sync implementation with retry logic
-    fn chat_sync(&self, request: &ChatRequest) -> Result<LlmCallResult> {
+    /// Route every call through a shared rate limiter
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Backend name for reporting, falling back to the base URL
+    fn backend_name(&self) -> &str {
+        self.limiter
+            .as_ref()
+            .map(|l| l.backend())
+            .unwrap_or(&self.base_url)
+    }
+
+    /// Chat call that returns the response only
+    pub async fn chat(
+        &self,
+        request: &ChatRequest,
+        cancel: &CancellationToken,
+    ) -> Result<ChatResponse> {
+        Ok(self.chat_with_metadata(request, cancel).await?.response)
+    }
+
+    /// Chat call with retry logic, returning latency and retry count
+    pub async fn chat_with_metadata(
+        &self,
+        request: &ChatRequest,
+        cancel: &CancellationToken,
+    ) -> Result<LlmCallResult> {
         let url = format!("{}/chat/completions", self.base_url);
         let start = std::time::Instant::now();
 
+        let estimated_tokens = estimate_request_tokens(request);
+
         let mut attempt = 0;
         let mut backoff_ms = INITIAL_BACKOFF_MS;
         let mut total_retries = 0;
@@ -220,7 +443,12 @@ impl Client {
         loop {
             attempt += 1;
 
-            let resp = self
+            if let Some(limiter) = &self.limiter {
+                let wait = limiter.reserve(estimated_tokens);
+                limited_wait(limiter.backend(), wait, "client_limit", cancel).await?;
+            }
+
+            let send = self
                 .http_client
                 .post(&url)
                 .header(
@@ -230,13 +458,30 @@ impl Client {
                 .header("Content-Type", "application/json")
                 .json(request)
                 .send();
+            let resp = tokio::select! {
+                _ = cancel.cancelled() => return Err(BackendError::Cancelled.into()),
+                resp = send => resp,
+            };
 
-            match resp {
+            let (wait, reason) = match resp {
                 Ok(response) => {
                     let status = response.status();
+                    let headers = RateLimitHeaders::parse(response.headers());
+                    if let Some(limiter) = &self.limiter {
+                        limiter.observe_headers(&headers);
+                    }
 
                     if status.is_success() {
-                        let body: ChatResponse = response.json()?;
+                        let body: ChatResponse = tokio::select! {
+                            _ = cancel.cancelled() => return Err(BackendError::Cancelled.into()),
+                            body = response.json() => body?,
+                        };
+                        if let (Some(limiter), Some(usage)) = (&self.limiter, &body.usage) {
+                            limiter.record_usage(
+                                estimated_tokens,
+                                usage.prompt_tokens + usage.completion_tokens,
+                            );
+                        }
                         return Ok(LlmCallResult {
                             response: body,
                             latency_ms: start.elapsed().as_millis() as u64,
@@ -245,49 +490,53 @@ impl Client {
                     }
 
                     let code = status.as_u16();
-                    if is_retryable_status(code) {
-                        if attempt >= MAX_RETRIES {
-                            let body = response.text().unwrap_or_default();
-                            return Err(anyhow!(
-                                "API error {} after {} retries: {}",
-                                code,
-                                MAX_RETRIES,
-                                body
-                            ));
+                    if !is_retryable_status(code) {
+                        // Non-retryable HTTP error (4xx except 429)
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(BackendError::from_response(code, body).into());
+                    }
+
+                    if attempt >= MAX_RETRIES {
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(BackendError::RetriesExhausted {
+                            status: code,
+                            retries: MAX_RETRIES,
+                            body,
                         }
+                        .into());
+                    }
 
-                        // Check for Retry-After header (common in 429 responses)
-                        let retry_after = response
-                            .headers()
-                            .get("Retry-After")
-                            .and_then(|v| v.to_str().ok())
-                            .and_then(|v| v.parse::<u64>().ok())
-                            .map(|s| s * 1000); // Convert seconds to ms
-
-                        let wait_ms = retry_after.unwrap_or_else(|| jittered_backoff(backoff_ms));
-
-                        eprintln!(
-                            "[llm] {} error, retrying in {}ms (attempt {}/{})",
-                            code, wait_ms, attempt, MAX_RETRIES
-                        );
-
-                        thread::sleep(Duration::from_millis(wait_ms));
-                        backoff_ms = (backoff_ms * 2).min(MAX_BACKOFF_MS);
-                        total_retries += 1;
-                    } else {
-                        // Non-retryable HTTP error (4xx except 429)
-                        let body = response.text().unwrap_or_default();
-                        return Err(anyhow!("API error {}: {}", code, body));
+                    // Prefer the provider's Retry-After / x-ratelimit-reset over our backoff
+                    let (wait, reason) = match headers.retry_wait() {
+                        Some(wait) => (wait, "retry_after"),
+                        None => (
+                            Duration::from_millis(jittered_backoff(backoff_ms)),
+                            "backoff",
+                        ),
+                    };
+                    if code == 429 {
+                        // Hold other callers sharing this backend too
+                        if let Some(limiter) = &self.limiter {
+                            limiter.block_for(wait);
+                        }
                     }
+                    eprintln!(
+                        "[llm] {} error, retrying in {}ms (attempt {}/{})",
+                        code,
+                        wait.as_millis(),
+                        attempt,
+                        MAX_RETRIES
+                    );
+                    (wait, reason)
                 }
                 Err(e) => {
                     // Connection/network error - retryable
                     if attempt >= MAX_RETRIES {
-                        return Err(anyhow!(
-                            "Connection error after {} retries: {}",
-                            MAX_RETRIES,
-                            e
-                        ));
+                        return Err(BackendError::Connection {
+                            retries: MAX_RETRIES,
+                            message: e.to_string(),
+                        }
+                        .into());
                     }
 
                     let wait_ms = jittered_backoff(backoff_ms);
@@ -295,24 +544,93 @@ impl Client {
                         "[llm] Connection error, retrying in {}ms (attempt {}/{}): {}",
                         wait_ms, attempt, MAX_RETRIES, e
                     );
-
-                    thread::sleep(Duration::from_millis(wait_ms));
-                    backoff_ms = (backoff_ms * 2).min(MAX_BACKOFF_MS);
-                    total_retries += 1;
+                    (Duration::from_millis(wait_ms), "backoff")
                 }
-            }
+            };
+
+            limited_wait(self.backend_name(), wait, reason, cancel).await?;
+            backoff_ms = (backoff_ms * 2).min(MAX_BACKOFF_MS);
+            total_retries += 1;
+        }
+    }
+}
+
+/// Runtime every blocking [`Client`] drives its calls on. Built on first
+/// use, so processes that only await [`AsyncClient`] never start it.
+fn blocking_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("brainpro-llm")
+            .enable_all()
+            .build()
+            .expect("Failed to create LLM client runtime")
+    })
+}
+
+/// Blocking wrapper around [`AsyncClient`] for the CLI and sync agent loops.
+///
+/// All clients share one small runtime, so pooled connections outlive
+/// individual calls without a thread per backend.
+pub struct Client {
+    inner: AsyncClient,
+}
+
+impl Client {
+    /// Create a new LLM client.
+    /// The API key is stored as a SecretString for secure memory handling.
+    pub fn new(base_url: &str, api_key: SecretString) -> Self {
+        Self {
+            inner: AsyncClient::new(base_url, api_key),
+        }
+    }
+
+    /// Route every call through a shared rate limiter
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.inner.limiter = Some(limiter);
+        self
+    }
+
+    /// The async client this wrapper drives
+    pub fn as_async(&self) -> &AsyncClient {
+        &self.inner
+    }
+
+    /// Blocking chat call that stops early when `cancel` fires
+    pub fn chat_with_cancel(
+        &self,
+        request: &ChatRequest,
+        cancel: &CancellationToken,
+    ) -> Result<ChatResponse> {
+        Ok(self.call(request, cancel)?.response)
+    }
+
+    fn call(&self, request: &ChatRequest, cancel: &CancellationToken) -> Result<LlmCallResult> {
+        let runtime = blocking_runtime();
+        let call = self.inner.chat_with_metadata(request, cancel);
+        if tokio::runtime::Handle::try_current().is_ok() {
+            // Called from async code: a runtime can't block inside another,
+            // so drive the call from a helper thread
+            thread::scope(|scope| {
+                scope
+                    .spawn(|| runtime.block_on(call))
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow!("LLM client thread panicked")))
+            })
+        } else {
+            runtime.block_on(call)
         }
     }
 }
 
 impl LlmClient for Client {
     fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
-        let result = self.chat_sync(request)?;
-        Ok(result.response)
+        self.chat_with_cancel(request, &CancellationToken::new())
     }
 
     fn chat_with_metadata(&self, request: &ChatRequest) -> Result<LlmCallResult> {
-        self.chat_sync(request)
+        self.call(request, &CancellationToken::new())
     }
 }
 
@@ -327,6 +645,8 @@ pub struct StreamingChatRequest {
     pub tools: Option<Vec<Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<String>,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<StreamOptions>,
@@ -342,6 +662,7 @@ pub struct StreamingClient {
     base_url: String,
     api_key: SecretString,
     http_client: reqwest::Client,
+    limiter: Option<Arc<RateLimiter>>,
 }
 
 impl StreamingClient {
@@ -356,15 +677,33 @@ impl StreamingClient {
             base_url: base_url.trim_end_matches('/').to_string(),
             api_key,
             http_client,
+            limiter: None,
         }
     }
 
+    /// Route every call through a shared rate limiter
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
     /// Stream chat completions, sending events to the provided channel.
     /// Accumulates the full response and returns it when complete.
     pub async fn chat_stream(
         &self,
         request: &ChatRequest,
         event_tx: tokio::sync::mpsc::Sender<StreamEvent>,
+    ) -> Result<ChatResponse> {
+        self.chat_stream_with_cancel(request, event_tx, &CancellationToken::new())
+            .await
+    }
+
+    /// Stream chat completions, stopping the request when `cancel` fires.
+    pub async fn chat_stream_with_cancel(
+        &self,
+        request: &ChatRequest,
+        event_tx: tokio::sync::mpsc::Sender<StreamEvent>,
+        cancel: &CancellationToken,
     ) -> Result<ChatResponse> {
         use futures::StreamExt;
 
@@ -375,12 +714,19 @@ impl StreamingClient {
             messages: request.messages.clone(),
             tools: request.tools.clone(),
             tool_choice: request.tool_choice.clone(),
+            sampling: request.sampling.clone(),
             stream: true,
             stream_options: Some(StreamOptions {
                 include_usage: true,
             }),
         };
 
+        let estimated_tokens = estimate_request_tokens(request);
+        if let Some(limiter) = &self.limiter {
+            let wait = limiter.reserve(estimated_tokens);
+            limited_wait(limiter.backend(), wait, "client_limit", cancel).await?;
+        }
+
         let response = self
             .http_client
             .post(&url)
@@ -391,14 +737,27 @@ impl StreamingClient {
             .header("Content-Type", "application/json")
             .header("Accept", "text/event-stream")
             .json(&streaming_request)
-            .send()
-            .await
-            .map_err(|e| anyhow!("HTTP error: {}", e))?;
+            .send();
+        let response = tokio::select! {
+            _ = cancel.cancelled() => return Err(BackendError::Cancelled.into()),
+            response = response => response.map_err(|e| anyhow!("HTTP error: {}", e))?,
+        };
+
+        let headers = RateLimitHeaders::parse(response.headers());
+        if let Some(limiter) = &self.limiter {
+            limiter.observe_headers(&headers);
+        }
 
         if !response.status().is_success() {
             let status = response.status();
+            if status.as_u16() == 429 {
+                // Streaming calls don't retry, but later calls should wait
+                if let (Some(limiter), Some(wait)) = (&self.limiter, headers.retry_wait()) {
+                    limiter.block_for(wait);
+                }
+            }
             let body = response.text().await.unwrap_or_default();
-            return Err(anyhow!("API error {}: {}", status, body));
+            return Err(BackendError::from_response(status.as_u16(), body).into());
         }
 
         // Accumulate full response
@@ -412,7 +771,14 @@ impl StreamingClient {
         let byte_stream = response.bytes_stream();
         let mut event_stream = byte_stream.eventsource();
 
-        while let Some(event_result) = event_stream.next().await {
+        loop {
+            let event_result = tokio::select! {
+                _ = cancel.cancelled() => return Err(BackendError::Cancelled.into()),
+                next = event_stream.next() => match next {
+                    Some(event_result) => event_result,
+                    None => break,
+                },
+            };
             let event = match event_result {
                 Ok(e) => e,
                 Err(e) => {
@@ -509,6 +875,13 @@ impl StreamingClient {
             }
         }
 
+        if let (Some(limiter), Some(usage)) = (&self.limiter, &final_usage) {
+            limiter.record_usage(
+                estimated_tokens,
+                usage.prompt_tokens + usage.completion_tokens,
+            );
+        }
+
         // Send done event
         let _ = event_tx
             .send(StreamEvent::Done {
@@ -556,6 +929,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sampling_params_merge() {
+        let base = SamplingParams {
+            temperature: Some(0.2),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let overlay = SamplingParams {
+            temperature: Some(0.9),
+            max_tokens: Some(1024),
+            ..Default::default()
+        };
+        let merged = base.merged(&overlay);
+        assert_eq!(merged.temperature, Some(0.9));
+        assert_eq!(merged.seed, Some(42));
+        assert_eq!(merged.max_tokens, Some(1024));
+        assert!(merged.top_p.is_none());
+    }
+
+    #[test]
+    fn test_chat_request_serializes_sampling() {
+        let request = ChatRequest {
+            model: "m".to_string(),
+            messages: vec![],
+            tools: None,
+            tool_choice: None,
+            sampling: SamplingParams {
+                temperature: Some(0.0),
+                seed: Some(7),
+                ..Default::default()
+            },
+        };
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["temperature"], 0.0);
+        assert_eq!(value["seed"], 7);
+        assert!(value.get("top_p").is_none());
+        assert!(value.get("parallel_tool_calls").is_none());
+    }
+
+    #[test]
+    fn test_backend_error_classifies_context_overflow() {
+        let openai = r#"{"error":{"code":"context_length_exceeded","message":"This model's maximum context length is 128000 tokens."}}"#;
+        let err: anyhow::Error = BackendError::from_response(400, openai.to_string()).into();
+        assert!(is_context_overflow(&err));
+
+        let anthropic = r#"{"error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#;
+        let err: anyhow::Error = BackendError::from_response(400, anthropic.to_string()).into();
+        assert!(is_context_overflow(&err));
+
+        // Other 400s and server errors are not overflows
+        let err: anyhow::Error =
+            BackendError::from_response(400, "invalid tool schema".to_string()).into();
+        assert!(!is_context_overflow(&err));
+        let err: anyhow::Error =
+            BackendError::from_response(500, "context length".to_string()).into();
+        assert!(!is_context_overflow(&err));
+        assert!(!is_context_overflow(&anyhow!("unrelated")));
+    }
+
+    #[test]
+    fn test_backend_failure_excludes_request_errors() {
+        let failing = [
+            BackendError::from_response(503, String::new()),
+            BackendError::from_response(401, String::new()),
+            BackendError::Connection {
+                retries: 3,
+                message: "refused".to_string(),
+            },
+        ];
+        for err in failing {
+            assert!(is_backend_failure(&err.into()));
+        }
+
+        let not_failing = [
+            BackendError::from_response(400, "invalid tool schema".to_string()),
+            BackendError::from_response(400, "context_length_exceeded".to_string()),
+            BackendError::Cancelled,
+        ];
+        for err in not_failing {
+            assert!(!is_backend_failure(&err.into()));
+        }
+    }
+
+    #[test]
+    fn test_cancelled_call_returns_cancelled_error() {
+        let client = Client::new("http://127.0.0.1:9", SecretString::from("test"));
+        let request = ChatRequest {
+            model: "test".to_string(),
+            messages: vec![],
+            tools: None,
+            tool_choice: None,
+            sampling: SamplingParams::default(),
+        };
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let start = std::time::Instant::now();
+        let err = client.chat_with_cancel(&request, &cancel).unwrap_err();
+        assert!(is_cancelled(&err));
+        // Must not sit through the retry backoff
+        assert!(start.elapsed() < Duration::from_millis(INITIAL_BACKOFF_MS));
+    }
+
     #[test]
     fn test_is_retryable_status() {
         assert!(is_retryable_status(429));
@@ -2,31 +2,40 @@ mod agent;
 mod agent_impl;
 mod agent_service;
 mod backend;
+mod cascade;
 mod circuit_breaker;
+mod classifier;
 mod cli;
 mod commands;
 mod compact;
 mod config;
 mod context_factory;
 mod cost;
+mod events;
 mod gateway;
 mod gateway_client;
 mod hooks;
 mod llm;
 mod metrics;
 mod model_routing;
+mod persona;
 mod plan;
 mod policy;
 mod privacy;
 mod protocol;
 mod provider_health;
+mod rate_limit;
+mod redact;
 mod session;
 mod skillpacks;
+mod spans;
 mod subagent;
+mod tokens;
 mod tool_display;
 mod tool_filter;
 mod tools;
 mod transcript;
+mod usage;
 mod vendors;
 
 use anyhow::Result;
@@ -39,6 +48,16 @@ async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     let args = Args::parse();
 
+    if let Some(cli::Command::Usage(usage_args)) = &args.command {
+        let mut usage_args = usage_args.clone();
+        if usage_args.transcripts.is_none() {
+            usage_args.transcripts = args.transcripts_dir.clone();
+        }
+        let report = usage::report(&usage_args, &std::env::current_dir()?)?;
+        print!("{}", usage::render(&report, usage_args.format));
+        return Ok(());
+    }
+
     // Gateway client mode: connect to remote gateway instead of running locally
     if let Some(gateway_url) = &args.gateway {
         return gateway_client::run_gateway_mode(gateway_url, args.prompt.as_deref(), args.yes);
@@ -130,6 +149,18 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Apply CLI privacy level override
+    if let Some(level_str) = &args.privacy_level {
+        if let Some(level) = privacy::PrivacyLevel::parse(level_str) {
+            cfg.privacy.default_level = level;
+        } else {
+            return Err(anyhow::anyhow!(
+                "Invalid privacy level: {}. Use: standard, sensitive, strict",
+                level_str
+            ));
+        }
+    }
+
     // Add CLI permission rules
     cfg.permissions.allow.extend(args.allowed_tools.clone());
     cfg.permissions.deny.extend(args.disallowed_tools.clone());
@@ -151,6 +182,8 @@ async fn main() -> Result<()> {
     std::fs::create_dir_all(&transcripts_dir)?;
 
     let session_id = uuid::Uuid::new_v4().to_string();
+    // Mask secrets in tool results and transcripts from here on
+    redact::configure(&session_id, &cfg.privacy);
     let transcript_path = transcripts_dir.join(format!("{}.jsonl", session_id));
     let mut transcript = transcript::Transcript::new(&transcript_path, &session_id, &root)?;
 
@@ -208,6 +241,8 @@ async fn main() -> Result<()> {
         turn_counter: RefCell::new(0),
         command_index: RefCell::new(command_index),
         todo_state: RefCell::new(tools::todo::TodoState::new()),
+        token_counter: RefCell::new(tokens::TokenCounter::new()),
+        cancel: tokio_util::sync::CancellationToken::new(),
     };
 
     // Fire SessionStart hook
@@ -6,10 +6,21 @@
 //! - Circuit breaker trips
 //! - Token usage
 //! - Cost tracking
+//! - Tool executions and latency by tool
+//! - Policy decisions, yields and queue depth
+//!
+//! Each process records into its own global collector. The agent daemon
+//! serves its metrics over its socket (`metrics` method) and the gateway
+//! serves its own plus the daemon's at `GET /metrics`. Gateway-only series
+//! (yields, queue depth) and daemon-only series (LLM, tools, policy) don't
+//! overlap, so the combined text stays valid Prometheus exposition.
 
 #![allow(dead_code)]
 
-use prometheus::{CounterVec, Encoder, HistogramOpts, HistogramVec, Opts, Registry, TextEncoder};
+use prometheus::{
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -21,6 +32,10 @@ const BACKEND_LABEL: &str = "backend";
 const MODEL_LABEL: &str = "model";
 const STATUS_LABEL: &str = "status";
 const DIRECTION_LABEL: &str = "direction";
+const TOOL_LABEL: &str = "tool";
+const DECISION_LABEL: &str = "decision";
+const REASON_LABEL: &str = "reason";
+const QUEUE_LABEL: &str = "queue";
 
 /// Metrics collector for LLM operations
 pub struct MetricsCollector {
@@ -41,6 +56,24 @@ pub struct MetricsCollector {
     /// Total cost in USD by backend, model
     cost_usd_total: CounterVec,
 
+    /// Tool executions by tool, status
+    tool_calls_total: CounterVec,
+
+    /// Tool execution duration in milliseconds by tool
+    tool_duration_ms: HistogramVec,
+
+    /// Policy decisions by decision (allow/deny/ask)
+    policy_decisions_total: CounterVec,
+
+    /// Turns paused for approval, input or budget, by reason
+    yields_total: CounterVec,
+
+    /// Requests waiting or running, by queue
+    queue_depth: IntGaugeVec,
+
+    /// Turns currently running in the agent daemon
+    turns_in_flight: IntGauge,
+
     /// JSON export data (accumulated)
     json_data: Arc<RwLock<MetricsSnapshot>>,
 }
@@ -101,6 +134,65 @@ impl MetricsCollector {
             .register(Box::new(cost_usd_total.clone()))
             .expect("Failed to register cost counter");
 
+        // Tool executions counter
+        let tool_calls_opts = Opts::new("brainpro_tool_calls_total", "Total tool executions");
+        let tool_calls_total = CounterVec::new(tool_calls_opts, &[TOOL_LABEL, STATUS_LABEL])
+            .expect("Failed to create tool calls counter");
+        registry
+            .register(Box::new(tool_calls_total.clone()))
+            .expect("Failed to register tool calls counter");
+
+        // Tool duration histogram
+        let tool_duration_opts = HistogramOpts::new(
+            "brainpro_tool_duration_ms",
+            "Tool execution duration in milliseconds",
+        )
+        .buckets(vec![
+            5.0, 25.0, 100.0, 250.0, 1000.0, 5000.0, 15000.0, 60000.0, 300000.0,
+        ]);
+        let tool_duration_ms = HistogramVec::new(tool_duration_opts, &[TOOL_LABEL])
+            .expect("Failed to create tool duration histogram");
+        registry
+            .register(Box::new(tool_duration_ms.clone()))
+            .expect("Failed to register tool duration histogram");
+
+        // Policy decisions counter
+        let policy_opts = Opts::new(
+            "brainpro_policy_decisions_total",
+            "Total tool permission decisions",
+        );
+        let policy_decisions_total = CounterVec::new(policy_opts, &[DECISION_LABEL])
+            .expect("Failed to create policy counter");
+        registry
+            .register(Box::new(policy_decisions_total.clone()))
+            .expect("Failed to register policy counter");
+
+        // Yields counter
+        let yields_opts = Opts::new("brainpro_yields_total", "Total turns paused by yield");
+        let yields_total =
+            CounterVec::new(yields_opts, &[REASON_LABEL]).expect("Failed to create yields counter");
+        registry
+            .register(Box::new(yields_total.clone()))
+            .expect("Failed to register yields counter");
+
+        // Queue depth gauge
+        let queue_opts = Opts::new("brainpro_queue_depth", "Requests waiting or running");
+        let queue_depth = IntGaugeVec::new(queue_opts, &[QUEUE_LABEL])
+            .expect("Failed to create queue depth gauge");
+        registry
+            .register(Box::new(queue_depth.clone()))
+            .expect("Failed to register queue depth gauge");
+
+        // In-flight turns gauge
+        let turns_in_flight = IntGauge::new(
+            "brainpro_agent_turns_in_flight",
+            "Turns running in the agent daemon",
+        )
+        .expect("Failed to create in-flight gauge");
+        registry
+            .register(Box::new(turns_in_flight.clone()))
+            .expect("Failed to register in-flight gauge");
+
         Self {
             registry,
             requests_total,
@@ -108,6 +200,12 @@ impl MetricsCollector {
             circuit_trips_total,
             tokens_total,
             cost_usd_total,
+            tool_calls_total,
+            tool_duration_ms,
+            policy_decisions_total,
+            yields_total,
+            queue_depth,
+            turns_in_flight,
             json_data: Arc::new(RwLock::new(MetricsSnapshot::default())),
         }
     }
@@ -237,6 +335,56 @@ impl MetricsCollector {
         *data.cost_by_model.entry(model.to_string()).or_default() += cost_usd;
     }
 
+    /// Record a tool execution
+    pub fn record_tool_call(&self, tool: &str, ok: bool, duration_ms: u64) {
+        let status = if ok { "success" } else { "failure" };
+        self.tool_calls_total
+            .with_label_values(&[tool, status])
+            .inc();
+        self.tool_duration_ms
+            .with_label_values(&[tool])
+            .observe(duration_ms as f64);
+
+        // Update JSON data
+        let mut data = self.json_data.write().unwrap();
+        data.tool_calls += 1;
+        if !ok {
+            data.failed_tool_calls += 1;
+        }
+        *data.tool_calls_by_tool.entry(tool.to_string()).or_default() += 1;
+    }
+
+    /// Record a policy decision
+    pub fn record_policy_decision(&self, decision: &str) {
+        self.policy_decisions_total
+            .with_label_values(&[decision])
+            .inc();
+
+        let mut data = self.json_data.write().unwrap();
+        *data
+            .policy_decisions
+            .entry(decision.to_string())
+            .or_default() += 1;
+    }
+
+    /// Record a yielded turn
+    pub fn record_yield(&self, reason: &str) {
+        self.yields_total.with_label_values(&[reason]).inc();
+
+        let mut data = self.json_data.write().unwrap();
+        *data.yields.entry(reason.to_string()).or_default() += 1;
+    }
+
+    /// Count a request in `queue` until the guard is dropped
+    pub fn track_queue(&self, queue: &str) -> GaugeGuard {
+        GaugeGuard::new(self.queue_depth.with_label_values(&[queue]))
+    }
+
+    /// Count a running agent turn until the guard is dropped
+    pub fn track_turn(&self) -> GaugeGuard {
+        GaugeGuard::new(self.turns_in_flight.clone())
+    }
+
     /// Get Prometheus-formatted metrics
     pub fn prometheus_metrics(&self) -> String {
         let encoder = TextEncoder::new();
@@ -284,6 +432,22 @@ impl Default for MetricsCollector {
     }
 }
 
+/// Keeps a gauge incremented for as long as it lives
+pub struct GaugeGuard(IntGauge);
+
+impl GaugeGuard {
+    fn new(gauge: IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
 /// Snapshot of metrics for JSON export
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MetricsSnapshot {
@@ -317,6 +481,23 @@ pub struct MetricsSnapshot {
     pub circuit_trips: u64,
     /// Circuit trips by backend
     pub circuit_trips_by_backend: HashMap<String, u64>,
+
+    /// Tool executions
+    #[serde(default)]
+    pub tool_calls: u64,
+    /// Failed tool executions
+    #[serde(default)]
+    pub failed_tool_calls: u64,
+    /// Tool executions by tool
+    #[serde(default)]
+    pub tool_calls_by_tool: HashMap<String, u64>,
+
+    /// Policy decisions by decision
+    #[serde(default)]
+    pub policy_decisions: HashMap<String, u64>,
+    /// Yields by reason
+    #[serde(default)]
+    pub yields: HashMap<String, u64>,
 }
 
 impl MetricsSnapshot {
@@ -363,6 +544,31 @@ pub fn record_circuit_trip(backend: &str) {
     global().record_circuit_trip(backend);
 }
 
+/// Record a tool execution to global metrics
+pub fn record_tool_call(tool: &str, ok: bool, duration_ms: u64) {
+    global().record_tool_call(tool, ok, duration_ms);
+}
+
+/// Record a policy decision to global metrics
+pub fn record_policy_decision(decision: &str) {
+    global().record_policy_decision(decision);
+}
+
+/// Record a yielded turn to global metrics
+pub fn record_yield(reason: &str) {
+    global().record_yield(reason);
+}
+
+/// Count a request in `queue` of the global metrics until the guard drops
+pub fn track_queue(queue: &str) -> GaugeGuard {
+    global().track_queue(queue)
+}
+
+/// Count a running agent turn in the global metrics until the guard drops
+pub fn track_turn() -> GaugeGuard {
+    global().track_turn()
+}
+
 /// Get Prometheus metrics from global collector
 pub fn prometheus() -> String {
     global().prometheus_metrics()
@@ -405,6 +611,35 @@ mod tests {
         assert!(prom.contains("brainpro_tokens_total"));
     }
 
+    #[test]
+    fn test_tool_policy_and_queue_metrics() {
+        let collector = MetricsCollector::new();
+
+        collector.record_tool_call("Bash", true, 120);
+        collector.record_tool_call("Bash", false, 40);
+        collector.record_tool_call("Read", true, 2);
+        collector.record_policy_decision("ask");
+        collector.record_yield("awaiting_approval");
+
+        let snapshot = collector.json_snapshot();
+        assert_eq!(snapshot.tool_calls, 3);
+        assert_eq!(snapshot.failed_tool_calls, 1);
+        assert_eq!(snapshot.tool_calls_by_tool["Bash"], 2);
+        assert_eq!(snapshot.policy_decisions["ask"], 1);
+        assert_eq!(snapshot.yields["awaiting_approval"], 1);
+
+        {
+            let _first = collector.track_queue("main");
+            let _second = collector.track_queue("main");
+            let prom = collector.prometheus_metrics();
+            assert!(prom.contains("brainpro_queue_depth{queue=\"main\"} 2"));
+        }
+        let prom = collector.prometheus_metrics();
+        assert!(prom.contains("brainpro_queue_depth{queue=\"main\"} 0"));
+        assert!(prom.contains("brainpro_tool_duration_ms_bucket{tool=\"Bash\",le=\"250\"} 2"));
+        assert!(prom.contains("brainpro_policy_decisions_total{decision=\"ask\"} 1"));
+    }
+
     #[test]
     fn test_snapshot_timestamp() {
         let snapshot = MetricsSnapshot::default().with_timestamp();
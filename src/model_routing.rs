@@ -66,6 +66,33 @@ impl RouteCategory {
         }
     }
 
+    /// Keyword classification of a user prompt.
+    ///
+    /// Short questions are `Fast`; everything else goes through the same
+    /// keyword matching as agent names.
+    pub fn from_prompt(prompt: &str) -> Self {
+        let trimmed = prompt.trim();
+        let words = trimmed.split_whitespace().count();
+        if words > 0 && words <= 12 && trimmed.ends_with('?') {
+            return RouteCategory::Fast;
+        }
+        Self::from_agent_name(trimmed, "")
+    }
+
+    /// Parse a category name as produced by `as_str`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "planning" => Some(RouteCategory::Planning),
+            "coding" => Some(RouteCategory::Coding),
+            "exploration" => Some(RouteCategory::Exploration),
+            "testing" => Some(RouteCategory::Testing),
+            "documentation" => Some(RouteCategory::Documentation),
+            "fast" => Some(RouteCategory::Fast),
+            "default" => Some(RouteCategory::Default),
+            _ => None,
+        }
+    }
+
     /// Get the category name as a string
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -78,6 +105,15 @@ impl RouteCategory {
             RouteCategory::Default => "default",
         }
     }
+
+    /// Whether this category moves to cheaper models as the budget runs low.
+    /// Coding and planning stay on their routed model.
+    pub fn steps_down_on_budget(&self) -> bool {
+        matches!(
+            self,
+            RouteCategory::Exploration | RouteCategory::Testing | RouteCategory::Documentation
+        )
+    }
 }
 
 /// Cost tier for models
@@ -91,6 +127,31 @@ pub enum CostTier {
     Premium,
 }
 
+impl CostTier {
+    fn rank(&self) -> u8 {
+        match self {
+            CostTier::Low => 0,
+            CostTier::Medium => 1,
+            CostTier::High => 2,
+            CostTier::Premium => 3,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CostTier::Low => "low",
+            CostTier::Medium => "medium",
+            CostTier::High => "high",
+            CostTier::Premium => "premium",
+        }
+    }
+}
+
+/// Budget left at or below which step-down categories are capped at medium tier
+const BUDGET_CAP_MEDIUM: f64 = 0.5;
+/// Budget left at or below which step-down categories are capped at low tier
+const BUDGET_CAP_LOW: f64 = 0.2;
+
 /// Capabilities of a specific model
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModelCapabilities {
@@ -212,6 +273,49 @@ pub struct ModelRoutingConfig {
     pub routes: HashMap<RouteCategory, String>, // category -> target string
     #[serde(default)]
     pub model_caps: HashMap<String, ModelCapabilities>,
+    #[serde(default)]
+    pub cascade: CascadeConfig,
+    #[serde(default)]
+    pub classifier: ClassifierConfig,
+}
+
+/// LLM prompt classification for routing the main turn
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ClassifierConfig {
+    /// Ask a fast model to label each prompt before routing it
+    #[serde(default)]
+    pub enabled: bool,
+    /// Target that classifies (model@backend); defaults to the `fast` route
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// Cascade routing: start cheap, escalate on failure signals
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CascadeConfig {
+    /// Start turns and subagents on the first target of their category's chain
+    #[serde(default)]
+    pub enabled: bool,
+    /// Consecutive failed tool calls that trigger escalation
+    #[serde(default = "default_tool_error_threshold")]
+    pub tool_error_threshold: usize,
+    /// Escalation chain per category, cheapest first (model@backend)
+    #[serde(default)]
+    pub chains: HashMap<RouteCategory, Vec<String>>,
+}
+
+fn default_tool_error_threshold() -> usize {
+    3
+}
+
+impl Default for CascadeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tool_error_threshold: default_tool_error_threshold(),
+            chains: HashMap::new(),
+        }
+    }
 }
 
 /// Hardcoded default routes (sensible defaults)
@@ -272,6 +376,8 @@ pub struct RoutingContext {
     pub explicit_model: Option<String>,
     /// Backends that are currently unavailable
     pub unavailable_backends: Vec<String>,
+    /// Fraction of the tightest session/daily budget still unspent
+    pub budget_remaining: Option<f64>,
 }
 
 impl RoutingContext {
@@ -295,6 +401,14 @@ impl RoutingContext {
     }
 }
 
+/// A routing choice and the reason it was made
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteDecision {
+    pub target: Target,
+    pub category: RouteCategory,
+    pub reason: String,
+}
+
 /// Model router that resolves targets based on context
 pub struct ModelRouter {
     config: ModelRoutingConfig,
@@ -345,13 +459,7 @@ impl ModelRouter {
 
         // Check cost tier
         if let Some(max_tier) = ctx.max_cost_tier {
-            let tier_order = |t: CostTier| match t {
-                CostTier::Low => 0,
-                CostTier::Medium => 1,
-                CostTier::High => 2,
-                CostTier::Premium => 3,
-            };
-            if tier_order(caps.cost_tier) > tier_order(max_tier) {
+            if caps.cost_tier.rank() > max_tier.rank() {
                 return false;
             }
         }
@@ -364,6 +472,39 @@ impl ModelRouter {
         true
     }
 
+    /// Cost tier cap the remaining budget puts on `category`, if any
+    fn budget_tier_cap(category: RouteCategory, ctx: &RoutingContext) -> Option<CostTier> {
+        let remaining = ctx.budget_remaining?;
+        if !category.steps_down_on_budget() {
+            return None;
+        }
+        if remaining <= BUDGET_CAP_LOW {
+            Some(CostTier::Low)
+        } else if remaining <= BUDGET_CAP_MEDIUM {
+            Some(CostTier::Medium)
+        } else {
+            None
+        }
+    }
+
+    /// Apply the budget tier cap for `category` on top of any explicit limit
+    fn with_budget_cap(
+        category: RouteCategory,
+        ctx: &RoutingContext,
+    ) -> (RoutingContext, Option<CostTier>) {
+        let cap = Self::budget_tier_cap(category, ctx);
+        let max_cost_tier = match (ctx.max_cost_tier, cap) {
+            (Some(max), Some(cap)) if max.rank() < cap.rank() => Some(max),
+            (max, None) => max,
+            (_, cap) => cap,
+        };
+        let ctx = RoutingContext {
+            max_cost_tier,
+            ..ctx.clone()
+        };
+        (ctx, cap)
+    }
+
     /// Resolve target for a route category with context
     pub fn resolve_with_context(
         &self,
@@ -371,11 +512,56 @@ impl ModelRouter {
         ctx: &RoutingContext,
         fallback: &Target,
     ) -> Target {
+        self.decide(category, ctx, fallback).target
+    }
+
+    /// Resolve target for a route category, explaining the choice.
+    ///
+    /// As the budget runs low, step-down categories are capped at a cheaper
+    /// cost tier; when none of their routes fit, the most capable routed
+    /// target within the cap is used.
+    pub fn decide(
+        &self,
+        category: RouteCategory,
+        ctx: &RoutingContext,
+        fallback: &Target,
+    ) -> RouteDecision {
+        let (ctx, cap) = Self::with_budget_cap(category, ctx);
+
+        let budget_note = match (ctx.budget_remaining, cap) {
+            (Some(left), Some(cap)) => format!(
+                "; {:.0}% of budget left, capped at {} tier",
+                left * 100.0,
+                cap.as_str()
+            ),
+            (Some(left), None) if category.steps_down_on_budget() => {
+                format!("; {:.0}% of budget left", left * 100.0)
+            }
+            (Some(left), None) => format!(
+                "; {:.0}% of budget left, {} keeps its routed tier",
+                left * 100.0,
+                category.as_str()
+            ),
+            (None, _) => String::new(),
+        };
+        let decision = |target: Target, source: &str| RouteDecision {
+            reason: format!(
+                "{}: {} {} ({} tier){}",
+                category.as_str(),
+                source,
+                target,
+                self.get_capabilities(&target.model).cost_tier.as_str(),
+                budget_note
+            ),
+            target,
+            category,
+        };
+
         // Check for explicit model annotation first
         if let Some(ref annotation) = ctx.explicit_model {
             if let Some(target) = Target::parse(annotation) {
-                if self.meets_requirements(&target, ctx) {
-                    return target;
+                if self.meets_requirements(&target, &ctx) {
+                    return decision(target, "explicit target");
                 }
             }
         }
@@ -383,8 +569,8 @@ impl ModelRouter {
         // Check user config
         if let Some(target_str) = self.config.routes.get(&category) {
             if let Some(target) = Target::parse(target_str) {
-                if self.meets_requirements(&target, ctx) {
-                    return target;
+                if self.meets_requirements(&target, &ctx) {
+                    return decision(target, "configured route");
                 }
             }
         }
@@ -392,19 +578,70 @@ impl ModelRouter {
         // Check defaults
         if let Some(target_str) = self.defaults.get(&category) {
             if let Some(target) = Target::parse(target_str) {
-                if self.meets_requirements(&target, ctx) {
-                    return target;
+                if self.meets_requirements(&target, &ctx) {
+                    return decision(target, "default route");
                 }
             }
         }
 
+        // Under a budget cap, step down to the best routed target that fits
+        if cap.is_some() {
+            if let Some(target) = self.best_routed_target(&ctx) {
+                return decision(target, "stepped down to");
+            }
+        }
+
         // Fallback if it meets requirements
-        if self.meets_requirements(fallback, ctx) {
-            return fallback.clone();
+        if self.meets_requirements(fallback, &ctx) {
+            return decision(fallback.clone(), "fallback");
         }
 
         // Last resort: return fallback even if it doesn't meet all requirements
-        fallback.clone()
+        decision(fallback.clone(), "fallback (requirements not met)")
+    }
+
+    /// Most capable routed target that meets the context's requirements
+    fn best_routed_target(&self, ctx: &RoutingContext) -> Option<Target> {
+        let mut candidates = self.filter_available(&self.route_targets(), ctx);
+        candidates.sort_by_key(|t| {
+            (
+                std::cmp::Reverse(self.get_capabilities(&t.model).cost_tier.rank()),
+                t.to_string(),
+            )
+        });
+        candidates.into_iter().next()
+    }
+
+    /// Hold a target that wasn't routed (pinned, or chosen without a
+    /// classification) to the budget cap for `category`. Returns the step-down
+    /// decision when `target` is above the capped tier, `None` to keep it.
+    pub fn cap_for_budget(
+        &self,
+        category: RouteCategory,
+        ctx: &RoutingContext,
+        target: &Target,
+    ) -> Option<RouteDecision> {
+        let (ctx, cap) = Self::with_budget_cap(category, ctx);
+        let cap = cap?;
+        let tier = self.get_capabilities(&target.model).cost_tier;
+        if tier.rank() <= cap.rank() {
+            return None;
+        }
+        let stepped = self.best_routed_target(&ctx)?;
+        Some(RouteDecision {
+            reason: format!(
+                "{}: stepped down from {} ({} tier) to {} ({} tier); {:.0}% of budget left, capped at {} tier",
+                category.as_str(),
+                target,
+                tier.as_str(),
+                stepped,
+                self.get_capabilities(&stepped.model).cost_tier.as_str(),
+                ctx.budget_remaining.unwrap_or_default() * 100.0,
+                cap.as_str()
+            ),
+            target: stepped,
+            category,
+        })
     }
 
     /// Resolve target for a route category (simple version without context)
@@ -441,18 +678,86 @@ impl ModelRouter {
         ctx: &RoutingContext,
         fallback: &Target,
     ) -> Target {
+        self.decide_for_agent(
+            agent_name,
+            agent_description,
+            explicit_target,
+            ctx,
+            fallback,
+        )
+        .target
+    }
+
+    /// Resolve target for an agent spec with full routing context, explaining the choice
+    pub fn decide_for_agent(
+        &self,
+        agent_name: &str,
+        agent_description: &str,
+        explicit_target: Option<&str>,
+        ctx: &RoutingContext,
+        fallback: &Target,
+    ) -> RouteDecision {
         // Explicit target takes priority if it meets requirements
+        let category = RouteCategory::from_agent_name(agent_name, agent_description);
         if let Some(target_str) = explicit_target {
             if let Some(target) = Target::parse(target_str) {
-                if self.meets_requirements(&target, ctx) {
-                    return target;
+                let (capped, _) = Self::with_budget_cap(category, ctx);
+                if self.meets_requirements(&target, &capped) {
+                    return RouteDecision {
+                        reason: format!("{}: agent target {}", category.as_str(), target),
+                        target,
+                        category,
+                    };
                 }
             }
         }
 
         // Infer category and route with context
-        let category = RouteCategory::from_agent_name(agent_name, agent_description);
-        self.resolve_with_context(category, ctx, fallback)
+        self.decide(category, ctx, fallback)
+    }
+
+    /// All targets reachable through routing: configured routes, then defaults
+    pub fn route_targets(&self) -> Vec<Target> {
+        self.config
+            .routes
+            .values()
+            .chain(self.defaults.values())
+            .filter_map(|s| Target::parse(s))
+            .collect()
+    }
+
+    /// Find a routed target whose context window can hold `needed_tokens`.
+    ///
+    /// Used after a backend rejects a request for context length. Only targets
+    /// with a larger window than `current` are considered; the smallest window
+    /// that fits wins so we don't jump to the largest (often priciest) model.
+    pub fn resolve_larger_window(
+        &self,
+        current: &Target,
+        needed_tokens: usize,
+        ctx: &RoutingContext,
+    ) -> Option<Target> {
+        let current_window = self.get_capabilities(&current.model).context_window;
+        let ctx = RoutingContext {
+            estimated_tokens: Some(needed_tokens),
+            ..ctx.clone()
+        };
+
+        let mut candidates: Vec<Target> = self
+            .route_targets()
+            .into_iter()
+            .filter(|t| t != current)
+            .filter(|t| self.get_capabilities(&t.model).context_window > current_window)
+            .filter(|t| self.meets_requirements(t, &ctx))
+            .collect();
+        candidates.sort_by_key(|t| {
+            (
+                self.get_capabilities(&t.model).context_window,
+                t.to_string(),
+            )
+        });
+        candidates.dedup();
+        candidates.into_iter().next()
     }
 
     /// Filter targets by availability and requirements
@@ -581,4 +886,138 @@ mod tests {
         // Claude has ZDR
         assert!(caps.zdr || result.backend == "claude");
     }
+
+    #[test]
+    fn test_resolve_larger_window() {
+        let router = ModelRouter::new(ModelRoutingConfig::default());
+        let current = Target {
+            model: "gpt-4o-mini".to_string(),
+            backend: "chatgpt".to_string(),
+        };
+
+        let result = router
+            .resolve_larger_window(&current, 150000, &RoutingContext::default())
+            .expect("a larger window should be routable");
+        assert!(router.get_capabilities(&result.model).context_window >= 150000);
+
+        // Nothing routable holds a million tokens
+        assert!(router
+            .resolve_larger_window(&current, 1_000_000, &RoutingContext::default())
+            .is_none());
+    }
+
+    #[test]
+    fn test_budget_steps_down_exploration() {
+        let mut config = ModelRoutingConfig::default();
+        config
+            .routes
+            .insert(RouteCategory::Exploration, "gpt-4o@chatgpt".to_string());
+        let router = ModelRouter::new(config);
+        let fallback = Target {
+            model: "gpt-4o".to_string(),
+            backend: "chatgpt".to_string(),
+        };
+
+        let plenty = RoutingContext {
+            budget_remaining: Some(0.9),
+            ..Default::default()
+        };
+        let decision = router.decide(RouteCategory::Exploration, &plenty, &fallback);
+        assert_eq!(decision.target.model, "gpt-4o");
+
+        // Under half the budget left, the high-tier route is skipped
+        let low = RoutingContext {
+            budget_remaining: Some(0.4),
+            ..Default::default()
+        };
+        let decision = router.decide(RouteCategory::Exploration, &low, &fallback);
+        assert_eq!(decision.target.model, "gpt-4o-mini");
+        assert!(decision.reason.contains("capped at medium tier"));
+    }
+
+    #[test]
+    fn test_budget_keeps_coding_premium() {
+        let mut config = ModelRoutingConfig::default();
+        config
+            .routes
+            .insert(RouteCategory::Coding, "claude-3-opus@claude".to_string());
+        let router = ModelRouter::new(config);
+        let fallback = Target {
+            model: "gpt-4o-mini".to_string(),
+            backend: "chatgpt".to_string(),
+        };
+
+        let ctx = RoutingContext {
+            budget_remaining: Some(0.05),
+            ..Default::default()
+        };
+        let decision = router.decide(RouteCategory::Coding, &ctx, &fallback);
+        assert_eq!(decision.target.model, "claude-3-opus");
+        assert!(decision.reason.contains("coding keeps its routed tier"));
+    }
+
+    #[test]
+    fn test_budget_step_down_uses_best_routed_target() {
+        let mut config = ModelRoutingConfig::default();
+        config
+            .routes
+            .insert(RouteCategory::Testing, "claude-3-opus@claude".to_string());
+        let mut router = ModelRouter::new(config);
+        router
+            .defaults
+            .insert(RouteCategory::Testing, "gpt-4o@chatgpt".to_string());
+        let fallback = Target {
+            model: "claude-3-opus".to_string(),
+            backend: "claude".to_string(),
+        };
+
+        let ctx = RoutingContext {
+            budget_remaining: Some(0.3),
+            ..Default::default()
+        };
+        let decision = router.decide(RouteCategory::Testing, &ctx, &fallback);
+        let tier = router.get_capabilities(&decision.target.model).cost_tier;
+        assert_eq!(tier, CostTier::Medium);
+        assert!(decision.reason.contains("stepped down to"));
+    }
+
+    #[test]
+    fn test_budget_caps_unrouted_target() {
+        let mut router = ModelRouter::new(ModelRoutingConfig::default());
+        router
+            .defaults
+            .insert(RouteCategory::Testing, "gpt-4o@chatgpt".to_string());
+        let pinned = Target {
+            model: "claude-3-opus".to_string(),
+            backend: "claude".to_string(),
+        };
+
+        let plenty = RoutingContext {
+            budget_remaining: Some(0.9),
+            ..Default::default()
+        };
+        assert!(router
+            .cap_for_budget(RouteCategory::Testing, &plenty, &pinned)
+            .is_none());
+
+        let low = RoutingContext {
+            budget_remaining: Some(0.3),
+            ..Default::default()
+        };
+        let decision = router
+            .cap_for_budget(RouteCategory::Testing, &low, &pinned)
+            .expect("opus is above the medium cap");
+        assert_eq!(
+            router.get_capabilities(&decision.target.model).cost_tier,
+            CostTier::Medium
+        );
+        assert!(decision
+            .reason
+            .contains("stepped down from claude-3-opus@claude"));
+
+        // Coding keeps whatever it was given
+        assert!(router
+            .cap_for_budget(RouteCategory::Coding, &low, &pinned)
+            .is_none());
+    }
 }
@@ -5,6 +5,7 @@
 
 use crate::agent::core::AgentHooks;
 use crate::cli::Context;
+use crate::llm::SamplingParams;
 use crate::persona::loader::{self, PersonaConfig};
 use crate::persona::PromptContext;
 use crate::plan;
@@ -60,6 +61,10 @@ impl<'a> AgentHooks for PersonaHooks<'a> {
         system_prompt
     }
 
+    fn sampling(&self) -> SamplingParams {
+        self.config.sampling.clone()
+    }
+
     fn filter_tools(&self, schemas: Vec<Value>, in_planning_mode: bool) -> Vec<Value> {
         if in_planning_mode {
             // Only read-only tools in planning mode
@@ -9,6 +9,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::config::PermissionMode;
+use crate::llm::SamplingParams;
 use crate::persona::PromptContext;
 
 /// Parsed YAML frontmatter from a prompt section file
@@ -43,6 +44,8 @@ struct ManifestFrontmatter {
     default_tools: Vec<String>,
     #[serde(default = "default_permission_mode")]
     permission_mode: String,
+    #[serde(default)]
+    sampling: SamplingParams,
 }
 
 fn default_permission_mode() -> String {
@@ -57,6 +60,8 @@ pub struct PersonaConfig {
     pub description: String,
     pub default_tools: Vec<String>,
     pub permission_mode: PermissionMode,
+    /// Sampling overrides from the manifest's `sampling:` block
+    pub sampling: SamplingParams,
     pub sections: Vec<PromptSection>,
 }
 
@@ -211,6 +216,7 @@ pub fn load_persona(name: &str) -> Result<PersonaConfig> {
         description: manifest.description,
         default_tools: manifest.default_tools,
         permission_mode,
+        sampling: manifest.sampling,
         sections,
     })
 }
@@ -289,6 +295,23 @@ This is the body content."#;
         assert_eq!(body, "This is the body content.");
     }
 
+    #[test]
+    fn test_manifest_sampling() {
+        let content = r#"---
+name: planner
+description: Planning persona
+default_tools: [Read]
+sampling:
+  temperature: 0.8
+  top_p: 0.95
+---
+"#;
+        let (fm, _): (ManifestFrontmatter, String) = parse_frontmatter(content).unwrap();
+        assert_eq!(fm.sampling.temperature, Some(0.8));
+        assert_eq!(fm.sampling.top_p, Some(0.95));
+        assert!(fm.sampling.seed.is_none());
+    }
+
     #[test]
     fn test_render_template() {
         let ctx = PromptContext {
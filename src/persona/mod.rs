@@ -4,6 +4,8 @@
 //! - MrCode: Direct CLI, focused coding assistant with minimal toolset
 //! - MrBot: Gateway/Docker path, conversational bot with SOUL.md support
 
+#![allow(dead_code)]
+
 pub mod hooks;
 pub mod loader;
 pub mod mrbot;
@@ -87,6 +89,9 @@ pub trait Persona: Send + Sync {
     fn permission_mode(&self) -> PermissionMode;
 }
 
+/// Names of the built-in personas
+pub const NAMES: &[&str] = &["mrcode", "mrbot"];
+
 /// Get persona by name
 pub fn get_persona(name: &str) -> Option<Box<dyn Persona>> {
     match name.to_lowercase().as_str() {
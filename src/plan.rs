@@ -368,7 +368,7 @@ pub fn list_plans(root: &Path) -> Result<Vec<PlanMetadata>> {
     }
 
     // Sort by creation date, newest first
-    plans.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    plans.sort_by_key(|b| std::cmp::Reverse(b.created_at));
     Ok(plans)
 }
 
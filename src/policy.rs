@@ -4,8 +4,11 @@
 //! and three modes: Default, AcceptEdits, and BypassPermissions.
 
 use crate::config::{PermissionMode, PermissionsConfig};
+use crate::metrics;
+use crate::spans;
 use serde_json::Value;
 use std::io::{self, Write};
+use std::time::Instant;
 
 /// Permission decision result
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +18,16 @@ pub enum Decision {
     Ask,
 }
 
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Allow => "allow",
+            Decision::Deny => "deny",
+            Decision::Ask => "ask",
+        }
+    }
+}
+
 /// Tool category for default behavior
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolCategory {
@@ -34,6 +47,7 @@ impl ToolCategory {
             "Write" | "Edit" => ToolCategory::Mutation,
             "Bash" => ToolCategory::Execution,
             _ if name.starts_with("mcp.") => ToolCategory::Execution, // MCP tools require permission
+            _ if name.starts_with("node.") => ToolCategory::Execution, // Tools on gateway nodes
             _ => ToolCategory::Execution, // Unknown tools require permission
         }
     }
@@ -100,6 +114,41 @@ impl PolicyEngine {
         }
     }
 
+    /// Allow rule covering calls like this one, for "approve similar".
+    /// Bash commands generalize to their program and subcommand
+    /// (`Bash(cargo test:*)`), file tools to the exact path and other tools
+    /// to their name. Returns None when no rule would be narrower than
+    /// allowing the whole tool.
+    pub fn suggest_rule(tool: &str, args: &Value) -> Option<String> {
+        let arg = Self::extract_tool_arg(tool, args);
+        match tool {
+            "Bash" => {
+                let command = arg?;
+                let command = command.trim();
+                // Compound commands only ever get an exact rule
+                if command.contains(['&', '|', ';', '>', '<', '`', '$', '\n']) {
+                    return Some(format!("Bash({})", command));
+                }
+                let mut words = command.split_whitespace();
+                let program = words.next()?;
+                let prefix = match words.next() {
+                    Some(sub)
+                        if sub
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                            && !sub.starts_with('-') =>
+                    {
+                        format!("{} {}", program, sub)
+                    }
+                    _ => program.to_string(),
+                };
+                Some(format!("Bash({}:*)", prefix))
+            }
+            "Write" | "Edit" | "Read" => arg.map(|path| format!("{}({})", tool, path)),
+            _ => Some(tool.to_string()),
+        }
+    }
+
     /// Check if a rule pattern matches a tool call
     /// Pattern format: "ToolName" or "ToolName(prefix:*)" or "mcp.*" or "mcp.server.*"
     fn rule_matches(pattern: &str, tool: &str, arg: Option<&str>) -> bool {
@@ -109,6 +158,21 @@ impl PolicyEngine {
     /// Determine the permission decision for a tool call
     /// Returns (Decision, Option<matched_rule>)
     pub fn decide(&self, tool: &str, args: &Value) -> (Decision, Option<String>) {
+        let started = Instant::now();
+        let (decision, rule) = self.evaluate(tool, args);
+        metrics::record_policy_decision(decision.as_str());
+        let mut attributes = vec![
+            ("tool.name", tool.into()),
+            ("policy.decision", decision.as_str().into()),
+        ];
+        if let Some(rule) = &rule {
+            attributes.push(("policy.rule", rule.as_str().into()));
+        }
+        spans::record("policy", started, attributes, None);
+        (decision, rule)
+    }
+
+    fn evaluate(&self, tool: &str, args: &Value) -> (Decision, Option<String>) {
         let arg = Self::extract_tool_arg(tool, args);
         let arg_ref = arg.as_deref();
 
@@ -126,21 +190,28 @@ impl PolicyEngine {
             }
         }
 
-        // 3. Check ask rules
+        // 3. Check operator-approved rules, which answered an ask already
+        for rule in &self.config.approved {
+            if crate::tool_filter::tool_allows(tool, rule, arg_ref) {
+                return (Decision::Allow, Some(rule.clone()));
+            }
+        }
+
+        // 4. Check ask rules
         for rule in &self.config.ask {
             if Self::rule_matches(rule, tool, arg_ref) {
                 return (Decision::Ask, Some(rule.clone()));
             }
         }
 
-        // 4. Check allow rules
+        // 5. Check allow rules
         for rule in &self.config.allow {
             if Self::rule_matches(rule, tool, arg_ref) {
                 return (Decision::Allow, Some(rule.clone()));
             }
         }
 
-        // 5. Apply mode-based defaults
+        // 6. Apply mode-based defaults
         let decision = match self.config.mode {
             PermissionMode::BypassPermissions => Decision::Allow,
             PermissionMode::AcceptEdits => match ToolCategory::from_tool_name(tool) {
@@ -247,6 +318,30 @@ mod tests {
         PolicyEngine::new(PermissionsConfig::default(), false, false)
     }
 
+    #[test]
+    fn test_suggest_rule() {
+        let bash =
+            |command: &str| PolicyEngine::suggest_rule("Bash", &json!({ "command": command }));
+        assert_eq!(
+            bash("cargo test --workspace").as_deref(),
+            Some("Bash(cargo test:*)")
+        );
+        assert_eq!(bash("ls -la").as_deref(), Some("Bash(ls:*)"));
+        assert_eq!(
+            bash("cargo test && rm -rf target").as_deref(),
+            Some("Bash(cargo test && rm -rf target)")
+        );
+        assert_eq!(bash("  "), None);
+        assert_eq!(
+            PolicyEngine::suggest_rule("Edit", &json!({ "path": "src/lib.rs" })).as_deref(),
+            Some("Edit(src/lib.rs)")
+        );
+        assert_eq!(
+            PolicyEngine::suggest_rule("mcp.github.list_prs", &json!({})).as_deref(),
+            Some("mcp.github.list_prs")
+        );
+    }
+
     #[test]
     fn test_tool_category() {
         assert_eq!(ToolCategory::from_tool_name("Read"), ToolCategory::ReadOnly);
@@ -387,6 +482,42 @@ mod tests {
         assert_eq!(decision, Decision::Ask);
     }
 
+    #[test]
+    fn test_approved_rule_overrides_ask() {
+        let mut config = PermissionsConfig::default();
+        config.ask.push("Bash(git push:*)".to_string());
+        config.approved.push("Bash(git push:*)".to_string());
+        config.deny.push("Bash(git push --force:*)".to_string());
+        let engine = PolicyEngine::new(config, false, false);
+
+        let (decision, rule) = engine.decide("Bash", &json!({"command": "git push origin main"}));
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(rule.as_deref(), Some("Bash(git push:*)"));
+
+        // Deny rules still win, and the approval only covers what it names
+        let (decision, _) = engine.decide("Bash", &json!({"command": "git push --force"}));
+        assert_eq!(decision, Decision::Deny);
+        let (decision, _) = engine.decide("Bash", &json!({"command": "git push; rm -rf ."}));
+        assert_eq!(decision, Decision::Ask);
+    }
+
+    #[test]
+    fn test_only_approved_rules_match_strictly() {
+        let mut config = PermissionsConfig::default();
+        config.allow.push("Bash(cargo:*)".to_string());
+        config.approved.push("Bash(ls:*)".to_string());
+        let engine = PolicyEngine::new(config, false, false);
+
+        // Configured allow rules keep plain prefix matching
+        let (decision, _) = engine.decide("Bash", &json!({"command": "cargo test && echo ok"}));
+        assert_eq!(decision, Decision::Allow);
+
+        let (decision, _) = engine.decide("Bash", &json!({"command": "ls -la"}));
+        assert_eq!(decision, Decision::Allow);
+        let (decision, _) = engine.decide("Bash", &json!({"command": "lsof -i"}));
+        assert_eq!(decision, Decision::Ask);
+    }
+
     #[test]
     fn test_deny_rule_highest_priority() {
         let mut config = PermissionsConfig::default();
@@ -5,12 +5,18 @@
 //! - Prompt scanning for sensitive patterns
 //! - Auto-escalation to Strict when sensitive data detected
 //! - ZDR-aware provider filtering
+//! - Persistent audit of sensitive data sent to non-ZDR providers
 
 #![allow(dead_code)]
 
+use crate::redact;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 /// Privacy level for a request
@@ -75,6 +81,14 @@ pub struct PrivacyConfig {
     /// Prefer local provider for sensitive data
     #[serde(default)]
     pub prefer_local_for_sensitive: bool,
+
+    /// Mask secrets in tool results and transcripts (see `redact`)
+    #[serde(default = "default_redact_secrets")]
+    pub redact_secrets: bool,
+}
+
+fn default_redact_secrets() -> bool {
+    true
 }
 
 fn default_strict_patterns() -> Vec<String> {
@@ -101,6 +115,7 @@ impl Default for PrivacyConfig {
             strict_patterns: default_strict_patterns(),
             audit_zdr_violations: true,
             prefer_local_for_sensitive: true,
+            redact_secrets: default_redact_secrets(),
         }
     }
 }
@@ -192,6 +207,15 @@ impl PrivacyScanner {
         }
     }
 
+    /// Scan the conversation part of an outgoing request.
+    ///
+    /// System messages are skipped since they are ours; user messages, tool
+    /// call arguments and tool results (which carry file contents) are scanned.
+    /// Redaction placeholders are ignored, as the secrets behind them are not sent.
+    pub fn scan_messages(&self, messages: &[Value]) -> PrivacyScanResult {
+        self.scan(&redact::strip_placeholders(&request_text(messages)))
+    }
+
     /// Check if a backend is acceptable for a given privacy level
     pub fn is_backend_acceptable(&self, backend_zdr: bool, level: PrivacyLevel) -> bool {
         match level {
@@ -207,8 +231,35 @@ impl PrivacyScanner {
     }
 }
 
+/// Collect the text of non-system messages, including tool call arguments
+pub fn request_text(messages: &[Value]) -> String {
+    let mut text = String::new();
+    for msg in messages {
+        if msg.get("role").and_then(|r| r.as_str()) == Some("system") {
+            continue;
+        }
+        if let Some(content) = msg.get("content").and_then(|c| c.as_str()) {
+            text.push_str(content);
+            text.push('\n');
+        }
+        if let Some(calls) = msg.get("tool_calls").and_then(|c| c.as_array()) {
+            for call in calls {
+                if let Some(args) = call
+                    .get("function")
+                    .and_then(|f| f.get("arguments"))
+                    .and_then(|a| a.as_str())
+                {
+                    text.push_str(args);
+                    text.push('\n');
+                }
+            }
+        }
+    }
+    text
+}
+
 /// ZDR violation audit record
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ZdrViolation {
     pub timestamp: u64,
     pub privacy_level: PrivacyLevel,
@@ -217,13 +268,49 @@ pub struct ZdrViolation {
     pub matched_patterns: Vec<String>,
 }
 
+/// Default audit file (~/.brainpro/privacy_audit.jsonl)
+pub fn audit_log_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".brainpro").join("privacy_audit.jsonl"))
+}
+
 /// Audit log for ZDR violations
 #[derive(Debug, Default)]
 pub struct PrivacyAuditLog {
     violations: Vec<ZdrViolation>,
+    /// JSONL file violations are appended to, if persistent
+    path: Option<PathBuf>,
 }
 
 impl PrivacyAuditLog {
+    /// Create an audit log that also appends each violation to `path`
+    pub fn persistent(path: PathBuf) -> Self {
+        Self {
+            violations: Vec::new(),
+            path: Some(path),
+        }
+    }
+
+    /// Read violations previously written to an audit file.
+    /// A missing file means no violations; malformed lines are skipped.
+    pub fn load(path: &Path) -> Vec<ZdrViolation> {
+        std::fs::read_to_string(path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn append(path: &Path, violation: &ZdrViolation) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(violation)?)
+    }
+
     /// Record a potential ZDR violation
     pub fn record_violation(
         &mut self,
@@ -239,13 +326,19 @@ impl PrivacyAuditLog {
                 .unwrap_or_default()
                 .as_secs();
 
-            self.violations.push(ZdrViolation {
+            let violation = ZdrViolation {
                 timestamp,
                 privacy_level,
                 backend: backend.to_string(),
                 backend_has_zdr,
                 matched_patterns,
-            });
+            };
+            if let Some(path) = &self.path {
+                if let Err(e) = Self::append(path, &violation) {
+                    eprintln!("[privacy:audit] Failed to write {}: {}", path.display(), e);
+                }
+            }
+            self.violations.push(violation);
 
             eprintln!(
                 "[privacy:audit] ZDR violation: {} data sent to non-ZDR backend '{}'",
@@ -422,4 +515,47 @@ mod tests {
         audit.record_violation(PrivacyLevel::Strict, "claude", true, vec![]);
         assert_eq!(audit.violations().len(), 1);
     }
+
+    #[test]
+    fn test_audit_log_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let mut audit = PrivacyAuditLog::persistent(path.clone());
+        audit.record_violation(PrivacyLevel::Standard, "chatgpt", false, vec![]);
+        audit.record_violation(
+            PrivacyLevel::Strict,
+            "chatgpt",
+            false,
+            vec!["password".to_string()],
+        );
+
+        let loaded = PrivacyAuditLog::load(&path);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].privacy_level, PrivacyLevel::Strict);
+        assert_eq!(loaded[0].matched_patterns, vec!["password".to_string()]);
+    }
+
+    #[test]
+    fn test_scan_messages_skips_system_prompt() {
+        let scanner = PrivacyScanner::new(PrivacyConfig::default());
+        let messages = vec![
+            serde_json::json!({"role": "system", "content": "Never print a password"}),
+            serde_json::json!({"role": "user", "content": "Refactor main.rs"}),
+        ];
+        assert!(!scanner.scan_messages(&messages).sensitive_detected);
+
+        let messages = vec![
+            serde_json::json!({"role": "user", "content": "Read the config"}),
+            serde_json::json!({"role": "tool", "tool_call_id": "1", "content": "DB_PASSWORD=hunter2"}),
+        ];
+        assert!(scanner.scan_messages(&messages).sensitive_detected);
+
+        let messages = vec![serde_json::json!({
+            "role": "tool",
+            "tool_call_id": "1",
+            "content": "GH=[[REDACTED:github_token:1]]"
+        })];
+        assert!(!scanner.scan_messages(&messages).sensitive_detected);
+    }
 }
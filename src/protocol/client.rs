@@ -20,6 +20,9 @@ pub struct ClientCapabilities {
     /// Tools this client can execute (for nodes)
     #[serde(default)]
     pub tools: Vec<String>,
+    /// Schemas for the tools above, so the agent knows how to call them
+    #[serde(default)]
+    pub tool_schemas: Vec<ToolSchema>,
     /// Supported protocol version
     #[serde(default = "default_protocol_version")]
     pub protocol_version: u32,
@@ -29,6 +32,21 @@ fn default_protocol_version() -> u32 {
     1
 }
 
+/// Schema of a tool a node executes, in the shape of an LLM function definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON Schema for the tool's arguments
+    #[serde(default = "default_tool_parameters")]
+    pub parameters: Value,
+}
+
+fn default_tool_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
 /// Handshake: Client hello
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hello {
@@ -45,9 +63,17 @@ pub struct Challenge {
 }
 
 /// Handshake: Client auth response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Auth {
+    /// HMAC-SHA256 of the challenge keyed with the shared gateway token
+    #[serde(default)]
     pub signature: String,
+    /// Ed25519 public key of the device (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_key: Option<String>,
+    /// Device key signature over the challenge (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_signature: Option<String>,
 }
 
 /// Handshake: Server welcome
@@ -104,6 +130,9 @@ pub struct ClientEvent {
     pub data: Value,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// Position in the session's event log, for replay after a reconnect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
 }
 
 /// Incoming WebSocket message types (can be any of the above)
@@ -146,21 +175,30 @@ pub struct EventPayload {
     pub data: Value,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// Position in the session's event log, for replay after a reconnect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
 }
 
 /// Method names for client requests
 pub mod methods {
     pub const CHAT_SEND: &str = "chat.send";
+    pub const CHAT_CANCEL: &str = "chat.cancel";
     pub const SESSION_CREATE: &str = "session.create";
     pub const SESSION_LIST: &str = "session.list";
     pub const SESSION_GET: &str = "session.get";
+    pub const SESSION_ATTACH: &str = "session.attach";
     pub const TOOL_APPROVE: &str = "tool.approve";
     pub const TURN_RESUME: &str = "turn.resume";
     pub const CRON_ADD: &str = "cron.add";
     pub const CRON_REMOVE: &str = "cron.remove";
     pub const CRON_LIST: &str = "cron.list";
     pub const DEVICE_PAIR: &str = "device.pair";
+    pub const DEVICE_LIST: &str = "device.list";
+    pub const DEVICE_REVOKE: &str = "device.revoke";
     pub const HEALTH_STATUS: &str = "health.status";
+    /// Gateway → node: run one of the node's tools
+    pub const NODE_INVOKE: &str = "node.invoke";
 }
 
 /// Event names for server push
@@ -174,9 +212,14 @@ pub mod events {
     pub const AGENT_ERROR: &str = "agent.error";
     pub const AGENT_AWAITING_APPROVAL: &str = "agent.awaiting_approval";
     pub const AGENT_AWAITING_INPUT: &str = "agent.awaiting_input";
+    pub const AGENT_BUDGET_EXCEEDED: &str = "agent.budget_exceeded";
     pub const PRESENCE_UPDATE: &str = "presence.update";
+    pub const APPROVAL_RESOLVED: &str = "approval.resolved";
     pub const HEALTH_TICK: &str = "health.tick";
     pub const CRON_FIRED: &str = "cron.fired";
+    pub const DEVICE_PAIR_REQUESTED: &str = "device.pair_requested";
+    pub const QUEUE_ENQUEUED: &str = "queue.enqueued";
+    pub const QUEUE_DEQUEUED: &str = "queue.dequeued";
 }
 
 impl ClientRequest {
@@ -222,6 +265,7 @@ impl ClientEvent {
             event: event.to_string(),
             data,
             session_id,
+            seq: None,
         }
     }
 }
@@ -19,7 +19,7 @@ pub struct AgentRequest {
     /// Target model@backend
     #[serde(default)]
     pub target: Option<String>,
-    /// Tool schemas to provide
+    /// Node tool schemas to offer alongside the built-in tools
     #[serde(default)]
     pub tools: Option<Vec<Value>>,
     /// Working directory for tool execution
@@ -28,10 +28,20 @@ pub struct AgentRequest {
     /// Data for resuming a yielded turn
     #[serde(default)]
     pub resume_data: Option<ResumeData>,
+    /// Persona to run the turn as, instead of the daemon's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    /// Extra allow rules for this turn, from approvals scoped to the session
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_rules: Vec<String>,
+    /// Keep no history for the session; the caller sends the whole
+    /// conversation with every turn
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ephemeral: bool,
 }
 
 /// Data for resuming a yielded turn
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResumeData {
     /// Turn ID being resumed
     pub turn_id: String,
@@ -40,9 +50,21 @@ pub struct ResumeData {
     /// Whether the tool was approved (for awaiting_approval)
     #[serde(default)]
     pub approved: Option<bool>,
+    /// Arguments the operator edited before approving (for awaiting_approval)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_args: Option<Value>,
+    /// Allow rule to save to project permissions with the approval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_rule: Option<String>,
     /// User's answers (for awaiting_input)
     #[serde(default)]
     pub answers: Option<Value>,
+    /// Result of a tool the gateway ran on a node (for node_tool)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// How long the node took to run the tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
 }
 
 /// Reason for yielding a turn
@@ -53,6 +75,10 @@ pub enum YieldReason {
     AwaitingApproval,
     /// Waiting for user input (AskUserQuestion)
     AwaitingInput,
+    /// Waiting for approval to keep spending past a cost budget
+    BudgetExceeded,
+    /// Waiting for the gateway to run a tool on a node client
+    NodeTool,
 }
 
 /// Methods the agent can execute
@@ -63,10 +89,14 @@ pub enum AgentMethod {
     RunTurn,
     /// Resume a yielded turn
     ResumeTurn,
-    /// Cancel an in-flight request
+    /// Cancel the session's in-flight turns
     Cancel,
     /// Health check
     Ping,
+    /// Prometheus metrics of the agent daemon
+    Metrics,
+    /// Stored conversation history of a session
+    SessionGet,
 }
 
 /// Streaming response events from Agent to Gateway (NDJSON)
@@ -128,6 +158,14 @@ pub enum AgentEventType {
     Error { code: String, message: String },
     /// Pong response to ping
     Pong,
+    /// Prometheus text exposition of the agent daemon's metrics
+    Metrics { text: String },
+    /// Stored conversation history of a session
+    SessionHistory {
+        session_id: String,
+        messages: Vec<Value>,
+        turn_count: u32,
+    },
 }
 
 /// Token usage statistics
@@ -136,6 +174,9 @@ pub struct UsageStats {
     pub input_tokens: u64,
     pub output_tokens: u64,
     pub tool_uses: u64,
+    /// Cost of the request's LLM calls
+    #[serde(default)]
+    pub cost_usd: f64,
 }
 
 impl AgentEvent {
@@ -231,6 +272,29 @@ impl AgentEvent {
         }
     }
 
+    pub fn metrics(id: &str, text: String) -> Self {
+        Self {
+            id: id.to_string(),
+            event: AgentEventType::Metrics { text },
+        }
+    }
+
+    pub fn session_history(
+        id: &str,
+        session_id: &str,
+        messages: Vec<Value>,
+        turn_count: u32,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            event: AgentEventType::SessionHistory {
+                session_id: session_id.to_string(),
+                messages,
+                turn_count,
+            },
+        }
+    }
+
     pub fn yield_approval(
         id: &str,
         turn_id: &str,
@@ -268,6 +332,42 @@ impl AgentEvent {
         }
     }
 
+    pub fn yield_node_tool(
+        id: &str,
+        turn_id: &str,
+        tool_call_id: &str,
+        tool_name: &str,
+        tool_args: Value,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            event: AgentEventType::Yield {
+                turn_id: turn_id.to_string(),
+                reason: YieldReason::NodeTool,
+                tool_call_id: tool_call_id.to_string(),
+                tool_name: tool_name.to_string(),
+                tool_args,
+                questions: None,
+                policy_rule: None,
+            },
+        }
+    }
+
+    pub fn yield_budget(id: &str, turn_id: &str, tool_call_id: &str, budget: Value) -> Self {
+        Self {
+            id: id.to_string(),
+            event: AgentEventType::Yield {
+                turn_id: turn_id.to_string(),
+                reason: YieldReason::BudgetExceeded,
+                tool_call_id: tool_call_id.to_string(),
+                tool_name: "CostBudget".to_string(),
+                tool_args: budget,
+                questions: None,
+                policy_rule: None,
+            },
+        }
+    }
+
     /// Serialize to NDJSON line (with trailing newline)
     pub fn to_ndjson(&self) -> String {
         let mut json = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
@@ -298,10 +398,44 @@ impl AgentRequest {
             tools: None,
             working_dir: None,
             resume_data: None,
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
         }
     }
 
-    /// Create a cancel request
+    /// Run the turn as `persona` instead of the daemon's default
+    pub fn with_persona(mut self, persona: Option<String>) -> Self {
+        self.persona = persona;
+        self
+    }
+
+    /// Offer the agent extra tool schemas (node tools) for this turn
+    pub fn with_tools(mut self, tools: Vec<Value>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Allow tools matching `rules` for this turn on top of the configured
+    /// permissions
+    pub fn with_allow_rules(mut self, rules: Vec<String>) -> Self {
+        self.allow_rules = rules;
+        self
+    }
+
+    /// Don't save the session's history after the turn
+    pub fn ephemeral(mut self) -> Self {
+        self.ephemeral = true;
+        self
+    }
+
+    /// Run the turn's tools in `working_dir`
+    pub fn with_working_dir(mut self, working_dir: Option<String>) -> Self {
+        self.working_dir = working_dir;
+        self
+    }
+
+    /// Create a request that cancels every in-flight turn of a session
     pub fn cancel(id: &str, session_id: &str) -> Self {
         Self {
             id: id.to_string(),
@@ -312,6 +446,9 @@ impl AgentRequest {
             tools: None,
             working_dir: None,
             resume_data: None,
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
         }
     }
 
@@ -326,6 +463,43 @@ impl AgentRequest {
             tools: None,
             working_dir: None,
             resume_data: None,
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
+        }
+    }
+
+    /// Create a metrics request
+    pub fn metrics(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            method: AgentMethod::Metrics,
+            session_id: String::new(),
+            messages: Vec::new(),
+            target: None,
+            tools: None,
+            working_dir: None,
+            resume_data: None,
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
+        }
+    }
+
+    /// Create a session_get request
+    pub fn session_get(id: &str, session_id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            method: AgentMethod::SessionGet,
+            session_id: session_id.to_string(),
+            messages: Vec::new(),
+            target: None,
+            tools: None,
+            working_dir: None,
+            resume_data: None,
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
         }
     }
 
@@ -340,6 +514,9 @@ impl AgentRequest {
             tools: None,
             working_dir: None,
             resume_data: Some(resume_data),
+            persona: None,
+            allow_rules: Vec::new(),
+            ephemeral: false,
         }
     }
 }
@@ -371,6 +548,7 @@ mod tests {
             input_tokens: 100,
             output_tokens: 50,
             tool_uses: 3,
+            cost_usd: 0.01,
         };
         let event = AgentEvent::done("req-1", usage);
         let json = event.to_ndjson();
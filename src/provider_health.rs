@@ -8,10 +8,12 @@
 
 #![allow(dead_code)]
 
-use crate::circuit_breaker::{CircuitBreakerRegistry, CircuitState};
+use crate::circuit_breaker::{
+    CircuitBreakerConfig, CircuitBreakerDecision, CircuitBreakerRegistry, CircuitState,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 
 /// Health state of a provider
@@ -304,6 +306,25 @@ impl ProviderHealthRegistry {
         }
     }
 
+    /// Decide whether a request to a backend may be sent right now.
+    ///
+    /// Unlike `is_available`, this asks the circuit breaker for a decision, so
+    /// an open circuit whose recovery timeout has passed lets a probe through.
+    /// A backend in cooldown is refused until the cooldown expires.
+    pub fn admit(&self, backend: &str) -> bool {
+        if let Some(cb) = &self.circuit_breakers {
+            if cb.check(backend) == CircuitBreakerDecision::Reject {
+                return false;
+            }
+        }
+
+        let providers = self.providers.read().unwrap();
+        !providers
+            .get(backend)
+            .map(|s| s.is_in_cooldown())
+            .unwrap_or(false)
+    }
+
     /// Get list of available backends from a list of candidates
     pub fn filter_available(&self, backends: &[String]) -> Vec<String> {
         backends
@@ -338,6 +359,21 @@ impl Default for ProviderHealthRegistry {
     }
 }
 
+static GLOBAL: OnceLock<Arc<ProviderHealthRegistry>> = OnceLock::new();
+
+/// Get the process-wide health registry, backed by one circuit breaker per backend.
+///
+/// The first caller's config wins; every LLM call in the process shares it.
+pub fn global(
+    health: &HealthConfig,
+    breakers: &CircuitBreakerConfig,
+) -> Arc<ProviderHealthRegistry> {
+    Arc::clone(GLOBAL.get_or_init(|| {
+        let cb = Arc::new(CircuitBreakerRegistry::new(breakers.clone()));
+        Arc::new(ProviderHealthRegistry::new(health.clone()).with_circuit_breakers(cb))
+    }))
+}
+
 /// Combined status for a provider
 #[derive(Debug, Clone, Serialize)]
 pub struct ProviderStatus {
@@ -426,6 +462,43 @@ mod tests {
         assert_eq!(registry.get_health("test"), HealthState::Degraded);
     }
 
+    #[test]
+    fn test_admit_follows_circuit_breaker() {
+        let cb = Arc::new(CircuitBreakerRegistry::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            recovery_timeout_secs: 0,
+            ..Default::default()
+        }));
+        let registry = ProviderHealthRegistry::new(HealthConfig {
+            unhealthy_failure_count: 10,
+            ..Default::default()
+        })
+        .with_circuit_breakers(cb.clone());
+
+        registry.record_failure("test");
+        assert!(registry.admit("test"));
+        registry.record_failure("test");
+        assert!(cb.is_open("test"));
+
+        // Recovery timeout of zero lets the next request through as a probe
+        assert!(registry.admit("test"));
+        assert_eq!(cb.stats("test").unwrap().state, CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn test_admit_refuses_during_cooldown() {
+        let registry = ProviderHealthRegistry::new(HealthConfig {
+            unhealthy_failure_count: 2,
+            cooldown_secs: 60,
+            ..Default::default()
+        });
+
+        registry.record_failure("test");
+        assert!(registry.admit("test"));
+        registry.record_failure("test");
+        assert!(!registry.admit("test"));
+    }
+
     #[test]
     fn test_filter_available() {
         let config = HealthConfig {
@@ -0,0 +1,405 @@
+//! Client-side rate limiting and provider rate-limit header parsing.
+//!
+//! Each backend can be given `requests_per_minute` and `tokens_per_minute`
+//! budgets. One limiter per backend is shared process-wide, so the main
+//! agent, parallel subagents and compaction calls all draw from the same
+//! buckets. Provider `Retry-After` and `x-ratelimit-*` headers feed back into
+//! the limiter so we stop sending before the provider starts rejecting.
+
+#![allow(dead_code)]
+
+use crate::events::{self, Event};
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Longest wait we honor from a provider header
+const MAX_HEADER_WAIT: Duration = Duration::from_secs(300);
+
+/// Token bucket that refills continuously.
+///
+/// `reserve` always succeeds and may drive the balance negative; the caller
+/// then waits out the returned delay. This keeps concurrent callers in FIFO
+/// order without a separate queue.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32, now: Instant) -> Self {
+        let capacity = limit.max(1) as f64;
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec: capacity / 60.0,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Take `amount` units and return how long to wait before using them
+    fn reserve(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        // A single request larger than the bucket would otherwise never fit
+        self.available -= amount.min(self.capacity);
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / self.refill_per_sec)
+        }
+    }
+
+    /// Return (or take) units once the real cost of a request is known
+    fn adjust(&mut self, delta: f64, now: Instant) {
+        self.refill(now);
+        self.available = (self.available + delta).min(self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+    /// Provider told us to hold off until this instant
+    blocked_until: Option<Instant>,
+}
+
+/// Per-backend rate limiter
+#[derive(Debug)]
+pub struct RateLimiter {
+    backend: String,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        backend: &str,
+        requests_per_minute: Option<u32>,
+        tokens_per_minute: Option<u32>,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            backend: backend.to_string(),
+            state: Mutex::new(LimiterState {
+                requests: requests_per_minute.map(|rpm| TokenBucket::per_minute(rpm, now)),
+                tokens: tokens_per_minute.map(|tpm| TokenBucket::per_minute(tpm, now)),
+                blocked_until: None,
+            }),
+        }
+    }
+
+    /// Reserve capacity for one request of roughly `estimated_tokens`.
+    ///
+    /// Returns how long the caller must wait before sending.
+    pub fn reserve(&self, estimated_tokens: u64) -> Duration {
+        self.reserve_at(estimated_tokens, Instant::now())
+    }
+
+    fn reserve_at(&self, estimated_tokens: u64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let mut wait = state
+            .blocked_until
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or(Duration::ZERO);
+        if let Some(bucket) = state.requests.as_mut() {
+            wait = wait.max(bucket.reserve(1.0, now));
+        }
+        if let Some(bucket) = state.tokens.as_mut() {
+            wait = wait.max(bucket.reserve(estimated_tokens as f64, now));
+        }
+        wait
+    }
+
+    /// Correct the token bucket once the provider reports actual usage
+    pub fn record_usage(&self, estimated_tokens: u64, actual_tokens: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(bucket) = state.tokens.as_mut() {
+            bucket.adjust(
+                estimated_tokens as f64 - actual_tokens as f64,
+                Instant::now(),
+            );
+        }
+    }
+
+    /// Hold all requests to this backend for `wait`
+    pub fn block_for(&self, wait: Duration) {
+        let until = Instant::now() + wait.min(MAX_HEADER_WAIT);
+        let mut state = self.state.lock().unwrap();
+        if state.blocked_until.is_none_or(|current| current < until) {
+            state.blocked_until = Some(until);
+        }
+    }
+
+    /// Apply provider rate-limit headers from any response
+    pub fn observe_headers(&self, headers: &RateLimitHeaders) {
+        if let Some(wait) = headers.exhausted_wait() {
+            self.block_for(wait);
+        }
+    }
+
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+}
+
+/// Shared limiters, one per backend name
+static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+
+/// Get the process-wide limiter for a backend.
+///
+/// The first caller's limits win; later calls for the same backend share it.
+pub fn limiter_for(
+    backend: &str,
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+) -> Arc<RateLimiter> {
+    let mut limiters = LIMITERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap();
+    Arc::clone(limiters.entry(backend.to_string()).or_insert_with(|| {
+        Arc::new(RateLimiter::new(
+            backend,
+            requests_per_minute,
+            tokens_per_minute,
+        ))
+    }))
+}
+
+/// Report a rate-limit wait on the events bus
+pub fn report_wait(backend: &str, wait: Duration, reason: &str) {
+    if wait.is_zero() {
+        return;
+    }
+    events::emit(Event::rate_limit_wait(
+        backend,
+        wait.as_millis() as u64,
+        reason,
+    ));
+}
+
+/// Rate-limit information from provider response headers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitHeaders {
+    /// `Retry-After` / `retry-after-ms`
+    pub retry_after: Option<Duration>,
+    pub remaining_requests: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub reset_requests: Option<Duration>,
+    pub reset_tokens: Option<Duration>,
+}
+
+impl RateLimitHeaders {
+    pub fn parse(headers: &HeaderMap) -> Self {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let first = |names: &[&str]| names.iter().find_map(|n| get(n));
+
+        let retry_after = get("retry-after-ms")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|ms| Duration::from_secs_f64(ms.max(0.0) / 1000.0))
+            .or_else(|| get("retry-after").and_then(parse_retry_after));
+
+        Self {
+            retry_after,
+            remaining_requests: first(&[
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+            ])
+            .and_then(|v| v.trim().parse().ok()),
+            remaining_tokens: first(&[
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+            ])
+            .and_then(|v| v.trim().parse().ok()),
+            reset_requests: first(&[
+                "x-ratelimit-reset-requests",
+                "anthropic-ratelimit-requests-reset",
+            ])
+            .and_then(parse_reset),
+            reset_tokens: first(&[
+                "x-ratelimit-reset-tokens",
+                "anthropic-ratelimit-tokens-reset",
+            ])
+            .and_then(parse_reset),
+        }
+    }
+
+    /// How long until an exhausted request or token budget resets
+    pub fn exhausted_wait(&self) -> Option<Duration> {
+        let requests = self
+            .reset_requests
+            .filter(|_| self.remaining_requests == Some(0));
+        let tokens = self
+            .reset_tokens
+            .filter(|_| self.remaining_tokens == Some(0));
+        requests.max(tokens)
+    }
+
+    /// How long to wait before retrying a 429, if the provider said
+    pub fn retry_wait(&self) -> Option<Duration> {
+        self.retry_after
+            .or_else(|| self.exhausted_wait())
+            .map(|wait| wait.min(MAX_HEADER_WAIT))
+    }
+}
+
+/// Parse `Retry-After`: delay in seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(secs.max(0.0)));
+    }
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|at| until(SystemTime::from(at)))
+}
+
+/// Parse a reset header: Go-style duration (`1m30s`, `250ms`), seconds,
+/// a Unix timestamp or an RFC 3339 time
+fn parse_reset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(num) = value.parse::<f64>() {
+        // Large values are absolute Unix timestamps rather than delays
+        if num > 1_000_000_000.0 {
+            let at = SystemTime::UNIX_EPOCH + Duration::from_secs_f64(num);
+            return Some(until(at));
+        }
+        return Some(Duration::from_secs_f64(num.max(0.0)));
+    }
+    if let Ok(at) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(until(SystemTime::from(at)));
+    }
+    parse_go_duration(value)
+}
+
+fn until(at: SystemTime) -> Duration {
+    at.duration_since(SystemTime::now())
+        .unwrap_or(Duration::ZERO)
+}
+
+/// Parse durations like `6m0s`, `1.5s`, `20ms`, `1h2m`
+fn parse_go_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let num: f64 = rest[..num_end].parse().ok()?;
+        rest = &rest[num_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let secs = match &rest[..unit_end] {
+            "h" => num * 3600.0,
+            "m" => num * 60.0,
+            "s" => num,
+            "ms" => num / 1000.0,
+            "us" | "µs" => num / 1_000_000.0,
+            "ns" => num / 1_000_000_000.0,
+            _ => return None,
+        };
+        total += secs;
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_parse_go_duration() {
+        assert_eq!(parse_go_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_go_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_go_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_go_duration("soon"), None);
+        assert_eq!(parse_go_duration(""), None);
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        headers.insert(
+            "x-ratelimit-remaining-requests",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("2s"));
+        headers.insert(
+            "x-ratelimit-remaining-tokens",
+            HeaderValue::from_static("1200"),
+        );
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("30s"));
+
+        let parsed = RateLimitHeaders::parse(&headers);
+        assert_eq!(parsed.retry_after, Some(Duration::from_secs(7)));
+        assert_eq!(parsed.remaining_requests, Some(0));
+        assert_eq!(parsed.remaining_tokens, Some(1200));
+        // Only the exhausted request budget counts
+        assert_eq!(parsed.exhausted_wait(), Some(Duration::from_secs(2)));
+        // Retry-After wins for retries
+        assert_eq!(parsed.retry_wait(), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn test_retry_after_ms_preferred() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        let parsed = RateLimitHeaders::parse(&headers);
+        assert_eq!(parsed.retry_after, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_request_bucket_waits_when_empty() {
+        let limiter = RateLimiter::new("test", Some(60), None);
+        let now = Instant::now();
+        for _ in 0..60 {
+            assert_eq!(limiter.reserve_at(0, now), Duration::ZERO);
+        }
+        // 61st request in the same instant waits one refill interval (1s at 60/min)
+        let wait = limiter.reserve_at(0, now);
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_token_bucket_and_usage_correction() {
+        let limiter = RateLimiter::new("test", None, Some(6000));
+        let now = Instant::now();
+        assert_eq!(limiter.reserve_at(6000, now), Duration::ZERO);
+        assert!(limiter.reserve_at(1000, now) > Duration::ZERO);
+
+        // Actual usage was far lower than estimated; the refund frees capacity
+        let limiter = RateLimiter::new("test", None, Some(6000));
+        assert_eq!(limiter.reserve_at(6000, now), Duration::ZERO);
+        limiter.record_usage(6000, 1000);
+        assert_eq!(limiter.reserve_at(1000, Instant::now()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_block_for_delays_reservations() {
+        let limiter = RateLimiter::new("test", None, None);
+        assert_eq!(limiter.reserve(100), Duration::ZERO);
+        limiter.block_for(Duration::from_secs(5));
+        let wait = limiter.reserve(100);
+        assert!(wait > Duration::from_secs(4));
+    }
+}
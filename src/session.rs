@@ -30,8 +30,28 @@ fn session_path(session_id: &str) -> PathBuf {
     sessions_dir().join(format!("{}.json", session_id))
 }
 
+/// Session ids come from clients, so only allow names that stay inside the
+/// sessions directory
+pub fn is_valid_session_id(session_id: &str) -> bool {
+    !session_id.is_empty()
+        && session_id.len() <= 128
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 /// Save a session to disk
 pub fn save_session(session_id: &str, messages: &[Value], turn_count: u32) -> Result<()> {
+    let path = write_session(session_id, messages, turn_count)?;
+    eprintln!("Session saved: {}", path.display());
+    Ok(())
+}
+
+/// Write a session file without announcing it, returning its path
+pub fn write_session(session_id: &str, messages: &[Value], turn_count: u32) -> Result<PathBuf> {
+    if !is_valid_session_id(session_id) {
+        anyhow::bail!("Invalid session id: {}", session_id);
+    }
     let dir = sessions_dir();
     fs::create_dir_all(&dir)?;
 
@@ -41,18 +61,45 @@ pub fn save_session(session_id: &str, messages: &[Value], turn_count: u32) -> Re
         turn_count,
     };
 
+    // Write to a temp file first so a crash never leaves half a history
     let path = session_path(session_id);
+    let tmp = path.with_extension("json.tmp");
     let json = serde_json::to_string_pretty(&session)?;
-    fs::write(&path, json)?;
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
 
-    eprintln!("Session saved: {}", path.display());
-    Ok(())
+/// Record a completed turn: replace the stored history with `messages` and
+/// bump the turn count
+pub fn append_turn(session_id: &str, messages: &[Value]) -> Result<u32> {
+    let turn_count = load_session(session_id).map(|s| s.turn_count).unwrap_or(0) + 1;
+    write_session(session_id, messages, turn_count)?;
+    Ok(turn_count)
 }
 
 /// Load a session from disk
 pub fn load_session(session_id: &str) -> Result<SavedSession> {
+    if !is_valid_session_id(session_id) {
+        anyhow::bail!("Invalid session id: {}", session_id);
+    }
     let path = session_path(session_id);
     let json = fs::read_to_string(&path)?;
     let session: SavedSession = serde_json::from_str(&json)?;
     Ok(session)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_validation() {
+        assert!(is_valid_session_id("3f2b9c1e-8a7d-4e0f-9b6a-1c2d3e4f5a6b"));
+        assert!(is_valid_session_id("my_session"));
+        assert!(!is_valid_session_id(""));
+        assert!(!is_valid_session_id("../etc/passwd"));
+        assert!(!is_valid_session_id("a/b"));
+        assert!(!is_valid_session_id(&"x".repeat(200)));
+    }
+}
@@ -0,0 +1,516 @@
+//! Span tracing for turns, LLM calls, tool calls, hooks, subagents and
+//! compaction.
+//!
+//! Each turn is one trace. Spans nest through a per-thread stack: the turn
+//! opens the root span, and anything started on the same thread while it is
+//! open becomes its descendant. Nothing is recorded unless `[telemetry]`
+//! names an OTLP collector or a file, and nothing outside a turn is traced.
+//!
+//! When the root span closes the whole trace is exported as OTLP/HTTP JSON:
+//! POSTed to `<otlp_endpoint>/v1/traces` and/or appended to `file` as one
+//! JSON document per line.
+
+use crate::events::RunContext;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long an export may take before it is abandoned
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where finished traces are sent
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector base URL, e.g. "http://localhost:4318"
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    /// Local JSONL file that receives one OTLP JSON document per trace
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// `service.name` resource attribute
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+fn default_service_name() -> String {
+    "brainpro".to_string()
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            file: None,
+            service_name: default_service_name(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.otlp_endpoint.is_some() || self.file.is_some()
+    }
+}
+
+/// Span attribute value
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl From<&str> for AttrValue {
+    fn from(v: &str) -> Self {
+        AttrValue::Str(v.to_string())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(v: String) -> Self {
+        AttrValue::Str(v)
+    }
+}
+
+impl From<u64> for AttrValue {
+    fn from(v: u64) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<i32> for AttrValue {
+    fn from(v: i32) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<u32> for AttrValue {
+    fn from(v: u32) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<usize> for AttrValue {
+    fn from(v: usize) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<f64> for AttrValue {
+    fn from(v: f64) -> Self {
+        AttrValue::Float(v)
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(v: bool) -> Self {
+        AttrValue::Bool(v)
+    }
+}
+
+impl AttrValue {
+    fn to_otlp(&self) -> Value {
+        match self {
+            AttrValue::Str(s) => json!({ "stringValue": s }),
+            // OTLP JSON encodes 64-bit integers as strings
+            AttrValue::Int(i) => json!({ "intValue": i.to_string() }),
+            AttrValue::Float(f) => json!({ "doubleValue": f }),
+            AttrValue::Bool(b) => json!({ "boolValue": b }),
+        }
+    }
+}
+
+/// A finished (or in-progress) span
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub start_unix_nanos: u128,
+    pub end_unix_nanos: u128,
+    pub attributes: Vec<(String, AttrValue)>,
+    pub error: Option<String>,
+}
+
+impl SpanRecord {
+    fn set(&mut self, key: &str, value: AttrValue) {
+        match self.attributes.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.attributes.push((key.to_string(), value)),
+        }
+    }
+
+    fn to_otlp(&self) -> Value {
+        let mut span = json!({
+            "traceId": self.trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": 1,
+            "startTimeUnixNano": self.start_unix_nanos.to_string(),
+            "endTimeUnixNano": self.end_unix_nanos.to_string(),
+            "attributes": self.attributes.iter().map(|(k, v)| {
+                json!({ "key": k, "value": v.to_otlp() })
+            }).collect::<Vec<_>>(),
+            "status": match &self.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 1 }),
+            },
+        });
+        if let Some(parent) = &self.parent_span_id {
+            span["parentSpanId"] = json!(parent);
+        }
+        span
+    }
+}
+
+/// The trace being built on this thread
+struct ActiveTrace {
+    trace_id: String,
+    config: TelemetryConfig,
+    stack: Vec<String>,
+    finished: Vec<SpanRecord>,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<ActiveTrace>> = const { RefCell::new(None) };
+}
+
+fn now_unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+fn new_span_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+/// An open span; it ends (and the trace is exported, for a root) on drop
+pub struct SpanGuard {
+    record: Option<SpanRecord>,
+    root: bool,
+}
+
+impl SpanGuard {
+    fn noop() -> Self {
+        Self {
+            record: None,
+            root: false,
+        }
+    }
+
+    /// Whether this span is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.record.is_some()
+    }
+
+    /// Set an attribute
+    pub fn set(&mut self, key: &str, value: impl Into<AttrValue>) {
+        if let Some(record) = &mut self.record {
+            record.set(key, value.into());
+        }
+    }
+
+    /// Mark the span as failed
+    pub fn error(&mut self, message: &str) {
+        if let Some(record) = &mut self.record {
+            record.error = Some(message.to_string());
+        }
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let Some(mut record) = self.record.take() else {
+            return;
+        };
+        record.end_unix_nanos = now_unix_nanos();
+        let finished = TRACE.with(|trace| {
+            let mut trace = trace.borrow_mut();
+            let active = trace.as_mut()?;
+            if let Some(pos) = active.stack.iter().rposition(|id| *id == record.span_id) {
+                active.stack.truncate(pos);
+            }
+            active.finished.push(record);
+            if self.root {
+                trace.take()
+            } else {
+                None
+            }
+        });
+        if let Some(trace) = finished {
+            export(&trace.config, &trace.finished);
+        }
+    }
+}
+
+/// Start the root span of a turn.
+///
+/// Inside an already traced turn (a turn run by a subagent, say) this is
+/// just a child span.
+pub fn start_turn(config: &TelemetryConfig, run: &RunContext) -> SpanGuard {
+    let nested = TRACE.with(|t| t.borrow().is_some());
+    let mut guard = if nested {
+        start("turn")
+    } else if config.is_enabled() {
+        let span_id = new_span_id();
+        let trace_id = uuid::Uuid::new_v4().simple().to_string();
+        TRACE.with(|t| {
+            *t.borrow_mut() = Some(ActiveTrace {
+                trace_id: trace_id.clone(),
+                config: config.clone(),
+                stack: vec![span_id.clone()],
+                finished: Vec::new(),
+            })
+        });
+        SpanGuard {
+            record: Some(SpanRecord {
+                trace_id,
+                span_id,
+                parent_span_id: None,
+                name: "turn".to_string(),
+                start_unix_nanos: now_unix_nanos(),
+                end_unix_nanos: 0,
+                attributes: Vec::new(),
+                error: None,
+            }),
+            root: true,
+        }
+    } else {
+        return SpanGuard::noop();
+    };
+    guard.set("session.id", run.session_id.as_str());
+    if let Some(agent) = &run.agent_id {
+        guard.set("agent.id", agent.as_str());
+    }
+    if let Some(turn) = run.turn_number {
+        guard.set("turn.number", turn);
+    }
+    guard
+}
+
+/// Start a child of the current span; a no-op outside a traced turn
+pub fn start(name: &str) -> SpanGuard {
+    TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        let Some(active) = trace.as_mut() else {
+            return SpanGuard::noop();
+        };
+        let span_id = new_span_id();
+        let parent_span_id = active.stack.last().cloned();
+        active.stack.push(span_id.clone());
+        SpanGuard {
+            record: Some(SpanRecord {
+                trace_id: active.trace_id.clone(),
+                span_id,
+                parent_span_id,
+                name: name.to_string(),
+                start_unix_nanos: now_unix_nanos(),
+                end_unix_nanos: 0,
+                attributes: Vec::new(),
+                error: None,
+            }),
+            root: false,
+        }
+    })
+}
+
+/// Record a span that already finished, e.g. an LLM call timed by its caller
+pub fn record(
+    name: &str,
+    started: Instant,
+    attributes: Vec<(&str, AttrValue)>,
+    error: Option<&str>,
+) {
+    TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        let Some(active) = trace.as_mut() else {
+            return;
+        };
+        let end = now_unix_nanos();
+        active.finished.push(SpanRecord {
+            trace_id: active.trace_id.clone(),
+            span_id: new_span_id(),
+            parent_span_id: active.stack.last().cloned(),
+            name: name.to_string(),
+            start_unix_nanos: end.saturating_sub(started.elapsed().as_nanos()),
+            end_unix_nanos: end,
+            attributes: attributes
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            error: error.map(|e| e.to_string()),
+        });
+    });
+}
+
+/// Add attributes to the most recently finished span called `name`
+pub fn annotate_last(name: &str, attributes: Vec<(&str, AttrValue)>) {
+    TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        let Some(span) = trace
+            .as_mut()
+            .and_then(|active| active.finished.iter_mut().rev().find(|s| s.name == name))
+        else {
+            return;
+        };
+        for (key, value) in attributes {
+            span.set(key, value);
+        }
+    });
+}
+
+/// Build the OTLP/HTTP JSON export request for a set of spans
+pub fn otlp_document(service_name: &str, spans: &[SpanRecord]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                ]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "brainpro" },
+                "spans": spans.iter().map(SpanRecord::to_otlp).collect::<Vec<_>>(),
+            }]
+        }]
+    })
+}
+
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+fn export(config: &TelemetryConfig, spans: &[SpanRecord]) {
+    let document = otlp_document(&config.service_name, spans);
+
+    if let Some(path) = &config.file {
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", document));
+        if let Err(e) = written {
+            eprintln!("[telemetry] Failed to write {}: {}", path.display(), e);
+        }
+    }
+
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let url = traces_url(endpoint);
+        // Blocking reqwest can't run on a runtime thread, so post from a
+        // plain helper thread and wait for it
+        let posted = std::thread::spawn(move || {
+            reqwest::blocking::Client::builder()
+                .timeout(EXPORT_TIMEOUT)
+                .build()
+                .and_then(|client| client.post(&url).json(&document).send())
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .join()
+        .unwrap_or_else(|_| Err("export thread panicked".to_string()));
+        if let Err(e) = posted {
+            eprintln!("[telemetry] Failed to export trace: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> RunContext {
+        RunContext {
+            session_id: "s-1".to_string(),
+            agent_id: None,
+            turn_number: Some(3),
+        }
+    }
+
+    fn read_spans(path: &std::path::Path) -> Vec<Value> {
+        let text = std::fs::read_to_string(path).unwrap();
+        let doc: Value = serde_json::from_str(text.lines().last().unwrap()).unwrap();
+        doc["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_disabled_records_nothing() {
+        let mut turn = start_turn(&TelemetryConfig::default(), &run());
+        assert!(!turn.is_recording());
+        turn.set("ignored", 1u64);
+        assert!(!start("tool").is_recording());
+    }
+
+    #[test]
+    fn test_turn_trace_exported_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spans.jsonl");
+        let config = TelemetryConfig {
+            file: Some(path.clone()),
+            ..Default::default()
+        };
+
+        {
+            let _turn = start_turn(&config, &run());
+            record(
+                "llm.call",
+                Instant::now(),
+                vec![("gen_ai.request.model", "gpt-4o".into())],
+                None,
+            );
+            annotate_last(
+                "llm.call",
+                vec![("gen_ai.usage.input_tokens", 120u64.into())],
+            );
+            let mut tool = start("tool");
+            tool.set("tool.name", "Bash");
+            tool.error("exit 1");
+        }
+
+        let spans = read_spans(&path);
+        assert_eq!(spans.len(), 3);
+        let turn = spans.iter().find(|s| s["name"] == "turn").unwrap();
+        assert!(turn.get("parentSpanId").is_none());
+        for child in spans.iter().filter(|s| s["name"] != "turn") {
+            assert_eq!(child["traceId"], turn["traceId"]);
+            assert_eq!(child["parentSpanId"], turn["spanId"]);
+        }
+
+        let llm = spans.iter().find(|s| s["name"] == "llm.call").unwrap();
+        assert_eq!(llm["attributes"][1]["value"]["intValue"], "120");
+        let tool = spans.iter().find(|s| s["name"] == "tool").unwrap();
+        assert_eq!(tool["status"]["code"], 2);
+
+        // The trace is closed once its root ends
+        assert!(!start("tool").is_recording());
+    }
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318/"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://collector/v1/traces"),
+            "http://collector/v1/traces"
+        );
+    }
+}
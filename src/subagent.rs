@@ -1,9 +1,16 @@
 //! Subagent runtime for executing specialized, restricted agent tasks.
 
+use crate::agent::core::{
+    admit_target, budget_remaining, enforce_budget, enforce_privacy, escalate_cascade,
+    failover_target, hash_tool_call, record_call_outcome, record_cost, recover_context_overflow,
+    DoomLoopDetector, MAX_FAILOVERS, MAX_OVERFLOW_RECOVERIES,
+};
 use crate::agent::CommandStats;
-use crate::config::{AgentSpec, PermissionMode};
-use crate::llm::LlmClient;
+use crate::cascade::{Cascade, EscalationSignal};
+use crate::config::{AgentSpec, PermissionMode, Target};
+use crate::model_routing::{RouteCategory, RoutingContext};
 use crate::policy::{Decision, PolicyEngine};
+use crate::spans;
 use crate::{cli::Context, llm, tools};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -126,6 +133,8 @@ pub fn run_subagent(
     let start_time = Instant::now();
     let mut stats = CommandStats::default();
     let agent_name = &spec.name;
+    let mut span = spans::start("subagent");
+    span.set("agent.name", agent_name.as_str());
 
     // Get parent permission mode
     let parent_mode = ctx.config.borrow().permissions.mode;
@@ -176,16 +185,38 @@ pub fn run_subagent(
             .or_else(|| config.get_default_target())
             .ok_or_else(|| anyhow::anyhow!("No target configured for subagent"))?
     };
-    let target = {
-        let router = ctx.model_router.borrow();
-        router.resolve_for_agent(
-            &spec.name,
-            &spec.description,
-            spec.target.as_deref(),
-            &fallback,
-        )
+    // Get filtered tool schemas
+    let schema_opts = tools::SchemaOptions::new(ctx.args.optimize);
+    let all_tool_schemas = filter_tool_schemas(&spec.allowed_tools, &schema_opts);
+
+    // Estimate the opening request so routing can skip models whose window is too small
+    let estimated_tokens = {
+        let counter = ctx.token_counter.borrow();
+        counter.count_text(&fallback.model, spec.system_prompt.as_deref().unwrap_or(""))
+            + counter.count_text(&fallback.model, prompt)
+            + counter.count_tools(&fallback.model, &all_tool_schemas)
+    };
+    let routing_ctx = RoutingContext {
+        estimated_tokens: Some(estimated_tokens),
+        budget_remaining: budget_remaining(ctx),
+        ..Default::default()
     };
+    let decision = ctx.model_router.borrow().decide_for_agent(
+        &spec.name,
+        &spec.description,
+        spec.target.as_deref(),
+        &routing_ctx,
+        &fallback,
+    );
+    let _ = ctx.transcript.borrow_mut().routing_decision(
+        decision.category.as_str(),
+        &decision.target.to_string(),
+        &decision.reason,
+    );
+    trace(ctx, agent_name, "ROUTE", &decision.reason);
+    let mut target = decision.target;
     let bash_config = config.bash.clone();
+    let mut sampling = config.sampling_for(&target).merged(&spec.sampling);
     drop(config);
 
     trace(ctx, agent_name, "TARGET", &format!("{}", target));
@@ -228,9 +259,40 @@ pub fn run_subagent(
         "content": task_prompt
     }));
 
-    // Get filtered tool schemas
-    let schema_opts = tools::SchemaOptions::new(ctx.args.optimize);
-    let all_tool_schemas = filter_tool_schemas(&spec.allowed_tools, &schema_opts);
+    if all_tool_schemas.is_empty() {
+        sampling = sampling.without_tool_params();
+    }
+    let sampling_for = |target: &Target| {
+        let sampling = ctx
+            .config
+            .borrow()
+            .sampling_for(target)
+            .merged(&spec.sampling);
+        if all_tool_schemas.is_empty() {
+            sampling.without_tool_params()
+        } else {
+            sampling
+        }
+    };
+    let category = RouteCategory::from_agent_name(&spec.name, &spec.description);
+
+    // Cascade routing starts on the cheapest target unless the agent pins one
+    let mut cascade = if spec.target.is_none() {
+        Cascade::new(&ctx.config.borrow().model_routing.cascade, category)
+    } else {
+        None
+    };
+    if let Some(cascade) = &cascade {
+        target = cascade.current().clone();
+        sampling = sampling_for(&target);
+        trace(
+            ctx,
+            agent_name,
+            "CASCADE",
+            &format!("starting on {}", target),
+        );
+    }
+    let mut doom_detector = DoomLoopDetector::new();
 
     trace(
         ctx,
@@ -247,10 +309,22 @@ pub fn run_subagent(
     let mut last_error: Option<SubagentError> = None;
 
     // Run subagent loop
+    let mut overflow_recoveries = 0;
+    let mut failovers = 0;
     for iteration in 1..=spec.max_turns {
         trace(ctx, agent_name, "ITER", &format!("iteration {}", iteration));
 
+        // Skip backends whose circuit is open, then apply the privacy level
+        enforce_budget(ctx)?;
+        let admitted = admit_target(ctx, &target, Some(category))?;
+        let admitted = enforce_privacy(ctx, &admitted, &messages)?;
+        if admitted != target {
+            target = admitted;
+            sampling = sampling_for(&target);
+        }
+
         // Get client for target's backend
+        let estimated_prompt_tokens;
         let response = {
             let mut backends = ctx.backends.borrow_mut();
             let client = backends.get_client(&target.backend)?;
@@ -260,6 +334,11 @@ pub fn run_subagent(
                 "content": system_prompt
             })];
             req_messages.extend(messages.clone());
+            estimated_prompt_tokens = ctx.token_counter.borrow().count_request(
+                &target.model,
+                &req_messages,
+                &all_tool_schemas,
+            );
 
             let request = llm::ChatRequest {
                 model: target.model.clone(),
@@ -274,23 +353,71 @@ pub fn run_subagent(
                 } else {
                     Some("auto".to_string())
                 },
+                sampling: sampling.clone(),
             };
 
-            client.chat(&request)?
+            let started = Instant::now();
+            let response = client.chat_with_cancel(&request, &ctx.cancel);
+            record_call_outcome(ctx, &target, started, &response);
+            response
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e)
+                if llm::is_context_overflow(&e)
+                    && overflow_recoveries < MAX_OVERFLOW_RECOVERIES =>
+            {
+                overflow_recoveries += 1;
+                match recover_context_overflow(
+                    ctx,
+                    &target,
+                    &system_prompt,
+                    &all_tool_schemas,
+                    &mut messages,
+                    &e,
+                    overflow_recoveries,
+                ) {
+                    Some(next) => {
+                        if next != target {
+                            target = next;
+                            sampling = sampling_for(&target);
+                        }
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) if llm::is_backend_failure(&e) && failovers < MAX_FAILOVERS => {
+                failovers += 1;
+                match failover_target(ctx, &target, Some(category), &format!("failed: {}", e)) {
+                    Some(next) => {
+                        target = next;
+                        sampling = sampling_for(&target);
+                        continue;
+                    }
+                    None => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
         };
 
         // Track token usage from this LLM call
         if let Some(usage) = &response.usage {
+            ctx.token_counter.borrow_mut().observe(
+                &target.model,
+                estimated_prompt_tokens,
+                usage.prompt_tokens,
+            );
             stats.input_tokens += usage.prompt_tokens;
             stats.output_tokens += usage.completion_tokens;
 
             // Record cost for this operation (uses parent turn number)
-            let turn_number = *ctx.turn_counter.borrow();
-            let op = ctx.session_costs.borrow_mut().record_operation(
-                turn_number,
-                &target.model,
+            let op = record_cost(
+                ctx,
+                &target,
                 usage.prompt_tokens,
                 usage.completion_tokens,
+                Some(agent_name),
             );
 
             // Log token usage to transcript
@@ -308,6 +435,7 @@ pub fn run_subagent(
 
         let choice = &response.choices[0];
         let msg = &choice.message;
+        let mut escalation: Option<EscalationSignal> = None;
 
         // Warn if response was truncated due to length limit
         if choice.finish_reason.as_deref() == Some("length") {
@@ -327,6 +455,7 @@ pub fn run_subagent(
                 }
                 collected_text.push_str(content);
                 trace(ctx, agent_name, "TEXT", content);
+                escalation = cascade.as_ref().and_then(|c| c.observe_response(content));
             }
         }
 
@@ -338,6 +467,14 @@ pub fn run_subagent(
                     "role": "assistant",
                     "content": msg.content
                 }));
+                // A model that gave up gets another try on a stronger target
+                let next =
+                    escalation.and_then(|signal| escalate_cascade(ctx, cascade.as_mut()?, &signal));
+                if let Some(next) = next {
+                    target = next;
+                    sampling = sampling_for(&target);
+                    continue;
+                }
                 break;
             }
         };
@@ -356,6 +493,11 @@ pub fn run_subagent(
             // Count this tool use
             stats.tool_uses += 1;
 
+            if cascade.is_some() && doom_detector.record(hash_tool_call(name, &args)) {
+                escalation.get_or_insert(EscalationSignal::DoomLoop { tool: name.clone() });
+                doom_detector.reset();
+            }
+
             trace(
                 ctx,
                 agent_name,
@@ -441,7 +583,7 @@ pub fn run_subagent(
 
             let result = if allowed {
                 // Execute built-in tool
-                tools::execute(name, args.clone(), &ctx.root, &bash_config)?
+                tools::execute(name, args.clone(), &ctx.root, &bash_config, &ctx.session_id)?
             } else {
                 let reason = match decision {
                     Decision::Deny => "Denied by policy",
@@ -471,12 +613,30 @@ pub fn run_subagent(
                 &serde_json::to_string(&result).unwrap_or_default(),
             );
 
+            if let Some(signal) = cascade
+                .as_mut()
+                .and_then(|c| c.observe_tool_result(name, &args, &result))
+            {
+                escalation.get_or_insert(signal);
+            }
+
             messages.push(json!({
                 "role": "tool",
                 "tool_call_id": tc.id,
                 "content": serde_json::to_string(&result)?
             }));
         }
+
+        // Carry the conversation over to a stronger target on failure signals
+        if let Some(signal) = escalation {
+            if let Some(next) = cascade
+                .as_mut()
+                .and_then(|c| escalate_cascade(ctx, c, &signal))
+            {
+                target = next;
+                sampling = sampling_for(&target);
+            }
+        }
     }
 
     let duration_ms = start_time.elapsed().as_millis() as u64;
@@ -499,6 +659,14 @@ pub fn run_subagent(
         &format!("duration={}ms", duration_ms),
     );
 
+    span.set("gen_ai.request.model", target.model.as_str());
+    span.set("gen_ai.system", target.backend.as_str());
+    span.set("gen_ai.usage.input_tokens", stats.input_tokens);
+    span.set("gen_ai.usage.output_tokens", stats.output_tokens);
+    if let Some(error) = &last_error {
+        span.error(&error.message);
+    }
+
     Ok((
         SubagentResult {
             agent: agent_name.clone(),
@@ -0,0 +1,421 @@
+//! Token estimation for context accounting.
+//!
+//! Context limits are expressed in tokens (`ModelCapabilities::context_window`),
+//! so compaction and routing need token counts rather than character counts.
+//! We don't ship BPE vocabularies, so each model family gets a calibrated
+//! heuristic: text is split into word, symbol, whitespace and non-ASCII runs
+//! the way BPE tokenizers tend to split them, then scaled by a per-family
+//! correction factor learned from the `usage.prompt_tokens` backends report.
+
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Tokens added per message for role and framing
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Tokens added per request for reply priming
+const REQUEST_OVERHEAD_TOKENS: usize = 3;
+
+/// Tokens added per tool schema for function framing
+const TOOL_OVERHEAD_TOKENS: usize = 8;
+
+/// Calibration factors are clamped to this range to ignore outliers
+const MIN_FACTOR: f64 = 0.5;
+const MAX_FACTOR: f64 = 2.0;
+
+/// Weight of a new observation once calibration has warmed up
+const CALIBRATION_ALPHA: f64 = 0.2;
+
+/// Tokenizer family a model belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenizerFamily {
+    /// OpenAI o200k_base (gpt-4o, o1, o3)
+    OpenAiO200k,
+    /// OpenAI cl100k_base (gpt-4, gpt-3.5)
+    OpenAiCl100k,
+    /// Anthropic Claude models
+    Claude,
+    /// Meta Llama models
+    Llama,
+    /// Alibaba Qwen models
+    Qwen,
+    /// Anything else
+    Generic,
+}
+
+impl TokenizerFamily {
+    /// Infer the tokenizer family from a model name
+    pub fn for_model(model: &str) -> Self {
+        let m = model.to_lowercase();
+        if m.starts_with("gpt-4o")
+            || m.starts_with("gpt-4.1")
+            || m.starts_with("gpt-5")
+            || m.starts_with("o1")
+            || m.starts_with("o3")
+            || m.starts_with("o4")
+        {
+            TokenizerFamily::OpenAiO200k
+        } else if m.starts_with("gpt-") {
+            TokenizerFamily::OpenAiCl100k
+        } else if m.contains("claude") {
+            TokenizerFamily::Claude
+        } else if m.contains("llama") {
+            TokenizerFamily::Llama
+        } else if m.contains("qwen") {
+            TokenizerFamily::Qwen
+        } else {
+            TokenizerFamily::Generic
+        }
+    }
+
+    /// Average characters per token for a run of ASCII letters/digits
+    fn word_chars_per_token(&self) -> f64 {
+        match self {
+            TokenizerFamily::OpenAiO200k => 4.4,
+            TokenizerFamily::OpenAiCl100k => 4.0,
+            TokenizerFamily::Claude => 3.7,
+            TokenizerFamily::Llama => 4.2,
+            TokenizerFamily::Qwen => 4.1,
+            TokenizerFamily::Generic => 3.6,
+        }
+    }
+
+    /// Tokens per non-ASCII character (CJK, emoji, accented text)
+    fn non_ascii_tokens_per_char(&self) -> f64 {
+        match self {
+            TokenizerFamily::OpenAiO200k | TokenizerFamily::Qwen => 0.7,
+            TokenizerFamily::OpenAiCl100k | TokenizerFamily::Llama => 1.0,
+            TokenizerFamily::Claude | TokenizerFamily::Generic => 1.0,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenizerFamily::OpenAiO200k => "o200k",
+            TokenizerFamily::OpenAiCl100k => "cl100k",
+            TokenizerFamily::Claude => "claude",
+            TokenizerFamily::Llama => "llama",
+            TokenizerFamily::Qwen => "qwen",
+            TokenizerFamily::Generic => "generic",
+        }
+    }
+}
+
+/// Uncalibrated token estimate for a piece of text
+pub fn estimate_text(family: TokenizerFamily, text: &str) -> usize {
+    #[derive(PartialEq, Clone, Copy)]
+    enum Run {
+        None,
+        Word,
+        Symbol,
+        Space,
+    }
+
+    let word_chunk = family.word_chars_per_token();
+    let mut tokens = 0.0f64;
+    let mut run = Run::None;
+    let mut run_len = 0usize;
+    let mut run_has_newline = false;
+
+    let flush = |run: Run, len: usize, has_newline: bool, tokens: &mut f64| match run {
+        Run::Word => *tokens += (len as f64 / word_chunk).ceil(),
+        // Common operator pairs ("->", "::", "();") usually merge
+        Run::Symbol => *tokens += (len as f64 / 2.0).ceil(),
+        // A single space folds into the next word; longer runs (indentation)
+        // and newlines become tokens of their own
+        Run::Space => {
+            if has_newline {
+                *tokens += 1.0 + (len.saturating_sub(1) as f64 / 8.0).floor();
+            } else if len > 1 {
+                *tokens += (len as f64 / 4.0).ceil();
+            }
+        }
+        Run::None => {}
+    };
+
+    for c in text.chars() {
+        if !c.is_ascii() {
+            flush(run, run_len, run_has_newline, &mut tokens);
+            run = Run::None;
+            run_len = 0;
+            run_has_newline = false;
+            tokens += family.non_ascii_tokens_per_char();
+            continue;
+        }
+        let kind = if c.is_ascii_alphanumeric() || c == '_' {
+            Run::Word
+        } else if c.is_ascii_whitespace() {
+            Run::Space
+        } else {
+            Run::Symbol
+        };
+        if kind != run {
+            flush(run, run_len, run_has_newline, &mut tokens);
+            run = kind;
+            run_len = 0;
+            run_has_newline = false;
+        }
+        run_len += 1;
+        if c == '\n' {
+            run_has_newline = true;
+        }
+    }
+    flush(run, run_len, run_has_newline, &mut tokens);
+
+    tokens.ceil() as usize
+}
+
+/// Text a message contributes to the prompt
+fn message_text(msg: &Value) -> String {
+    let mut text = String::new();
+    match msg.get("content") {
+        Some(Value::String(s)) => text.push_str(s),
+        Some(Value::Null) | None => {}
+        Some(other) => text.push_str(&other.to_string()),
+    }
+    if let Some(tool_calls) = msg.get("tool_calls") {
+        text.push_str(&tool_calls.to_string());
+    }
+    text
+}
+
+/// Learned correction for a tokenizer family
+#[derive(Debug, Clone, Copy)]
+struct Calibration {
+    factor: f64,
+    samples: u32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            samples: 0,
+        }
+    }
+}
+
+/// Token usage split by where it comes from in the request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextBreakdown {
+    pub system: usize,
+    pub tools: usize,
+    pub history: usize,
+    pub tool_results: usize,
+}
+
+impl ContextBreakdown {
+    pub fn total(&self) -> usize {
+        self.system + self.tools + self.history + self.tool_results + REQUEST_OVERHEAD_TOKENS
+    }
+}
+
+/// Calibrated token counter shared by the agent loop, compaction and routing
+#[derive(Debug, Clone, Default)]
+pub struct TokenCounter {
+    calibrations: HashMap<TokenizerFamily, Calibration>,
+}
+
+impl TokenCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current correction factor for a model's tokenizer family
+    pub fn factor(&self, model: &str) -> f64 {
+        self.calibrations
+            .get(&TokenizerFamily::for_model(model))
+            .map(|c| c.factor)
+            .unwrap_or(1.0)
+    }
+
+    /// Whether backend usage reports have calibrated the model's family yet
+    pub fn is_calibrated(&self, model: &str) -> bool {
+        self.calibrations
+            .contains_key(&TokenizerFamily::for_model(model))
+    }
+
+    fn scale(&self, model: &str, raw: usize) -> usize {
+        (raw as f64 * self.factor(model)).ceil() as usize
+    }
+
+    fn raw_messages(family: TokenizerFamily, messages: &[Value]) -> usize {
+        messages
+            .iter()
+            .map(|m| estimate_text(family, &message_text(m)) + MESSAGE_OVERHEAD_TOKENS)
+            .sum()
+    }
+
+    fn raw_tools(family: TokenizerFamily, tools: &[Value]) -> usize {
+        tools
+            .iter()
+            .map(|t| estimate_text(family, &t.to_string()) + TOOL_OVERHEAD_TOKENS)
+            .sum()
+    }
+
+    /// Estimate tokens for plain text
+    pub fn count_text(&self, model: &str, text: &str) -> usize {
+        self.scale(
+            model,
+            estimate_text(TokenizerFamily::for_model(model), text),
+        )
+    }
+
+    /// Estimate tokens for a list of chat messages
+    pub fn count_messages(&self, model: &str, messages: &[Value]) -> usize {
+        self.scale(
+            model,
+            Self::raw_messages(TokenizerFamily::for_model(model), messages),
+        )
+    }
+
+    /// Estimate tokens for tool schemas
+    pub fn count_tools(&self, model: &str, tools: &[Value]) -> usize {
+        self.scale(
+            model,
+            Self::raw_tools(TokenizerFamily::for_model(model), tools),
+        )
+    }
+
+    /// Estimate the prompt tokens of a full request (messages include the system prompt)
+    pub fn count_request(&self, model: &str, messages: &[Value], tools: &[Value]) -> usize {
+        self.count_messages(model, messages)
+            + self.count_tools(model, tools)
+            + REQUEST_OVERHEAD_TOKENS
+    }
+
+    /// Split an upcoming request into system prompt, tools, history and tool results
+    pub fn breakdown(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        tools: &[Value],
+        messages: &[Value],
+    ) -> ContextBreakdown {
+        let (tool_msgs, history): (Vec<Value>, Vec<Value>) = messages
+            .iter()
+            .cloned()
+            .partition(|m| m["role"].as_str() == Some("tool"));
+        ContextBreakdown {
+            system: self.count_text(model, system_prompt) + MESSAGE_OVERHEAD_TOKENS,
+            tools: self.count_tools(model, tools),
+            history: self.count_messages(model, &history),
+            tool_results: self.count_messages(model, &tool_msgs),
+        }
+    }
+
+    /// Feed back the prompt tokens a backend reported for a request we estimated.
+    ///
+    /// `estimated` is the calibrated estimate that was made for the request.
+    pub fn observe(&mut self, model: &str, estimated: usize, actual: u64) {
+        if estimated == 0 || actual == 0 {
+            return;
+        }
+        let family = TokenizerFamily::for_model(model);
+        let entry = self.calibrations.entry(family).or_default();
+        // Recover the raw estimate so the ratio isn't compounded by the old factor
+        let raw = estimated as f64 / entry.factor;
+        let observed = (actual as f64 / raw).clamp(MIN_FACTOR, MAX_FACTOR);
+        // Plain average while warming up, then an exponential moving average
+        let alpha = (1.0 / (entry.samples as f64 + 1.0)).max(CALIBRATION_ALPHA);
+        entry.factor =
+            (entry.factor * (1.0 - alpha) + observed * alpha).clamp(MIN_FACTOR, MAX_FACTOR);
+        entry.samples += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_family_for_model() {
+        assert_eq!(
+            TokenizerFamily::for_model("gpt-4o-mini"),
+            TokenizerFamily::OpenAiO200k
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("gpt-4-turbo"),
+            TokenizerFamily::OpenAiCl100k
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("claude-3-5-sonnet-latest"),
+            TokenizerFamily::Claude
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("qwen3-235b-a22b-instruct-2507"),
+            TokenizerFamily::Qwen
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("llama3:8b"),
+            TokenizerFamily::Llama
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("mystery"),
+            TokenizerFamily::Generic
+        );
+    }
+
+    #[test]
+    fn test_estimate_text_english() {
+        // "The quick brown fox jumps over the lazy dog." is 10 tokens in cl100k
+        let n = estimate_text(
+            TokenizerFamily::OpenAiCl100k,
+            "The quick brown fox jumps over the lazy dog.",
+        );
+        assert!((9..=14).contains(&n), "got {}", n);
+    }
+
+    #[test]
+    fn test_estimate_text_empty() {
+        assert_eq!(estimate_text(TokenizerFamily::Generic, ""), 0);
+    }
+
+    #[test]
+    fn test_estimate_fewer_tokens_than_chars() {
+        let code = "fn main() {\n    println!(\"hello, world\");\n}\n";
+        let n = estimate_text(TokenizerFamily::Generic, code);
+        assert!(n > 0 && n < code.len());
+    }
+
+    #[test]
+    fn test_breakdown_splits_tool_results() {
+        let counter = TokenCounter::new();
+        let messages = vec![
+            json!({"role": "user", "content": "read the file"}),
+            json!({"role": "tool", "tool_call_id": "1", "content": "a".repeat(400)}),
+        ];
+        let b = counter.breakdown("gpt-4o", "You are helpful.", &[], &messages);
+        assert!(b.system > 0);
+        assert_eq!(b.tools, 0);
+        assert!(b.tool_results > b.history);
+        assert_eq!(
+            b.total(),
+            b.system + b.history + b.tool_results + REQUEST_OVERHEAD_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_observe_calibrates_toward_actual() {
+        let mut counter = TokenCounter::new();
+        let messages = vec![json!({"role": "user", "content": "hello there ".repeat(50)})];
+        let estimate = counter.count_messages("gpt-4o", &messages);
+        counter.observe("gpt-4o", estimate, (estimate as u64) * 3 / 2);
+        let recalibrated = counter.count_messages("gpt-4o", &messages);
+        assert!(recalibrated > estimate);
+        // Other families are unaffected
+        assert_eq!(counter.factor("claude-3-opus"), 1.0);
+    }
+
+    #[test]
+    fn test_observe_clamps_outliers() {
+        let mut counter = TokenCounter::new();
+        counter.observe("gpt-4o", 10, 10_000);
+        assert!(counter.factor("gpt-4o") <= MAX_FACTOR);
+        counter.observe("gpt-4o", 0, 100);
+        assert!(counter.factor("gpt-4o") <= MAX_FACTOR);
+    }
+}
@@ -13,6 +13,9 @@ pub mod todo;
 mod write;
 
 use crate::config::BashConfig;
+use crate::metrics;
+use crate::redact;
+use crate::spans;
 use anyhow::Result;
 use serde_json::{json, Value};
 use std::path::Path;
@@ -64,7 +67,40 @@ pub fn schemas_with_task(opts: &SchemaOptions) -> Vec<Value> {
 
 /// Execute a tool by name
 /// For Bash tool, uses the provided BashConfig; other tools ignore it
-pub fn execute(name: &str, args: Value, root: &Path, bash_config: &BashConfig) -> Result<Value> {
+/// Secrets in the result are replaced with placeholders before it reaches the
+/// conversation; placeholders in Write/Edit/Patch arguments are swapped back
+/// for the real values so files keep their secrets, and the call fails if a
+/// placeholder can't be restored. Both use the vault of `session_id`.
+pub fn execute(
+    name: &str,
+    mut args: Value,
+    root: &Path,
+    bash_config: &BashConfig,
+    session_id: &str,
+) -> Result<Value> {
+    if matches!(name, "Write" | "Edit" | "Patch") {
+        if let Err(message) = redact::restore_value(session_id, &mut args) {
+            return Ok(json!({ "error": { "code": "unknown_placeholder", "message": message } }));
+        }
+    }
+    let mut span = spans::start("tool");
+    span.set("tool.name", name);
+    let started = std::time::Instant::now();
+    let result = execute_raw(name, args, root, bash_config);
+    let ok = matches!(&result, Ok(v) if v.get("error").is_none());
+    metrics::record_tool_call(name, ok, started.elapsed().as_millis() as u64);
+    span.set("tool.ok", ok);
+    if let Ok(code) = result.as_ref().map(|v| &v["exit_code"]) {
+        if let Some(code) = code.as_i64() {
+            span.set("tool.exit_code", code as i32);
+        }
+    }
+    let mut result = result?;
+    redact::redact_value(session_id, &mut result);
+    Ok(result)
+}
+
+fn execute_raw(name: &str, args: Value, root: &Path, bash_config: &BashConfig) -> Result<Value> {
     match name {
         "Read" => read::execute(args, root),
         "Write" => write::execute(args, root),
@@ -1,3 +1,4 @@
+use crate::redact;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
@@ -35,7 +36,8 @@ impl Transcript {
         })
     }
 
-    pub fn log(&mut self, event_type: &str, data: serde_json::Value) -> Result<()> {
+    pub fn log(&mut self, event_type: &str, mut data: serde_json::Value) -> Result<()> {
+        redact::redact_value(&self.session_id, &mut data);
         let event = Event {
             ts: Utc::now(),
             session_id: &self.session_id,
@@ -270,6 +272,138 @@ impl Transcript {
         )
     }
 
+    /// Log a backend rejecting a request for exceeding the context window
+    pub fn context_overflow(
+        &mut self,
+        model: &str,
+        estimated_tokens: usize,
+        error: &str,
+    ) -> Result<()> {
+        self.log(
+            "context_overflow",
+            serde_json::json!({
+                "model": model,
+                "estimated_tokens": estimated_tokens,
+                "error": error,
+            }),
+        )
+    }
+
+    /// Log an emergency compaction triggered by a context overflow
+    pub fn emergency_compact(
+        &mut self,
+        original_tokens: usize,
+        compacted_tokens: usize,
+        ok: bool,
+    ) -> Result<()> {
+        self.log(
+            "emergency_compact",
+            serde_json::json!({
+                "original_tokens": original_tokens,
+                "compacted_tokens": compacted_tokens,
+                "ok": ok,
+            }),
+        )
+    }
+
+    /// Log rerouting to a target with a larger context window
+    pub fn context_reroute(
+        &mut self,
+        from: &str,
+        to: Option<&str>,
+        needed_tokens: usize,
+    ) -> Result<()> {
+        self.log(
+            "context_reroute",
+            serde_json::json!({
+                "from": from,
+                "to": to,
+                "needed_tokens": needed_tokens,
+            }),
+        )
+    }
+
+    /// Log a cascade moving to a stronger target
+    pub fn cascade_escalation(
+        &mut self,
+        category: &str,
+        from: &str,
+        to: &str,
+        signal: &str,
+        detail: &str,
+    ) -> Result<()> {
+        self.log(
+            "cascade_escalation",
+            serde_json::json!({
+                "category": category,
+                "from": from,
+                "to": to,
+                "signal": signal,
+                "detail": detail,
+            }),
+        )
+    }
+
+    /// Log why the router picked a target
+    pub fn routing_decision(&mut self, category: &str, target: &str, reason: &str) -> Result<()> {
+        self.log(
+            "routing_decision",
+            serde_json::json!({
+                "category": category,
+                "target": target,
+                "reason": reason,
+            }),
+        )
+    }
+
+    /// Log the category and complexity assigned to a user prompt
+    pub fn prompt_classified(
+        &mut self,
+        category: &str,
+        complexity: Option<&str>,
+        source: &str,
+    ) -> Result<()> {
+        self.log(
+            "prompt_classified",
+            serde_json::json!({
+                "category": category,
+                "complexity": complexity,
+                "source": source,
+            }),
+        )
+    }
+
+    /// Log failing over from an unhealthy backend to the next target in the chain
+    pub fn backend_failover(&mut self, from: &str, to: Option<&str>, reason: &str) -> Result<()> {
+        self.log(
+            "backend_failover",
+            serde_json::json!({
+                "from": from,
+                "to": to,
+                "reason": reason,
+            }),
+        )
+    }
+
+    /// Log a privacy decision taken before sending a request
+    pub fn privacy_decision(
+        &mut self,
+        level: &str,
+        target: &str,
+        action: &str,
+        matched_patterns: &[String],
+    ) -> Result<()> {
+        self.log(
+            "privacy_decision",
+            serde_json::json!({
+                "level": level,
+                "target": target,
+                "action": action,
+                "matched_patterns": matched_patterns,
+            }),
+        )
+    }
+
     /// Log token usage for an LLM call
     pub fn token_usage(
         &mut self,
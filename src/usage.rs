@@ -0,0 +1,500 @@
+//! Historical usage reporting for `brainpro usage` and `/usage`.
+//!
+//! Reads priced calls from the cost ledger (`~/.brainpro/costs/`) plus the
+//! `token_usage` events of transcripts written before the ledger existed,
+//! and aggregates tokens and USD by day, model, backend, project or subagent.
+
+use crate::cost::{
+    format_cost, format_tokens, CostLedger, LedgerEntry, OperationCost, PricingTable, SessionCosts,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Days, Local, NaiveDate, Utc};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Options for the usage report
+#[derive(clap::Args, Debug, Clone)]
+pub struct UsageArgs {
+    #[arg(
+        long,
+        value_enum,
+        default_value = "day",
+        help = "Group rows by day, model, backend, project or agent"
+    )]
+    pub by: GroupBy,
+
+    #[arg(long, value_name = "DATE", help = "First day to include (YYYY-MM-DD)")]
+    pub since: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "Last day to include (default: today)"
+    )]
+    pub until: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "30",
+        help = "Days to include when --since is not given"
+    )]
+    pub days: u64,
+
+    #[arg(long, value_enum, default_value = "table", help = "Output format")]
+    pub format: OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Transcripts to read (default: .brainpro/sessions)"
+    )]
+    pub transcripts: Option<PathBuf>,
+}
+
+/// `/usage` arguments, parsed with the same options as the subcommand
+#[derive(Parser)]
+#[command(name = "/usage", no_binary_name = true)]
+struct UsageCommand {
+    #[command(flatten)]
+    args: UsageArgs,
+}
+
+/// Parse the arguments of a `/usage` REPL command
+pub fn parse_command(line: &str) -> Result<UsageArgs, clap::Error> {
+    UsageCommand::try_parse_from(line.split_whitespace()).map(|c| c.args)
+}
+
+/// Dimension rows are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Day,
+    Model,
+    Backend,
+    Project,
+    #[value(alias = "subagent")]
+    Agent,
+}
+
+impl GroupBy {
+    fn header(&self) -> &'static str {
+        match self {
+            GroupBy::Day => "Day",
+            GroupBy::Model => "Model",
+            GroupBy::Backend => "Backend",
+            GroupBy::Project => "Project",
+            GroupBy::Agent => "Agent",
+        }
+    }
+
+    fn key(&self, entry: &LedgerEntry) -> String {
+        match self {
+            GroupBy::Day => entry.local_date().format("%Y-%m-%d").to_string(),
+            GroupBy::Model => entry.model.clone(),
+            GroupBy::Backend => entry.backend.clone(),
+            GroupBy::Project => entry.project.clone(),
+            GroupBy::Agent => entry.agent.clone().unwrap_or_else(|| "(main)".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Totals for one group
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub key: String,
+    pub calls: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageRow {
+    fn from_costs(key: String, costs: &SessionCosts) -> Self {
+        Self {
+            key,
+            calls: costs.operation_count(),
+            input_tokens: costs.input_tokens(),
+            output_tokens: costs.output_tokens(),
+            total_tokens: costs.total_tokens(),
+            cost_usd: costs.total_cost(),
+        }
+    }
+}
+
+/// Aggregated usage over a date range
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: GroupBy,
+    pub rows: Vec<UsageRow>,
+    pub total: UsageRow,
+    #[serde(skip)]
+    totals: SessionCosts,
+}
+
+/// Price a list of calls with their recorded cost, not today's pricing
+fn session_costs(entries: &[&LedgerEntry]) -> SessionCosts {
+    let mut costs = SessionCosts::new("usage".to_string(), PricingTable::default());
+    costs.merge_operations(
+        0,
+        entries
+            .iter()
+            .map(|e| {
+                OperationCost::new(e.model.clone(), e.input_tokens, e.output_tokens, e.cost_usd)
+            })
+            .collect(),
+    );
+    costs
+}
+
+/// Read every priced call between two local dates (inclusive).
+///
+/// Ledger entries win; transcript `token_usage` events are only used for
+/// sessions the ledger has never seen, so nothing is counted twice.
+pub fn collect(
+    ledger_dir: Option<&Path>,
+    transcripts_dir: Option<&Path>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<LedgerEntry> {
+    let mut entries = ledger_dir
+        .map(|dir| CostLedger::load_entries(dir, from, to))
+        .unwrap_or_default();
+    let ledger_sessions: HashSet<String> = entries.iter().map(|e| e.session_id.clone()).collect();
+
+    if let Some(files) = transcripts_dir.and_then(|dir| fs::read_dir(dir).ok()) {
+        for path in files.flatten().map(|f| f.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Ok(file) = fs::File::open(&path) else {
+                continue;
+            };
+            entries.extend(
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| transcript_entry(&line))
+                    .filter(|e| !ledger_sessions.contains(&e.session_id))
+                    .filter(|e| (from..=to).contains(&e.local_date())),
+            );
+        }
+    }
+
+    entries.sort_by_key(|e| e.ts);
+    entries
+}
+
+/// Turn a transcript `token_usage` event into a ledger entry. Transcripts
+/// don't record the backend or subagent.
+fn transcript_entry(line: &str) -> Option<LedgerEntry> {
+    let event: Value = serde_json::from_str(line).ok()?;
+    if event.get("type")?.as_str()? != "token_usage" {
+        return None;
+    }
+    let ts: DateTime<Utc> = event.get("ts")?.as_str()?.parse().ok()?;
+    let tokens = |key: &str| event.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    Some(LedgerEntry {
+        ts,
+        session_id: event.get("session_id")?.as_str()?.to_string(),
+        project: event
+            .get("cwd")
+            .and_then(|c| c.as_str())
+            .unwrap_or("")
+            .to_string(),
+        model: event.get("model")?.as_str()?.to_string(),
+        backend: "unknown".to_string(),
+        agent: None,
+        input_tokens: tokens("input_tokens"),
+        output_tokens: tokens("output_tokens"),
+        cost_usd: event
+            .get("cost_usd")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+    })
+}
+
+/// Group entries into report rows
+pub fn aggregate(
+    entries: &[LedgerEntry],
+    by: GroupBy,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> UsageReport {
+    let mut groups: BTreeMap<String, Vec<&LedgerEntry>> = BTreeMap::new();
+    for entry in entries {
+        groups.entry(by.key(entry)).or_default().push(entry);
+    }
+
+    let mut rows: Vec<UsageRow> = groups
+        .into_iter()
+        .map(|(key, group)| UsageRow::from_costs(key, &session_costs(&group)))
+        .collect();
+    // Days read best in order; everything else by spend
+    if by != GroupBy::Day {
+        rows.sort_by(|a, b| {
+            b.cost_usd
+                .partial_cmp(&a.cost_usd)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    let totals = session_costs(&entries.iter().collect::<Vec<_>>());
+    UsageReport {
+        from,
+        to,
+        group_by: by,
+        rows,
+        total: UsageRow::from_costs("total".to_string(), &totals),
+        totals,
+    }
+}
+
+/// Build the report described by `args`, reading transcripts under `root`
+/// unless another directory is given
+pub fn report(args: &UsageArgs, root: &Path) -> Result<UsageReport> {
+    let to = args.until.unwrap_or_else(|| Local::now().date_naive());
+    let from = match args.since {
+        Some(since) => since,
+        None => to
+            .checked_sub_days(Days::new(args.days.saturating_sub(1)))
+            .unwrap_or(to),
+    };
+    if from > to {
+        return Err(anyhow!("--since {} is after --until {}", from, to));
+    }
+
+    let transcripts = args
+        .transcripts
+        .clone()
+        .unwrap_or_else(|| root.join(".brainpro").join("sessions"));
+    let entries = collect(
+        CostLedger::default_dir().as_deref(),
+        Some(&transcripts),
+        from,
+        to,
+    );
+    Ok(aggregate(&entries, args.by, from, to))
+}
+
+/// Render a report in the requested format
+pub fn render(report: &UsageReport, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Table => render_table(report),
+        OutputFormat::Json => serde_json::to_string_pretty(report).unwrap_or_default() + "\n",
+        OutputFormat::Csv => render_csv(report),
+    }
+}
+
+fn render_table(report: &UsageReport) -> String {
+    let mut out = format!("Usage {} to {}\n", report.from, report.to);
+    if report.rows.is_empty() {
+        out.push_str("No usage recorded.\n");
+        return out;
+    }
+
+    let width = report
+        .rows
+        .iter()
+        .map(|r| r.key.chars().count())
+        .chain([report.group_by.header().len(), "Total".len()])
+        .max()
+        .unwrap_or(0);
+    let line = |key: &str, calls: String, input: String, output: String, cost: String| {
+        format!(
+            "{:<width$}  {:>6}  {:>8}  {:>8}  {:>10}\n",
+            key,
+            calls,
+            input,
+            output,
+            cost,
+            width = width
+        )
+    };
+
+    out.push('\n');
+    out.push_str(&line(
+        report.group_by.header(),
+        "Calls".into(),
+        "Input".into(),
+        "Output".into(),
+        "Cost".into(),
+    ));
+    for row in &report.rows {
+        out.push_str(&line(
+            &row.key,
+            row.calls.to_string(),
+            format_tokens(row.input_tokens),
+            format_tokens(row.output_tokens),
+            format_cost(row.cost_usd),
+        ));
+    }
+    out.push_str(&line(
+        "Total",
+        report.total.calls.to_string(),
+        format_tokens(report.total.input_tokens),
+        format_tokens(report.total.output_tokens),
+        format_cost(report.total.cost_usd),
+    ));
+
+    if report.group_by != GroupBy::Model {
+        let mut models: Vec<_> = report.totals.cost_by_model().into_iter().collect();
+        models.sort_by(|a, b| {
+            b.1 .1
+                .partial_cmp(&a.1 .1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        out.push_str("\nBy Model:\n");
+        for (model, (tokens, cost)) in models {
+            out.push_str(&format!(
+                "  {}: {} ({} tokens)\n",
+                model,
+                format_cost(cost),
+                format_tokens(tokens)
+            ));
+        }
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_csv(report: &UsageReport) -> String {
+    let mut out = format!(
+        "{},calls,input_tokens,output_tokens,total_tokens,cost_usd\n",
+        report.group_by.header().to_lowercase()
+    );
+    for row in &report.rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{:.6}\n",
+            csv_field(&row.key),
+            row.calls,
+            row.input_tokens,
+            row.output_tokens,
+            row.total_tokens,
+            row.cost_usd
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(model: &str, backend: &str, agent: Option<&str>, cost: f64) -> LedgerEntry {
+        LedgerEntry {
+            ts: Utc::now(),
+            session_id: "s1".to_string(),
+            project: "/work/app".to_string(),
+            model: model.to_string(),
+            backend: backend.to_string(),
+            agent: agent.map(|a| a.to_string()),
+            input_tokens: 1000,
+            output_tokens: 200,
+            cost_usd: cost,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_by_backend_sorts_by_cost() {
+        let entries = vec![
+            entry("gpt-4o-mini", "chatgpt", None, 0.01),
+            entry("claude-3-5-sonnet", "claude", Some("scout"), 0.20),
+            entry("gpt-4o", "chatgpt", None, 0.05),
+        ];
+        let today = Local::now().date_naive();
+        let report = aggregate(&entries, GroupBy::Backend, today, today);
+
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(report.rows[0].key, "claude");
+        assert_eq!(report.rows[1].calls, 2);
+        assert_eq!(report.rows[1].input_tokens, 2000);
+        assert!((report.total.cost_usd - 0.26).abs() < 1e-9);
+
+        let agents = aggregate(&entries, GroupBy::Agent, today, today);
+        let keys: Vec<_> = agents.rows.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, vec!["scout", "(main)"]);
+    }
+
+    #[test]
+    fn test_collect_skips_transcripts_covered_by_ledger() {
+        let ledger_dir = tempfile::tempdir().unwrap();
+        let transcripts = tempfile::tempdir().unwrap();
+        let mut ledger = CostLedger::open(Some(ledger_dir.path().to_path_buf()));
+        ledger.record(entry("gpt-4o", "chatgpt", None, 0.05));
+
+        let ts = Utc::now().to_rfc3339();
+        let events = [
+            ("s1", "token_usage"),
+            ("old", "token_usage"),
+            ("old", "user_message"),
+        ]
+        .iter()
+        .map(|(session, kind)| {
+            serde_json::json!({
+                "ts": ts, "session_id": session, "cwd": "/work/old", "type": kind,
+                "model": "gpt-4o-mini", "input_tokens": 10, "output_tokens": 5, "cost_usd": 0.001,
+            })
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+        fs::write(transcripts.path().join("old.jsonl"), events).unwrap();
+
+        let today = Local::now().date_naive();
+        let entries = collect(
+            Some(ledger_dir.path()),
+            Some(transcripts.path()),
+            today,
+            today,
+        );
+        assert_eq!(entries.len(), 2);
+        let old = entries.iter().find(|e| e.session_id == "old").unwrap();
+        assert_eq!(old.backend, "unknown");
+        assert_eq!(old.project, "/work/old");
+    }
+
+    #[test]
+    fn test_csv_output_quotes_keys() {
+        let mut project = entry("gpt-4o", "chatgpt", None, 0.5);
+        project.project = "/work/a,b".to_string();
+        let today = Local::now().date_naive();
+        let report = aggregate(&[project], GroupBy::Project, today, today);
+        let csv = render(&report, OutputFormat::Csv);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "\"/work/a,b\",1,1000,200,1200,0.500000"
+        );
+    }
+
+    #[test]
+    fn test_parse_usage_command() {
+        let args = parse_command("--by subagent --format json --days 7").unwrap();
+        assert_eq!(args.by, GroupBy::Agent);
+        assert_eq!(args.format, OutputFormat::Json);
+        assert_eq!(args.days, 7);
+        assert!(parse_command("--by nothing").is_err());
+    }
+}